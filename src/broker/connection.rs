@@ -5,7 +5,8 @@
 //! 2. Reads the first message (must be `Hello`) and forwards it to
 //!    the broker loop for handshake validation.
 //! 3. Enters a select loop: forward requests to the broker loop,
-//!    receive inject commands for unsolicited delivery.
+//!    receive inject commands and mirrored output for unsolicited
+//!    delivery.
 //! 4. On disconnect, notifies the broker loop for cleanup.
 //!
 //! See CONTRACT_BROKER.md §Wire Protocol, §Handshake.
@@ -60,10 +61,11 @@ pub fn spawn_connection(
     conn_id: ConnectionId,
     cmd_tx: mpsc::UnboundedSender<BrokerCommand>,
     inject_rx: mpsc::UnboundedReceiver<Message>,
+    mirror_rx: mpsc::Receiver<Message>,
     disconnect_tx: mpsc::UnboundedSender<DisconnectNotice>,
 ) {
     tokio::spawn(async move {
-        if let Err(e) = handle_connection(stream, conn_id, cmd_tx, inject_rx).await {
+        if let Err(e) = handle_connection(stream, conn_id, cmd_tx, inject_rx, mirror_rx).await {
             tracing::debug!(?conn_id, error = %e, "connection closed");
         }
         // Always notify broker of disconnect for cleanup.
@@ -78,7 +80,10 @@ async fn handle_connection(
     conn_id: ConnectionId,
     cmd_tx: mpsc::UnboundedSender<BrokerCommand>,
     mut inject_rx: mpsc::UnboundedReceiver<Message>,
+    mirror_rx: mpsc::Receiver<Message>,
 ) -> Result<(), ConnectionError> {
+    // Closed by the broker loop once a viewer is cut off.
+    let mut mirror_rx = Some(mirror_rx);
     let mut framed = Framed::new(stream, FrameCodec::new());
 
     // -- Handshake: first message must be Hello --
@@ -117,7 +122,7 @@ async fn handle_connection(
         return Ok(());
     }
 
-    // -- Main loop: requests + inject delivery --
+    // -- Main loop: requests + inject and mirrored output delivery --
     loop {
         tokio::select! {
             frame = framed.next() => {
@@ -147,6 +152,10 @@ async fn handle_connection(
                             interrupted: None,
                             truncated: None,
                             turns: None,
+                            exit_status: None,
+                            language: None,
+                            prompt: None,
+                            screen_rendered: None,
                         };
                         framed.send(response).await.map_err(ConnectionError::Codec)?;
                     }
@@ -167,6 +176,19 @@ async fn handle_connection(
                     }
                 }
             }
+            tail = async {
+                match mirror_rx.as_mut() {
+                    Some(rx) => rx.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                match tail {
+                    Some(msg) => {
+                        framed.send(msg).await.map_err(ConnectionError::Codec)?;
+                    }
+                    None => mirror_rx = None,
+                }
+            }
         }
     }
 }
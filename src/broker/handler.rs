@@ -6,9 +6,14 @@
 //!
 //! See CONTRACT_BROKER.md §Request / Response.
 
-use crate::ipc::protocol::{Message, PROTOCOL_VERSION, Role, Status, TurnDescriptor};
+use crate::ipc::protocol::{
+    CodeBlockDescriptor, ContentFormat, EndReason, InjectOptions, Message, PROTOCOL_VERSION, Role,
+    SegmentDescriptor, Status, TurnDescriptor,
+};
+use crate::turn::screen::ScreenSize;
 
-use super::state::{BrokerState, ConnectionId, SinkMetadata};
+use super::registry::TurnMeta;
+use super::state::{BrokerState, ConnectionId, SessionEnd, SessionMeta, SinkMetadata};
 
 /// An inject command that the broker loop must send to a wrapper.
 ///
@@ -47,6 +52,18 @@ pub enum SideEffect {
         metadata: SinkMetadata,
         request_id: u32,
     },
+    /// Fan a session's output out to its viewers.
+    Tail {
+        session: String,
+        viewers: Vec<ConnectionId>,
+        data: Vec<u8>,
+    },
+    /// Tell a session's viewers that no more output follows.
+    EndTail {
+        session: String,
+        viewers: Vec<ConnectionId>,
+        reason: &'static str,
+    },
 }
 
 /// Dispatch a request message to the appropriate handler.
@@ -74,20 +91,53 @@ pub fn handle_message(
             id,
             session,
             pid,
-            pattern: _,
+            pattern,
+            boundary,
+            parser,
+            name,
+            command,
+            cwd,
+            started_at,
+            cols,
+            rows,
         } => {
             if !is_wrapper(state, connection_id) {
                 return (error_response(id, "unknown_type"), None);
             }
-            let response = handle_register(state, id, session, pid, connection_id);
+            let meta = SessionMeta {
+                boundary,
+                parser,
+                name,
+                pattern: Some(pattern),
+                command,
+                cwd,
+                started_at,
+                size: (cols != 0 && rows != 0).then_some(ScreenSize { cols, rows }),
+            };
+            let response = handle_register(state, id, session, pid, meta, connection_id);
             (response, None)
         }
-        Message::Deregister { id, session } => {
+        Message::Deregister {
+            id,
+            session,
+            exit_code,
+            reason,
+        } => {
             if !is_wrapper(state, connection_id) {
                 return (error_response(id, "unknown_type"), None);
             }
-            let response = handle_deregister(state, id, &session);
-            (response, None)
+            let end = SessionEnd {
+                at: crate::turn::epoch_millis(),
+                exit_code,
+                reason: reason.unwrap_or(EndReason::Exited),
+            };
+            handle_deregister(state, id, session, connection_id, end)
+        }
+        Message::Output { id, session, data } => {
+            if !is_wrapper(state, connection_id) {
+                return (error_response(id, "unknown_type"), None);
+            }
+            handle_output(state, id, session, data)
         }
         Message::TurnCompleted {
             id,
@@ -95,6 +145,11 @@ pub fn handle_message(
             content,
             interrupted,
             timestamp,
+            cols,
+            rows,
+            exit_status,
+            prompt,
+            screen_rendered,
         } => {
             if !is_wrapper(state, connection_id) {
                 return (error_response(id, "unknown_type"), None);
@@ -106,30 +161,80 @@ pub fn handle_message(
             } else {
                 timestamp
             };
-            let response = handle_turn_completed(state, id, &session, content, interrupted, ts);
+            let meta = TurnMeta {
+                interrupted,
+                timestamp: ts,
+                size: ScreenSize::or_default(cols, rows),
+                exit_status,
+                prompt,
+                screen_rendered,
+            };
+            let response = handle_turn_completed(state, id, &session, connection_id, content, meta);
+            (response, None)
+        }
+        Message::TurnProgress {
+            id,
+            session,
+            content,
+            timestamp,
+            cols,
+            rows,
+        } => {
+            if !is_wrapper(state, connection_id) {
+                return (error_response(id, "unknown_type"), None);
+            }
+            let meta = TurnMeta {
+                interrupted: false,
+                timestamp: if timestamp == 0 {
+                    crate::turn::epoch_millis()
+                } else {
+                    timestamp
+                },
+                size: ScreenSize::or_default(cols, rows),
+                ..TurnMeta::default()
+            };
+            let response = handle_turn_progress(state, id, &session, connection_id, content, meta);
             (response, None)
         }
         // -- Any role --
-        Message::Capture { id, session } => {
-            let response = handle_capture(state, id, &session);
+        Message::Capture {
+            id,
+            session,
+            format,
+        } => {
+            let response = handle_capture(state, id, &session, format.unwrap_or_default());
             (response, None)
         }
-        Message::Paste { id, session } => handle_paste(state, id, &session),
+        Message::Paste {
+            id,
+            session,
+            format,
+            options,
+        } => handle_paste(state, id, &session, format, options),
         Message::ListSessions { id } => {
             let response = handle_list_sessions(state, id);
             (response, None)
         }
+        Message::Subscribe { id, session } => handle_subscribe(state, id, &session, connection_id),
         // -- Turn registry queries (v1, any role) --
-        Message::GetTurn { id, turn_id } => {
-            let response = handle_get_turn(state, id, &turn_id);
+        Message::GetTurn {
+            id,
+            turn_id,
+            format,
+        } => {
+            let response = handle_get_turn(state, id, &turn_id, format.unwrap_or_default());
             (response, None)
         }
         Message::ListTurns { id, session, limit } => {
             let response = handle_list_turns(state, id, &session, limit);
             (response, None)
         }
-        Message::CaptureByID { id, turn_id } => {
-            let response = handle_capture_by_id(state, id, &turn_id);
+        Message::CaptureByID {
+            id,
+            turn_id,
+            format,
+        } => {
+            let response = handle_capture_by_id(state, id, &turn_id, format.unwrap_or_default());
             (response, None)
         }
         // -- Sink delivery (v1, any role) --
@@ -138,11 +243,24 @@ pub fn handle_message(
             sink,
             session,
             path,
-        } => handle_deliver(state, id, &sink, session.as_deref(), path.as_deref()),
+            format,
+            options,
+        } => handle_deliver(
+            state,
+            id,
+            &sink,
+            session.as_deref(),
+            path.as_deref(),
+            format,
+            options,
+        ),
         // Server-originated messages should never be sent by clients.
         Message::HelloAck { id, .. }
         | Message::Response { id, .. }
-        | Message::Inject { id, .. } => (error_response(id, "unknown_type"), None),
+        | Message::Inject { id, .. }
+        | Message::Mirror { id, .. }
+        | Message::Tail { id, .. }
+        | Message::TailEnded { id, .. } => (error_response(id, "unknown_type"), None),
     }
 }
 
@@ -184,48 +302,114 @@ fn handle_register(
     id: u32,
     session: String,
     pid: u32,
+    meta: SessionMeta,
     connection_id: ConnectionId,
 ) -> Message {
-    match state.register_session(session, connection_id, pid) {
+    match state.register_session(session, connection_id, pid, meta) {
         Ok(()) => ok_response(id),
         Err(reason) => error_response(id, reason),
     }
 }
 
-fn handle_deregister(state: &mut BrokerState, id: u32, session: &str) -> Message {
-    state.deregister_session(session);
-    ok_response(id)
+fn handle_deregister(
+    state: &mut BrokerState,
+    id: u32,
+    session: String,
+    connection_id: ConnectionId,
+    end: SessionEnd,
+) -> (Message, Option<SideEffect>) {
+    let viewers = state.deregister_session(&session, connection_id, end);
+    let effect = (!viewers.is_empty()).then_some(SideEffect::EndTail {
+        session,
+        viewers,
+        reason: "session_ended",
+    });
+    (ok_response(id), effect)
+}
+
+/// Output is passed on to the session's viewers, if it still has any
+/// (CONTRACT_BROKER.md §Live Mirroring).
+fn handle_output(
+    state: &BrokerState,
+    id: u32,
+    session: String,
+    data: Vec<u8>,
+) -> (Message, Option<SideEffect>) {
+    let viewers = state.viewers(&session);
+    let effect = (!viewers.is_empty()).then_some(SideEffect::Tail {
+        session,
+        viewers,
+        data,
+    });
+    (ok_response(id), effect)
+}
+
+/// The first viewer of a session asks its wrapper for output.
+fn handle_subscribe(
+    state: &mut BrokerState,
+    id: u32,
+    session: &str,
+    connection_id: ConnectionId,
+) -> (Message, Option<SideEffect>) {
+    match state.subscribe(connection_id, session) {
+        Ok((session, Some(wrapper))) => {
+            let action = InjectAction {
+                target_connection: wrapper,
+                message: Message::Mirror {
+                    id: 0,
+                    session,
+                    enabled: true,
+                },
+            };
+            (
+                ok_response(id),
+                Some(SideEffect::Inject {
+                    action,
+                    request_id: id,
+                }),
+            )
+        }
+        Ok((_, None)) => (ok_response(id), None),
+        Err(reason) => (error_response(id, reason), None),
+    }
 }
 
 fn handle_turn_completed(
     state: &mut BrokerState,
     id: u32,
     session: &str,
+    connection_id: ConnectionId,
     content: Vec<u8>,
-    interrupted: bool,
-    timestamp: u64,
+    meta: TurnMeta,
 ) -> Message {
-    match state.store_turn(session, content, interrupted, timestamp) {
-        Ok(turn_id) => Message::Response {
-            id,
-            status: Status::Ok,
-            error: None,
-            size: None,
-            sessions: None,
-            turn_id: Some(turn_id),
-            content: None,
-            timestamp: None,
-            byte_length: None,
-            interrupted: None,
-            truncated: None,
-            turns: None,
-        },
+    match state.store_turn(session, connection_id, content, meta) {
+        Ok(turn_id) => stored_response(id, turn_id),
+        Err(reason) => error_response(id, reason),
+    }
+}
+
+fn handle_turn_progress(
+    state: &mut BrokerState,
+    id: u32,
+    session: &str,
+    connection_id: ConnectionId,
+    content: Vec<u8>,
+    meta: TurnMeta,
+) -> Message {
+    match state.store_progress(session, connection_id, content, meta) {
+        Ok(Some(turn_id)) => stored_response(id, turn_id),
+        Ok(None) => ok_response(id),
         Err(reason) => error_response(id, reason),
     }
 }
 
-fn handle_capture(state: &mut BrokerState, id: u32, session: &str) -> Message {
-    match state.capture(session) {
+fn handle_capture(
+    state: &mut BrokerState,
+    id: u32,
+    session: &str,
+    format: ContentFormat,
+) -> Message {
+    match state.capture(session, format) {
         Ok(result) => Message::Response {
             id,
             status: Status::Ok,
@@ -239,17 +423,41 @@ fn handle_capture(state: &mut BrokerState, id: u32, session: &str) -> Message {
             interrupted: None,
             truncated: None,
             turns: None,
+            exit_status: None,
+            language: None,
+            prompt: None,
+            screen_rendered: None,
         },
         Err(reason) => error_response(id, reason),
     }
 }
 
-fn handle_paste(state: &mut BrokerState, id: u32, session: &str) -> (Message, Option<SideEffect>) {
-    match state.paste_content(session) {
+fn handle_paste(
+    state: &mut BrokerState,
+    id: u32,
+    session: &str,
+    format: Option<ContentFormat>,
+    options: InjectOptions,
+) -> (Message, Option<SideEffect>) {
+    if let Err(reason) = options.validate() {
+        return (error_response(id, reason), None);
+    }
+    match state.paste_content(session, format) {
+        Ok((content, _))
+            if options
+                .max_bytes
+                .is_some_and(|max| content.len() > max as usize) =>
+        {
+            (error_response(id, "content_too_large"), None)
+        }
         Ok((content, target_conn)) => {
             let action = InjectAction {
                 target_connection: target_conn,
-                message: Message::Inject { id: 0, content },
+                message: Message::Inject {
+                    id: 0,
+                    content,
+                    options,
+                },
             };
             (
                 ok_response(id),
@@ -278,25 +486,54 @@ fn handle_list_sessions(state: &BrokerState, id: u32) -> Message {
         interrupted: None,
         truncated: None,
         turns: None,
+        exit_status: None,
+        language: None,
+        prompt: None,
+        screen_rendered: None,
     }
 }
 
-fn handle_get_turn(state: &BrokerState, id: u32, turn_id: &str) -> Message {
-    match state.get_turn(turn_id) {
-        Ok(record) => Message::Response {
-            id,
-            status: Status::Ok,
-            error: None,
-            size: None,
-            sessions: None,
-            turn_id: Some(record.turn_id.clone()),
-            content: Some(record.content.clone()),
-            timestamp: Some(record.timestamp),
-            byte_length: Some(record.byte_length),
-            interrupted: Some(record.interrupted),
-            truncated: Some(record.truncated),
-            turns: None,
-        },
+fn handle_get_turn(state: &BrokerState, id: u32, turn_id: &str, format: ContentFormat) -> Message {
+    match state.resolve_turn(turn_id) {
+        Ok((record, block)) => {
+            // A fragment returns the code block itself (already plain
+            // text, so `format` does not apply) under its own ID, and
+            // carries no prompt.
+            let (turn_id, byte_length, language, content, prompt) = match block {
+                Some((fragment_id, block)) => (
+                    fragment_id,
+                    block.content.len() as u32,
+                    block.language,
+                    block.content,
+                    None,
+                ),
+                None => (
+                    record.turn_id.clone(),
+                    record.byte_length,
+                    None,
+                    record.content_as(format).into_owned(),
+                    record.prompt.clone(),
+                ),
+            };
+            Message::Response {
+                id,
+                status: Status::Ok,
+                error: None,
+                size: None,
+                sessions: None,
+                turn_id: Some(turn_id),
+                content: Some(content),
+                timestamp: Some(record.timestamp),
+                byte_length: Some(byte_length),
+                interrupted: Some(record.interrupted),
+                truncated: Some(record.truncated),
+                turns: None,
+                exit_status: record.exit_status,
+                language,
+                prompt,
+                screen_rendered: Some(record.screen_rendered),
+            }
+        }
         Err(reason) => error_response(id, reason),
     }
 }
@@ -313,6 +550,26 @@ fn handle_list_turns(state: &BrokerState, id: u32, session: &str, limit: Option<
                     byte_length: r.byte_length,
                     interrupted: r.interrupted,
                     truncated: r.truncated,
+                    exit_status: r.exit_status,
+                    screen_rendered: r.screen_rendered,
+                    code_blocks: r
+                        .code_blocks()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, block)| CodeBlockDescriptor {
+                            fragment_id: format!("{}#code{}", r.turn_id, i + 1),
+                            language: block.language,
+                            byte_length: block.content.len() as u32,
+                        })
+                        .collect(),
+                    segments: r
+                        .segments()
+                        .iter()
+                        .map(|segment| SegmentDescriptor {
+                            kind: segment.kind.as_str().to_string(),
+                            byte_length: segment.content.len() as u32,
+                        })
+                        .collect(),
                 })
                 .collect();
             Message::Response {
@@ -328,14 +585,23 @@ fn handle_list_turns(state: &BrokerState, id: u32, session: &str, limit: Option<
                 interrupted: None,
                 truncated: None,
                 turns: Some(turns),
+                exit_status: None,
+                language: None,
+                prompt: None,
+                screen_rendered: None,
             }
         }
         Err(reason) => error_response(id, reason),
     }
 }
 
-fn handle_capture_by_id(state: &mut BrokerState, id: u32, turn_id: &str) -> Message {
-    match state.capture_by_id(turn_id) {
+fn handle_capture_by_id(
+    state: &mut BrokerState,
+    id: u32,
+    turn_id: &str,
+    format: ContentFormat,
+) -> Message {
+    match state.capture_by_id(turn_id, format) {
         Ok(result) => Message::Response {
             id,
             status: Status::Ok,
@@ -349,6 +615,10 @@ fn handle_capture_by_id(state: &mut BrokerState, id: u32, turn_id: &str) -> Mess
             interrupted: None,
             truncated: None,
             turns: None,
+            exit_status: None,
+            language: None,
+            prompt: None,
+            screen_rendered: None,
         },
         Err(reason) => error_response(id, reason),
     }
@@ -360,6 +630,8 @@ fn handle_deliver(
     sink: &str,
     session: Option<&str>,
     path: Option<&str>,
+    format: Option<ContentFormat>,
+    options: InjectOptions,
 ) -> (Message, Option<SideEffect>) {
     match sink {
         "inject" => {
@@ -367,10 +639,10 @@ fn handle_deliver(
                 Some(s) => s,
                 None => return (error_response(id, "missing_field"), None),
             };
-            handle_paste(state, id, session)
+            handle_paste(state, id, session, format, options)
         }
         "clipboard" => {
-            let (content, metadata) = match state.relay_content() {
+            let (content, metadata) = match state.relay_content(format) {
                 Some(pair) => pair,
                 None => return (error_response(id, "buffer_empty"), None),
             };
@@ -388,7 +660,7 @@ fn handle_deliver(
                 Some(p) => p,
                 None => return (error_response(id, "missing_field"), None),
             };
-            let (content, metadata) = match state.relay_content() {
+            let (content, metadata) = match state.relay_content(format) {
                 Some(pair) => pair,
                 None => return (error_response(id, "buffer_empty"), None),
             };
@@ -412,6 +684,28 @@ fn is_wrapper(state: &BrokerState, connection_id: ConnectionId) -> bool {
     state.connection_role(connection_id) == Some(Role::Wrapper)
 }
 
+/// Success response carrying the ID under which a turn was stored.
+fn stored_response(id: u32, turn_id: String) -> Message {
+    Message::Response {
+        id,
+        status: Status::Ok,
+        error: None,
+        size: None,
+        sessions: None,
+        turn_id: Some(turn_id),
+        content: None,
+        timestamp: None,
+        byte_length: None,
+        interrupted: None,
+        truncated: None,
+        turns: None,
+        exit_status: None,
+        language: None,
+        prompt: None,
+        screen_rendered: None,
+    }
+}
+
 fn ok_response(id: u32) -> Message {
     Message::Response {
         id,
@@ -426,6 +720,10 @@ fn ok_response(id: u32) -> Message {
         interrupted: None,
         truncated: None,
         turns: None,
+        exit_status: None,
+        language: None,
+        prompt: None,
+        screen_rendered: None,
     }
 }
 
@@ -443,12 +741,17 @@ pub(super) fn error_response(id: u32, reason: &str) -> Message {
         interrupted: None,
         truncated: None,
         turns: None,
+        exit_status: None,
+        language: None,
+        prompt: None,
+        screen_rendered: None,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ipc::protocol::MAX_CHUNK_DELAY_MS;
 
     fn fresh() -> (BrokerState, ConnectionId) {
         use crate::broker::state::RingConfig;
@@ -471,6 +774,14 @@ mod tests {
             session: session.into(),
             pid,
             pattern: "generic".into(),
+            boundary: None,
+            parser: None,
+            name: None,
+            command: Vec::new(),
+            cwd: None,
+            started_at: None,
+            cols: 0,
+            rows: 0,
         }
     }
 
@@ -605,6 +916,8 @@ mod tests {
             Message::Deregister {
                 id: 2,
                 session: "s1".into(),
+                exit_code: Some(137),
+                reason: Some(EndReason::Killed),
             },
             c,
         );
@@ -615,6 +928,9 @@ mod tests {
                 ..
             }
         ));
+        let list = s.list_sessions();
+        assert_eq!(list[0].exit_code, Some(137));
+        assert_eq!(list[0].end_reason, Some(EndReason::Killed));
     }
 
     // -- Turn completed --
@@ -632,6 +948,11 @@ mod tests {
                 content: b"output".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -656,6 +977,11 @@ mod tests {
                 content: b"data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -687,6 +1013,11 @@ mod tests {
                 content: b"data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -713,6 +1044,11 @@ mod tests {
                 content: b"12345".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -721,6 +1057,7 @@ mod tests {
             Message::Capture {
                 id: 3,
                 session: "s1".into(),
+                format: None,
             },
             c,
         );
@@ -736,6 +1073,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn capture_screen_format_uses_wrapper_size() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+        // On a 4-column terminal "abcd" wraps, so the erase only
+        // clears the second row.
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 2,
+                session: "s1".into(),
+                content: b"abcdef\r\x1b[Kx\r\n".to_vec(),
+                interrupted: false,
+                timestamp: 1000,
+                cols: 4,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
+            },
+            c,
+        );
+        handle_message(
+            &mut s,
+            Message::Capture {
+                id: 3,
+                session: "s1".into(),
+                format: Some(ContentFormat::Screen),
+            },
+            c,
+        );
+        let (_, effect) = handle_message(
+            &mut s,
+            Message::Paste {
+                id: 4,
+                session: "s1".into(),
+                format: None,
+                options: InjectOptions::default(),
+            },
+            c,
+        );
+        match effect {
+            Some(SideEffect::Inject { action, .. }) => match action.message {
+                Message::Inject { content, .. } => assert_eq!(content, b"abcdx\n"),
+                _ => panic!("expected Inject message"),
+            },
+            _ => panic!("expected SideEffect::Inject"),
+        }
+    }
+
     // -- Paste --
 
     #[test]
@@ -761,6 +1149,11 @@ mod tests {
                 content: b"turn data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c1,
         );
@@ -769,6 +1162,7 @@ mod tests {
             Message::Capture {
                 id: 3,
                 session: "s1".into(),
+                format: None,
             },
             c2,
         );
@@ -778,6 +1172,8 @@ mod tests {
             Message::Paste {
                 id: 4,
                 session: "s1".into(),
+                format: None,
+                options: InjectOptions::default(),
             },
             c2,
         );
@@ -795,7 +1191,12 @@ mod tests {
                 assert_eq!(request_id, 4);
                 assert_eq!(action.target_connection, c1);
                 match action.message {
-                    Message::Inject { id, content } => {
+                    Message::Inject {
+                        id,
+                        content,
+                        options,
+                    } => {
+                        assert_eq!(options, InjectOptions::default());
                         assert_eq!(id, 0);
                         assert_eq!(content, b"turn data");
                     }
@@ -816,6 +1217,8 @@ mod tests {
             Message::Paste {
                 id: 2,
                 session: "s1".into(),
+                format: None,
+                options: InjectOptions::default(),
             },
             c,
         );
@@ -841,6 +1244,9 @@ mod tests {
                 let sessions = sessions.unwrap();
                 assert_eq!(sessions.len(), 1);
                 assert_eq!(sessions[0].session, "s1");
+                assert_eq!(sessions[0].pattern.as_deref(), Some("generic"));
+                // The register helper reports no size.
+                assert_eq!((sessions[0].cols, sessions[0].rows), (0, 0));
             }
             _ => panic!("expected Response"),
         }
@@ -876,6 +1282,7 @@ mod tests {
             Message::Inject {
                 id: 2,
                 content: vec![],
+                options: InjectOptions::default(),
             },
             c,
         );
@@ -903,6 +1310,11 @@ mod tests {
                 content: b"data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -928,6 +1340,11 @@ mod tests {
                 content: b"data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -955,6 +1372,11 @@ mod tests {
                 content: b"data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -964,6 +1386,7 @@ mod tests {
             Message::Capture {
                 id: 3,
                 session: "s1".into(),
+                format: None,
             },
             c,
         );
@@ -996,6 +1419,11 @@ mod tests {
                 content: b"data".to_vec(),
                 interrupted: true,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -1026,6 +1454,11 @@ mod tests {
                 content: b"a".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -1037,6 +1470,11 @@ mod tests {
                 content: b"b".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -1072,6 +1510,11 @@ mod tests {
                 content: b"hello world".to_vec(),
                 interrupted: false,
                 timestamp: 5000,
+                cols: 80,
+                rows: 24,
+                exit_status: Some(0),
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -1086,6 +1529,7 @@ mod tests {
             Message::GetTurn {
                 id: 10,
                 turn_id: "s1:1".into(),
+                format: None,
             },
             c,
         );
@@ -1099,6 +1543,7 @@ mod tests {
                 byte_length,
                 interrupted,
                 truncated,
+                exit_status,
                 ..
             } => {
                 assert_eq!(id, 10);
@@ -1109,6 +1554,107 @@ mod tests {
                 assert_eq!(byte_length, Some(11));
                 assert_eq!(interrupted, Some(false));
                 assert_eq!(truncated, Some(false));
+                assert_eq!(exit_status, Some(0));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    #[test]
+    fn get_turn_plain_format() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 2,
+                session: "s1".into(),
+                content: b"\x1b[32mok\x1b[0m \r\n".to_vec(),
+                interrupted: false,
+                timestamp: 5000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
+            },
+            c,
+        );
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::GetTurn {
+                id: 10,
+                turn_id: "s1:1".into(),
+                format: Some(ContentFormat::Plain),
+            },
+            c,
+        );
+        match resp {
+            Message::Response {
+                content,
+                byte_length,
+                ..
+            } => {
+                assert_eq!(content, Some(b"ok\n".to_vec()));
+                assert_eq!(byte_length, Some(14));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    #[test]
+    fn get_turn_returns_prompt() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 2,
+                session: "s1".into(),
+                content: b"Sure:\r\n```sh\nls\n```\r\n".to_vec(),
+                interrupted: false,
+                timestamp: 5000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: Some(b"list files".to_vec()),
+                screen_rendered: false,
+            },
+            c,
+        );
+
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::GetTurn {
+                id: 10,
+                turn_id: "s1:1".into(),
+                format: Some(ContentFormat::Exchange),
+            },
+            c,
+        );
+        match resp {
+            Message::Response {
+                content, prompt, ..
+            } => {
+                assert_eq!(
+                    content,
+                    Some(b"> list files\n\nSure:\n```sh\nls\n```\n".to_vec())
+                );
+                assert_eq!(prompt, Some(b"list files".to_vec()));
+            }
+            _ => panic!("expected Response"),
+        }
+
+        // A code block fragment carries no prompt.
+        let (resp, _) = handle_message(&mut s, get_turn(11, "s1:1#code1"), c);
+        match resp {
+            Message::Response {
+                content, prompt, ..
+            } => {
+                assert_eq!(content, Some(b"ls\n".to_vec()));
+                assert_eq!(prompt, None);
             }
             _ => panic!("expected Response"),
         }
@@ -1122,15 +1668,137 @@ mod tests {
             Message::GetTurn {
                 id: 10,
                 turn_id: "s1:999".into(),
+                format: None,
+            },
+            c,
+        );
+        match resp {
+            Message::Response { error, .. } => {
+                assert_eq!(error.as_deref(), Some("turn_not_found"));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    // -- Turn progress --
+
+    fn turn_progress(id: u32, content: &[u8]) -> Message {
+        Message::TurnProgress {
+            id,
+            session: "s1".into(),
+            content: content.to_vec(),
+            timestamp: 4000,
+            cols: 80,
+            rows: 24,
+        }
+    }
+
+    fn get_turn(id: u32, turn_id: &str) -> Message {
+        Message::GetTurn {
+            id,
+            turn_id: turn_id.into(),
+            format: None,
+        }
+    }
+
+    #[test]
+    fn turn_progress_exposes_current_turn() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+
+        let (resp, _) = handle_message(&mut s, turn_progress(2, b"par"), c);
+        match resp {
+            Message::Response {
+                status, turn_id, ..
+            } => {
+                assert_eq!(status, Status::Ok);
+                assert_eq!(turn_id, Some("s1:current".into()));
+            }
+            _ => panic!("expected Response"),
+        }
+
+        // A later snapshot replaces the earlier one.
+        handle_message(&mut s, turn_progress(3, b"partial"), c);
+        let (resp, _) = handle_message(&mut s, get_turn(10, "s1:current"), c);
+        match resp {
+            Message::Response {
+                content,
+                timestamp,
+                interrupted,
+                ..
+            } => {
+                assert_eq!(content, Some(b"partial".to_vec()));
+                assert_eq!(timestamp, Some(4000));
+                assert_eq!(interrupted, Some(false));
+            }
+            _ => panic!("expected Response"),
+        }
+
+        // The provisional record is not part of the history.
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::ListTurns {
+                id: 11,
+                session: "s1".into(),
+                limit: None,
+            },
+            c,
+        );
+        match resp {
+            Message::Response { turns, .. } => assert_eq!(turns, Some(vec![])),
+            _ => panic!("expected Response"),
+        }
+    }
+
+    #[test]
+    fn turn_completed_drops_current_turn() {
+        let (mut s, c) = setup_with_turn();
+        handle_message(&mut s, turn_progress(3, b"partial"), c);
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 4,
+                session: "s1".into(),
+                content: b"partial done".to_vec(),
+                interrupted: false,
+                timestamp: 6000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
+
+        let (resp, _) = handle_message(&mut s, get_turn(10, "s1:current"), c);
         match resp {
             Message::Response { error, .. } => {
                 assert_eq!(error.as_deref(), Some("turn_not_found"));
             }
             _ => panic!("expected Response"),
         }
+        let (resp, _) = handle_message(&mut s, get_turn(11, "s1:2"), c);
+        match resp {
+            Message::Response { content, .. } => {
+                assert_eq!(content, Some(b"partial done".to_vec()));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    #[test]
+    fn turn_progress_session_not_found() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        let (resp, _) = handle_message(&mut s, turn_progress(1, b"partial"), c);
+        match resp {
+            Message::Response { error, .. } => {
+                assert_eq!(error.as_deref(), Some("session_not_found"));
+            }
+            _ => panic!("expected Response"),
+        }
     }
 
     // -- ListTurns --
@@ -1149,6 +1817,11 @@ mod tests {
                     content: format!("turn-{i}").into_bytes(),
                     interrupted: false,
                     timestamp: 1000 + u64::from(i),
+                    cols: 80,
+                    rows: 24,
+                    exit_status: None,
+                    prompt: None,
+                    screen_rendered: false,
                 },
                 c,
             );
@@ -1180,6 +1853,236 @@ mod tests {
         }
     }
 
+    #[test]
+    fn segments_listed_and_viewable() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(
+            &mut s,
+            Message::Register {
+                id: 1,
+                session: "s1".into(),
+                pid: 100,
+                pattern: "aider".into(),
+                boundary: None,
+                parser: Some("aider".into()),
+                name: None,
+                command: Vec::new(),
+                cwd: None,
+                started_at: None,
+                cols: 0,
+                rows: 0,
+            },
+            c,
+        );
+        let content = "Fixing.\r\n<<<<<<< SEARCH\r\na\r\n=======\r\nb\r\n>>>>>>> REPLACE\r\n\
+                       Applied edit to x.py\r\n";
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 2,
+                session: "s1".into(),
+                content: content.as_bytes().to_vec(),
+                interrupted: false,
+                timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
+            },
+            c,
+        );
+
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::ListTurns {
+                id: 3,
+                session: "s1".into(),
+                limit: None,
+            },
+            c,
+        );
+        match resp {
+            Message::Response {
+                turns: Some(turns), ..
+            } => {
+                let kinds: Vec<&str> = turns[0].segments.iter().map(|s| s.kind.as_str()).collect();
+                assert_eq!(kinds, ["prose", "edit_block", "tool_result"]);
+                assert_eq!(turns[0].segments[0].byte_length, 8);
+            }
+            _ => panic!("expected Response with turns"),
+        }
+
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::GetTurn {
+                id: 4,
+                turn_id: "s1:1".into(),
+                format: Some(ContentFormat::Edits),
+            },
+            c,
+        );
+        match resp {
+            Message::Response {
+                content: Some(content),
+                ..
+            } => assert_eq!(content, b"<<<<<<< SEARCH\na\n=======\nb\n>>>>>>> REPLACE\n"),
+            _ => panic!("expected Response with content"),
+        }
+    }
+
+    #[test]
+    fn code_block_fragments() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 2,
+                session: "s1".into(),
+                content: b"```\r\nplain\r\n```\r\n```toml\r\na = 1\r\n```\r\n".to_vec(),
+                interrupted: false,
+                timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
+            },
+            c,
+        );
+
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::ListTurns {
+                id: 10,
+                session: "s1".into(),
+                limit: None,
+            },
+            c,
+        );
+        match resp {
+            Message::Response {
+                turns: Some(turns), ..
+            } => {
+                let blocks = &turns[0].code_blocks;
+                assert_eq!(blocks.len(), 2);
+                assert_eq!(blocks[0].fragment_id, "s1:1#code1");
+                assert_eq!(blocks[0].language, None);
+                assert_eq!(blocks[1].fragment_id, "s1:1#code2");
+                assert_eq!(blocks[1].language.as_deref(), Some("toml"));
+                assert_eq!(blocks[1].byte_length, 6);
+            }
+            _ => panic!("expected Response with turns"),
+        }
+
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::GetTurn {
+                id: 11,
+                turn_id: "s1:1#code2".into(),
+                format: None,
+            },
+            c,
+        );
+        match resp {
+            Message::Response {
+                status,
+                turn_id,
+                content,
+                byte_length,
+                language,
+                ..
+            } => {
+                assert_eq!(status, Status::Ok);
+                assert_eq!(turn_id.as_deref(), Some("s1:1#code2"));
+                assert_eq!(content, Some(b"a = 1\n".to_vec()));
+                assert_eq!(byte_length, Some(6));
+                assert_eq!(language.as_deref(), Some("toml"));
+            }
+            _ => panic!("expected Response"),
+        }
+
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::GetTurn {
+                id: 12,
+                turn_id: "s1:1#code3".into(),
+                format: None,
+            },
+            c,
+        );
+        match resp {
+            Message::Response { error, .. } => {
+                assert_eq!(error.as_deref(), Some("fragment_not_found"));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    #[test]
+    fn late_turns_keep_detection_timestamps() {
+        // A wrapper registering late sends the turns it held, oldest
+        // first, stamped when they were detected.
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+        let detected = [1_000, 61_000, 62_500];
+        for (id, timestamp) in (2..).zip(detected) {
+            let (resp, _) = handle_message(
+                &mut s,
+                Message::TurnCompleted {
+                    id,
+                    session: "s1".into(),
+                    content: b"held".to_vec(),
+                    interrupted: false,
+                    timestamp,
+                    cols: 80,
+                    rows: 24,
+                    exit_status: None,
+                    prompt: None,
+                    screen_rendered: false,
+                },
+                c,
+            );
+            assert!(matches!(
+                resp,
+                Message::Response {
+                    status: Status::Ok,
+                    ..
+                }
+            ));
+        }
+
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::ListTurns {
+                id: 10,
+                session: "s1".into(),
+                limit: None,
+            },
+            c,
+        );
+        let Message::Response { turns, .. } = resp else {
+            panic!("expected Response");
+        };
+        let listed: Vec<_> = turns
+            .unwrap()
+            .iter()
+            .map(|t| (t.turn_id.clone(), t.timestamp))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("s1:3".to_string(), 62_500),
+                ("s1:2".to_string(), 61_000),
+                ("s1:1".to_string(), 1_000),
+            ]
+        );
+    }
+
     #[test]
     fn list_turns_with_limit() {
         let (mut s, c) = fresh();
@@ -1194,6 +2097,11 @@ mod tests {
                     content: b"x".to_vec(),
                     interrupted: false,
                     timestamp: 1000,
+                    cols: 80,
+                    rows: 24,
+                    exit_status: None,
+                    prompt: None,
+                    screen_rendered: false,
                 },
                 c,
             );
@@ -1252,6 +2160,11 @@ mod tests {
                 content: b"first".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -1263,6 +2176,11 @@ mod tests {
                 content: b"second".to_vec(),
                 interrupted: false,
                 timestamp: 2000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c,
         );
@@ -1273,6 +2191,7 @@ mod tests {
             Message::CaptureByID {
                 id: 10,
                 turn_id: "s1:1".into(),
+                format: None,
             },
             c,
         );
@@ -1299,6 +2218,7 @@ mod tests {
             Message::CaptureByID {
                 id: 10,
                 turn_id: "s1:999".into(),
+                format: None,
             },
             c,
         );
@@ -1333,6 +2253,11 @@ mod tests {
                 content: b"first".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c1,
         );
@@ -1344,6 +2269,11 @@ mod tests {
                 content: b"second".to_vec(),
                 interrupted: false,
                 timestamp: 2000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c1,
         );
@@ -1354,6 +2284,7 @@ mod tests {
             Message::CaptureByID {
                 id: 4,
                 turn_id: "s1:1".into(),
+                format: None,
             },
             c2,
         );
@@ -1364,6 +2295,8 @@ mod tests {
             Message::Paste {
                 id: 5,
                 session: "s1".into(),
+                format: None,
+                options: InjectOptions::default(),
             },
             c2,
         );
@@ -1412,6 +2345,11 @@ mod tests {
                 content: b"data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             w,
         );
@@ -1422,6 +2360,7 @@ mod tests {
             Message::GetTurn {
                 id: 10,
                 turn_id: "s1:1".into(),
+                format: None,
             },
             c,
         );
@@ -1457,6 +2396,7 @@ mod tests {
             Message::CaptureByID {
                 id: 12,
                 turn_id: "s1:1".into(),
+                format: None,
             },
             c,
         );
@@ -1494,6 +2434,11 @@ mod tests {
                 content: b"turn data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             c1,
         );
@@ -1502,6 +2447,7 @@ mod tests {
             Message::Capture {
                 id: 3,
                 session: "s1".into(),
+                format: None,
             },
             c2,
         );
@@ -1518,6 +2464,8 @@ mod tests {
                 sink: "inject".into(),
                 session: Some("s1".into()),
                 path: None,
+                format: None,
+                options: InjectOptions::default(),
             },
             c2,
         );
@@ -1543,6 +2491,100 @@ mod tests {
         }
     }
 
+    #[test]
+    fn inject_carries_options() {
+        let (mut s, _c1, c2) = setup_with_captured_turn();
+        let requests = [
+            Message::Paste {
+                id: 10,
+                session: "s1".into(),
+                format: None,
+                options: InjectOptions {
+                    bracketed: Some(false),
+                    ..InjectOptions::default()
+                },
+            },
+            Message::Deliver {
+                id: 11,
+                sink: "inject".into(),
+                session: Some("s1".into()),
+                path: None,
+                format: None,
+                options: InjectOptions {
+                    bracketed: Some(true),
+                    submit: true,
+                    ..InjectOptions::default()
+                },
+            },
+        ];
+        for request in requests {
+            let expected = match &request {
+                Message::Paste { options, .. } | Message::Deliver { options, .. } => {
+                    options.clone()
+                }
+                _ => unreachable!(),
+            };
+            match handle_message(&mut s, request, c2).1 {
+                Some(SideEffect::Inject { action, .. }) => match action.message {
+                    Message::Inject { options, .. } => assert_eq!(options, expected),
+                    _ => panic!("expected Inject message"),
+                },
+                _ => panic!("expected SideEffect::Inject"),
+            }
+        }
+    }
+
+    #[test]
+    fn inject_options_checked() {
+        let (mut s, _c1, c2) = setup_with_captured_turn();
+        let paste = |id, options| Message::Paste {
+            id,
+            session: "s1".into(),
+            format: None,
+            options,
+        };
+        let cases = [
+            (
+                InjectOptions {
+                    max_bytes: Some(4),
+                    ..InjectOptions::default()
+                },
+                "content_too_large",
+            ),
+            (
+                InjectOptions {
+                    chunk_size: Some(0),
+                    ..InjectOptions::default()
+                },
+                "invalid_options",
+            ),
+            (
+                InjectOptions {
+                    chunk_size: Some(64),
+                    chunk_delay_ms: Some(MAX_CHUNK_DELAY_MS + 1),
+                    ..InjectOptions::default()
+                },
+                "invalid_options",
+            ),
+        ];
+        for (id, (options, reason)) in (10..).zip(cases) {
+            let (resp, effect) = handle_message(&mut s, paste(id, options), c2);
+            assert!(effect.is_none());
+            match resp {
+                Message::Response { error, .. } => assert_eq!(error.as_deref(), Some(reason)),
+                other => panic!("expected Response, got {other:?}"),
+            }
+        }
+
+        // "turn data" is exactly 9 bytes.
+        let options = InjectOptions {
+            max_bytes: Some(9),
+            ..InjectOptions::default()
+        };
+        let (_, effect) = handle_message(&mut s, paste(20, options), c2);
+        assert!(matches!(effect, Some(SideEffect::Inject { .. })));
+    }
+
     #[test]
     fn deliver_inject_missing_session() {
         let (mut s, _c1, c2) = setup_with_captured_turn();
@@ -1553,6 +2595,8 @@ mod tests {
                 sink: "inject".into(),
                 session: None,
                 path: None,
+                format: None,
+                options: InjectOptions::default(),
             },
             c2,
         );
@@ -1575,6 +2619,8 @@ mod tests {
                 sink: "clipboard".into(),
                 session: None,
                 path: None,
+                format: None,
+                options: InjectOptions::default(),
             },
             c2,
         );
@@ -1613,6 +2659,8 @@ mod tests {
                 sink: "clipboard".into(),
                 session: None,
                 path: None,
+                format: None,
+                options: InjectOptions::default(),
             },
             c,
         );
@@ -1635,6 +2683,8 @@ mod tests {
                 sink: "file".into(),
                 session: None,
                 path: Some("/tmp/turn.txt".into()),
+                format: None,
+                options: InjectOptions::default(),
             },
             c2,
         );
@@ -1674,6 +2724,8 @@ mod tests {
                 sink: "file".into(),
                 session: None,
                 path: None,
+                format: None,
+                options: InjectOptions::default(),
             },
             c2,
         );
@@ -1696,6 +2748,8 @@ mod tests {
                 sink: "fax_machine".into(),
                 session: None,
                 path: None,
+                format: None,
+                options: InjectOptions::default(),
             },
             c2,
         );
@@ -7,7 +7,8 @@
 //! Architecture: channel-based actor. A single broker loop owns all
 //! mutable state ([`state::BrokerState`]). Per-connection tasks
 //! forward commands via mpsc channels. Inject commands for paste
-//! are routed to wrapper connections via per-connection channels.
+//! are routed to wrapper connections via per-connection channels;
+//! mirrored output goes to viewers through bounded ones.
 //!
 //! See CONTRACT_BROKER.md.
 
@@ -26,6 +27,7 @@ use tokio::sync::mpsc;
 use connection::{BrokerCommand, DisconnectNotice};
 use handler::{InjectAction, SideEffect};
 use state::{BrokerState, ConnectionId};
+use tokio::sync::mpsc::error::TrySendError;
 
 use crate::ipc::protocol::Message;
 
@@ -35,6 +37,14 @@ use crate::ipc::protocol::Message;
 /// independent of resolver types.
 pub type ClipboardWriterFn = Box<dyn Fn(&[u8]) -> Result<(), String> + Send + Sync>;
 
+/// Output messages buffered per viewer. A viewer that falls this far
+/// behind is cut off (CONTRACT_BROKER.md §Live Mirroring).
+pub const TAIL_BUFFER: usize = 256;
+
+/// How often ended sessions are checked for the end of their grace
+/// period (CONTRACT_BROKER.md §Ended sessions).
+const EXPIRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
 /// Broker startup/runtime errors.
 #[derive(Debug, thiserror::Error)]
 pub enum BrokerError {
@@ -84,6 +94,8 @@ pub async fn run(
 
     // Per-connection inject channels for paste → inject routing.
     let mut inject_senders: HashMap<ConnectionId, mpsc::UnboundedSender<Message>> = HashMap::new();
+    // Per-connection bounded channels for mirrored output to viewers.
+    let mut mirror_senders: HashMap<ConnectionId, mpsc::Sender<Message>> = HashMap::new();
 
     let mut state = BrokerState::new(config);
 
@@ -91,6 +103,8 @@ pub async fn run(
     let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
     let mut sigint = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?;
 
+    let mut expiry = tokio::time::interval(EXPIRY_INTERVAL);
+
     loop {
         tokio::select! {
             // -- New connection --
@@ -102,6 +116,7 @@ pub async fn run(
                             &cmd_tx,
                             &disconnect_tx,
                             &mut inject_senders,
+                            &mut mirror_senders,
                         );
                     }
                     Err(e) => {
@@ -136,6 +151,21 @@ pub async fn run(
                                 response = handler::error_response(request_id, &reason);
                             }
                         }
+                        SideEffect::Tail { session, viewers, data } => {
+                            dispatch_tail(
+                                &mut state,
+                                &inject_senders,
+                                &mut mirror_senders,
+                                &session,
+                                viewers,
+                                data,
+                            );
+                        }
+                        SideEffect::EndTail { session, viewers, reason } => {
+                            for viewer in viewers {
+                                end_tail(&inject_senders, &mirror_senders, viewer, &session, reason);
+                            }
+                        }
                     }
                 }
 
@@ -145,11 +175,15 @@ pub async fn run(
             // -- Connection disconnected --
             Some(notice) = disconnect_rx.recv() => {
                 let conn_id = notice.connection_id;
-                inject_senders.remove(&conn_id);
-                state.remove_connection(conn_id);
+                remove_connection(&mut state, &mut inject_senders, &mut mirror_senders, conn_id);
                 tracing::debug!(?conn_id, "connection cleaned up");
             }
 
+            // -- Ended sessions past their grace period --
+            _ = expiry.tick() => {
+                expire_sessions(&mut state);
+            }
+
             // -- Shutdown signals --
             _ = sigterm.recv() => {
                 tracing::info!("received SIGTERM, shutting down");
@@ -178,16 +212,20 @@ fn accept_connection(
     cmd_tx: &mpsc::UnboundedSender<BrokerCommand>,
     disconnect_tx: &mpsc::UnboundedSender<DisconnectNotice>,
     inject_senders: &mut HashMap<ConnectionId, mpsc::UnboundedSender<Message>>,
+    mirror_senders: &mut HashMap<ConnectionId, mpsc::Sender<Message>>,
 ) {
     let conn_id = ConnectionId::new();
     let (inject_tx, inject_rx) = mpsc::unbounded_channel();
     inject_senders.insert(conn_id, inject_tx);
+    let (mirror_tx, mirror_rx) = mpsc::channel(TAIL_BUFFER);
+    mirror_senders.insert(conn_id, mirror_tx);
 
     connection::spawn_connection(
         stream,
         conn_id,
         cmd_tx.clone(),
         inject_rx,
+        mirror_rx,
         disconnect_tx.clone(),
     );
 
@@ -221,6 +259,117 @@ fn dispatch_inject(
     false
 }
 
+/// Clean up after a closed connection: its channels, what it
+/// followed, and its session, whose viewers are told it ended.
+fn remove_connection(
+    state: &mut BrokerState,
+    inject_senders: &mut HashMap<ConnectionId, mpsc::UnboundedSender<Message>>,
+    mirror_senders: &mut HashMap<ConnectionId, mpsc::Sender<Message>>,
+    conn_id: ConnectionId,
+) {
+    inject_senders.remove(&conn_id);
+    mirror_senders.remove(&conn_id);
+    if let Some(left) = state.unsubscribe(conn_id) {
+        stop_mirror(inject_senders, left);
+    }
+    for (viewer, session) in state.remove_connection(conn_id, crate::turn::epoch_millis()) {
+        end_tail(
+            inject_senders,
+            mirror_senders,
+            viewer,
+            &session,
+            "session_ended",
+        );
+    }
+}
+
+/// Remove ended sessions whose grace period is over.
+fn expire_sessions(state: &mut BrokerState) {
+    for session in state.expire_sessions(crate::turn::epoch_millis()) {
+        tracing::debug!(session, "ended session expired");
+    }
+}
+
+/// Queue a session's output for each of its viewers.
+///
+/// Never waits: a viewer whose buffer is full has fallen behind, and
+/// is cut off with `tail_ended` (`lagged`) rather than holding up the
+/// broker and, through it, the wrapper.
+fn dispatch_tail(
+    state: &mut BrokerState,
+    inject_senders: &HashMap<ConnectionId, mpsc::UnboundedSender<Message>>,
+    mirror_senders: &mut HashMap<ConnectionId, mpsc::Sender<Message>>,
+    session: &str,
+    viewers: Vec<ConnectionId>,
+    data: Vec<u8>,
+) {
+    for viewer in viewers {
+        let Some(tx) = mirror_senders.get(&viewer) else {
+            continue;
+        };
+        let message = Message::Tail {
+            id: 0,
+            session: session.to_string(),
+            data: data.clone(),
+        };
+        if let Err(TrySendError::Full(_)) = tx.try_send(message) {
+            tracing::warn!(conn_id = ?viewer, session, "viewer fell behind — cutting off");
+            // Dropping the sender ends its output once drained.
+            mirror_senders.remove(&viewer);
+            end_tail(inject_senders, mirror_senders, viewer, session, "lagged");
+            if let Some(left) = state.unsubscribe(viewer) {
+                stop_mirror(inject_senders, left);
+            }
+        }
+    }
+}
+
+/// Tell a viewer that no more output of `session` follows: after its
+/// buffered output if there is room, else straight away.
+fn end_tail(
+    inject_senders: &HashMap<ConnectionId, mpsc::UnboundedSender<Message>>,
+    mirror_senders: &HashMap<ConnectionId, mpsc::Sender<Message>>,
+    viewer: ConnectionId,
+    session: &str,
+    reason: &str,
+) {
+    let message = Message::TailEnded {
+        id: 0,
+        session: session.to_string(),
+        reason: reason.to_string(),
+    };
+    let message = match mirror_senders.get(&viewer) {
+        Some(tx) => match tx.try_send(message) {
+            Ok(()) => return,
+            Err(TrySendError::Full(message) | TrySendError::Closed(message)) => message,
+        },
+        None => message,
+    };
+    if let Some(tx) = inject_senders.get(&viewer) {
+        let _ = tx.send(message);
+    }
+}
+
+/// Ask a session's wrapper to stop mirroring once its last viewer has
+/// left.
+fn stop_mirror(
+    inject_senders: &HashMap<ConnectionId, mpsc::UnboundedSender<Message>>,
+    left: state::Unsubscribed,
+) {
+    let Some(wrapper) = left.last_of else {
+        return;
+    };
+    let action = InjectAction {
+        target_connection: wrapper,
+        message: Message::Mirror {
+            id: 0,
+            session: left.session,
+            enabled: false,
+        },
+    };
+    dispatch_inject(inject_senders, action);
+}
+
 // -- Socket setup --
 
 /// Resolve the broker socket path from `$XDG_RUNTIME_DIR`.
@@ -299,7 +448,9 @@ mod tests {
     use tokio_util::codec::Framed;
 
     use crate::ipc::codec::{FrameCodec, LengthPrefixedCodec};
-    use crate::ipc::protocol::{Message, PROTOCOL_VERSION, Role, Status};
+    use crate::ipc::protocol::{
+        EndReason, InjectOptions, Message, PROTOCOL_VERSION, Role, SessionState, Status,
+    };
 
     /// Start a broker on a temp socket and return the socket path.
     /// The broker runs as a background task and is cancelled on drop.
@@ -313,6 +464,7 @@ mod tests {
             let (disconnect_tx, mut disconnect_rx) = mpsc::unbounded_channel::<DisconnectNotice>();
             let mut inject_senders: HashMap<ConnectionId, mpsc::UnboundedSender<Message>> =
                 HashMap::new();
+            let mut mirror_senders: HashMap<ConnectionId, mpsc::Sender<Message>> = HashMap::new();
             let mut state = BrokerState::new(state::RingConfig::default());
 
             // Test clipboard writer — uses xclip like the real broker.
@@ -338,6 +490,7 @@ mod tests {
                     Err("clipboard_failed".to_string())
                 }
             });
+            let mut expiry = tokio::time::interval(EXPIRY_INTERVAL);
 
             loop {
                 tokio::select! {
@@ -348,6 +501,7 @@ mod tests {
                                 &cmd_tx,
                                 &disconnect_tx,
                                 &mut inject_senders,
+                                &mut mirror_senders,
                             );
                         }
                     }
@@ -375,13 +529,35 @@ mod tests {
                                         response = handler::error_response(request_id, &reason);
                                     }
                                 }
+                                SideEffect::Tail { session, viewers, data } => {
+                                    dispatch_tail(
+                                        &mut state,
+                                        &inject_senders,
+                                        &mut mirror_senders,
+                                        &session,
+                                        viewers,
+                                        data,
+                                    );
+                                }
+                                SideEffect::EndTail { session, viewers, reason } => {
+                                    for viewer in viewers {
+                                        end_tail(&inject_senders, &mirror_senders, viewer, &session, reason);
+                                    }
+                                }
                             }
                         }
                         let _ = cmd.response_tx.send(response);
                     }
                     Some(notice) = disconnect_rx.recv() => {
-                        inject_senders.remove(&notice.connection_id);
-                        state.remove_connection(notice.connection_id);
+                        remove_connection(
+                            &mut state,
+                            &mut inject_senders,
+                            &mut mirror_senders,
+                            notice.connection_id,
+                        );
+                    }
+                    _ = expiry.tick() => {
+                        expire_sessions(&mut state);
                     }
                 }
             }
@@ -440,6 +616,14 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                boundary: None,
+                parser: None,
+                name: None,
+                command: Vec::new(),
+                cwd: None,
+                started_at: None,
+                cols: 0,
+                rows: 0,
             },
         )
         .await;
@@ -460,6 +644,11 @@ mod tests {
                 content: b"hello from agent".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
         )
         .await;
@@ -480,6 +669,7 @@ mod tests {
             Message::Capture {
                 id: 1,
                 session: "s1".into(),
+                format: None,
             },
         )
         .await;
@@ -500,6 +690,8 @@ mod tests {
             Message::Paste {
                 id: 2,
                 session: "s1".into(),
+                format: None,
+                options: InjectOptions::default(),
             },
         )
         .await;
@@ -514,7 +706,12 @@ mod tests {
         // -- Wrapper should receive the inject command --
         let inject = wrapper.next().await.unwrap().unwrap();
         match inject {
-            Message::Inject { id, content } => {
+            Message::Inject {
+                id,
+                content,
+                options,
+            } => {
+                assert_eq!(options, InjectOptions::default());
                 assert_eq!(id, 0); // Unsolicited
                 assert_eq!(content, b"hello from agent");
             }
@@ -554,7 +751,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn implicit_deregister_on_disconnect() {
+    async fn disconnect_ends_session() {
         let dir = tempfile::tempdir().unwrap();
         let sock = dir.path().join("broker.sock");
         let _broker = start_broker(&sock).await;
@@ -570,6 +767,30 @@ mod tests {
                 session: "s-temp".into(),
                 pid: 1,
                 pattern: "generic".into(),
+                boundary: None,
+                parser: None,
+                name: None,
+                command: Vec::new(),
+                cwd: None,
+                started_at: None,
+                cols: 0,
+                rows: 0,
+            },
+        )
+        .await;
+        send_recv(
+            &mut wrapper,
+            Message::TurnCompleted {
+                id: 2,
+                session: "s-temp".into(),
+                content: b"last words".to_vec(),
+                interrupted: false,
+                timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
         )
         .await;
@@ -578,21 +799,52 @@ mod tests {
         drop(wrapper);
         tokio::time::sleep(std::time::Duration::from_millis(50)).await;
 
-        // Client tries to capture the disconnected session.
+        // The session has ended, but its turns are still there.
         let mut client = connect(&sock).await;
         handshake(&mut client, Role::Client).await;
+        match send_recv(&mut client, Message::ListSessions { id: 1 }).await {
+            Message::Response {
+                sessions: Some(sessions),
+                ..
+            } => {
+                assert_eq!(sessions[0].state, SessionState::Ended);
+                assert_eq!(sessions[0].end_reason, Some(EndReason::Disconnected));
+            }
+            other => panic!("expected sessions, got {other:?}"),
+        }
         let resp = send_recv(
             &mut client,
             Message::Capture {
-                id: 1,
+                id: 2,
                 session: "s-temp".into(),
+                format: None,
             },
         )
         .await;
+        match resp {
+            Message::Response {
+                status, turn_id, ..
+            } => {
+                assert_eq!(status, Status::Ok);
+                assert_eq!(turn_id.as_deref(), Some("s-temp:1"));
+            }
+            other => panic!("expected capture response, got {other:?}"),
+        }
 
+        // Pasting into it fails.
+        let resp = send_recv(
+            &mut client,
+            Message::Paste {
+                id: 3,
+                session: "s-temp".into(),
+                format: None,
+                options: InjectOptions::default(),
+            },
+        )
+        .await;
         match resp {
             Message::Response { error, .. } => {
-                assert_eq!(error.as_deref(), Some("session_not_found"));
+                assert_eq!(error.as_deref(), Some("session_disconnected"));
             }
             other => panic!("expected error response, got {other:?}"),
         }
@@ -615,6 +867,14 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                boundary: None,
+                parser: None,
+                name: None,
+                command: Vec::new(),
+                cwd: None,
+                started_at: None,
+                cols: 0,
+                rows: 0,
             },
         )
         .await;
@@ -626,6 +886,11 @@ mod tests {
                 content: b"data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
         )
         .await;
@@ -661,6 +926,14 @@ mod tests {
             session: "s1".into(),
             pid: 42,
             pattern: "generic".into(),
+            boundary: None,
+            parser: None,
+            name: None,
+            command: Vec::new(),
+            cwd: None,
+            started_at: None,
+            cols: 0,
+            rows: 0,
         })
         .await
         .unwrap();
@@ -794,6 +1067,14 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                boundary: None,
+                parser: None,
+                name: None,
+                command: Vec::new(),
+                cwd: None,
+                started_at: None,
+                cols: 0,
+                rows: 0,
             },
         )
         .await;
@@ -806,6 +1087,11 @@ mod tests {
                 content: b"first turn".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
         )
         .await;
@@ -822,6 +1108,11 @@ mod tests {
                 content: b"second turn".to_vec(),
                 interrupted: true,
                 timestamp: 2000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
         )
         .await;
@@ -840,6 +1131,7 @@ mod tests {
             Message::GetTurn {
                 id: 10,
                 turn_id: first_turn_id.clone(),
+                format: None,
             },
         )
         .await;
@@ -919,6 +1211,7 @@ mod tests {
             Message::GetTurn {
                 id: 13,
                 turn_id: "s1:999".into(),
+                format: None,
             },
         )
         .await;
@@ -948,6 +1241,14 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                boundary: None,
+                parser: None,
+                name: None,
+                command: Vec::new(),
+                cwd: None,
+                started_at: None,
+                cols: 0,
+                rows: 0,
             },
         )
         .await;
@@ -960,6 +1261,11 @@ mod tests {
                 content: b"older turn content".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
         )
         .await;
@@ -976,6 +1282,11 @@ mod tests {
                 content: b"newer turn content".to_vec(),
                 interrupted: false,
                 timestamp: 2000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
         )
         .await;
@@ -989,6 +1300,7 @@ mod tests {
             Message::CaptureByID {
                 id: 10,
                 turn_id: first_turn_id.clone(),
+                format: None,
             },
         )
         .await;
@@ -1014,6 +1326,8 @@ mod tests {
             Message::Paste {
                 id: 11,
                 session: "s1".into(),
+                format: None,
+                options: InjectOptions::default(),
             },
         )
         .await;
@@ -1028,7 +1342,12 @@ mod tests {
         // Wrapper receives inject with the older turn's content.
         let inject = wrapper.next().await.unwrap().unwrap();
         match inject {
-            Message::Inject { id, content } => {
+            Message::Inject {
+                id,
+                content,
+                options,
+            } => {
+                assert_eq!(options, InjectOptions::default());
                 assert_eq!(id, 0); // Unsolicited
                 assert_eq!(content, b"older turn content");
             }
@@ -1041,6 +1360,7 @@ mod tests {
             Message::CaptureByID {
                 id: 12,
                 turn_id: "s1:999".into(),
+                format: None,
             },
         )
         .await;
@@ -1070,6 +1390,14 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                boundary: None,
+                parser: None,
+                name: None,
+                command: Vec::new(),
+                cwd: None,
+                started_at: None,
+                cols: 0,
+                rows: 0,
             },
         )
         .await;
@@ -1081,6 +1409,11 @@ mod tests {
                 content: b"deliver inject content".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
         )
         .await;
@@ -1093,6 +1426,7 @@ mod tests {
             Message::Capture {
                 id: 1,
                 session: "s1".into(),
+                format: None,
             },
         )
         .await;
@@ -1104,6 +1438,8 @@ mod tests {
                 sink: "inject".into(),
                 session: Some("s1".into()),
                 path: None,
+                format: None,
+                options: InjectOptions::default(),
             },
         )
         .await;
@@ -1118,7 +1454,12 @@ mod tests {
         // Wrapper should receive the inject command.
         let inject = wrapper.next().await.unwrap().unwrap();
         match inject {
-            Message::Inject { id, content } => {
+            Message::Inject {
+                id,
+                content,
+                options,
+            } => {
+                assert_eq!(options, InjectOptions::default());
                 assert_eq!(id, 0);
                 assert_eq!(content, b"deliver inject content");
             }
@@ -1126,6 +1467,178 @@ mod tests {
         }
     }
 
+    /// Connect a wrapper and register `session`.
+    async fn register_wrapper(
+        sock: &std::path::Path,
+        session: &str,
+    ) -> Framed<UnixStream, LengthPrefixedCodec> {
+        let mut wrapper = connect(sock).await;
+        handshake(&mut wrapper, Role::Wrapper).await;
+        send_recv(
+            &mut wrapper,
+            Message::Register {
+                id: 1,
+                session: session.into(),
+                pid: 42,
+                pattern: "generic".into(),
+                boundary: None,
+                parser: None,
+                name: Some("planner".into()),
+                command: Vec::new(),
+                cwd: None,
+                started_at: None,
+                cols: 0,
+                rows: 0,
+            },
+        )
+        .await;
+        wrapper
+    }
+
+    fn output(id: u32, data: &[u8]) -> Message {
+        Message::Output {
+            id,
+            session: "s1".into(),
+            data: data.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn tail_follows_session_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("broker.sock");
+        let _broker = start_broker(&sock).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let mut wrapper = register_wrapper(&sock, "s1").await;
+
+        // Output nobody follows goes nowhere.
+        let resp = send_recv(&mut wrapper, output(2, b"unseen")).await;
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+
+        // The first viewer turns mirroring on; later ones do not.
+        let mut viewers = Vec::new();
+        for _ in 0..2 {
+            let mut viewer = connect(&sock).await;
+            handshake(&mut viewer, Role::Client).await;
+            let resp = send_recv(
+                &mut viewer,
+                Message::Subscribe {
+                    id: 1,
+                    session: "planner".into(),
+                },
+            )
+            .await;
+            assert!(matches!(
+                resp,
+                Message::Response {
+                    status: Status::Ok,
+                    ..
+                }
+            ));
+            viewers.push(viewer);
+        }
+        match wrapper.next().await.unwrap().unwrap() {
+            Message::Mirror {
+                session, enabled, ..
+            } => assert_eq!((session.as_str(), enabled), ("s1", true)),
+            other => panic!("expected Mirror, got {other:?}"),
+        }
+
+        // Output fans out to both.
+        send_recv(&mut wrapper, output(3, b"hello")).await;
+        for viewer in &mut viewers {
+            match viewer.next().await.unwrap().unwrap() {
+                Message::Tail { session, data, .. } => {
+                    assert_eq!((session.as_str(), data.as_slice()), ("s1", &b"hello"[..]));
+                }
+                other => panic!("expected Tail, got {other:?}"),
+            }
+        }
+
+        // The remaining viewer follows on when another leaves.
+        let mut last = viewers.pop().unwrap();
+        drop(viewers);
+        send_recv(&mut wrapper, output(4, b"still one")).await;
+        assert!(matches!(
+            last.next().await.unwrap().unwrap(),
+            Message::Tail { .. }
+        ));
+
+        // The session ending ends the tail.
+        send_recv(
+            &mut wrapper,
+            Message::Deregister {
+                id: 5,
+                session: "s1".into(),
+                exit_code: None,
+                reason: None,
+            },
+        )
+        .await;
+        match last.next().await.unwrap().unwrap() {
+            Message::TailEnded { reason, .. } => assert_eq!(reason, "session_ended"),
+            other => panic!("expected TailEnded, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn lagging_viewer_cut_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("broker.sock");
+        let _broker = start_broker(&sock).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let mut wrapper = register_wrapper(&sock, "s1").await;
+
+        let mut viewer = connect(&sock).await;
+        handshake(&mut viewer, Role::Client).await;
+        send_recv(
+            &mut viewer,
+            Message::Subscribe {
+                id: 1,
+                session: "s1".into(),
+            },
+        )
+        .await;
+
+        // The viewer reads nothing while the wrapper writes far more
+        // than its buffer and socket hold. The wrapper is never held
+        // up, and is told to stop once its only viewer is cut off.
+        let chunk = vec![b'x'; 64 * 1024];
+        let mut enabled = Vec::new();
+        for id in 2..(TAIL_BUFFER as u32 + 200) {
+            wrapper.send(output(id, &chunk)).await.unwrap();
+            loop {
+                match wrapper.next().await.unwrap().unwrap() {
+                    Message::Response { status, .. } => {
+                        assert_eq!(status, Status::Ok);
+                        break;
+                    }
+                    Message::Mirror { enabled: on, .. } => enabled.push(on),
+                    other => panic!("unexpected {other:?}"),
+                }
+            }
+        }
+        assert_eq!(enabled, [true, false]);
+
+        // The viewer gets what fitted, then the reason it was cut off.
+        loop {
+            match viewer.next().await.unwrap().unwrap() {
+                Message::Tail { .. } => {}
+                Message::TailEnded { reason, .. } => {
+                    assert_eq!(reason, "lagged");
+                    break;
+                }
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+    }
+
     #[tokio::test]
     async fn deliver_file_flow() {
         let dir = tempfile::tempdir().unwrap();
@@ -1144,6 +1657,14 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                boundary: None,
+                parser: None,
+                name: None,
+                command: Vec::new(),
+                cwd: None,
+                started_at: None,
+                cols: 0,
+                rows: 0,
             },
         )
         .await;
@@ -1155,6 +1676,11 @@ mod tests {
                 content: b"file sink content".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
         )
         .await;
@@ -1167,6 +1693,7 @@ mod tests {
             Message::Capture {
                 id: 1,
                 session: "s1".into(),
+                format: None,
             },
         )
         .await;
@@ -1178,6 +1705,8 @@ mod tests {
                 sink: "file".into(),
                 session: None,
                 path: Some(output_path.to_str().unwrap().into()),
+                format: None,
+                options: InjectOptions::default(),
             },
         )
         .await;
@@ -1210,6 +1739,7 @@ mod tests {
             message: Message::Inject {
                 id: 0,
                 content: b"data".to_vec(),
+                options: InjectOptions::default(),
             },
         };
         assert!(!dispatch_inject(&senders, action));
@@ -1223,6 +1753,7 @@ mod tests {
             message: Message::Inject {
                 id: 0,
                 content: b"data".to_vec(),
+                options: InjectOptions::default(),
             },
         };
         assert!(!dispatch_inject(&senders, action));
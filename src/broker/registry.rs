@@ -4,15 +4,33 @@
 //! with a bounded ring buffer of [`TurnRecord`] entries carrying stable
 //! turn IDs and metadata. See CONTRACT_REGISTRY.md.
 
+use std::borrow::Cow;
 use std::collections::VecDeque;
 
+use crate::ipc::protocol::ContentFormat;
+use crate::turn::fence::{self, CodeBlock};
+use crate::turn::parsers::{self, Parser, Segment, SegmentKind};
+use crate::turn::render;
+use crate::turn::screen::{self, ScreenSize};
+
 /// A single completed turn stored in the ring buffer.
+///
+/// Only the raw content is stored. The other representations, code
+/// blocks and segments are derived from it when asked for, so that a
+/// turn costs its raw size and an update of the turn in progress does
+/// no rendering.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TurnRecord {
     /// Stable turn identifier: `<session_id>:<seq>`.
     pub turn_id: String,
     /// Raw turn content (bytes, no interpretation).
     pub content: Vec<u8>,
+    /// The wrapper's terminal size, used to render the screen
+    /// representation.
+    pub size: ScreenSize,
+    /// The session's output parser, used to split the turn into
+    /// segments.
+    pub parser: Option<Parser>,
     /// Unix epoch milliseconds when the turn was stored.
     pub timestamp: u64,
     /// Length of the original content in bytes (before truncation).
@@ -21,6 +39,99 @@ pub struct TurnRecord {
     pub interrupted: bool,
     /// Whether the content was truncated to fit `max_turn_bytes`.
     pub truncated: bool,
+    /// Exit status from an OSC 133 `D` marker, if one ended the turn.
+    pub exit_status: Option<i32>,
+    /// The user input that started the turn (CONTRACT_TURN.md
+    /// §Exchange).
+    pub prompt: Option<Vec<u8>>,
+    /// `content` was extracted from the alternate screen
+    /// (CONTRACT_TURN.md §Alternate Screen).
+    pub screen_rendered: bool,
+}
+
+impl TurnRecord {
+    /// The turn's content in the given representation: the visible
+    /// text replayed through the screen model (CONTRACT_TURN.md
+    /// §Rendered content), line-by-line plain text (§Plain content),
+    /// the prompt quoted before the plain text (§Exchange), or the
+    /// prose or edit block segments (§Segments).
+    pub fn content_as(&self, format: ContentFormat) -> Cow<'_, [u8]> {
+        match format {
+            ContentFormat::Raw => Cow::Borrowed(&self.content),
+            ContentFormat::Screen => Cow::Owned(screen::render(&self.content, self.size)),
+            ContentFormat::Plain => Cow::Owned(self.plain()),
+            ContentFormat::Exchange => {
+                Cow::Owned(render::exchange(self.prompt.as_deref(), &self.plain()))
+            }
+            ContentFormat::Prose => Cow::Owned(parsers::view(&self.segments(), SegmentKind::Prose)),
+            ContentFormat::Edits => {
+                Cow::Owned(parsers::view(&self.segments(), SegmentKind::EditBlock))
+            }
+        }
+    }
+
+    /// Line-by-line plain text of the content.
+    pub fn plain(&self) -> Vec<u8> {
+        render::plain_text(&self.content)
+    }
+
+    /// Fenced code blocks in the plain text, addressable as
+    /// `<turn_id>#code<N>` (CONTRACT_REGISTRY.md §Fragments).
+    pub fn code_blocks(&self) -> Vec<CodeBlock> {
+        fence::code_blocks(&self.plain())
+    }
+
+    /// The `n`th code block (1-based), if the turn has one.
+    pub fn code_block(&self, n: usize) -> Option<CodeBlock> {
+        n.checked_sub(1)
+            .and_then(|i| self.code_blocks().into_iter().nth(i))
+    }
+
+    /// The plain text split into typed segments by the session's
+    /// parser (CONTRACT_TURN.md §Segments).
+    pub fn segments(&self) -> Vec<Segment> {
+        parsers::segments(self.parser, &self.plain())
+    }
+}
+
+/// Sequence part of the provisional turn ID, `<session_id>:current`
+/// (CONTRACT_REGISTRY.md §Current turn).
+pub const CURRENT_TURN: &str = "current";
+
+/// Split a turn reference `<session>:<seq>[#code<N>]` into the turn ID
+/// and the 1-based code block number, if a fragment is given.
+///
+/// Returns `"invalid_fragment"` for a `#` suffix that is not
+/// `code<N>` with `N >= 1`.
+pub fn parse_turn_ref(turn_ref: &str) -> Result<(&str, Option<usize>), &'static str> {
+    let Some((turn_id, fragment)) = turn_ref.split_once('#') else {
+        return Ok((turn_ref, None));
+    };
+    let n = fragment
+        .strip_prefix("code")
+        .filter(|n| n.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|n| n.parse::<usize>().ok())
+        .filter(|&n| n >= 1)
+        .ok_or("invalid_fragment")?;
+    Ok((turn_id, Some(n)))
+}
+
+/// Wrapper-reported metadata stored with a turn's content.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TurnMeta {
+    /// Whether the turn was interrupted (signal-terminated).
+    pub interrupted: bool,
+    /// Unix epoch milliseconds when the turn was detected.
+    pub timestamp: u64,
+    /// The wrapper's terminal size, used to render `rendered`.
+    pub size: ScreenSize,
+    /// Exit status from an OSC 133 `D` marker (CONTRACT_TURN.md
+    /// §Semantic Prompt Markers).
+    pub exit_status: Option<i32>,
+    /// The submitted user input that started the turn.
+    pub prompt: Option<Vec<u8>>,
+    /// The content was extracted from the alternate screen.
+    pub screen_rendered: bool,
 }
 
 /// Per-session ring buffer of completed turns.
@@ -30,10 +141,15 @@ pub struct TurnRecord {
 #[derive(Debug)]
 pub struct TurnRingBuffer {
     entries: VecDeque<TurnRecord>,
+    /// Provisional record of the turn in progress, addressable as
+    /// `<session_id>:current` until the turn completes.
+    current: Option<TurnRecord>,
     capacity: usize,
     max_turn_bytes: usize,
     next_seq: u64,
     session_id: String,
+    /// Output parser for the session's agent, if it has one.
+    parser: Option<Parser>,
 }
 
 impl TurnRingBuffer {
@@ -46,48 +162,96 @@ impl TurnRingBuffer {
         assert!(capacity >= 1, "ring buffer capacity must be >= 1");
         Self {
             entries: VecDeque::with_capacity(capacity),
+            current: None,
             capacity,
             max_turn_bytes,
             next_seq: 1,
             session_id,
+            parser: None,
         }
     }
 
+    /// Split turns into segments with `parser` (all prose if `None`).
+    pub fn with_parser(mut self, parser: Option<Parser>) -> Self {
+        self.set_parser(parser);
+        self
+    }
+
+    /// Split turns pushed from now on with `parser`. Turns already
+    /// stored keep the parser they were stored with.
+    pub fn set_parser(&mut self, parser: Option<Parser>) {
+        self.parser = parser;
+    }
+
     /// Push a new turn into the ring buffer.
     ///
     /// Assigns a monotonically increasing turn ID, truncates content
     /// if it exceeds `max_turn_bytes`, and evicts the oldest turn if
-    /// the buffer is at capacity.
+    /// the buffer is at capacity. The provisional `current` record, if
+    /// any, is dropped.
     ///
-    /// `timestamp` is the detection-time Unix epoch millis, set by the
-    /// wrapper when the turn was completed (CONTRACT_REGISTRY.md §73).
+    /// `meta` carries the wrapper-reported metadata stored with the
+    /// turn: the detection-time `timestamp` (Unix epoch millis, set by
+    /// the wrapper when the turn was completed; CONTRACT_REGISTRY.md
+    /// §73), the interrupted flag, exit status and prompt, and the
+    /// terminal `size` used to render the stored (possibly truncated)
+    /// content.
     ///
     /// Returns a reference to the newly inserted record.
-    pub fn push(&mut self, mut content: Vec<u8>, interrupted: bool, timestamp: u64) -> &TurnRecord {
+    pub fn push(&mut self, content: Vec<u8>, meta: TurnMeta) -> &TurnRecord {
         let turn_id = format!("{}:{}", self.session_id, self.next_seq);
         self.next_seq += 1;
+        let record = self.record(turn_id, content, meta);
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_back();
+        }
+
+        // The turn in progress has completed.
+        self.current = None;
+        self.entries.push_front(record);
+        &self.entries[0]
+    }
+
+    /// Replace the provisional record of the turn in progress.
+    ///
+    /// The record is built like a pushed turn (truncation; the raw
+    /// content only, rendered when read) but gets the ID
+    /// `<session_id>:current` and no sequence number. It is dropped by
+    /// the next [`push`](Self::push).
+    pub fn set_current(&mut self, content: Vec<u8>, meta: TurnMeta) -> &TurnRecord {
+        let turn_id = format!("{}:{CURRENT_TURN}", self.session_id);
+        let record = self.record(turn_id, content, meta);
+        self.current.insert(record)
+    }
+
+    /// Drop the provisional record: the turn in progress ended without
+    /// being pushed, or the session ended.
+    pub fn clear_current(&mut self) {
+        self.current = None;
+    }
 
+    /// Build a record, truncating content to `max_turn_bytes`.
+    fn record(&self, turn_id: String, mut content: Vec<u8>, meta: TurnMeta) -> TurnRecord {
         let byte_length = content.len() as u32;
         let truncated = content.len() > self.max_turn_bytes;
         if truncated {
             content.truncate(self.max_turn_bytes);
         }
 
-        let record = TurnRecord {
+        TurnRecord {
             turn_id,
             content,
-            timestamp,
+            size: meta.size,
+            parser: self.parser,
+            timestamp: meta.timestamp,
             byte_length,
-            interrupted,
+            interrupted: meta.interrupted,
             truncated,
-        };
-
-        if self.entries.len() == self.capacity {
-            self.entries.pop_back();
+            exit_status: meta.exit_status,
+            prompt: meta.prompt,
+            screen_rendered: meta.screen_rendered,
         }
-
-        self.entries.push_front(record);
-        &self.entries[0]
     }
 
     /// Get the most recent turn (ring head), or `None` if empty.
@@ -96,8 +260,13 @@ impl TurnRingBuffer {
     }
 
     /// Look up a turn by its ID. Linear scan (capacity is small).
+    ///
+    /// `<session_id>:current` resolves to the turn in progress, if any.
     pub fn get(&self, turn_id: &str) -> Option<&TurnRecord> {
-        self.entries.iter().find(|r| r.turn_id == turn_id)
+        self.current
+            .iter()
+            .chain(&self.entries)
+            .find(|r| r.turn_id == turn_id)
     }
 
     /// Iterate turns newest-first, with an optional limit.
@@ -115,6 +284,11 @@ impl TurnRingBuffer {
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
     }
+
+    /// Turns pushed so far, including any since evicted.
+    pub fn turn_count(&self) -> u64 {
+        self.next_seq - 1
+    }
 }
 
 #[cfg(test)]
@@ -125,10 +299,18 @@ mod tests {
         TurnRingBuffer::new("test-session".into(), capacity, 4 * 1024 * 1024)
     }
 
+    fn meta(interrupted: bool, timestamp: u64) -> TurnMeta {
+        TurnMeta {
+            interrupted,
+            timestamp,
+            ..TurnMeta::default()
+        }
+    }
+
     #[test]
     fn push_and_read_head() {
         let mut r = ring(4);
-        r.push(b"hello".to_vec(), false, 1000);
+        r.push(b"hello".to_vec(), meta(false, 1000));
         let head = r.head().unwrap();
         assert_eq!(head.content, b"hello");
         assert!(!head.interrupted);
@@ -139,9 +321,9 @@ mod tests {
     #[test]
     fn turn_id_format() {
         let mut r = ring(4);
-        r.push(b"a".to_vec(), false, 1000);
+        r.push(b"a".to_vec(), meta(false, 1000));
         assert_eq!(r.head().unwrap().turn_id, "test-session:1");
-        r.push(b"b".to_vec(), false, 1000);
+        r.push(b"b".to_vec(), meta(false, 1000));
         assert_eq!(r.head().unwrap().turn_id, "test-session:2");
     }
 
@@ -149,7 +331,7 @@ mod tests {
     fn sequence_monotonically_increasing() {
         let mut r = ring(8);
         for i in 1..=5 {
-            r.push(format!("turn-{i}").into_bytes(), false, 1000);
+            r.push(format!("turn-{i}").into_bytes(), meta(false, 1000));
             assert_eq!(r.head().unwrap().turn_id, format!("test-session:{i}"));
         }
     }
@@ -157,12 +339,12 @@ mod tests {
     #[test]
     fn ring_eviction_at_capacity() {
         let mut r = ring(3);
-        r.push(b"a".to_vec(), false, 1000); // seq 1
-        r.push(b"b".to_vec(), false, 1000); // seq 2
-        r.push(b"c".to_vec(), false, 1000); // seq 3
+        r.push(b"a".to_vec(), meta(false, 1000)); // seq 1
+        r.push(b"b".to_vec(), meta(false, 1000)); // seq 2
+        r.push(b"c".to_vec(), meta(false, 1000)); // seq 3
         assert_eq!(r.len(), 3);
 
-        r.push(b"d".to_vec(), false, 1000); // seq 4 — evicts seq 1
+        r.push(b"d".to_vec(), meta(false, 1000)); // seq 4 — evicts seq 1
         assert_eq!(r.len(), 3);
         assert!(r.get("test-session:1").is_none(), "seq 1 should be evicted");
         assert!(r.get("test-session:2").is_some());
@@ -173,17 +355,39 @@ mod tests {
     fn truncation_at_max_turn_bytes() {
         let mut r = TurnRingBuffer::new("s".into(), 4, 10);
         let content = vec![0u8; 20];
-        r.push(content, false, 1000);
+        r.push(content, meta(false, 1000));
         let head = r.head().unwrap();
         assert!(head.truncated);
         assert_eq!(head.content.len(), 10);
         assert_eq!(head.byte_length, 20); // Original length preserved
     }
 
+    #[test]
+    fn rendered_content_derived_from_raw() {
+        let mut r = ring(4);
+        r.push(b"50%\r\x1b[2K100%\r\n".to_vec(), meta(false, 1000));
+        let head = r.head().unwrap();
+        assert_eq!(head.content, b"50%\r\x1b[2K100%\r\n");
+        assert_eq!(*head.content_as(ContentFormat::Screen), *b"100%\n");
+    }
+
+    #[test]
+    fn plain_content_derived_from_raw() {
+        let mut r = ring(4);
+        r.push(
+            b"\x1b[32mok\x1b[0m   \r\nabk\x08c\r\n".to_vec(),
+            meta(false, 1000),
+        );
+        let head = r.head().unwrap();
+        assert_eq!(head.plain(), b"ok\nabc\n");
+        assert_eq!(*head.content_as(ContentFormat::Plain), *b"ok\nabc\n");
+        assert_eq!(*head.content_as(ContentFormat::Raw), *head.content);
+    }
+
     #[test]
     fn no_truncation_within_limit() {
         let mut r = TurnRingBuffer::new("s".into(), 4, 100);
-        r.push(vec![0u8; 50], false, 1000);
+        r.push(vec![0u8; 50], meta(false, 1000));
         let head = r.head().unwrap();
         assert!(!head.truncated);
         assert_eq!(head.content.len(), 50);
@@ -193,7 +397,7 @@ mod tests {
     #[test]
     fn get_hit_and_miss() {
         let mut r = ring(4);
-        r.push(b"data".to_vec(), false, 1000);
+        r.push(b"data".to_vec(), meta(false, 1000));
         assert!(r.get("test-session:1").is_some());
         assert!(r.get("test-session:999").is_none());
         assert!(r.get("other-session:1").is_none());
@@ -202,9 +406,9 @@ mod tests {
     #[test]
     fn iter_newest_first_ordering() {
         let mut r = ring(4);
-        r.push(b"first".to_vec(), false, 1000);
-        r.push(b"second".to_vec(), false, 1000);
-        r.push(b"third".to_vec(), false, 1000);
+        r.push(b"first".to_vec(), meta(false, 1000));
+        r.push(b"second".to_vec(), meta(false, 1000));
+        r.push(b"third".to_vec(), meta(false, 1000));
 
         let ids: Vec<&str> = r
             .iter_newest_first(None)
@@ -220,7 +424,7 @@ mod tests {
     fn iter_newest_first_with_limit() {
         let mut r = ring(8);
         for _ in 0..5 {
-            r.push(b"x".to_vec(), false, 1000);
+            r.push(b"x".to_vec(), meta(false, 1000));
         }
         let count = r.iter_newest_first(Some(2)).count();
         assert_eq!(count, 2);
@@ -237,21 +441,94 @@ mod tests {
     #[test]
     fn timestamp_preserved_from_caller() {
         let mut r = ring(4);
-        r.push(b"data".to_vec(), false, 1700000000000);
+        r.push(b"data".to_vec(), meta(false, 1700000000000));
         assert_eq!(r.head().unwrap().timestamp, 1700000000000);
     }
 
     #[test]
     fn interrupted_flag_stored() {
         let mut r = ring(4);
-        r.push(b"data".to_vec(), true, 1000);
+        r.push(b"data".to_vec(), meta(true, 1000));
         assert!(r.head().unwrap().interrupted);
     }
 
+    #[test]
+    fn code_blocks_parsed_from_plain() {
+        let mut r = ring(4);
+        r.push(
+            b"See:\r\n\x1b[2m```rust\x1b[0m\r\nfn f() {}   \r\n```\r\n".to_vec(),
+            meta(false, 1000),
+        );
+        let head = r.head().unwrap();
+        assert_eq!(head.code_blocks().len(), 1);
+        let block = head.code_block(1).unwrap();
+        assert_eq!(block.language.as_deref(), Some("rust"));
+        assert_eq!(block.content, b"fn f() {}\n");
+        assert!(head.code_block(0).is_none());
+        assert!(head.code_block(2).is_none());
+    }
+
+    #[test]
+    fn segments_split_by_parser() {
+        let content = "⏺ Reading it.\r\n⏺ Read(a.rs)\r\n  ⎿  Read 3 lines\r\n⏺ Done.\r\n";
+        let mut r = ring(4).with_parser(Some(Parser::Claude));
+        r.push(content.as_bytes().to_vec(), meta(false, 1000));
+        let head = r.head().unwrap();
+        assert_eq!(head.segments().len(), 4);
+        assert_eq!(
+            *head.content_as(ContentFormat::Prose),
+            *b"Reading it.\n\nDone.\n"
+        );
+        assert!(head.content_as(ContentFormat::Edits).is_empty());
+
+        // Without a parser the whole turn is prose.
+        let mut r = ring(4);
+        r.push(content.as_bytes().to_vec(), meta(false, 1000));
+        let head = r.head().unwrap();
+        assert_eq!(head.segments().len(), 1);
+        assert_eq!(*head.content_as(ContentFormat::Prose), *head.plain());
+    }
+
+    #[test]
+    fn current_turn_replaced_and_cleared() {
+        let mut r = ring(4);
+        r.push(b"done".to_vec(), meta(false, 1000));
+        r.set_current(b"par".to_vec(), meta(false, 2000));
+        r.set_current(b"partial".to_vec(), meta(false, 3000));
+
+        let current = r.get("test-session:current").unwrap();
+        assert_eq!(current.content, b"partial");
+        assert_eq!(current.timestamp, 3000);
+        // Rendered from the latest progress when read.
+        assert_eq!(*current.content_as(ContentFormat::Plain), *b"partial\n");
+        // Not part of the history.
+        assert_eq!(r.len(), 1);
+        assert_eq!(r.head().unwrap().turn_id, "test-session:1");
+
+        r.push(b"partial done".to_vec(), meta(false, 4000));
+        assert!(r.get("test-session:current").is_none());
+        assert_eq!(r.head().unwrap().turn_id, "test-session:2");
+
+        r.set_current(b"abandoned".to_vec(), meta(false, 5000));
+        r.clear_current();
+        assert!(r.get("test-session:current").is_none());
+        assert_eq!(r.head().unwrap().turn_id, "test-session:2");
+    }
+
+    #[test]
+    fn parse_turn_ref_forms() {
+        assert_eq!(parse_turn_ref("s1:3"), Ok(("s1:3", None)));
+        assert_eq!(parse_turn_ref("s1:3#code2"), Ok(("s1:3", Some(2))));
+        assert_eq!(parse_turn_ref("s1:3#code0"), Err("invalid_fragment"));
+        assert_eq!(parse_turn_ref("s1:3#code"), Err("invalid_fragment"));
+        assert_eq!(parse_turn_ref("s1:3#code+1"), Err("invalid_fragment"));
+        assert_eq!(parse_turn_ref("s1:3#line4"), Err("invalid_fragment"));
+    }
+
     #[test]
     fn metadata_correctness() {
         let mut r = ring(4);
-        r.push(b"hello world".to_vec(), true, 42000);
+        r.push(b"hello world".to_vec(), meta(true, 42000));
         let head = r.head().unwrap();
         assert_eq!(head.byte_length, 11);
         assert!(head.interrupted);
@@ -263,21 +540,21 @@ mod tests {
     #[test]
     fn sequence_continues_after_eviction() {
         let mut r = ring(2);
-        r.push(b"a".to_vec(), false, 1000); // seq 1
-        r.push(b"b".to_vec(), false, 1000); // seq 2
-        r.push(b"c".to_vec(), false, 1000); // seq 3 — evicts seq 1
+        r.push(b"a".to_vec(), meta(false, 1000)); // seq 1
+        r.push(b"b".to_vec(), meta(false, 1000)); // seq 2
+        r.push(b"c".to_vec(), meta(false, 1000)); // seq 3 — evicts seq 1
         assert_eq!(r.head().unwrap().turn_id, "test-session:3");
         // Sequence never resets
-        r.push(b"d".to_vec(), false, 1000); // seq 4
+        r.push(b"d".to_vec(), meta(false, 1000)); // seq 4
         assert_eq!(r.head().unwrap().turn_id, "test-session:4");
     }
 
     #[test]
     fn capacity_one_ring() {
         let mut r = ring(1);
-        r.push(b"first".to_vec(), false, 1000);
+        r.push(b"first".to_vec(), meta(false, 1000));
         assert_eq!(r.len(), 1);
-        r.push(b"second".to_vec(), false, 1000);
+        r.push(b"second".to_vec(), meta(false, 1000));
         assert_eq!(r.len(), 1);
         assert_eq!(r.head().unwrap().content, b"second");
         assert!(r.get("test-session:1").is_none());
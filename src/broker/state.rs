@@ -4,20 +4,30 @@
 //! are machine-readable reasons from CONTRACT_BROKER.md §Error Semantics
 //! and CONTRACT_REGISTRY.md.
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::ipc::protocol::{Role, SessionDescriptor};
+use crate::ipc::protocol::{
+    ContentFormat, EndReason, Role, SessionDescriptor, SessionState, validate_session_name,
+};
+use crate::turn::fence::CodeBlock;
+use crate::turn::parsers::Parser;
+use crate::turn::screen::ScreenSize;
 
-use super::registry::{TurnRecord, TurnRingBuffer};
+use super::registry::{TurnMeta, TurnRecord, TurnRingBuffer, parse_turn_ref};
 
-/// Configuration for per-session turn ring buffers.
+/// Configuration for per-session turn ring buffers and how long they
+/// are retained.
 #[derive(Debug, Clone)]
 pub struct RingConfig {
     /// Maximum number of turns retained per session.
     pub depth: usize,
     /// Maximum byte size per turn (content is truncated beyond this).
     pub max_turn_bytes: usize,
+    /// How long an ended session is kept, in milliseconds
+    /// (CONTRACT_BROKER.md §Ended sessions). 0 removes it right away.
+    pub ended_grace_ms: u64,
 }
 
 impl Default for RingConfig {
@@ -25,6 +35,7 @@ impl Default for RingConfig {
         Self {
             depth: 32,
             max_turn_bytes: 4 * 1024 * 1024,
+            ended_grace_ms: 5 * 60 * 1000,
         }
     }
 }
@@ -43,13 +54,77 @@ pub struct SinkMetadata {
     pub truncated: bool,
 }
 
+/// A code block addressed by a turn reference, with its canonical
+/// `<turn_id>#code<N>` fragment ID.
+pub type Fragment = (String, CodeBlock);
+
+/// What a relay buffer entry holds.
+#[derive(Debug)]
+enum RelayContent {
+    /// A copy of a whole turn; paste derives the representation it
+    /// asks for.
+    Turn(TurnRecord),
+    /// A code block, already plain text: the same in every
+    /// representation.
+    Block(Vec<u8>),
+}
+
 /// Relay buffer entry — captured turn content with metadata.
+///
+/// The raw turn is kept so that paste can select any representation;
+/// `format` is the one chosen at capture time and used by default.
 #[derive(Debug)]
 struct RelayEntry {
-    content: Vec<u8>,
+    content: RelayContent,
+    format: ContentFormat,
     metadata: SinkMetadata,
 }
 
+impl RelayEntry {
+    fn from_record(record: &TurnRecord, format: ContentFormat) -> Self {
+        Self {
+            content: RelayContent::Turn(record.clone()),
+            format,
+            metadata: SinkMetadata {
+                turn_id: record.turn_id.clone(),
+                timestamp: record.timestamp,
+                byte_length: record.byte_length,
+                interrupted: record.interrupted,
+                truncated: record.truncated,
+            },
+        }
+    }
+
+    /// Relay entry for one code block of a turn. `fragment_id` is the
+    /// full `<turn_id>#code<N>` reference.
+    fn from_block(
+        record: &TurnRecord,
+        block: CodeBlock,
+        fragment_id: &str,
+        format: ContentFormat,
+    ) -> Self {
+        Self {
+            metadata: SinkMetadata {
+                turn_id: fragment_id.to_string(),
+                timestamp: record.timestamp,
+                byte_length: block.content.len() as u32,
+                interrupted: record.interrupted,
+                truncated: record.truncated,
+            },
+            content: RelayContent::Block(block.content),
+            format,
+        }
+    }
+
+    /// Content in the given format, or the capture-time format if `None`.
+    fn content_as(&self, format: Option<ContentFormat>) -> Cow<'_, [u8]> {
+        match &self.content {
+            RelayContent::Turn(record) => record.content_as(format.unwrap_or(self.format)),
+            RelayContent::Block(content) => Cow::Borrowed(content),
+        }
+    }
+}
+
 /// Result of a capture operation.
 #[derive(Debug, PartialEq, Eq)]
 pub struct CaptureResult {
@@ -71,13 +146,63 @@ impl ConnectionId {
     }
 }
 
+/// Wrapper-reported session metadata, carried by `register`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionMeta {
+    /// Turn boundary strategy spec (CONTRACT_TURN.md §Boundary
+    /// strategies). `None` for wrappers that do not report one.
+    pub boundary: Option<String>,
+    /// Output parser name (CONTRACT_TURN.md §Segments). Unknown names
+    /// are treated as none.
+    pub parser: Option<String>,
+    /// Human-chosen session name, unique among live sessions and
+    /// accepted wherever a session ID is (CONTRACT_BROKER.md §Session
+    /// names).
+    pub name: Option<String>,
+    /// Prompt preset name or custom regex.
+    pub pattern: Option<String>,
+    /// The wrapped command line; empty if not reported.
+    pub command: Vec<String>,
+    /// The command's working directory.
+    pub cwd: Option<String>,
+    /// Unix epoch millis when the session started.
+    pub started_at: Option<u64>,
+    /// Terminal size, as registered and then as reported with each
+    /// turn.
+    pub size: Option<ScreenSize>,
+}
+
+/// How and when a session ended (CONTRACT_BROKER.md §Ended sessions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionEnd {
+    /// Unix epoch millis.
+    pub at: u64,
+    /// The child's exit code, if the wrapper reported it.
+    pub exit_code: Option<i32>,
+    pub reason: EndReason,
+}
+
+/// A viewer that stopped following a session's live output.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Unsubscribed {
+    /// The session it followed.
+    pub session: String,
+    /// The session's wrapper, if this was its last viewer: mirroring
+    /// can stop.
+    pub last_of: Option<ConnectionId>,
+}
+
 /// Session entry in the broker's session table.
 #[derive(Debug)]
 struct SessionEntry {
     connection_id: ConnectionId,
     pid: u32,
+    meta: SessionMeta,
     /// Per-session ring buffer of completed turns.
     ring: TurnRingBuffer,
+    /// Set once the wrapper is gone; the entry is kept for the grace
+    /// period.
+    ended: Option<SessionEnd>,
 }
 
 /// Broker state — session table and relay buffer.
@@ -95,6 +220,9 @@ pub struct BrokerState {
     connections: HashMap<ConnectionId, Role>,
     /// Ring buffer configuration applied to new sessions.
     ring_config: RingConfig,
+    /// Connections following a session's live output, with the ID of
+    /// the session each follows (CONTRACT_BROKER.md §Live Mirroring).
+    viewers: HashMap<ConnectionId, String>,
 }
 
 impl BrokerState {
@@ -104,6 +232,7 @@ impl BrokerState {
             relay_buffer: None,
             connections: HashMap::new(),
             ring_config: config,
+            viewers: HashMap::new(),
         }
     }
 
@@ -120,48 +249,249 @@ impl BrokerState {
     /// Remove a connection and implicitly deregister any associated session.
     ///
     /// CONTRACT_BROKER.md §Implicit deregister: if a wrapper connection
-    /// drops without sending `deregister`, the session is removed.
-    pub fn remove_connection(&mut self, id: ConnectionId) {
+    /// drops without sending `deregister`, the session ends
+    /// (`disconnected`) at `now` (Unix epoch millis). Returns the
+    /// viewers of ended sessions, with the session each followed; they
+    /// stop following.
+    pub fn remove_connection(&mut self, id: ConnectionId, now: u64) -> Vec<(ConnectionId, String)> {
         self.connections.remove(&id);
-        // Find and remove any session owned by this connection.
-        self.sessions.retain(|_, entry| entry.connection_id != id);
+        // Find and end any live session owned by this connection.
+        let owned: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|(_, entry)| entry.connection_id == id && entry.ended.is_none())
+            .map(|(session, _)| session.clone())
+            .collect();
+        let end = SessionEnd {
+            at: now,
+            exit_code: None,
+            reason: EndReason::Disconnected,
+        };
+        let mut ended = Vec::new();
+        for session in owned {
+            for viewer in self.deregister_session(&session, id, end) {
+                ended.push((viewer, session.clone()));
+            }
+        }
+        ended
     }
 
-    /// Register a new session.
+    /// Register a new session, or reclaim an ended one.
     ///
-    /// Returns `Err("duplicate_session")` if the session ID is already registered.
+    /// A wrapper registering the ID of an ended session takes it over:
+    /// the session is live again, with its turns (CONTRACT_BROKER.md
+    /// §Ended sessions).
+    ///
+    /// Returns `Err("duplicate_session")` if the session ID is already
+    /// registered live (or is another session's name),
+    /// `Err("invalid_name")` for a malformed name and
+    /// `Err("duplicate_name")` if the name is taken by another live
+    /// session's name or any session's ID. A name held by an ended
+    /// session passes to the new one.
     pub fn register_session(
         &mut self,
         session_id: String,
         connection_id: ConnectionId,
         pid: u32,
+        meta: SessionMeta,
     ) -> Result<(), &'static str> {
-        if self.sessions.contains_key(&session_id) {
-            return Err("duplicate_session");
+        let reclaim = match self.sessions.get(&session_id) {
+            Some(entry) if entry.ended.is_some() => true,
+            Some(_) => return Err("duplicate_session"),
+            None if self.resolve_session(&session_id).is_some() => {
+                return Err("duplicate_session");
+            }
+            None => false,
+        };
+        let mut ended_holder = None;
+        if let Some(name) = &meta.name {
+            validate_session_name(name)?;
+            if *name == session_id || self.sessions.contains_key(name) {
+                return Err("duplicate_name");
+            }
+            if let Some(holder) = self.resolve_session(name)
+                && holder != session_id
+            {
+                if self.sessions[holder].ended.is_none() {
+                    return Err("duplicate_name");
+                }
+                ended_holder = Some(holder.to_string());
+            }
+        }
+        if let Some(holder) = ended_holder
+            && let Some(entry) = self.sessions.get_mut(&holder)
+        {
+            entry.meta.name = None;
         }
+
+        let parser = meta.parser.as_deref().and_then(Parser::from_name);
+        if reclaim {
+            // The new run may be a different agent: its turns are
+            // parsed with its own parser.
+            let entry = self.sessions.get_mut(&session_id).expect("checked above");
+            entry.connection_id = connection_id;
+            entry.pid = pid;
+            entry.meta = meta;
+            entry.ring.set_parser(parser);
+            entry.ring.clear_current();
+            entry.ended = None;
+            return Ok(());
+        }
+
         let ring = TurnRingBuffer::new(
             session_id.clone(),
             self.ring_config.depth,
             self.ring_config.max_turn_bytes,
-        );
+        )
+        .with_parser(parser);
         self.sessions.insert(
             session_id,
             SessionEntry {
                 connection_id,
                 pid,
+                meta,
                 ring,
+                ended: None,
             },
         );
         Ok(())
     }
 
-    /// Deregister a session. Idempotent — returns `Ok(())` even if
-    /// the session was already removed.
+    /// Resolve a session ID or name to the session ID.
+    ///
+    /// IDs take precedence; registration keeps names and IDs from
+    /// overlapping.
+    pub fn resolve_session(&self, session: &str) -> Option<&str> {
+        match self.sessions.get_key_value(session) {
+            Some((id, _)) => Some(id),
+            None => self
+                .sessions
+                .iter()
+                .find(|(_, entry)| entry.meta.name.as_deref() == Some(session))
+                .map(|(id, _)| id.as_str()),
+        }
+    }
+
+    /// The entry for a session ID or name.
+    fn session(&self, session: &str) -> Option<&SessionEntry> {
+        let id = self.resolve_session(session)?;
+        self.sessions.get(id)
+    }
+
+    /// Deregister a session registered by `connection_id`: it ends,
+    /// and is kept for the grace period with its turns (removed right
+    /// away if the grace period is 0). Idempotent — an unknown or
+    /// already ended session, or one another connection registered, is
+    /// left as is.
     ///
     /// CONTRACT_BROKER.md §Deregister: relay buffer is NOT cleared
-    /// (content was already captured).
-    pub fn deregister_session(&mut self, session_id: &str) {
-        self.sessions.remove(session_id);
+    /// (content was already captured). Returns the session's viewers,
+    /// which stop following it.
+    pub fn deregister_session(
+        &mut self,
+        session_id: &str,
+        connection_id: ConnectionId,
+        end: SessionEnd,
+    ) -> Vec<ConnectionId> {
+        if self.owned_session(session_id, connection_id).is_err() {
+            return Vec::new();
+        }
+        if self.ring_config.ended_grace_ms == 0 {
+            self.sessions.remove(session_id);
+        } else if let Some(entry) = self.sessions.get_mut(session_id) {
+            // Its turn in progress will never complete.
+            entry.ring.clear_current();
+            entry.ended = Some(end);
+        }
+        let viewers = self.viewers(session_id);
+        self.viewers.retain(|_, session| session != session_id);
+        viewers
+    }
+
+    /// The entry of a live session, if `connection_id` registered it:
+    /// only a session's own wrapper may write to it.
+    fn owned_session(
+        &mut self,
+        session_id: &str,
+        connection_id: ConnectionId,
+    ) -> Result<&mut SessionEntry, &'static str> {
+        let entry = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or("session_not_found")?;
+        if entry.ended.is_some() {
+            return Err("session_ended");
+        }
+        if entry.connection_id != connection_id {
+            return Err("not_session_owner");
+        }
+        Ok(entry)
+    }
+
+    /// Remove sessions whose grace period is over at `now` (Unix epoch
+    /// millis). Returns their IDs.
+    pub fn expire_sessions(&mut self, now: u64) -> Vec<String> {
+        let grace = self.ring_config.ended_grace_ms;
+        let expired: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .ended
+                    .is_some_and(|end| now >= end.at.saturating_add(grace))
+            })
+            .map(|(session, _)| session.clone())
+            .collect();
+        for session in &expired {
+            self.sessions.remove(session);
+        }
+        expired
+    }
+
+    /// Follow a session's live output from `viewer`.
+    ///
+    /// Returns the session ID and, if `viewer` is its first viewer, the
+    /// wrapper connection to ask for output. A connection follows one
+    /// session: `Err("already_subscribed")` if it follows one already.
+    pub fn subscribe(
+        &mut self,
+        viewer: ConnectionId,
+        session: &str,
+    ) -> Result<(String, Option<ConnectionId>), &'static str> {
+        if self.viewers.contains_key(&viewer) {
+            return Err("already_subscribed");
+        }
+        let session_id = self
+            .resolve_session(session)
+            .ok_or("session_not_found")?
+            .to_string();
+        let entry = &self.sessions[&session_id];
+        if entry.ended.is_some() {
+            return Err("session_ended");
+        }
+        let wrapper = entry.connection_id;
+        let first = self.viewers(&session_id).is_empty();
+        self.viewers.insert(viewer, session_id.clone());
+        Ok((session_id, first.then_some(wrapper)))
+    }
+
+    /// Stop `viewer` following the session it follows, if any.
+    pub fn unsubscribe(&mut self, viewer: ConnectionId) -> Option<Unsubscribed> {
+        let session = self.viewers.remove(&viewer)?;
+        let last_of = match self.sessions.get(&session) {
+            Some(entry) if self.viewers(&session).is_empty() => Some(entry.connection_id),
+            _ => None,
+        };
+        Some(Unsubscribed { session, last_of })
+    }
+
+    /// Connections following a session's live output.
+    pub fn viewers(&self, session_id: &str) -> Vec<ConnectionId> {
+        self.viewers
+            .iter()
+            .filter(|(_, session)| *session == session_id)
+            .map(|(viewer, _)| *viewer)
+            .collect()
     }
 
     /// Store a completed turn for a session.
@@ -171,55 +501,88 @@ impl BrokerState {
     ///
     /// CONTRACT_BROKER.md §Turn Storage: raw bytes, no interpretation.
     /// CONTRACT_REGISTRY.md: turn IDs, metadata, ring eviction.
-    /// `timestamp` is the detection-time Unix epoch millis from the wrapper.
+    /// `meta.timestamp` is the detection-time Unix epoch millis from the
+    /// wrapper; `meta.size` is the wrapper's terminal size for screen
+    /// rendering.
+    ///
+    /// Only the wrapper connection that registered a live session may
+    /// store to it: an ended session is `Err("session_ended")`, another
+    /// connection `Err("not_session_owner")`.
     pub fn store_turn(
         &mut self,
         session_id: &str,
+        connection_id: ConnectionId,
         content: Vec<u8>,
-        interrupted: bool,
-        timestamp: u64,
+        meta: TurnMeta,
     ) -> Result<String, &'static str> {
-        let entry = self
-            .sessions
-            .get_mut(session_id)
-            .ok_or("session_not_found")?;
-        let record = entry.ring.push(content, interrupted, timestamp);
+        let entry = self.owned_session(session_id, connection_id)?;
+        entry.meta.size = Some(meta.size);
+        let record = entry.ring.push(content, meta);
         Ok(record.turn_id.clone())
     }
 
+    /// Store the content of a session's turn in progress.
+    ///
+    /// Replaces the provisional `<session_id>:current` record until the
+    /// turn completes (CONTRACT_REGISTRY.md §Current turn). Returns the
+    /// provisional turn ID, or `None` for empty content, which
+    /// withdraws the record of a turn that ended without content.
+    /// Rejected like [`store_turn`](Self::store_turn) unless from the
+    /// session's own wrapper.
+    pub fn store_progress(
+        &mut self,
+        session_id: &str,
+        connection_id: ConnectionId,
+        content: Vec<u8>,
+        meta: TurnMeta,
+    ) -> Result<Option<String>, &'static str> {
+        let entry = self.owned_session(session_id, connection_id)?;
+        if content.is_empty() {
+            entry.ring.clear_current();
+            return Ok(None);
+        }
+        entry.meta.size = Some(meta.size);
+        let record = entry.ring.set_current(content, meta);
+        Ok(Some(record.turn_id.clone()))
+    }
+
     /// Capture: copy a session's latest turn into the relay buffer.
     ///
-    /// Returns a [`CaptureResult`] with the byte size and turn ID.
-    /// The session's turn is NOT cleared.
+    /// Returns a [`CaptureResult`] with the byte size (of `format`)
+    /// and turn ID. The session's turn is NOT cleared.
     /// The relay buffer is overwritten (previous content replaced).
-    pub fn capture(&mut self, session_id: &str) -> Result<CaptureResult, &'static str> {
-        let entry = self.sessions.get(session_id).ok_or("session_not_found")?;
+    pub fn capture(
+        &mut self,
+        session_id: &str,
+        format: ContentFormat,
+    ) -> Result<CaptureResult, &'static str> {
+        let entry = self.session(session_id).ok_or("session_not_found")?;
         let head = entry.ring.head().ok_or("no_turn")?;
-        let size = head.content.len() as u32;
-        let turn_id = head.turn_id.clone();
-        self.relay_buffer = Some(RelayEntry {
-            content: head.content.clone(),
-            metadata: SinkMetadata {
-                turn_id: turn_id.clone(),
-                timestamp: head.timestamp,
-                byte_length: head.byte_length,
-                interrupted: head.interrupted,
-                truncated: head.truncated,
-            },
-        });
-        Ok(CaptureResult { size, turn_id })
+        let relay = RelayEntry::from_record(head, format);
+        let result = CaptureResult {
+            size: relay.content_as(None).len() as u32,
+            turn_id: relay.metadata.turn_id.clone(),
+        };
+        self.relay_buffer = Some(relay);
+        Ok(result)
     }
 
     /// Read relay buffer content and resolve the target wrapper connection.
     ///
-    /// Returns `(content, target_connection_id)` on success.
-    /// Does NOT clear the relay buffer (same content can be pasted
-    /// multiple times per CONTRACT_BROKER.md §Relay buffer persistence).
-    pub fn paste_content(&self, session_id: &str) -> Result<(Vec<u8>, ConnectionId), &'static str> {
+    /// `format` selects the representation; `None` uses the format
+    /// chosen at capture time. Returns `(content, target_connection_id)`
+    /// on success. Does NOT clear the relay buffer (same content can be
+    /// pasted multiple times per CONTRACT_BROKER.md §Relay buffer
+    /// persistence).
+    pub fn paste_content(
+        &self,
+        session_id: &str,
+        format: Option<ContentFormat>,
+    ) -> Result<(Vec<u8>, ConnectionId), &'static str> {
         let relay = self.relay_buffer.as_ref().ok_or("buffer_empty")?;
-        let content = relay.content.clone();
-        let entry = self.sessions.get(session_id).ok_or("session_not_found")?;
-        if !self.connections.contains_key(&entry.connection_id) {
+        let content = relay.content_as(format).into_owned();
+        let entry = self.session(session_id).ok_or("session_not_found")?;
+        if entry.ended.is_some() || !self.connections.contains_key(&entry.connection_id) {
             return Err("session_disconnected");
         }
         Ok((content, entry.connection_id))
@@ -228,20 +591,22 @@ impl BrokerState {
     /// Read a clone of the relay buffer content and metadata, if present.
     ///
     /// Used by non-inject sinks (clipboard, file) that need the
-    /// content and metadata without session routing. Returns `None`
-    /// if no turn has been captured yet.
+    /// content and metadata without session routing. `format` selects
+    /// the representation; `None` uses the format chosen at capture
+    /// time. Returns `None` if no turn has been captured yet.
     ///
     /// CONTRACT_REGISTRY.md §266: sinks receive `(content, metadata)`.
-    pub fn relay_content(&self) -> Option<(Vec<u8>, SinkMetadata)> {
+    pub fn relay_content(&self, format: Option<ContentFormat>) -> Option<(Vec<u8>, SinkMetadata)> {
         self.relay_buffer
             .as_ref()
-            .map(|r| (r.content.clone(), r.metadata.clone()))
+            .map(|r| (r.content_as(format).into_owned(), r.metadata.clone()))
     }
 
-    /// List all active sessions.
+    /// List all sessions, live and ended.
     ///
     /// Returns a descriptor for each session including whether it
-    /// has a completed turn. Backward compatible with v0.
+    /// has a completed turn and, for an ended session, how it ended.
+    /// Backward compatible with v0.
     pub fn list_sessions(&self) -> Vec<SessionDescriptor> {
         self.sessions
             .iter()
@@ -249,6 +614,25 @@ impl BrokerState {
                 session: id.clone(),
                 pid: entry.pid,
                 has_turn: !entry.ring.is_empty(),
+                boundary: entry.meta.boundary.clone(),
+                name: entry.meta.name.clone(),
+                state: if entry.ended.is_some() {
+                    SessionState::Ended
+                } else {
+                    SessionState::Live
+                },
+                exit_code: entry.ended.and_then(|end| end.exit_code),
+                end_reason: entry.ended.map(|end| end.reason),
+                ended_at: entry.ended.map(|end| end.at),
+                pattern: entry.meta.pattern.clone(),
+                command: entry.meta.command.clone(),
+                cwd: entry.meta.cwd.clone(),
+                started_at: entry.meta.started_at,
+                cols: entry.meta.size.map_or(0, |size| size.cols),
+                rows: entry.meta.size.map_or(0, |size| size.rows),
+                turn_count: entry.ring.turn_count(),
+                latest_turn: entry.ring.head().map(|head| head.turn_id.clone()),
+                last_turn_at: entry.ring.head().map(|head| head.timestamp),
             })
             .collect()
     }
@@ -256,14 +640,38 @@ impl BrokerState {
     /// Look up a specific turn by its ID.
     ///
     /// Turn IDs have the format `<session_id>:<seq>`. The session ID
-    /// is extracted by splitting on the first `:`.
+    /// is extracted by splitting on the first `:`; the session's name
+    /// may stand in for it.
     pub fn get_turn(&self, turn_id: &str) -> Result<&TurnRecord, &'static str> {
-        let session_id = turn_id
-            .split_once(':')
-            .map(|(s, _)| s)
-            .ok_or("turn_not_found")?;
-        let entry = self.sessions.get(session_id).ok_or("turn_not_found")?;
-        entry.ring.get(turn_id).ok_or("turn_not_found")
+        let (session, seq) = turn_id.split_once(':').ok_or("turn_not_found")?;
+        let session_id = self.resolve_session(session).ok_or("turn_not_found")?;
+        let entry = &self.sessions[session_id];
+        entry
+            .ring
+            .get(&format!("{session_id}:{seq}"))
+            .ok_or("turn_not_found")
+    }
+
+    /// Resolve a turn reference, which may name a code block fragment.
+    ///
+    /// Accepts `<session_id>:<seq>` or `<session_id>:<seq>#code<N>`
+    /// (CONTRACT_REGISTRY.md §Fragments). Returns the turn and, for a
+    /// fragment reference, the addressed code block with its
+    /// canonical fragment ID.
+    pub fn resolve_turn(
+        &self,
+        turn_ref: &str,
+    ) -> Result<(&TurnRecord, Option<Fragment>), &'static str> {
+        let (turn_id, fragment) = parse_turn_ref(turn_ref)?;
+        let record = self.get_turn(turn_id)?;
+        match fragment {
+            Some(n) => {
+                let block = record.code_block(n).ok_or("fragment_not_found")?;
+                let fragment_id = format!("{}#code{n}", record.turn_id);
+                Ok((record, Some((fragment_id, block))))
+            }
+            None => Ok((record, None)),
+        }
     }
 
     /// List turn descriptors for a session, newest first.
@@ -272,34 +680,32 @@ impl BrokerState {
         session_id: &str,
         limit: Option<usize>,
     ) -> Result<Vec<&TurnRecord>, &'static str> {
-        let entry = self.sessions.get(session_id).ok_or("session_not_found")?;
+        let entry = self.session(session_id).ok_or("session_not_found")?;
         Ok(entry.ring.iter_newest_first(limit).collect())
     }
 
     /// Capture a specific turn by ID into the relay buffer.
     ///
     /// Like [`capture`](Self::capture) but resolves a specific turn
-    /// from the ring instead of the head.
-    pub fn capture_by_id(&mut self, turn_id: &str) -> Result<CaptureResult, &'static str> {
-        let session_id = turn_id
-            .split_once(':')
-            .map(|(s, _)| s)
-            .ok_or("turn_not_found")?;
-        let entry = self.sessions.get(session_id).ok_or("turn_not_found")?;
-        let record = entry.ring.get(turn_id).ok_or("turn_not_found")?;
-        let size = record.content.len() as u32;
-        let turn_id = record.turn_id.clone();
-        self.relay_buffer = Some(RelayEntry {
-            content: record.content.clone(),
-            metadata: SinkMetadata {
-                turn_id: turn_id.clone(),
-                timestamp: record.timestamp,
-                byte_length: record.byte_length,
-                interrupted: record.interrupted,
-                truncated: record.truncated,
-            },
-        });
-        Ok(CaptureResult { size, turn_id })
+    /// from the ring instead of the head. A fragment reference
+    /// captures just that code block.
+    pub fn capture_by_id(
+        &mut self,
+        turn_id: &str,
+        format: ContentFormat,
+    ) -> Result<CaptureResult, &'static str> {
+        let relay = match self.resolve_turn(turn_id)? {
+            (record, Some((fragment_id, block))) => {
+                RelayEntry::from_block(record, block, &fragment_id, format)
+            }
+            (record, None) => RelayEntry::from_record(record, format),
+        };
+        let result = CaptureResult {
+            size: relay.content_as(None).len() as u32,
+            turn_id: relay.metadata.turn_id.clone(),
+        };
+        self.relay_buffer = Some(relay);
+        Ok(result)
     }
 }
 
@@ -315,6 +721,24 @@ mod tests {
         ConnectionId::new()
     }
 
+    fn meta(interrupted: bool, timestamp: u64) -> TurnMeta {
+        TurnMeta {
+            interrupted,
+            timestamp,
+            ..TurnMeta::default()
+        }
+    }
+
+    fn exited(at: u64) -> SessionEnd {
+        SessionEnd {
+            at,
+            exit_code: Some(0),
+            reason: EndReason::Exited,
+        }
+    }
+
+    const GRACE: u64 = 5 * 60 * 1000;
+
     // -- Connection tracking --
 
     #[test]
@@ -323,7 +747,7 @@ mod tests {
         let c = conn();
         s.add_connection(c, Role::Wrapper);
         assert!(s.connections.contains_key(&c));
-        s.remove_connection(c);
+        s.remove_connection(c, 1000);
         assert!(!s.connections.contains_key(&c));
     }
 
@@ -333,7 +757,7 @@ mod tests {
         let c = conn();
         s.add_connection(c, Role::Client);
         assert_eq!(s.connection_role(c), Some(Role::Client));
-        s.remove_connection(c);
+        s.remove_connection(c, 1000);
         assert_eq!(s.connection_role(c), None);
     }
 
@@ -344,7 +768,10 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        assert!(s.register_session("s1".into(), c, 100).is_ok());
+        assert!(
+            s.register_session("s1".into(), c, 100, SessionMeta::default())
+                .is_ok()
+        );
         assert_eq!(s.sessions.len(), 1);
     }
 
@@ -353,20 +780,156 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
         assert_eq!(
-            s.register_session("s1".into(), c, 200),
+            s.register_session("s1".into(), c, 200, SessionMeta::default()),
             Err("duplicate_session")
         );
     }
 
+    fn named(name: &str) -> SessionMeta {
+        SessionMeta {
+            name: Some(name.into()),
+            ..SessionMeta::default()
+        }
+    }
+
     #[test]
-    fn deregister_session_removes_entry() {
+    fn register_names_unique() {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.deregister_session("s1");
+        s.register_session("s1".into(), c, 100, named("planner"))
+            .unwrap();
+        assert_eq!(
+            s.register_session("s2".into(), c, 200, named("planner")),
+            Err("duplicate_name")
+        );
+        // Names and IDs share one namespace.
+        assert_eq!(
+            s.register_session("s2".into(), c, 200, named("s1")),
+            Err("duplicate_name")
+        );
+        assert_eq!(
+            s.register_session("planner".into(), c, 200, SessionMeta::default()),
+            Err("duplicate_session")
+        );
+        assert_eq!(
+            s.register_session("s2".into(), c, 200, named("has:colon")),
+            Err("invalid_name")
+        );
+
+        // A name is free again once its session has ended; the ended
+        // session gives it up.
+        s.deregister_session("s1", c, exited(1000));
+        s.register_session("s2".into(), c, 200, named("planner"))
+            .unwrap();
+        assert_eq!(s.resolve_session("planner"), Some("s2"));
+        assert_eq!(s.sessions["s1"].meta.name, None);
+        // An ended session's ID is still taken.
+        assert_eq!(
+            s.register_session("s3".into(), c, 300, named("s1")),
+            Err("duplicate_name")
+        );
+    }
+
+    #[test]
+    fn names_resolve_like_ids() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, named("planner"))
+            .unwrap();
+        s.store_turn("s1", c, b"```\nls\n```\n".to_vec(), meta(false, 1000))
+            .unwrap();
+
+        assert_eq!(s.resolve_session("planner"), Some("s1"));
+        assert_eq!(s.resolve_session("s1"), Some("s1"));
+        assert_eq!(s.resolve_session("other"), None);
+
+        assert_eq!(s.get_turn("planner:1").unwrap().turn_id, "s1:1");
+        assert_eq!(s.list_turns("planner", None).unwrap().len(), 1);
+        assert_eq!(
+            s.capture("planner", ContentFormat::Raw).unwrap().turn_id,
+            "s1:1"
+        );
+        let captured = s
+            .capture_by_id("planner:1#code1", ContentFormat::Raw)
+            .unwrap();
+        assert_eq!(captured.turn_id, "s1:1#code1");
+        assert!(s.paste_content("planner", None).is_ok());
+        assert_eq!(s.list_sessions()[0].name.as_deref(), Some("planner"));
+    }
+
+    #[test]
+    fn viewers_follow_sessions() {
+        let mut s = state();
+        let w = conn();
+        s.add_connection(w, Role::Wrapper);
+        s.register_session("s1".into(), w, 100, named("planner"))
+            .unwrap();
+        let (v1, v2) = (conn(), conn());
+
+        // The first viewer starts mirroring; names resolve.
+        assert_eq!(s.subscribe(v1, "planner"), Ok(("s1".into(), Some(w))));
+        assert_eq!(s.subscribe(v2, "s1"), Ok(("s1".into(), None)));
+        assert_eq!(s.subscribe(v2, "s1"), Err("already_subscribed"));
+        assert_eq!(s.subscribe(conn(), "nope"), Err("session_not_found"));
+        let mut viewers = s.viewers("s1");
+        viewers.sort_by_key(|c| c.0);
+        assert_eq!(viewers, [v1, v2]);
+
+        // The last viewer to leave stops it.
+        let left = s.unsubscribe(v1).unwrap();
+        assert_eq!((left.session.as_str(), left.last_of), ("s1", None));
+        assert_eq!(s.unsubscribe(v2).unwrap().last_of, Some(w));
+        assert_eq!(s.unsubscribe(v2), None);
+
+        // Viewers of a session that goes away are reported.
+        s.subscribe(v1, "s1").unwrap();
+        assert_eq!(s.remove_connection(w, 1000), [(v1, "s1".to_string())]);
+        assert!(s.viewers("s1").is_empty());
+        assert_eq!(s.unsubscribe(v1), None);
+    }
+
+    #[test]
+    fn deregister_session_ends_entry() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"last words".to_vec(), meta(false, 500))
+            .unwrap();
+        s.deregister_session("s1", c, exited(1000));
+
+        // Kept, with its turns, until the grace period is over.
+        let list = s.list_sessions();
+        assert_eq!(list[0].state, SessionState::Ended);
+        assert_eq!(list[0].exit_code, Some(0));
+        assert_eq!(list[0].end_reason, Some(EndReason::Exited));
+        assert_eq!(list[0].ended_at, Some(1000));
+        assert_eq!(s.capture("s1", ContentFormat::Raw).unwrap().turn_id, "s1:1");
+
+        // A second end does not move the end time.
+        s.deregister_session("s1", c, exited(2000));
+        assert!(s.expire_sessions(1000 + GRACE - 1).is_empty());
+        assert_eq!(s.expire_sessions(1000 + GRACE), ["s1"]);
+        assert!(s.sessions.is_empty());
+    }
+
+    #[test]
+    fn deregister_without_grace_removes_entry() {
+        let mut s = BrokerState::new(RingConfig {
+            ended_grace_ms: 0,
+            ..RingConfig::default()
+        });
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.deregister_session("s1", c, exited(1000));
         assert!(s.sessions.is_empty());
     }
 
@@ -374,19 +937,122 @@ mod tests {
     fn deregister_nonexistent_is_ok() {
         let mut s = state();
         // No panic, no error.
-        s.deregister_session("nonexistent");
+        s.deregister_session("nonexistent", conn(), exited(1000));
+    }
+
+    #[test]
+    fn ended_session_not_followed_or_pasted() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"data".to_vec(), meta(false, 500))
+            .unwrap();
+        s.capture("s1", ContentFormat::Raw).unwrap();
+        // The wrapper deregisters but has not closed its connection yet.
+        s.deregister_session("s1", c, exited(1000));
+        assert_eq!(s.subscribe(conn(), "s1"), Err("session_ended"));
+        assert_eq!(s.paste_content("s1", None), Err("session_disconnected"));
+        assert_eq!(s.list_turns("s1", None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn reclaim_ended_session() {
+        let mut s = state();
+        let (c1, c2) = (conn(), conn());
+        s.add_connection(c1, Role::Wrapper);
+        s.register_session("s1".into(), c1, 100, named("planner"))
+            .unwrap();
+        s.store_turn("s1", c1, b"before".to_vec(), meta(false, 500))
+            .unwrap();
+        s.remove_connection(c1, 1000);
+
+        // The wrapper comes back on a new connection, under the same ID.
+        s.add_connection(c2, Role::Wrapper);
+        s.register_session("s1".into(), c2, 100, named("planner"))
+            .unwrap();
+        let list = s.list_sessions();
+        assert_eq!(list[0].state, SessionState::Live);
+        assert_eq!(list[0].end_reason, None);
+        assert_eq!(
+            s.store_turn("s1", c2, b"after".to_vec(), meta(false, 2000)),
+            Ok("s1:2".to_string())
+        );
+        assert_eq!(s.paste_content("planner", None), Err("buffer_empty"));
+        assert!(s.expire_sessions(1000 + GRACE).is_empty());
+
+        // A live session cannot be claimed.
+        assert_eq!(
+            s.register_session("s1".into(), c1, 100, SessionMeta::default()),
+            Err("duplicate_session")
+        );
+    }
+
+    #[test]
+    fn reclaim_with_different_parser() {
+        let mut s = state();
+        let (c1, c2) = (conn(), conn());
+        let with_parser = |parser: Option<&str>| SessionMeta {
+            parser: parser.map(String::from),
+            ..SessionMeta::default()
+        };
+        s.add_connection(c1, Role::Wrapper);
+        s.register_session("s1".into(), c1, 100, with_parser(Some("claude")))
+            .unwrap();
+        s.store_turn("s1", c1, b"before".to_vec(), meta(false, 500))
+            .unwrap();
+        s.remove_connection(c1, 1000);
+
+        s.add_connection(c2, Role::Wrapper);
+        s.register_session("s1".into(), c2, 200, with_parser(Some("aider")))
+            .unwrap();
+        s.store_turn("s1", c2, b"after".to_vec(), meta(false, 2000))
+            .unwrap();
+        assert_eq!(s.get_turn("s1:1").unwrap().parser, Some(Parser::Claude));
+        assert_eq!(s.get_turn("s1:2").unwrap().parser, Some(Parser::Aider));
+    }
+
+    #[test]
+    fn current_turn_withdrawn_and_dropped_at_end() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        assert_eq!(
+            s.store_progress("s1", c, b"partial".to_vec(), meta(false, 500)),
+            Ok(Some("s1:current".into()))
+        );
+
+        // The turn ended without content.
+        assert_eq!(
+            s.store_progress("s1", c, Vec::new(), meta(false, 600)),
+            Ok(None)
+        );
+        assert!(s.get_turn("s1:current").is_err());
+
+        // The session ended mid-turn.
+        s.store_progress("s1", c, b"partial".to_vec(), meta(false, 700))
+            .unwrap();
+        s.remove_connection(c, 1000);
+        assert!(s.get_turn("s1:current").is_err());
     }
 
     // -- Implicit deregister --
 
     #[test]
-    fn remove_connection_deregisters_session() {
+    fn remove_connection_ends_session() {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.remove_connection(c);
-        assert!(s.sessions.is_empty());
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.remove_connection(c, 1000);
+        let list = s.list_sessions();
+        assert_eq!(list[0].state, SessionState::Ended);
+        assert_eq!(list[0].end_reason, Some(EndReason::Disconnected));
+        assert_eq!(list[0].exit_code, None);
     }
 
     #[test]
@@ -396,11 +1062,13 @@ mod tests {
         let c2 = conn();
         s.add_connection(c1, Role::Wrapper);
         s.add_connection(c2, Role::Wrapper);
-        s.register_session("s1".into(), c1, 100).unwrap();
-        s.register_session("s2".into(), c2, 200).unwrap();
-        s.remove_connection(c1);
-        assert_eq!(s.sessions.len(), 1);
-        assert!(s.sessions.contains_key("s2"));
+        s.register_session("s1".into(), c1, 100, SessionMeta::default())
+            .unwrap();
+        s.register_session("s2".into(), c2, 200, SessionMeta::default())
+            .unwrap();
+        s.remove_connection(c1, 1000);
+        assert!(s.sessions["s1"].ended.is_some());
+        assert!(s.sessions["s2"].ended.is_none());
     }
 
     // -- Turn storage --
@@ -410,9 +1078,10 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
         let turn_id = s
-            .store_turn("s1", b"turn content".to_vec(), false, 1000)
+            .store_turn("s1", c, b"turn content".to_vec(), meta(false, 1000))
             .unwrap();
         assert_eq!(turn_id, "s1:1");
     }
@@ -422,9 +1091,14 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        let t1 = s.store_turn("s1", b"first".to_vec(), false, 1000).unwrap();
-        let t2 = s.store_turn("s1", b"second".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        let t1 = s
+            .store_turn("s1", c, b"first".to_vec(), meta(false, 1000))
+            .unwrap();
+        let t2 = s
+            .store_turn("s1", c, b"second".to_vec(), meta(false, 1000))
+            .unwrap();
         assert_eq!(t1, "s1:1");
         assert_eq!(t2, "s1:2");
     }
@@ -434,9 +1108,12 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"first".to_vec(), false, 1000).unwrap();
-        s.store_turn("s1", b"second".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"first".to_vec(), meta(false, 1000))
+            .unwrap();
+        s.store_turn("s1", c, b"second".to_vec(), meta(false, 1000))
+            .unwrap();
         let head = s.sessions["s1"].ring.head().unwrap();
         assert_eq!(head.content, b"second");
     }
@@ -446,8 +1123,10 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), true, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"data".to_vec(), meta(true, 1000))
+            .unwrap();
         let head = s.sessions["s1"].ring.head().unwrap();
         assert!(head.interrupted);
     }
@@ -456,11 +1135,44 @@ mod tests {
     fn store_turn_session_not_found() {
         let mut s = state();
         assert_eq!(
-            s.store_turn("nonexistent", b"data".to_vec(), false, 1000),
+            s.store_turn("nonexistent", conn(), b"data".to_vec(), meta(false, 1000)),
             Err("session_not_found")
         );
     }
 
+    #[test]
+    fn writes_only_from_owner_of_live_session() {
+        let mut s = state();
+        let (owner, other) = (conn(), conn());
+        s.add_connection(owner, Role::Wrapper);
+        s.add_connection(other, Role::Wrapper);
+        s.register_session("s1".into(), owner, 100, SessionMeta::default())
+            .unwrap();
+
+        assert_eq!(
+            s.store_turn("s1", other, b"x".to_vec(), meta(false, 1000)),
+            Err("not_session_owner")
+        );
+        assert_eq!(
+            s.store_progress("s1", other, b"x".to_vec(), meta(false, 1000)),
+            Err("not_session_owner")
+        );
+        // Another connection cannot end it either.
+        s.deregister_session("s1", other, exited(1000));
+        assert_eq!(s.list_sessions()[0].state, SessionState::Live);
+
+        s.deregister_session("s1", owner, exited(1000));
+        assert_eq!(
+            s.store_turn("s1", owner, b"late".to_vec(), meta(false, 2000)),
+            Err("session_ended")
+        );
+        assert_eq!(
+            s.store_progress("s1", owner, b"late".to_vec(), meta(false, 2000)),
+            Err("session_ended")
+        );
+        assert!(s.sessions["s1"].ring.head().is_none());
+    }
+
     // -- Capture --
 
     #[test]
@@ -468,15 +1180,16 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"turn data".to_vec(), false, 1000)
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"turn data".to_vec(), meta(false, 1000))
             .unwrap();
-        let result = s.capture("s1").unwrap();
+        let result = s.capture("s1", ContentFormat::Raw).unwrap();
         assert_eq!(result.size, 9);
         assert_eq!(result.turn_id, "s1:1");
         assert_eq!(
-            s.relay_buffer.as_ref().unwrap().content,
-            b"turn data".to_vec()
+            *s.relay_buffer.as_ref().unwrap().content_as(None),
+            *b"turn data"
         );
     }
 
@@ -485,10 +1198,13 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"a".to_vec(), false, 1000).unwrap();
-        s.store_turn("s1", b"b".to_vec(), false, 1000).unwrap();
-        let result = s.capture("s1").unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"a".to_vec(), meta(false, 1000))
+            .unwrap();
+        s.store_turn("s1", c, b"b".to_vec(), meta(false, 1000))
+            .unwrap();
+        let result = s.capture("s1", ContentFormat::Raw).unwrap();
         // Captures the head (latest = seq 2).
         assert_eq!(result.turn_id, "s1:2");
     }
@@ -496,7 +1212,10 @@ mod tests {
     #[test]
     fn capture_session_not_found() {
         let mut s = state();
-        assert_eq!(s.capture("nonexistent"), Err("session_not_found"));
+        assert_eq!(
+            s.capture("nonexistent", ContentFormat::Raw),
+            Err("session_not_found")
+        );
     }
 
     #[test]
@@ -504,8 +1223,9 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        assert_eq!(s.capture("s1"), Err("no_turn"));
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        assert_eq!(s.capture("s1", ContentFormat::Raw), Err("no_turn"));
     }
 
     #[test]
@@ -513,10 +1233,11 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"turn data".to_vec(), false, 1000)
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"turn data".to_vec(), meta(false, 1000))
             .unwrap();
-        s.capture("s1").unwrap();
+        s.capture("s1", ContentFormat::Raw).unwrap();
         // Session's ring still has the turn.
         assert!(!s.sessions["s1"].ring.is_empty());
     }
@@ -526,12 +1247,18 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"first".to_vec(), false, 1000).unwrap();
-        s.capture("s1").unwrap();
-        s.store_turn("s1", b"second".to_vec(), false, 1000).unwrap();
-        s.capture("s1").unwrap();
-        assert_eq!(s.relay_buffer.as_ref().unwrap().content, b"second".to_vec());
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"first".to_vec(), meta(false, 1000))
+            .unwrap();
+        s.capture("s1", ContentFormat::Raw).unwrap();
+        s.store_turn("s1", c, b"second".to_vec(), meta(false, 1000))
+            .unwrap();
+        s.capture("s1", ContentFormat::Raw).unwrap();
+        assert_eq!(
+            *s.relay_buffer.as_ref().unwrap().content_as(None),
+            *b"second"
+        );
     }
 
     // -- Paste --
@@ -543,13 +1270,15 @@ mod tests {
         let c2 = conn();
         s.add_connection(c1, Role::Wrapper);
         s.add_connection(c2, Role::Wrapper);
-        s.register_session("s1".into(), c1, 100).unwrap();
-        s.register_session("s2".into(), c2, 200).unwrap();
-        s.store_turn("s1", b"turn data".to_vec(), false, 1000)
+        s.register_session("s1".into(), c1, 100, SessionMeta::default())
+            .unwrap();
+        s.register_session("s2".into(), c2, 200, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c1, b"turn data".to_vec(), meta(false, 1000))
             .unwrap();
-        s.capture("s1").unwrap();
+        s.capture("s1", ContentFormat::Raw).unwrap();
 
-        let (content, target) = s.paste_content("s2").unwrap();
+        let (content, target) = s.paste_content("s2", None).unwrap();
         assert_eq!(content, b"turn data");
         assert_eq!(target, c2);
     }
@@ -559,15 +1288,17 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        assert_eq!(s.paste_content("s1"), Err("buffer_empty"));
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        assert_eq!(s.paste_content("s1", None), Err("buffer_empty"));
     }
 
     #[test]
     fn paste_session_not_found() {
         let mut s = state();
         s.relay_buffer = Some(RelayEntry {
-            content: b"data".to_vec(),
+            content: RelayContent::Block(b"data".to_vec()),
+            format: ContentFormat::Raw,
             metadata: SinkMetadata {
                 turn_id: "x:1".into(),
                 timestamp: 1000,
@@ -576,7 +1307,10 @@ mod tests {
                 truncated: false,
             },
         });
-        assert_eq!(s.paste_content("nonexistent"), Err("session_not_found"));
+        assert_eq!(
+            s.paste_content("nonexistent", None),
+            Err("session_not_found")
+        );
     }
 
     #[test]
@@ -584,13 +1318,14 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"turn data".to_vec(), false, 1000)
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"turn data".to_vec(), meta(false, 1000))
             .unwrap();
-        s.capture("s1").unwrap();
+        s.capture("s1", ContentFormat::Raw).unwrap();
         // Simulate disconnect without deregister.
         s.connections.remove(&c);
-        assert_eq!(s.paste_content("s1"), Err("session_disconnected"));
+        assert_eq!(s.paste_content("s1", None), Err("session_disconnected"));
     }
 
     #[test]
@@ -598,10 +1333,12 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), false, 1000).unwrap();
-        s.capture("s1").unwrap();
-        s.paste_content("s1").unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"data".to_vec(), meta(false, 1000))
+            .unwrap();
+        s.capture("s1", ContentFormat::Raw).unwrap();
+        s.paste_content("s1", None).unwrap();
         // Relay buffer still has content.
         assert!(s.relay_buffer.is_some());
     }
@@ -619,12 +1356,15 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"data".to_vec(), meta(false, 1000))
+            .unwrap();
 
         let c2 = conn();
         s.add_connection(c2, Role::Wrapper);
-        s.register_session("s2".into(), c2, 200).unwrap();
+        s.register_session("s2".into(), c2, 200, SessionMeta::default())
+            .unwrap();
 
         let mut list = s.list_sessions();
         list.sort_by(|a, b| a.session.cmp(&b.session));
@@ -637,6 +1377,68 @@ mod tests {
         assert!(!list[1].has_turn);
     }
 
+    #[test]
+    fn list_sessions_reports_boundary() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        let meta = SessionMeta {
+            boundary: Some("prompt+quiet:500".into()),
+            ..SessionMeta::default()
+        };
+        s.register_session("s1".into(), c, 100, meta).unwrap();
+
+        let list = s.list_sessions();
+        assert_eq!(list[0].boundary.as_deref(), Some("prompt+quiet:500"));
+    }
+
+    #[test]
+    fn list_sessions_reports_metadata() {
+        let mut s = BrokerState::new(RingConfig {
+            depth: 2,
+            ..RingConfig::default()
+        });
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        let session_meta = SessionMeta {
+            pattern: Some("claude".into()),
+            command: vec!["claude".into(), "--resume".into()],
+            cwd: Some("/home/user/project".into()),
+            started_at: Some(500),
+            size: Some(ScreenSize { cols: 80, rows: 24 }),
+            ..SessionMeta::default()
+        };
+        s.register_session("s1".into(), c, 100, session_meta)
+            .unwrap();
+
+        let session = &s.list_sessions()[0];
+        assert_eq!(session.pattern.as_deref(), Some("claude"));
+        assert_eq!(session.command, ["claude", "--resume"]);
+        assert_eq!(session.cwd.as_deref(), Some("/home/user/project"));
+        assert_eq!(session.started_at, Some(500));
+        assert_eq!((session.cols, session.rows), (80, 24));
+        assert_eq!(session.turn_count, 0);
+        assert_eq!(session.latest_turn, None);
+        assert_eq!(session.last_turn_at, None);
+
+        // Turns update the size and count, including evicted ones.
+        for ts in [1000, 2000, 3000] {
+            let turn = TurnMeta {
+                size: ScreenSize {
+                    cols: 120,
+                    rows: 40,
+                },
+                ..meta(false, ts)
+            };
+            s.store_turn("s1", c, b"t".to_vec(), turn).unwrap();
+        }
+        let session = &s.list_sessions()[0];
+        assert_eq!((session.cols, session.rows), (120, 40));
+        assert_eq!(session.turn_count, 3);
+        assert_eq!(session.latest_turn.as_deref(), Some("s1:3"));
+        assert_eq!(session.last_turn_at, Some(3000));
+    }
+
     // -- Get turn --
 
     #[test]
@@ -644,8 +1446,10 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"data".to_vec(), meta(false, 1000))
+            .unwrap();
         let record = s.get_turn("s1:1").unwrap();
         assert_eq!(record.content, b"data");
     }
@@ -655,7 +1459,8 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
         assert_eq!(s.get_turn("s1:99"), Err("turn_not_found"));
     }
 
@@ -670,11 +1475,49 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"data".to_vec(), meta(false, 1000))
+            .unwrap();
         assert_eq!(s.get_turn("s2:1"), Err("turn_not_found"));
     }
 
+    #[test]
+    fn resolve_turn_fragment() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            c,
+            b"```sh\nls\n```\n```py\nprint()\n```\n".to_vec(),
+            meta(false, 1000),
+        )
+        .unwrap();
+
+        let (record, block) = s.resolve_turn("s1:1").unwrap();
+        assert_eq!(record.turn_id, "s1:1");
+        assert!(block.is_none());
+        let (_, block) = s.resolve_turn("s1:1#code2").unwrap();
+        let (fragment_id, block) = block.unwrap();
+        assert_eq!(fragment_id, "s1:1#code2");
+        assert_eq!(block.content, b"print()\n");
+        assert_eq!(
+            s.resolve_turn("s1:1#code3").map(|_| ()),
+            Err("fragment_not_found")
+        );
+        assert_eq!(
+            s.resolve_turn("s1:1#line1").map(|_| ()),
+            Err("invalid_fragment")
+        );
+        assert_eq!(
+            s.resolve_turn("s1:2#code1").map(|_| ()),
+            Err("turn_not_found")
+        );
+    }
+
     // -- List turns --
 
     #[test]
@@ -682,10 +1525,14 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"a".to_vec(), false, 1000).unwrap();
-        s.store_turn("s1", b"b".to_vec(), false, 1000).unwrap();
-        s.store_turn("s1", b"c".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"a".to_vec(), meta(false, 1000))
+            .unwrap();
+        s.store_turn("s1", c, b"b".to_vec(), meta(false, 1000))
+            .unwrap();
+        s.store_turn("s1", c, b"c".to_vec(), meta(false, 1000))
+            .unwrap();
         let turns = s.list_turns("s1", None).unwrap();
         let ids: Vec<&str> = turns.iter().map(|t| t.turn_id.as_str()).collect();
         assert_eq!(ids, vec!["s1:3", "s1:2", "s1:1"]);
@@ -696,9 +1543,11 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
         for _ in 0..5 {
-            s.store_turn("s1", b"x".to_vec(), false, 1000).unwrap();
+            s.store_turn("s1", c, b"x".to_vec(), meta(false, 1000))
+                .unwrap();
         }
         let turns = s.list_turns("s1", Some(2)).unwrap();
         assert_eq!(turns.len(), 2);
@@ -717,14 +1566,20 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"first".to_vec(), false, 1000).unwrap();
-        s.store_turn("s1", b"second".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"first".to_vec(), meta(false, 1000))
+            .unwrap();
+        s.store_turn("s1", c, b"second".to_vec(), meta(false, 1000))
+            .unwrap();
         // Capture the first turn, not the head.
-        let result = s.capture_by_id("s1:1").unwrap();
+        let result = s.capture_by_id("s1:1", ContentFormat::Raw).unwrap();
         assert_eq!(result.turn_id, "s1:1");
         assert_eq!(result.size, 5);
-        assert_eq!(s.relay_buffer.as_ref().unwrap().content, b"first".to_vec());
+        assert_eq!(
+            *s.relay_buffer.as_ref().unwrap().content_as(None),
+            *b"first"
+        );
     }
 
     #[test]
@@ -732,14 +1587,111 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        assert_eq!(s.capture_by_id("s1:99"), Err("turn_not_found"));
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        assert_eq!(
+            s.capture_by_id("s1:99", ContentFormat::Raw),
+            Err("turn_not_found")
+        );
     }
 
     #[test]
     fn capture_by_id_wrong_session() {
         let mut s = state();
-        assert_eq!(s.capture_by_id("nonexistent:1"), Err("turn_not_found"));
+        assert_eq!(
+            s.capture_by_id("nonexistent:1", ContentFormat::Raw),
+            Err("turn_not_found")
+        );
+    }
+
+    #[test]
+    fn capture_by_id_fragment() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            c,
+            b"Try:\r\n```rust\r\nlet x = 1;\r\n```\r\n".to_vec(),
+            meta(true, 1000),
+        )
+        .unwrap();
+
+        let result = s.capture_by_id("s1:1#code1", ContentFormat::Raw).unwrap();
+        assert_eq!(result.turn_id, "s1:1#code1");
+        assert_eq!(result.size, 11);
+        let (content, metadata) = s.relay_content(None).unwrap();
+        assert_eq!(content, b"let x = 1;\n");
+        assert_eq!(metadata.turn_id, "s1:1#code1");
+        assert_eq!(metadata.byte_length, 11);
+        assert!(metadata.interrupted);
+    }
+
+    // -- Content formats --
+
+    #[test]
+    fn capture_screen_format_sets_relay_default() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            c,
+            b"working\r\x1b[2Kdone\r\n".to_vec(),
+            meta(false, 1000),
+        )
+        .unwrap();
+
+        let result = s.capture("s1", ContentFormat::Screen).unwrap();
+        assert_eq!(result.size, 5);
+        let (content, _) = s.paste_content("s1", None).unwrap();
+        assert_eq!(content, b"done\n");
+        let (content, _) = s.relay_content(None).unwrap();
+        assert_eq!(content, b"done\n");
+    }
+
+    #[test]
+    fn paste_format_overrides_capture_format() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"a\rb\r\n".to_vec(), meta(false, 1000))
+            .unwrap();
+        s.capture_by_id("s1:1", ContentFormat::Raw).unwrap();
+
+        let (content, _) = s.paste_content("s1", Some(ContentFormat::Screen)).unwrap();
+        assert_eq!(content, b"b\n");
+        let (content, _) = s.paste_content("s1", None).unwrap();
+        assert_eq!(content, b"a\rb\r\n");
+    }
+
+    #[test]
+    fn plain_format_for_capture_and_sinks() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            c,
+            b"\x1b[1mok\x1b[0m  \r\nabk\x08c\r\n".to_vec(),
+            meta(false, 1000),
+        )
+        .unwrap();
+
+        let result = s.capture("s1", ContentFormat::Plain).unwrap();
+        assert_eq!(result.size, 7);
+        let (content, _) = s.relay_content(None).unwrap();
+        assert_eq!(content, b"ok\nabc\n");
+        let (content, _) = s.relay_content(Some(ContentFormat::Raw)).unwrap();
+        assert_eq!(content, b"\x1b[1mok\x1b[0m  \r\nabk\x08c\r\n");
     }
 
     // -- Relay stores metadata --
@@ -749,11 +1701,13 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), true, 5000).unwrap();
-        s.capture("s1").unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn("s1", c, b"data".to_vec(), meta(true, 5000))
+            .unwrap();
+        s.capture("s1", ContentFormat::Raw).unwrap();
 
-        let (content, metadata) = s.relay_content().unwrap();
+        let (content, metadata) = s.relay_content(None).unwrap();
         assert_eq!(content, b"data");
         assert_eq!(metadata.turn_id, "s1:1");
         assert_eq!(metadata.timestamp, 5000);
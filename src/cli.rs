@@ -1,4 +1,9 @@
-use clap::{Parser, Subcommand};
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::ipc::protocol::{MAX_CHUNK_DELAY_MS, MAX_SESSION_NAME, validate_session_name};
 
 #[derive(Parser)]
 #[command(name = "clippyctl", about = "Keyboard-driven agent turn relay")]
@@ -11,15 +16,61 @@ pub struct Cli {
 pub enum Command {
     /// Run the PTY wrapper around an agent process
     Wrap {
-        /// Prompt pattern preset or custom regex
-        #[arg(long, default_value = "generic")]
-        pattern: String,
+        /// Prompt pattern preset or custom regex [default: the preset
+        /// for the command's name, else generic]
+        #[arg(long)]
+        pattern: Option<String>,
+
+        /// Turn boundary strategy: `prompt`, `quiet:<ms>` or
+        /// `prompt+quiet:<ms>` [default: the preset's, else prompt]
+        #[arg(long)]
+        boundary: Option<String>,
+
+        /// Record a timestamped transcript of PTY output, input and
+        /// signals to this file, for `detect replay`
+        #[arg(long, value_name = "FILE")]
+        record: Option<PathBuf>,
+
+        /// Transcript encoding
+        #[arg(long, value_enum, default_value = "json", requires = "record")]
+        record_format: RecordFormatArg,
+
+        /// Record the session's output, resizes and turn markers to this
+        /// file in asciinema v2 format, for replay with `asciinema play`
+        #[arg(long, value_name = "FILE")]
+        asciicast: Option<PathBuf>,
+
+        /// Session name, usable in place of the session ID by client
+        /// commands (unique among live sessions)
+        #[arg(long, value_parser = parse_session_name)]
+        name: Option<String>,
+
+        /// Set CLIPPY_SESSION_ID (and CLIPPY_SESSION_NAME) in the
+        /// command's environment, for tools that cooperate with clippy
+        #[arg(long)]
+        export_env: bool,
+
+        /// Run in the background, detached from this terminal, and
+        /// print the session ID; reattach with `clippyctl attach`
+        #[arg(long)]
+        detached: bool,
+
+        /// Run as the background wrapper of a detached session with this
+        /// ID (set by `--detached`)
+        #[arg(long, hide = true, conflicts_with = "detached")]
+        detached_session: Option<String>,
 
         /// Command to run
         #[arg(trailing_var_arg = true, required = true)]
         command: Vec<String>,
     },
 
+    /// Attach this terminal to a detached session (Ctrl-] detaches)
+    Attach {
+        /// Session ID or name
+        session: String,
+    },
+
     /// Run the broker daemon
     Broker {
         /// Maximum number of turns retained per session (minimum 1)
@@ -29,6 +80,10 @@ pub enum Command {
         /// Maximum byte size per turn (content truncated beyond this)
         #[arg(long, default_value = "4194304")]
         max_turn_size: usize,
+
+        /// Seconds an ended session's turns are kept (0: drop at once)
+        #[arg(long, default_value = "300", value_name = "SECS")]
+        ended_grace: u64,
     },
 
     /// Run the hotkey client
@@ -51,13 +106,52 @@ pub enum Command {
         #[command(subcommand)]
         action: ClientAction,
     },
+
+    /// Offline turn detection tools
+    Detect {
+        #[command(subcommand)]
+        action: DetectAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DetectAction {
+    /// Replay a transcript through the turn detector and print the
+    /// turns, boundaries and per-line decisions
+    Replay {
+        /// Transcript written by `wrap --record`
+        file: PathBuf,
+
+        /// Prompt pattern preset or custom regex [default: the recorded
+        /// pattern]
+        #[arg(long)]
+        pattern: Option<String>,
+
+        /// Turn boundary strategy [default: the --pattern preset's, else
+        /// the recorded one]
+        #[arg(long)]
+        boundary: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
 pub enum ClientAction {
     /// List all active sessions
     #[command(name = "list-sessions")]
-    ListSessions,
+    ListSessions {
+        /// Sort by this column
+        #[arg(long, value_enum, default_value_t = SessionColumn::Started)]
+        sort: SessionColumn,
+
+        /// Reverse the sort order
+        #[arg(short, long)]
+        reverse: bool,
+
+        /// Also list each session's latest turn ID, boundary strategy,
+        /// full working directory and command line
+        #[arg(short, long)]
+        long: bool,
+    },
 
     /// List turns for a session
     #[command(name = "list-turns")]
@@ -68,36 +162,95 @@ pub enum ClientAction {
         /// Maximum number of turns to return
         #[arg(long)]
         limit: Option<u32>,
+
+        /// List each turn's code block fragments
+        #[arg(long)]
+        code: bool,
+
+        /// List each turn's segments (prose, tool calls, edits…)
+        #[arg(long)]
+        segments: bool,
     },
 
     /// Get turn content and metadata by ID
     #[command(name = "get-turn")]
     GetTurn {
-        /// Turn ID (format: session_id:seq)
+        /// Turn ID (format: session_id:seq, or session_id:seq#codeN)
         turn_id: String,
 
         /// Show only metadata, omit content
         #[arg(long)]
         metadata_only: bool,
+
+        /// Print the user prompt that started the turn before its content
+        #[arg(long)]
+        with_prompt: bool,
+
+        /// Content representation to print (default: raw)
+        #[arg(long, value_enum)]
+        format: Option<FormatArg>,
+
+        /// Shorthand for `--format plain`
+        #[arg(long, conflicts_with = "format")]
+        plain: bool,
+    },
+
+    /// Print a session's latest turn, or follow its live output
+    /// read-only with -f
+    Tail {
+        /// Session ID
+        session: String,
+
+        /// Follow the session's output as it is written, until it ends
+        /// or Ctrl-C
+        #[arg(short, long)]
+        follow: bool,
     },
 
     /// Capture latest turn from session to relay buffer
     Capture {
         /// Session ID
         session: String,
+
+        /// Content representation used by later pastes and sinks
+        #[arg(long, value_enum)]
+        format: Option<FormatArg>,
+
+        /// Shorthand for `--format plain`
+        #[arg(long, conflicts_with = "format")]
+        plain: bool,
     },
 
     /// Capture specific turn by ID to relay buffer
     #[command(name = "capture-by-id")]
     CaptureByID {
-        /// Turn ID (format: session_id:seq)
+        /// Turn ID (format: session_id:seq, or session_id:seq#codeN)
         turn_id: String,
+
+        /// Content representation used by later pastes and sinks
+        #[arg(long, value_enum)]
+        format: Option<FormatArg>,
+
+        /// Shorthand for `--format plain`
+        #[arg(long, conflicts_with = "format")]
+        plain: bool,
     },
 
     /// Paste relay buffer content to session
     Paste {
         /// Target session ID
         session: String,
+
+        /// Content representation to paste (default: as captured)
+        #[arg(long, value_enum)]
+        format: Option<FormatArg>,
+
+        /// Shorthand for `--format plain`
+        #[arg(long, conflicts_with = "format")]
+        plain: bool,
+
+        #[command(flatten)]
+        inject: InjectArgs,
     },
 
     /// Deliver relay buffer to a sink
@@ -112,5 +265,216 @@ pub enum ClientAction {
         /// File path (required for file sink)
         #[arg(long)]
         path: Option<String>,
+
+        /// Content representation to deliver (default: as captured)
+        #[arg(long, value_enum)]
+        format: Option<FormatArg>,
+
+        /// Shorthand for `--format plain`
+        #[arg(long, conflicts_with = "format")]
+        plain: bool,
+
+        #[command(flatten)]
+        inject: InjectArgs,
     },
 }
+
+impl Command {
+    /// The `wrap` options and command a `wrap --detached` command passes
+    /// on to its background wrapper: all of them but `--detached`. `None`
+    /// for other commands.
+    pub fn detached_wrapper_args(&self) -> Option<Vec<OsString>> {
+        let Command::Wrap {
+            pattern,
+            boundary,
+            record,
+            record_format,
+            asciicast,
+            name,
+            export_env,
+            detached: true,
+            detached_session: _,
+            command,
+        } = self
+        else {
+            return None;
+        };
+        let mut args: Vec<OsString> = Vec::new();
+        let mut option = |flag: &str, value: OsString| {
+            args.push(flag.into());
+            args.push(value);
+        };
+        if let Some(pattern) = pattern {
+            option("--pattern", pattern.into());
+        }
+        if let Some(boundary) = boundary {
+            option("--boundary", boundary.into());
+        }
+        if let Some(record) = record {
+            option("--record", record.into());
+            let format = match record_format {
+                RecordFormatArg::Json => "json",
+                RecordFormatArg::Binary => "binary",
+            };
+            option("--record-format", format.into());
+        }
+        if let Some(asciicast) = asciicast {
+            option("--asciicast", asciicast.into());
+        }
+        if let Some(name) = name {
+            option("--name", name.into());
+        }
+        if *export_env {
+            args.push("--export-env".into());
+        }
+        args.push("--".into());
+        args.extend(command.iter().map(OsString::from));
+        Some(args)
+    }
+}
+
+/// Check a `wrap --name` value up front, as the broker would.
+fn parse_session_name(name: &str) -> Result<String, String> {
+    validate_session_name(name).map_err(|_| {
+        format!(
+            "must be 1-{MAX_SESSION_NAME} letters, digits, '-', '_' or '.', starting with a letter"
+        )
+    })?;
+    Ok(name.to_string())
+}
+
+/// A `list-sessions` column to sort by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SessionColumn {
+    Session,
+    Name,
+    Pid,
+    /// Live sessions first, then ended ones by when they ended
+    State,
+    Pattern,
+    /// Width, then height
+    Size,
+    Turns,
+    /// Time of the latest turn
+    LastTurn,
+    /// Start time
+    Started,
+    Cwd,
+    Command,
+}
+
+/// Transcript encoding for `wrap --record`.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum RecordFormatArg {
+    /// One JSON record per line
+    Json,
+    /// Length-prefixed MessagePack records
+    Binary,
+}
+
+/// How relayed content is written to the target agent.
+#[derive(Args)]
+#[command(next_help_heading = "Injection")]
+pub struct InjectArgs {
+    /// Inject as a bracketed paste [default: if the agent enabled
+    /// bracketed paste mode]
+    #[arg(long, conflicts_with = "no_bracketed")]
+    pub bracketed: bool,
+
+    /// Inject as typed input, even if the agent enabled bracketed
+    /// paste mode
+    #[arg(long)]
+    pub no_bracketed: bool,
+
+    /// Write the content in chunks of at most this many bytes
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u32).range(1..))]
+    pub chunk_size: Option<u32>,
+
+    /// Pause between chunks, and before the Enter of `--submit`
+    #[arg(
+        long,
+        value_name = "MS",
+        value_parser = clap::value_parser!(u32).range(..=i64::from(MAX_CHUNK_DELAY_MS))
+    )]
+    pub chunk_delay: Option<u32>,
+
+    /// Fail rather than inject more than this many bytes
+    #[arg(long, value_name = "BYTES")]
+    pub max_bytes: Option<u32>,
+
+    /// Press Enter after the content
+    #[arg(long)]
+    pub submit: bool,
+}
+
+/// Turn content representation.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum FormatArg {
+    /// Raw bytes as emitted by the agent (ANSI preserved)
+    Raw,
+    /// Visible text, as rendered on screen
+    Screen,
+    /// Line-by-line text with escapes stripped and whitespace trimmed
+    Plain,
+    /// The user's prompt, quoted, followed by the plain text
+    Exchange,
+    /// Assistant prose only, without tool calls, results or status
+    Prose,
+    /// Edit blocks only
+    Edits,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detached_wrapper_args_round_trip() {
+        let cli = Cli::parse_from([
+            "clippyctl",
+            "wrap",
+            "--detached",
+            "--name",
+            "planner",
+            "--record",
+            "t.bin",
+            "--record-format",
+            "binary",
+            "--export-env",
+            "--",
+            "claude",
+            "--detached",
+        ]);
+        let args = cli.command.detached_wrapper_args().unwrap();
+        let mut argv: Vec<OsString> = ["clippyctl", "wrap", "--detached-session", "id"]
+            .map(OsString::from)
+            .into();
+        argv.extend(args);
+        let Command::Wrap {
+            name,
+            record,
+            record_format,
+            export_env,
+            detached,
+            detached_session,
+            command,
+            ..
+        } = Cli::parse_from(argv).command
+        else {
+            panic!("not a wrap command");
+        };
+        assert_eq!(name.as_deref(), Some("planner"));
+        assert_eq!(record, Some(PathBuf::from("t.bin")));
+        assert!(matches!(record_format, RecordFormatArg::Binary));
+        assert!(export_env);
+        assert!(!detached);
+        assert_eq!(detached_session.as_deref(), Some("id"));
+        assert_eq!(command, ["claude", "--detached"]);
+    }
+
+    #[test]
+    fn only_detached_wrap_has_wrapper_args() {
+        let cli = Cli::parse_from(["clippyctl", "wrap", "claude"]);
+        assert!(cli.command.detached_wrapper_args().is_none());
+    }
+}
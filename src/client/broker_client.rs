@@ -12,7 +12,8 @@ use tokio_util::codec::Framed;
 
 use crate::ipc::codec::LengthPrefixedCodec;
 use crate::ipc::protocol::{
-    Message, PROTOCOL_VERSION, Role, SessionDescriptor, Status, TurnDescriptor,
+    ContentFormat, InjectOptions, Message, PROTOCOL_VERSION, Role, SessionDescriptor, Status,
+    TurnDescriptor,
 };
 
 use super::ClientError;
@@ -30,6 +31,21 @@ pub struct GetTurnResult {
     pub byte_length: u32,
     pub interrupted: bool,
     pub truncated: bool,
+    pub exit_status: Option<i32>,
+    /// Language tag, when the turn ID named a code block fragment.
+    pub language: Option<String>,
+    /// The user prompt that started the turn, if the wrapper saw one.
+    pub prompt: Option<Vec<u8>>,
+    /// Content was extracted from the alternate screen.
+    pub screen_rendered: bool,
+}
+
+/// An event on a followed session (`tail -f`).
+pub enum TailEvent {
+    /// A chunk of the session's output.
+    Output(Vec<u8>),
+    /// No more output follows, for this reason.
+    Ended(String),
 }
 
 /// Broker client for one-shot CLI commands.
@@ -120,7 +136,11 @@ impl BrokerClient {
     }
 
     /// Capture the latest turn from a session into the relay buffer.
-    pub async fn capture(&mut self, session: &str) -> Result<CaptureResult, ClientError> {
+    pub async fn capture(
+        &mut self,
+        session: &str,
+        format: Option<ContentFormat>,
+    ) -> Result<CaptureResult, ClientError> {
         let id = self.next_id;
         self.next_id += 1;
 
@@ -128,6 +148,7 @@ impl BrokerClient {
             .send(Message::Capture {
                 id,
                 session: session.to_string(),
+                format,
             })
             .await
             .map_err(|e| ClientError::Broker(format!("send capture: {e}")))?;
@@ -150,7 +171,12 @@ impl BrokerClient {
     }
 
     /// Paste relay buffer content to a session (inject into its PTY).
-    pub async fn paste(&mut self, session: &str) -> Result<(), ClientError> {
+    pub async fn paste(
+        &mut self,
+        session: &str,
+        format: Option<ContentFormat>,
+        options: InjectOptions,
+    ) -> Result<(), ClientError> {
         let id = self.next_id;
         self.next_id += 1;
 
@@ -158,6 +184,8 @@ impl BrokerClient {
             .send(Message::Paste {
                 id,
                 session: session.to_string(),
+                format,
+                options,
             })
             .await
             .map_err(|e| ClientError::Broker(format!("send paste: {e}")))?;
@@ -177,7 +205,11 @@ impl BrokerClient {
     }
 
     /// Get a turn's content and metadata by ID.
-    pub async fn get_turn(&mut self, turn_id: &str) -> Result<GetTurnResult, ClientError> {
+    pub async fn get_turn(
+        &mut self,
+        turn_id: &str,
+        format: Option<ContentFormat>,
+    ) -> Result<GetTurnResult, ClientError> {
         let id = self.next_id;
         self.next_id += 1;
 
@@ -185,6 +217,7 @@ impl BrokerClient {
             .send(Message::GetTurn {
                 id,
                 turn_id: turn_id.to_string(),
+                format,
             })
             .await
             .map_err(|e| ClientError::Broker(format!("send get_turn: {e}")))?;
@@ -197,6 +230,10 @@ impl BrokerClient {
                 byte_length: Some(byte_length),
                 interrupted: Some(interrupted),
                 truncated: Some(truncated),
+                exit_status,
+                language,
+                prompt,
+                screen_rendered,
                 ..
             })) => Ok(GetTurnResult {
                 content,
@@ -204,6 +241,10 @@ impl BrokerClient {
                 byte_length,
                 interrupted,
                 truncated,
+                exit_status,
+                language,
+                prompt,
+                screen_rendered: screen_rendered.unwrap_or(false),
             }),
             Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
                 "get_turn failed: {}",
@@ -255,7 +296,11 @@ impl BrokerClient {
     }
 
     /// Capture a specific turn by ID into the relay buffer.
-    pub async fn capture_by_id(&mut self, turn_id: &str) -> Result<CaptureResult, ClientError> {
+    pub async fn capture_by_id(
+        &mut self,
+        turn_id: &str,
+        format: Option<ContentFormat>,
+    ) -> Result<CaptureResult, ClientError> {
         let id = self.next_id;
         self.next_id += 1;
 
@@ -263,6 +308,7 @@ impl BrokerClient {
             .send(Message::CaptureByID {
                 id,
                 turn_id: turn_id.to_string(),
+                format,
             })
             .await
             .map_err(|e| ClientError::Broker(format!("send capture_by_id: {e}")))?;
@@ -290,6 +336,8 @@ impl BrokerClient {
         sink: &str,
         session: Option<String>,
         path: Option<String>,
+        format: Option<ContentFormat>,
+        options: InjectOptions,
     ) -> Result<(), ClientError> {
         let id = self.next_id;
         self.next_id += 1;
@@ -300,6 +348,8 @@ impl BrokerClient {
                 sink: sink.to_string(),
                 session,
                 path,
+                format,
+                options,
             })
             .await
             .map_err(|e| ClientError::Broker(format!("send deliver: {e}")))?;
@@ -319,6 +369,48 @@ impl BrokerClient {
     }
 }
 
+impl BrokerClient {
+    /// Follow a session's live output; read it with
+    /// [`next_tail`](Self::next_tail).
+    pub async fn subscribe(&mut self, session: &str) -> Result<(), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.framed
+            .send(Message::Subscribe {
+                id,
+                session: session.to_string(),
+            })
+            .await
+            .map_err(|e| ClientError::Broker(format!("send subscribe: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok, ..
+            })) => Ok(()),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "subscribe failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected subscribe response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Wait for the next event on the followed session.
+    pub async fn next_tail(&mut self) -> Result<TailEvent, ClientError> {
+        match self.framed.next().await {
+            Some(Ok(Message::Tail { data, .. })) => Ok(TailEvent::Output(data)),
+            Some(Ok(Message::TailEnded { reason, .. })) => Ok(TailEvent::Ended(reason)),
+            None => Ok(TailEvent::Ended("broker_disconnected".into())),
+            other => Err(ClientError::Broker(format!(
+                "unexpected tail message: {other:?}"
+            ))),
+        }
+    }
+}
+
 /// Resolve the broker socket path from `$XDG_RUNTIME_DIR`.
 fn resolve_socket_path() -> Result<PathBuf, ClientError> {
     let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
@@ -4,48 +4,181 @@
 //! metadata goes to stderr and raw content to stdout so that piping
 //! works naturally (`clippyctl client get-turn s1:5 | less`).
 
+use std::cmp::Ordering;
 use std::io::{self, Write};
 
-use crate::ipc::protocol::{SessionDescriptor, TurnDescriptor};
+use crate::cli::SessionColumn;
+use crate::ipc::protocol::{SessionDescriptor, SessionState, TurnDescriptor};
+use crate::turn::render;
 
 use super::broker_client::{CaptureResult, GetTurnResult};
 
+/// Width of the `PATTERN` column; longer patterns are clipped.
+const PATTERN_WIDTH: usize = 12;
+
+/// Width of the `CWD` column; longer paths keep their last part.
+const CWD_WIDTH: usize = 24;
+
+/// Least width of the `NAME` column, which widens to fit the longest
+/// name listed.
+const NAME_WIDTH: usize = 16;
+
 /// Print session descriptors as a table to stdout.
-pub fn print_sessions(sessions: &[SessionDescriptor]) {
+///
+/// Times are shown as ages (`42s`, `5m`, `3h`, `2d`). With `long`, each
+/// session's latest turn ID, boundary strategy, full working directory
+/// and command line are listed beneath it.
+pub fn print_sessions(sessions: &[SessionDescriptor], long: bool) {
     if sessions.is_empty() {
         println!("No active sessions");
         return;
     }
 
-    println!("{:<40} {:>8} HAS_TURN", "SESSION", "PID");
-    println!("{}", "-".repeat(60));
+    let now = crate::turn::epoch_millis();
+    let home = std::env::var("HOME").ok();
+    let name_width = name_width(sessions);
+    println!(
+        "{:<40} {:<name_width$} {:>8} {:<16} {:<12} {:>7} {:>5} {:>9} {:>7} {:<24} COMMAND",
+        "SESSION",
+        "NAME",
+        "PID",
+        "STATE",
+        "PATTERN",
+        "SIZE",
+        "TURNS",
+        "LAST_TURN",
+        "STARTED",
+        "CWD"
+    );
+    println!("{}", "-".repeat(145 + name_width));
     for s in sessions {
+        let cwd = s
+            .cwd
+            .as_deref()
+            .map(|cwd| abbreviate_home(cwd, home.as_deref()));
         println!(
-            "{:<40} {:>8} {}",
+            "{:<40} {:<name_width$} {:>8} {:<16} {:<12} {:>7} {:>5} {:>9} {:>7} {:<24} {}",
             s.session,
+            s.name.as_deref().unwrap_or("-"),
             s.pid,
-            if s.has_turn { "yes" } else { "no" }
+            format_state(s),
+            clip_end(s.pattern.as_deref().unwrap_or("-"), PATTERN_WIDTH),
+            format_size(s.cols, s.rows),
+            s.turn_count,
+            format_age(now, s.last_turn_at),
+            format_age(now, s.started_at),
+            clip_start(cwd.as_deref().unwrap_or("-"), CWD_WIDTH),
+            format_command(&s.command),
         );
+        if long {
+            let details = [
+                ("latest", s.latest_turn.as_deref()),
+                ("boundary", s.boundary.as_deref()),
+                ("cwd", s.cwd.as_deref()),
+            ];
+            for (label, value) in details {
+                println!("  {:<10}{}", format!("{label}:"), value.unwrap_or("-"));
+            }
+            println!("  {:<10}{}", "command:", format_command(&s.command));
+        }
+    }
+}
+
+/// Width of the `NAME` column: the longest name, but at least
+/// [`NAME_WIDTH`]. Names are ASCII, so bytes are columns.
+fn name_width(sessions: &[SessionDescriptor]) -> usize {
+    sessions
+        .iter()
+        .filter_map(|s| s.name.as_ref().map(String::len))
+        .fold(NAME_WIDTH, usize::max)
+}
+
+/// Order sessions by `column`, ascending unless `reverse`. Ties, and
+/// sessions missing the value, are ordered by session ID; missing
+/// values sort first.
+pub fn sort_sessions(sessions: &mut [SessionDescriptor], column: SessionColumn, reverse: bool) {
+    sessions.sort_by(|a, b| {
+        let order = compare_sessions(a, b, column).then_with(|| a.session.cmp(&b.session));
+        if reverse { order.reverse() } else { order }
+    });
+}
+
+fn compare_sessions(
+    a: &SessionDescriptor,
+    b: &SessionDescriptor,
+    column: SessionColumn,
+) -> Ordering {
+    match column {
+        SessionColumn::Session => Ordering::Equal,
+        SessionColumn::Name => a.name.cmp(&b.name),
+        SessionColumn::Pid => a.pid.cmp(&b.pid),
+        SessionColumn::State => (a.state == SessionState::Ended, a.ended_at)
+            .cmp(&(b.state == SessionState::Ended, b.ended_at)),
+        SessionColumn::Pattern => a.pattern.cmp(&b.pattern),
+        SessionColumn::Size => (a.cols, a.rows).cmp(&(b.cols, b.rows)),
+        SessionColumn::Turns => a.turn_count.cmp(&b.turn_count),
+        SessionColumn::LastTurn => a.last_turn_at.cmp(&b.last_turn_at),
+        SessionColumn::Started => a.started_at.cmp(&b.started_at),
+        SessionColumn::Cwd => a.cwd.cmp(&b.cwd),
+        SessionColumn::Command => a.command.cmp(&b.command),
+    }
+}
+
+/// Format a session's state: `live`, or how it ended, with the exit
+/// code if known (`exited(0)`, `disconnected`).
+fn format_state(session: &SessionDescriptor) -> String {
+    match (session.state, session.end_reason) {
+        (SessionState::Live, _) => "live".to_string(),
+        (SessionState::Ended, reason) => {
+            let reason = reason.map_or("ended", |reason| reason.as_str());
+            match session.exit_code {
+                Some(code) => format!("{reason}({code})"),
+                None => reason.to_string(),
+            }
+        }
     }
 }
 
 /// Print turn descriptors as a table to stdout.
-pub fn print_turns(turns: &[TurnDescriptor]) {
+///
+/// With `code`, each turn's code block fragments are listed beneath
+/// it; with `segments`, its segments.
+pub fn print_turns(turns: &[TurnDescriptor], code: bool, segments: bool) {
     if turns.is_empty() {
         println!("No turns in history");
         return;
     }
 
-    println!("{:<24} {:>10} {:>16} FLAGS", "TURN_ID", "SIZE", "TIMESTAMP");
-    println!("{}", "-".repeat(70));
+    println!(
+        "{:<24} {:>10} {:>16} {:>5} {:>4} FLAGS",
+        "TURN_ID", "SIZE", "TIMESTAMP", "EXIT", "CODE"
+    );
+    println!("{}", "-".repeat(81));
     for t in turns {
         println!(
-            "{:<24} {:>10} {:>16} {}",
+            "{:<24} {:>10} {:>16} {:>5} {:>4} {}",
             t.turn_id,
             t.byte_length,
             t.timestamp,
-            format_flags(t.interrupted, t.truncated),
+            format_exit_status(t.exit_status),
+            t.code_blocks.len(),
+            format_flags(t.interrupted, t.truncated, t.screen_rendered),
         );
+        if code {
+            for block in &t.code_blocks {
+                println!(
+                    "  {:<22} {:>10} {}",
+                    block.fragment_id,
+                    block.byte_length,
+                    block.language.as_deref().unwrap_or("-")
+                );
+            }
+        }
+        if segments {
+            for segment in &t.segments {
+                println!("  {:<22} {:>10}", segment.kind, segment.byte_length);
+            }
+        }
     }
 }
 
@@ -53,26 +186,45 @@ pub fn print_turns(turns: &[TurnDescriptor]) {
 ///
 /// Metadata header goes to stderr, raw content to stdout. With
 /// `metadata_only`, everything goes to stdout and content is omitted.
+/// With `with_prompt`, the turn's prompt is printed too: quoted ahead
+/// of the content, or as a `Prompt:` line with `metadata_only`.
 pub fn print_turn(
     turn_id: &str,
     result: &GetTurnResult,
     metadata_only: bool,
+    with_prompt: bool,
 ) -> Result<(), io::Error> {
-    let flags = format_flags(result.interrupted, result.truncated);
+    let flags = format_flags(result.interrupted, result.truncated, result.screen_rendered);
+    let exit = format_exit_status(result.exit_status);
 
     if metadata_only {
         println!("Turn:      {turn_id}");
         println!("Size:      {} bytes", result.byte_length);
         println!("Timestamp: {}", result.timestamp);
+        println!("Exit:      {exit}");
+        if let Some(language) = &result.language {
+            println!("Language:  {language}");
+        }
         println!("Flags:     {flags}");
+        if with_prompt {
+            println!("Prompt:    {}", format_prompt(result.prompt.as_deref()));
+        }
     } else {
         eprintln!("Turn:      {turn_id}");
         eprintln!("Size:      {} bytes", result.byte_length);
         eprintln!("Timestamp: {}", result.timestamp);
+        eprintln!("Exit:      {exit}");
+        if let Some(language) = &result.language {
+            eprintln!("Language:  {language}");
+        }
         eprintln!("Flags:     {flags}");
         eprintln!("---");
         let mut stdout = io::stdout().lock();
-        stdout.write_all(&result.content)?;
+        if with_prompt {
+            stdout.write_all(&render::exchange(result.prompt.as_deref(), &result.content))?;
+        } else {
+            stdout.write_all(&result.content)?;
+        }
     }
 
     Ok(())
@@ -93,8 +245,94 @@ pub fn print_deliver(sink: &str) {
     println!("Delivered to {sink} sink");
 }
 
-/// Format interrupted/truncated flags as a comma-separated string.
-fn format_flags(interrupted: bool, truncated: bool) -> String {
+/// Print why `tail -f` stopped, after the session's output.
+pub fn print_tail_ended(reason: &str) {
+    let why = match reason {
+        "session_ended" => "session ended",
+        "lagged" => "viewer fell behind",
+        "broker_disconnected" => "broker disconnected",
+        other => other,
+    };
+    eprintln!("\r\n[tail stopped: {why}]");
+}
+
+/// Format a terminal size as `<cols>x<rows>`, or `-` when unknown.
+fn format_size(cols: u16, rows: u16) -> String {
+    if cols == 0 || rows == 0 {
+        return "-".to_string();
+    }
+    format!("{cols}x{rows}")
+}
+
+/// Format how long ago `at` (Unix epoch millis) was, in its largest
+/// whole unit, or `-` when unknown.
+fn format_age(now: u64, at: Option<u64>) -> String {
+    let Some(at) = at else {
+        return "-".to_string();
+    };
+    let secs = now.saturating_sub(at) / 1000;
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86_400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+/// Format a command line, or `-` when it was not reported.
+fn format_command(command: &[String]) -> String {
+    if command.is_empty() {
+        return "-".to_string();
+    }
+    command.join(" ")
+}
+
+/// Show a path under `home` as `~/...`.
+fn abbreviate_home(path: &str, home: Option<&str>) -> String {
+    match home.and_then(|home| path.strip_prefix(home)) {
+        Some("") => "~".to_string(),
+        Some(rest) if rest.starts_with('/') => format!("~{rest}"),
+        _ => path.to_string(),
+    }
+}
+
+/// `text` cut to `width` characters, ending in `…` if it was cut.
+fn clip_end(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let head: String = text.chars().take(width - 1).collect();
+    format!("{head}…")
+}
+
+/// `text` cut to its last `width` characters, starting with `…` if it
+/// was cut.
+fn clip_start(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    if len <= width {
+        return text.to_string();
+    }
+    let tail: String = text.chars().skip(len - (width - 1)).collect();
+    format!("…{tail}")
+}
+
+/// Format an exit status, or `-` when none was reported.
+fn format_exit_status(exit_status: Option<i32>) -> String {
+    exit_status.map_or_else(|| "-".to_string(), |code| code.to_string())
+}
+
+/// Format a prompt on one line (newlines escaped), or `-` when none
+/// was recorded.
+fn format_prompt(prompt: Option<&[u8]>) -> String {
+    prompt.map_or_else(
+        || "-".to_string(),
+        |p| String::from_utf8_lossy(p).replace('\n', "\\n"),
+    )
+}
+
+/// Format interrupted/truncated/screen-rendered flags as a
+/// comma-separated string.
+fn format_flags(interrupted: bool, truncated: bool, screen_rendered: bool) -> String {
     let mut flags = Vec::new();
     if interrupted {
         flags.push("interrupted");
@@ -102,6 +340,9 @@ fn format_flags(interrupted: bool, truncated: bool) -> String {
     if truncated {
         flags.push("truncated");
     }
+    if screen_rendered {
+        flags.push("screen");
+    }
     if flags.is_empty() {
         "-".to_string()
     } else {
@@ -112,24 +353,145 @@ fn format_flags(interrupted: bool, truncated: bool) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ipc::protocol::EndReason;
 
     #[test]
     fn format_flags_none() {
-        assert_eq!(format_flags(false, false), "-");
+        assert_eq!(format_flags(false, false, false), "-");
     }
 
     #[test]
     fn format_flags_interrupted() {
-        assert_eq!(format_flags(true, false), "interrupted");
+        assert_eq!(format_flags(true, false, false), "interrupted");
     }
 
     #[test]
     fn format_flags_truncated() {
-        assert_eq!(format_flags(false, true), "truncated");
+        assert_eq!(format_flags(false, true, false), "truncated");
+    }
+
+    #[test]
+    fn format_exit_status_values() {
+        assert_eq!(format_exit_status(None), "-");
+        assert_eq!(format_exit_status(Some(0)), "0");
+        assert_eq!(format_exit_status(Some(-1)), "-1");
     }
 
     #[test]
     fn format_flags_both() {
-        assert_eq!(format_flags(true, true), "interrupted,truncated");
+        assert_eq!(format_flags(true, true, false), "interrupted,truncated");
+    }
+
+    #[test]
+    fn format_flags_screen_rendered() {
+        assert_eq!(format_flags(false, false, true), "screen");
+        assert_eq!(format_flags(true, false, true), "interrupted,screen");
+    }
+
+    #[test]
+    fn format_state_values() {
+        let mut session = SessionDescriptor {
+            session: "s1".into(),
+            pid: 100,
+            has_turn: true,
+            boundary: None,
+            name: None,
+            state: SessionState::Live,
+            exit_code: None,
+            end_reason: None,
+            ended_at: None,
+            ..SessionDescriptor::default()
+        };
+        assert_eq!(format_state(&session), "live");
+        session.state = SessionState::Ended;
+        session.end_reason = Some(EndReason::Exited);
+        session.exit_code = Some(130);
+        assert_eq!(format_state(&session), "exited(130)");
+        session.end_reason = Some(EndReason::Disconnected);
+        session.exit_code = None;
+        assert_eq!(format_state(&session), "disconnected");
+    }
+
+    #[test]
+    fn format_size_and_age() {
+        assert_eq!(format_size(120, 40), "120x40");
+        assert_eq!(format_size(0, 0), "-");
+        let now = 10_000_000;
+        assert_eq!(format_age(now, None), "-");
+        assert_eq!(format_age(now, Some(now - 42_000)), "42s");
+        assert_eq!(format_age(now, Some(now - 300_000)), "5m");
+        assert_eq!(format_age(now, Some(now - 7_200_000)), "2h");
+        assert_eq!(format_age(now + 2 * 86_400_000, Some(now)), "2d");
+        // A clock that went backwards reads as just now.
+        assert_eq!(format_age(now, Some(now + 5000)), "0s");
+    }
+
+    #[test]
+    fn cwd_abbreviated_and_clipped() {
+        let home = Some("/home/ada");
+        assert_eq!(abbreviate_home("/home/ada/src/app", home), "~/src/app");
+        assert_eq!(abbreviate_home("/home/ada", home), "~");
+        assert_eq!(abbreviate_home("/home/adam/x", home), "/home/adam/x");
+        assert_eq!(abbreviate_home("/srv", None), "/srv");
+        assert_eq!(clip_start("~/src/app", 24), "~/src/app");
+        assert_eq!(clip_start("/a/very/long/path/to/project", 10), "…o/project");
+        assert_eq!(clip_end("^❯ $", 12), "^❯ $");
+        assert_eq!(clip_end("^(>|\\$|%) $", 8), "^(>|\\$|…");
+    }
+
+    #[test]
+    fn sessions_sorted_by_column() {
+        let session = |id: &str, started_at: u64, turn_count: u64, ended: bool| SessionDescriptor {
+            session: id.into(),
+            started_at: Some(started_at),
+            turn_count,
+            state: if ended {
+                SessionState::Ended
+            } else {
+                SessionState::Live
+            },
+            ..SessionDescriptor::default()
+        };
+        let ids = |sessions: &[SessionDescriptor]| -> Vec<String> {
+            sessions.iter().map(|s| s.session.clone()).collect()
+        };
+        let mut sessions = vec![
+            session("c", 3000, 5, false),
+            session("a", 2000, 5, true),
+            session("b", 1000, 9, false),
+        ];
+
+        sort_sessions(&mut sessions, SessionColumn::Started, false);
+        assert_eq!(ids(&sessions), ["b", "a", "c"]);
+        // Equal counts fall back to the session ID.
+        sort_sessions(&mut sessions, SessionColumn::Turns, false);
+        assert_eq!(ids(&sessions), ["a", "c", "b"]);
+        sort_sessions(&mut sessions, SessionColumn::Turns, true);
+        assert_eq!(ids(&sessions), ["b", "c", "a"]);
+        sort_sessions(&mut sessions, SessionColumn::State, false);
+        assert_eq!(ids(&sessions), ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn name_column_fits_longest_name() {
+        let session = |name: Option<&str>| SessionDescriptor {
+            name: name.map(String::from),
+            ..SessionDescriptor::default()
+        };
+        assert_eq!(
+            name_width(&[session(None), session(Some("planner"))]),
+            NAME_WIDTH
+        );
+        let long = "n".repeat(64);
+        assert_eq!(
+            name_width(&[session(Some("planner")), session(Some(&long))]),
+            64
+        );
+    }
+
+    #[test]
+    fn format_prompt_values() {
+        assert_eq!(format_prompt(None), "-");
+        assert_eq!(format_prompt(Some(b"fix it\nplease")), "fix it\\nplease");
     }
 }
@@ -8,8 +8,11 @@
 mod broker_client;
 mod format;
 
-use crate::cli::ClientAction;
-use broker_client::BrokerClient;
+use std::io::Write;
+
+use crate::cli::{ClientAction, FormatArg, InjectArgs};
+use crate::ipc::protocol::{ContentFormat, InjectOptions};
+use broker_client::{BrokerClient, TailEvent};
 
 /// Client error type.
 #[derive(Debug, thiserror::Error)]
@@ -28,40 +31,94 @@ pub async fn run(action: ClientAction) -> Result<(), ClientError> {
     let mut broker = BrokerClient::connect().await?;
 
     match action {
-        ClientAction::ListSessions => {
-            let sessions = broker.list_sessions().await?;
-            format::print_sessions(&sessions);
+        ClientAction::ListSessions {
+            sort,
+            reverse,
+            long,
+        } => {
+            let mut sessions = broker.list_sessions().await?;
+            format::sort_sessions(&mut sessions, sort, reverse);
+            format::print_sessions(&sessions, long);
         }
-        ClientAction::ListTurns { session, limit } => {
+        ClientAction::ListTurns {
+            session,
+            limit,
+            code,
+            segments,
+        } => {
             let turns = broker.list_turns(&session, limit).await?;
-            format::print_turns(&turns);
+            format::print_turns(&turns, code, segments);
         }
         ClientAction::GetTurn {
             turn_id,
             metadata_only,
+            with_prompt,
+            format,
+            plain,
         } => {
-            let result = broker.get_turn(&turn_id).await?;
-            format::print_turn(&turn_id, &result, metadata_only)?;
+            let result = broker
+                .get_turn(&turn_id, select_format(format, plain))
+                .await?;
+            format::print_turn(&turn_id, &result, metadata_only, with_prompt)?;
+        }
+        ClientAction::Tail { session, follow } => {
+            if follow {
+                broker.subscribe(&session).await?;
+                follow_output(&mut broker).await?;
+            } else if let Some(turn) = broker.list_turns(&session, Some(1)).await?.first() {
+                let result = broker.get_turn(&turn.turn_id, None).await?;
+                std::io::stdout().write_all(&result.content)?;
+            }
         }
-        ClientAction::Capture { session } => {
-            let result = broker.capture(&session).await?;
+        ClientAction::Capture {
+            session,
+            format,
+            plain,
+        } => {
+            let result = broker
+                .capture(&session, select_format(format, plain))
+                .await?;
             format::print_capture(&result);
         }
-        ClientAction::CaptureByID { turn_id } => {
-            let result = broker.capture_by_id(&turn_id).await?;
+        ClientAction::CaptureByID {
+            turn_id,
+            format,
+            plain,
+        } => {
+            let result = broker
+                .capture_by_id(&turn_id, select_format(format, plain))
+                .await?;
             format::print_capture(&result);
         }
-        ClientAction::Paste { session } => {
-            broker.paste(&session).await?;
+        ClientAction::Paste {
+            session,
+            format,
+            plain,
+            inject,
+        } => {
+            broker
+                .paste(&session, select_format(format, plain), inject.into())
+                .await?;
             format::print_paste(&session);
         }
         ClientAction::Deliver {
             sink,
             session,
             path,
+            format,
+            plain,
+            inject,
         } => {
             validate_deliver_args(&sink, &session, &path)?;
-            broker.deliver(&sink, session, path).await?;
+            broker
+                .deliver(
+                    &sink,
+                    session,
+                    path,
+                    select_format(format, plain),
+                    inject.into(),
+                )
+                .await?;
             format::print_deliver(&sink);
         }
     }
@@ -69,6 +126,93 @@ pub async fn run(action: ClientAction) -> Result<(), ClientError> {
     Ok(())
 }
 
+/// Resolve a session ID or name to the session ID, as the broker does
+/// for client commands (CONTRACT_BROKER.md §Session names).
+pub async fn resolve_session(session: &str) -> Result<String, ClientError> {
+    let mut broker = BrokerClient::connect().await?;
+    let sessions = broker.list_sessions().await?;
+    sessions
+        .iter()
+        .find(|s| s.session == session)
+        .or_else(|| sessions.iter().find(|s| s.name.as_deref() == Some(session)))
+        .map(|s| s.session.clone())
+        .ok_or_else(|| ClientError::Broker(format!("no such session: {session}")))
+}
+
+/// Copy a followed session's output to stdout as it arrives, until it
+/// ends or Ctrl-C. The terminal is left as it is, not put in raw mode:
+/// nothing typed reaches the session.
+async fn follow_output(broker: &mut BrokerClient) -> Result<(), ClientError> {
+    let mut stdout = std::io::stdout();
+    loop {
+        tokio::select! {
+            event = broker.next_tail() => match event? {
+                TailEvent::Output(data) => {
+                    stdout.write_all(&data)?;
+                    stdout.flush()?;
+                }
+                TailEvent::Ended(reason) => {
+                    format::print_tail_ended(&reason);
+                    return Ok(());
+                }
+            },
+            _ = tokio::signal::ctrl_c() => {
+                // Undo attributes and a hidden cursor the session may
+                // have left behind.
+                stdout.write_all(b"\x1b[0m\x1b[?25h\r\n")?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl From<FormatArg> for ContentFormat {
+    fn from(arg: FormatArg) -> Self {
+        match arg {
+            FormatArg::Raw => ContentFormat::Raw,
+            FormatArg::Screen => ContentFormat::Screen,
+            FormatArg::Plain => ContentFormat::Plain,
+            FormatArg::Exchange => ContentFormat::Exchange,
+            FormatArg::Prose => ContentFormat::Prose,
+            FormatArg::Edits => ContentFormat::Edits,
+        }
+    }
+}
+
+/// Resolve `--format` / `--plain` into the requested representation.
+///
+/// clap rejects both flags together; `None` leaves the choice to the
+/// broker (raw, or the capture-time format for pastes and sinks).
+fn select_format(format: Option<FormatArg>, plain: bool) -> Option<ContentFormat> {
+    if plain {
+        Some(ContentFormat::Plain)
+    } else {
+        format.map(Into::into)
+    }
+}
+
+/// The injection override selected by `--bracketed` / `--no-bracketed`
+/// (mutually exclusive); neither leaves it to the wrapper.
+fn select_bracketed(bracketed: bool, no_bracketed: bool) -> Option<bool> {
+    match (bracketed, no_bracketed) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+impl From<InjectArgs> for InjectOptions {
+    fn from(args: InjectArgs) -> Self {
+        InjectOptions {
+            bracketed: select_bracketed(args.bracketed, args.no_bracketed),
+            chunk_size: args.chunk_size,
+            chunk_delay_ms: args.chunk_delay,
+            max_bytes: args.max_bytes,
+            submit: args.submit,
+        }
+    }
+}
+
 /// Validate deliver arguments before sending to the broker.
 ///
 /// Checks cross-field constraints: inject requires `--session`, file
@@ -108,6 +252,23 @@ fn validate_deliver_args(
 mod tests {
     use super::*;
 
+    #[test]
+    fn select_format_plain_flag() {
+        assert_eq!(select_format(None, false), None);
+        assert_eq!(select_format(None, true), Some(ContentFormat::Plain));
+        assert_eq!(
+            select_format(Some(FormatArg::Screen), false),
+            Some(ContentFormat::Screen)
+        );
+    }
+
+    #[test]
+    fn select_bracketed_flags() {
+        assert_eq!(select_bracketed(false, false), None);
+        assert_eq!(select_bracketed(true, false), Some(true));
+        assert_eq!(select_bracketed(false, true), Some(false));
+    }
+
     #[test]
     fn validate_deliver_clipboard_ok() {
         assert!(validate_deliver_args("clipboard", &None, &None).is_ok());
@@ -0,0 +1,414 @@
+//! Offline turn detection tools.
+//!
+//! `detect replay` feeds a transcript recorded by `wrap --record`
+//! through a [`TurnDetector`], making the same calls the PTY wrapper
+//! makes, and prints what the detector decided. Replays are
+//! deterministic: time is taken from the transcript, not the clock.
+//!
+//! See CONTRACT_TURN.md §Transcripts.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::cli::DetectAction;
+use crate::pty::input::{InputClassifier, InputEvent};
+use crate::turn::presets::{PresetError, PresetSet};
+use crate::turn::render;
+use crate::turn::screen::ScreenSize;
+use crate::turn::transcript::{Event, Transcript, TranscriptError};
+use crate::turn::{LineDecision, LineTrace, Turn, TurnDetector, TurnError, TurnEvent};
+
+/// Detect command error type.
+#[derive(Debug, thiserror::Error)]
+pub enum DetectError {
+    #[error("transcript: {0}")]
+    Transcript(#[from] TranscriptError),
+    #[error("turn detector error: {0}")]
+    TurnDetector(#[from] TurnError),
+    #[error("presets: {0}")]
+    Presets(#[from] PresetError),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Run the detect command. Called from `main.rs` for `Command::Detect`.
+pub fn run(action: DetectAction) -> Result<(), DetectError> {
+    match action {
+        DetectAction::Replay {
+            file,
+            pattern,
+            boundary,
+        } => {
+            let transcript = Transcript::read(&file)?;
+            let header = &transcript.header;
+
+            // An explicit --pattern resolves like `wrap --pattern`;
+            // otherwise the recorded regex is used as is.
+            let (pattern, preset_boundary) = match pattern {
+                Some(pattern) => {
+                    let argv0 = header.command.first().map_or("", String::as_str);
+                    let preset = PresetSet::load()?.resolve(Some(&pattern), argv0);
+                    (preset.pattern, preset.boundary)
+                }
+                None => (header.pattern.clone(), None),
+            };
+            let boundary = boundary
+                .or(preset_boundary)
+                .unwrap_or_else(|| header.boundary.clone());
+
+            let mut detector = TurnDetector::with_boundary(&pattern, &boundary)?;
+            let steps = replay(&transcript, &mut detector);
+
+            let mut out = io::stdout().lock();
+            writeln!(out, "Pattern:  {pattern}")?;
+            writeln!(out, "Boundary: {}", detector.boundary_spec())?;
+            writeln!(out, "---")?;
+            print_steps(&mut out, &steps)?;
+        }
+    }
+    Ok(())
+}
+
+/// Something that happened during a replay.
+#[derive(Debug)]
+pub enum Step {
+    /// A recorded input chunk and what it meant.
+    Input(Vec<u8>, Vec<InputEvent>),
+    /// A recorded signal.
+    Signal(String),
+    /// A completed output line and the detector's decision.
+    Line(LineTrace),
+    /// A detector event.
+    Event(TurnEvent),
+}
+
+/// Replay `transcript` through `detector`, returning each step with its
+/// time in milliseconds since the start of the recording.
+///
+/// Input and signals are handled as by the PTY wrapper: input chunks
+/// go through an [`InputClassifier`], and `SIGINT` interrupts the
+/// turn. Timers fire at their deadlines between events; any still
+/// pending at the end fire before the final partial line is flushed.
+/// The alternate screen is rendered at the recorded terminal size.
+pub fn replay(transcript: &Transcript, detector: &mut TurnDetector) -> Vec<(u64, Step)> {
+    let header = &transcript.header;
+    detector.set_screen_size(ScreenSize::or_default(header.cols, header.rows));
+    let start = Instant::now();
+    let at = |t_ms: u64| start + Duration::from_millis(t_ms);
+    detector.enable_trace();
+
+    let mut classifier = InputClassifier::new();
+    let mut steps = Vec::new();
+    for event in &transcript.events {
+        let t_ms = event.t_ms();
+        fire_timers(detector, start, Some(at(t_ms)), &mut steps);
+        match event {
+            Event::Output { data, .. } => {
+                let events = detector.feed_output_at(data, at(t_ms));
+                push_events(&mut steps, t_ms, detector, events);
+            }
+            Event::Input { data, .. } => {
+                let events = classifier.forward(data, detector);
+                steps.push((t_ms, Step::Input(data.clone(), events)));
+            }
+            Event::Signal { signal, .. } => {
+                steps.push((t_ms, Step::Signal(signal.clone())));
+                if signal == "SIGINT" {
+                    detector.notify_interrupt();
+                }
+            }
+        }
+    }
+
+    fire_timers(detector, start, None, &mut steps);
+    let end = steps.last().map_or(0, |(t_ms, _)| *t_ms);
+    let events = detector.flush_line();
+    push_events(&mut steps, end, detector, events);
+    steps
+}
+
+/// Fire the detector's timers in order, up to `until` (all of them if
+/// `None`).
+fn fire_timers(
+    detector: &mut TurnDetector,
+    start: Instant,
+    until: Option<Instant>,
+    steps: &mut Vec<(u64, Step)>,
+) {
+    while let Some(deadline) = detector
+        .deadline()
+        .filter(|&deadline| until.is_none_or(|until| deadline <= until))
+    {
+        let events = detector.poll_timer(deadline);
+        let t_ms = deadline.duration_since(start).as_millis() as u64;
+        push_events(steps, t_ms, detector, events);
+    }
+}
+
+/// Append the detector's line trace, then `events`, at `t_ms`.
+fn push_events(
+    steps: &mut Vec<(u64, Step)>,
+    t_ms: u64,
+    detector: &mut TurnDetector,
+    events: Vec<TurnEvent>,
+) {
+    steps.extend(
+        detector
+            .take_trace()
+            .into_iter()
+            .map(|line| (t_ms, Step::Line(line))),
+    );
+    steps.extend(events.into_iter().map(|event| (t_ms, Step::Event(event))));
+}
+
+/// Print replay steps, one per line, with each turn's plain text
+/// beneath it.
+fn print_steps(out: &mut impl Write, steps: &[(u64, Step)]) -> io::Result<()> {
+    let mut turns = 0;
+    for (t_ms, step) in steps {
+        write!(out, "{:>10}  ", format_time(*t_ms))?;
+        match step {
+            Step::Input(data, events) => {
+                write!(out, "input    {:?}", String::from_utf8_lossy(data))?;
+                for event in events {
+                    write!(out, " {}", input_event_name(*event))?;
+                }
+                writeln!(out)?
+            }
+            Step::Signal(signal) => writeln!(out, "signal   {signal}")?,
+            Step::Line(line) => writeln!(
+                out,
+                "line     {:<5} {:<9} {:?}",
+                if line.matched { "match" } else { "-" },
+                decision_name(line.decision),
+                line.text
+            )?,
+            Step::Event(TurnEvent::SessionReady) => writeln!(out, "ready")?,
+            Step::Event(TurnEvent::TurnProgress(content)) => {
+                writeln!(out, "progress {} bytes", content.len())?
+            }
+            Step::Event(TurnEvent::TurnCompleted(turn)) => {
+                turns += 1;
+                writeln!(out, "turn {turns}   {}", describe_turn(turn))?;
+                let text = render::plain_text(&turn.content);
+                for line in String::from_utf8_lossy(&text).lines() {
+                    writeln!(out, "{:>10}  | {line}", "")?;
+                }
+            }
+        }
+    }
+    writeln!(out, "---")?;
+    writeln!(out, "{turns} turn(s)")
+}
+
+/// Format milliseconds as seconds, e.g. `12.345s`.
+fn format_time(t_ms: u64) -> String {
+    format!("{}.{:03}s", t_ms / 1000, t_ms % 1000)
+}
+
+fn input_event_name(event: InputEvent) -> &'static str {
+    match event {
+        InputEvent::Submit => "[submit]",
+        InputEvent::Interrupt => "[interrupt]",
+        InputEvent::Cancel => "[cancel]",
+    }
+}
+
+fn decision_name(decision: LineDecision) -> &'static str {
+    match decision {
+        LineDecision::Echo => "echo",
+        LineDecision::Boundary => "boundary",
+        LineDecision::Tentative => "tentative",
+        LineDecision::Content => "content",
+        LineDecision::Idle => "idle",
+    }
+}
+
+/// Size and flags of a turn, e.g. `12 bytes, exit 1, interrupted`.
+fn describe_turn(turn: &Turn) -> String {
+    let mut parts = vec![format!("{} bytes", turn.content.len())];
+    if let Some(code) = turn.exit_status {
+        parts.push(format!("exit {code}"));
+    }
+    if turn.interrupted {
+        parts.push("interrupted".into());
+    }
+    if turn.screen_rendered {
+        parts.push("screen-rendered".into());
+    }
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::turn::transcript::{Header, TRANSCRIPT_VERSION};
+
+    fn transcript(boundary: &str, events: Vec<Event>) -> Transcript {
+        Transcript {
+            header: Header {
+                version: TRANSCRIPT_VERSION,
+                command: vec!["agent".into()],
+                preset: "test".into(),
+                pattern: r"^> $".into(),
+                boundary: boundary.into(),
+                cols: 80,
+                rows: 24,
+            },
+            events,
+        }
+    }
+
+    fn output(t_ms: u64, data: &[u8]) -> Event {
+        Event::Output {
+            t_ms,
+            data: data.to_vec(),
+        }
+    }
+
+    fn input(t_ms: u64, data: &[u8]) -> Event {
+        Event::Input {
+            t_ms,
+            data: data.to_vec(),
+        }
+    }
+
+    fn run(transcript: &Transcript) -> Vec<(u64, Step)> {
+        let header = &transcript.header;
+        let mut detector = TurnDetector::with_boundary(&header.pattern, &header.boundary).unwrap();
+        replay(transcript, &mut detector)
+    }
+
+    fn turns(steps: &[(u64, Step)]) -> Vec<(u64, &Turn)> {
+        steps
+            .iter()
+            .filter_map(|(t_ms, step)| match step {
+                Step::Event(TurnEvent::TurnCompleted(turn)) => Some((*t_ms, turn)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replay_reports_turns_and_line_decisions() {
+        let t = transcript(
+            "prompt",
+            vec![
+                output(0, b"> \n"),
+                input(100, b"hi\r"),
+                output(150, b"hi\r\nhello\r\n> \n"),
+            ],
+        );
+        let steps = run(&t);
+
+        let lines: Vec<_> = steps
+            .iter()
+            .filter_map(|(_, step)| match step {
+                Step::Line(line) => Some((line.text.as_str(), line.matched, line.decision)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("> ", true, LineDecision::Boundary),
+                ("hi", false, LineDecision::Echo),
+                ("hello", false, LineDecision::Content),
+                ("> ", true, LineDecision::Boundary),
+            ]
+        );
+        let turns = turns(&steps);
+        assert_eq!(turns.len(), 1);
+        assert_eq!(turns[0].0, 150);
+        assert_eq!(turns[0].1.content, b"hello\r\n");
+    }
+
+    #[test]
+    fn replay_fires_timers_at_recorded_times() {
+        let t = transcript(
+            "quiet:200",
+            vec![
+                output(0, b"> \n"),
+                input(1000, b"go\r"),
+                output(1100, b"working\n"),
+                // Resumes before the quiet period ends.
+                output(1250, b"done\n"),
+                input(5000, b"next\r"),
+            ],
+        );
+        let steps = run(&t);
+        assert!(matches!(
+            steps[1],
+            (200, Step::Event(TurnEvent::SessionReady))
+        ));
+        let turns = turns(&steps);
+        assert_eq!(turns.len(), 1);
+        assert_eq!(turns[0].0, 1450);
+        assert_eq!(turns[0].1.content, b"working\ndone\n");
+    }
+
+    #[test]
+    fn replay_sigint_marks_turn_interrupted() {
+        let t = transcript(
+            "prompt",
+            vec![
+                output(0, b"> \n"),
+                input(10, b"x\r"),
+                output(20, b"partial\n"),
+                Event::Signal {
+                    t_ms: 30,
+                    signal: "SIGINT".into(),
+                },
+                output(40, b"> \n"),
+            ],
+        );
+        let steps = run(&t);
+        let turns = turns(&steps);
+        assert!(turns[0].1.interrupted);
+    }
+
+    #[test]
+    fn replay_flushes_unterminated_prompt() {
+        let t = transcript(
+            "prompt",
+            vec![
+                output(0, b"> \n"),
+                input(10, b"x\r"),
+                output(20, b"out\n> "),
+            ],
+        );
+        let steps = run(&t);
+        let turns = turns(&steps);
+        assert_eq!(turns.len(), 1);
+        assert_eq!(turns[0].1.content, b"out\n");
+    }
+
+    #[test]
+    fn printed_replay() {
+        let t = transcript(
+            "prompt",
+            vec![
+                output(0, b"> \n"),
+                input(1500, b"hi\r"),
+                output(1600, b"\x1b[1mhello\x1b[0m\n> \n"),
+            ],
+        );
+        let mut out = Vec::new();
+        print_steps(&mut out, &run(&t)).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            concat!(
+                "    0.000s  line     match boundary  \"> \"\n",
+                "    0.000s  ready\n",
+                "    1.500s  input    \"hi\\r\" [submit]\n",
+                "    1.600s  line     -     content   \"hello\"\n",
+                "    1.600s  line     match boundary  \"> \"\n",
+                "    1.600s  turn 1   14 bytes\n",
+                "            | hello\n",
+                "---\n",
+                "1 turn(s)\n",
+            )
+        );
+    }
+}
@@ -12,7 +12,9 @@ use tokio::net::UnixStream;
 use tokio_util::codec::Framed;
 
 use crate::ipc::codec::LengthPrefixedCodec;
-use crate::ipc::protocol::{Message, PROTOCOL_VERSION, Role, SessionDescriptor, Status};
+use crate::ipc::protocol::{
+    InjectOptions, Message, PROTOCOL_VERSION, Role, SessionDescriptor, Status,
+};
 
 use super::HotkeyError;
 
@@ -121,6 +123,7 @@ impl BrokerClient {
             .send(Message::Capture {
                 id,
                 session: session.to_string(),
+                format: None,
             })
             .await
             .map_err(|e| HotkeyError::Broker(format!("send capture: {e}")))?;
@@ -152,6 +155,8 @@ impl BrokerClient {
                 sink: "clipboard".into(),
                 session: None,
                 path: None,
+                format: None,
+                options: InjectOptions::default(),
             })
             .await
             .map_err(|e| HotkeyError::Broker(format!("send deliver_clipboard: {e}")))?;
@@ -179,6 +184,8 @@ impl BrokerClient {
             .send(Message::Paste {
                 id,
                 session: session.to_string(),
+                format: None,
+                options: InjectOptions::default(),
             })
             .await
             .map_err(|e| HotkeyError::Broker(format!("send paste: {e}")))?;
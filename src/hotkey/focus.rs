@@ -4,7 +4,7 @@
 //! the process tree from each session's child PID upward to find the
 //! window-owning process. See CONTRACT_HOTKEY.md §94–128.
 
-use crate::ipc::protocol::SessionDescriptor;
+use crate::ipc::protocol::{SessionDescriptor, SessionState};
 
 /// Focus resolution error.
 #[derive(Debug)]
@@ -68,6 +68,8 @@ pub fn is_ancestor(ancestor_pid: u32, descendant_pid: u32) -> bool {
 /// window's process.
 ///
 /// Per CONTRACT_HOTKEY.md §104–114:
+/// - Ended sessions are skipped: their child is gone, and its PID may
+///   have been reused (CONTRACT_BROKER.md §Ended sessions).
 /// - Walk the process tree from each session's child PID upward.
 /// - If the window PID is an ancestor: the session is a candidate.
 /// - Exactly one match → return that session ID.
@@ -80,6 +82,9 @@ pub fn resolve_session(
     let mut matches = Vec::new();
 
     for session in sessions {
+        if session.state == SessionState::Ended {
+            continue;
+        }
         if session.pid == window_pid || is_ancestor(window_pid, session.pid) {
             matches.push(session.session.clone());
         }
@@ -159,6 +164,13 @@ mod tests {
             session: "s1".into(),
             pid: my_pid,
             has_turn: false,
+            boundary: None,
+            name: None,
+            state: SessionState::Live,
+            exit_code: None,
+            end_reason: None,
+            ended_at: None,
+            ..SessionDescriptor::default()
         }];
 
         // Our parent should be an ancestor of our PID.
@@ -173,6 +185,13 @@ mod tests {
             session: "s1".into(),
             pid: 1, // init — window PID 999999 is not an ancestor of PID 1
             has_turn: false,
+            boundary: None,
+            name: None,
+            state: SessionState::Live,
+            exit_code: None,
+            end_reason: None,
+            ended_at: None,
+            ..SessionDescriptor::default()
         }];
 
         let result = resolve_session(999_999, &sessions);
@@ -190,11 +209,25 @@ mod tests {
                 session: "s1".into(),
                 pid: my_pid,
                 has_turn: false,
+                boundary: None,
+                name: None,
+                state: SessionState::Live,
+                exit_code: None,
+                end_reason: None,
+                ended_at: None,
+                ..SessionDescriptor::default()
             },
             SessionDescriptor {
                 session: "s2".into(),
                 pid: my_pid,
                 has_turn: true,
+                boundary: None,
+                name: None,
+                state: SessionState::Live,
+                exit_code: None,
+                end_reason: None,
+                ended_at: None,
+                ..SessionDescriptor::default()
             },
         ];
 
@@ -208,6 +241,43 @@ mod tests {
         assert!(matches!(result, Err(FocusError::NoSession)));
     }
 
+    #[test]
+    fn resolve_session_skips_ended() {
+        let my_pid = std::process::id();
+        let sessions = vec![
+            SessionDescriptor {
+                session: "old".into(),
+                pid: my_pid,
+                has_turn: true,
+                boundary: None,
+                name: None,
+                state: SessionState::Ended,
+                exit_code: Some(0),
+                end_reason: None,
+                ended_at: Some(1000),
+                ..SessionDescriptor::default()
+            },
+            SessionDescriptor {
+                session: "s1".into(),
+                pid: my_pid,
+                has_turn: false,
+                boundary: None,
+                name: None,
+                state: SessionState::Live,
+                exit_code: None,
+                end_reason: None,
+                ended_at: None,
+                ..SessionDescriptor::default()
+            },
+        ];
+
+        assert_eq!(resolve_session(my_pid, &sessions).unwrap(), "s1");
+        assert!(matches!(
+            resolve_session(my_pid, &sessions[..1]),
+            Err(FocusError::NoSession)
+        ));
+    }
+
     #[test]
     fn resolve_session_direct_pid_match() {
         // If window PID == session PID, it should match.
@@ -216,6 +286,13 @@ mod tests {
             session: "s1".into(),
             pid: my_pid,
             has_turn: false,
+            boundary: None,
+            name: None,
+            state: SessionState::Live,
+            exit_code: None,
+            end_reason: None,
+            ended_at: None,
+            ..SessionDescriptor::default()
         }];
 
         let result = resolve_session(my_pid, &sessions);
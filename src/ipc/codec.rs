@@ -238,10 +238,20 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                boundary: None,
+                parser: None,
+                name: None,
+                command: Vec::new(),
+                cwd: None,
+                started_at: None,
+                cols: 0,
+                rows: 0,
             },
             Message::Deregister {
                 id: 2,
                 session: "s1".into(),
+                exit_code: Some(0),
+                reason: None,
             },
             Message::TurnCompleted {
                 id: 3,
@@ -249,23 +259,33 @@ mod tests {
                 content: b"turn content".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                cols: 80,
+                rows: 24,
+                exit_status: None,
+                prompt: None,
+                screen_rendered: false,
             },
             Message::Capture {
                 id: 4,
                 session: "s1".into(),
+                format: None,
             },
             Message::Paste {
                 id: 5,
                 session: "s1".into(),
+                format: None,
+                options: InjectOptions::default(),
             },
             Message::Inject {
                 id: 0,
                 content: b"inject bytes".to_vec(),
+                options: InjectOptions::default(),
             },
             Message::ListSessions { id: 6 },
             Message::GetTurn {
                 id: 7,
                 turn_id: "s1:1".into(),
+                format: None,
             },
             Message::ListTurns {
                 id: 8,
@@ -275,12 +295,15 @@ mod tests {
             Message::CaptureByID {
                 id: 9,
                 turn_id: "s1:2".into(),
+                format: None,
             },
             Message::Deliver {
                 id: 10,
                 sink: "clipboard".into(),
                 session: None,
                 path: None,
+                format: None,
+                options: InjectOptions::default(),
             },
             Message::Response {
                 id: 1,
@@ -295,6 +318,10 @@ mod tests {
                 interrupted: None,
                 truncated: None,
                 turns: None,
+                exit_status: None,
+                language: None,
+                prompt: None,
+                screen_rendered: None,
             },
         ];
 
@@ -337,6 +364,7 @@ mod tests {
         let msg2 = Message::Capture {
             id: 2,
             session: "s1".into(),
+            format: None,
         };
 
         let mut buf = BytesMut::new();
@@ -360,6 +388,11 @@ mod tests {
             content: content.clone(),
             interrupted: true,
             timestamp: 1000,
+            cols: 80,
+            rows: 24,
+            exit_status: None,
+            prompt: None,
+            screen_rendered: false,
         };
 
         let mut buf = encode_message(&msg);
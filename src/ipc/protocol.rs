@@ -30,10 +30,45 @@ pub enum Message {
         session: String,
         pid: u32,
         pattern: String,
+        /// Turn boundary strategy spec. Absent from older wrappers.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        boundary: Option<String>,
+        /// Output parser name (CONTRACT_TURN.md §Segments). Absent if
+        /// the preset has none.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parser: Option<String>,
+        /// Human-chosen session name (CONTRACT_BROKER.md §Session
+        /// names).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        /// The wrapped command line. Empty when absent (older wrappers).
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        command: Vec<String>,
+        /// The command's working directory.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cwd: Option<String>,
+        /// Unix epoch millis when the session started.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        started_at: Option<u64>,
+        /// Terminal width in columns. 0 when absent.
+        #[serde(default)]
+        cols: u16,
+        /// Terminal height in rows. 0 when absent.
+        #[serde(default)]
+        rows: u16,
     },
 
     #[serde(rename = "deregister")]
-    Deregister { id: u32, session: String },
+    Deregister {
+        id: u32,
+        session: String,
+        /// The child's exit code (CONTRACT_BROKER.md §Ended sessions).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit_code: Option<i32>,
+        /// Why the session ended; `exited` when absent.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<EndReason>,
+    },
 
     // -- Turn storage --
     #[serde(rename = "turn_completed")]
@@ -48,14 +83,80 @@ pub enum Message {
         /// to receipt time.
         #[serde(default)]
         timestamp: u64,
+        /// Terminal width in columns when the turn was produced, used
+        /// to render the screen representation. 0 when absent (older
+        /// wrappers); the broker assumes 80.
+        #[serde(default)]
+        cols: u16,
+        /// Terminal height in rows. 0 when absent; the broker assumes 24.
+        #[serde(default)]
+        rows: u16,
+        /// Exit status from an OSC 133 `D` marker, if one ended the turn.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit_status: Option<i32>,
+        /// The user input that started the turn (CONTRACT_TURN.md
+        /// §Exchange).
+        #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_bytes")]
+        prompt: Option<Vec<u8>>,
+        /// Whether `content` was extracted from the alternate screen
+        /// (CONTRACT_TURN.md §Alternate Screen). False when absent.
+        #[serde(default)]
+        screen_rendered: bool,
+    },
+
+    /// Content of the turn in progress, sent at most every 500 ms
+    /// while the agent is responding. Replaces any earlier progress
+    /// for the session (CONTRACT_BROKER.md §Turn Progress).
+    #[serde(rename = "turn_progress")]
+    TurnProgress {
+        id: u32,
+        session: String,
+        #[serde(with = "serde_bytes")]
+        content: Vec<u8>,
+        /// Unix epoch millis when the progress was sent.
+        #[serde(default)]
+        timestamp: u64,
+        /// See [`Message::TurnCompleted`].
+        #[serde(default)]
+        cols: u16,
+        #[serde(default)]
+        rows: u16,
+    },
+
+    /// A chunk of PTY output, sent only while the broker has asked
+    /// for it with [`Message::Mirror`] (CONTRACT_BROKER.md §Live
+    /// Mirroring).
+    #[serde(rename = "output")]
+    Output {
+        id: u32,
+        session: String,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
     },
 
     // -- Capture / Paste --
     #[serde(rename = "capture")]
-    Capture { id: u32, session: String },
+    Capture {
+        id: u32,
+        session: String,
+        /// Representation stored as the relay buffer's default.
+        /// Defaults to `raw` when absent.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        format: Option<ContentFormat>,
+    },
 
     #[serde(rename = "paste")]
-    Paste { id: u32, session: String },
+    Paste {
+        id: u32,
+        session: String,
+        /// Representation to inject. Defaults to the format selected
+        /// at capture time when absent.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        format: Option<ContentFormat>,
+        /// How the content is written to the child.
+        #[serde(flatten)]
+        options: InjectOptions,
+    },
 
     // -- Unsolicited commands (broker → wrapper) --
     #[serde(rename = "inject")]
@@ -63,6 +164,42 @@ pub enum Message {
         id: u32,
         #[serde(with = "serde_bytes")]
         content: Vec<u8>,
+        /// See [`Message::Paste`].
+        #[serde(flatten)]
+        options: InjectOptions,
+    },
+
+    /// Start (or stop) sending [`Message::Output`] for the session.
+    #[serde(rename = "mirror")]
+    Mirror {
+        id: u32,
+        session: String,
+        enabled: bool,
+    },
+
+    // -- Live mirroring --
+    /// Follow a session's live output. On success the broker sends
+    /// [`Message::Tail`] on this connection as output arrives, until
+    /// [`Message::TailEnded`].
+    #[serde(rename = "subscribe")]
+    Subscribe { id: u32, session: String },
+
+    /// Unsolicited (broker → viewer): output of a followed session.
+    #[serde(rename = "tail")]
+    Tail {
+        id: u32,
+        session: String,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+
+    /// Unsolicited (broker → viewer): no more output will follow.
+    /// `reason` is `session_ended` or `lagged`.
+    #[serde(rename = "tail_ended")]
+    TailEnded {
+        id: u32,
+        session: String,
+        reason: String,
     },
 
     // -- Query --
@@ -71,7 +208,14 @@ pub enum Message {
 
     // -- Turn registry (v1) --
     #[serde(rename = "get_turn")]
-    GetTurn { id: u32, turn_id: String },
+    GetTurn {
+        id: u32,
+        turn_id: String,
+        /// Representation of the returned content. Defaults to `raw`
+        /// when absent.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        format: Option<ContentFormat>,
+    },
 
     #[serde(rename = "list_turns")]
     ListTurns {
@@ -82,7 +226,13 @@ pub enum Message {
     },
 
     #[serde(rename = "capture_by_id")]
-    CaptureByID { id: u32, turn_id: String },
+    CaptureByID {
+        id: u32,
+        turn_id: String,
+        /// See [`Message::Capture`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        format: Option<ContentFormat>,
+    },
 
     // -- Sink delivery (v1) --
     #[serde(rename = "deliver")]
@@ -93,6 +243,12 @@ pub enum Message {
         session: Option<String>,
         #[serde(default, skip_serializing_if = "Option::is_none")]
         path: Option<String>,
+        /// See [`Message::Paste`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        format: Option<ContentFormat>,
+        /// See [`Message::Paste`]. `inject` sink only.
+        #[serde(flatten)]
+        options: InjectOptions,
     },
 
     // -- Generic response --
@@ -119,6 +275,16 @@ pub enum Message {
         interrupted: Option<bool>,
         #[serde(default, skip_serializing_if = "Option::is_none")]
         truncated: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit_status: Option<i32>,
+        /// Language tag of a code block fragment.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+        /// The user input that started the turn.
+        #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_bytes")]
+        prompt: Option<Vec<u8>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        screen_rendered: Option<bool>,
         // -- ListTurns descriptors (v1) --
         #[serde(default, skip_serializing_if = "Option::is_none")]
         turns: Option<Vec<TurnDescriptor>>,
@@ -141,12 +307,177 @@ pub enum Status {
     Error,
 }
 
+/// How injected content is written to the child's PTY. Carried by
+/// paste and deliver requests, and passed on in the inject command.
+///
+/// See CONTRACT_BROKER.md §Injection options.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InjectOptions {
+    /// Whether to inject as a bracketed paste. Defaults to whether the
+    /// child enabled bracketed paste mode when absent
+    /// (CONTRACT_PTY.md §Bracketed Paste).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bracketed: Option<bool>,
+    /// Write the content in chunks of at most this many bytes. The
+    /// content is written at once when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<u32>,
+    /// Pause between chunks, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_delay_ms: Option<u32>,
+    /// Refuse content longer than this many bytes (checked by the
+    /// broker).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u32>,
+    /// Press Enter after the content.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub submit: bool,
+}
+
+/// Longest pause between injected chunks.
+pub const MAX_CHUNK_DELAY_MS: u32 = 10_000;
+
+impl InjectOptions {
+    /// Check the options, as the broker does before dispatching.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.chunk_size == Some(0) || self.chunk_delay_ms > Some(MAX_CHUNK_DELAY_MS) {
+            return Err("invalid_options");
+        }
+        Ok(())
+    }
+}
+
+/// Turn content representation.
+///
+/// See CONTRACT_TURN.md §Rendered content.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentFormat {
+    /// Raw bytes as emitted by the agent (ANSI preserved).
+    #[default]
+    Raw,
+    /// Visible text after replaying the bytes through a screen model.
+    Screen,
+    /// Line-by-line text: escapes stripped, CR and BS applied, trailing
+    /// whitespace trimmed.
+    Plain,
+    /// The user's prompt, quoted, followed by the plain text
+    /// (CONTRACT_TURN.md §Exchange).
+    Exchange,
+    /// Assistant prose segments only (CONTRACT_TURN.md §Segments).
+    Prose,
+    /// Edit block segments only.
+    Edits,
+}
+
 /// Session descriptor returned in list_sessions responses.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+///
+/// Fields after `has_turn` are absent from older brokers and wrappers;
+/// see CONTRACT_BROKER.md §ListSessions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SessionDescriptor {
     pub session: String,
     pub pid: u32,
     pub has_turn: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boundary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `live` from brokers that predate ended sessions.
+    #[serde(default)]
+    pub state: SessionState,
+    /// The child's exit code, if the session ended and it is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Why the session ended.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_reason: Option<EndReason>,
+    /// Unix epoch millis when the session ended.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<u64>,
+    /// Prompt preset name or custom regex the wrapper detects turns with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// The wrapped command line.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+    /// The command's working directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Unix epoch millis when the session started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    /// Latest known terminal width; 0 if unknown.
+    #[serde(default)]
+    pub cols: u16,
+    /// Latest known terminal height; 0 if unknown.
+    #[serde(default)]
+    pub rows: u16,
+    /// Turns completed since the session first registered, including any
+    /// evicted from its ring.
+    #[serde(default)]
+    pub turn_count: u64,
+    /// ID of the latest completed turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_turn: Option<String>,
+    /// Unix epoch millis when the latest turn was detected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_turn_at: Option<u64>,
+}
+
+/// Whether a session's wrapper is still registered.
+///
+/// See CONTRACT_BROKER.md §Ended sessions.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionState {
+    #[default]
+    Live,
+    /// The wrapper is gone; the session's turns are kept for the
+    /// broker's grace period.
+    Ended,
+}
+
+/// Why a session ended.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EndReason {
+    /// The child exited and the wrapper deregistered.
+    Exited,
+    /// The child was killed by a signal and the wrapper deregistered.
+    Killed,
+    /// The wrapper stopped before the child exited (a termination
+    /// signal, closed input or an I/O error) and deregistered.
+    Terminated,
+    /// The wrapper's connection closed without a deregister.
+    Disconnected,
+}
+
+impl EndReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Exited => "exited",
+            Self::Killed => "killed",
+            Self::Terminated => "terminated",
+            Self::Disconnected => "disconnected",
+        }
+    }
+}
+
+/// Maximum length of a session name.
+pub const MAX_SESSION_NAME: usize = 64;
+
+/// Check a session name (CONTRACT_BROKER.md §Session names): 1 to
+/// [`MAX_SESSION_NAME`] ASCII letters, digits, `-`, `_` or `.`,
+/// starting with a letter. Names can then never contain the `:` and
+/// `#` of turn references.
+pub fn validate_session_name(name: &str) -> Result<(), &'static str> {
+    let valid = name.len() <= MAX_SESSION_NAME
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+    if valid { Ok(()) } else { Err("invalid_name") }
 }
 
 /// Turn descriptor returned in list_turns responses (metadata only, no content).
@@ -157,6 +488,36 @@ pub struct TurnDescriptor {
     pub byte_length: u32,
     pub interrupted: bool,
     pub truncated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_status: Option<i32>,
+    /// Content was extracted from the alternate screen.
+    #[serde(default)]
+    pub screen_rendered: bool,
+    /// Fenced code blocks in the turn, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub code_blocks: Vec<CodeBlockDescriptor>,
+    /// Typed segments of the turn, in order (CONTRACT_TURN.md
+    /// §Segments).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<SegmentDescriptor>,
+}
+
+/// Code block fragment descriptor within a [`TurnDescriptor`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CodeBlockDescriptor {
+    /// Fragment reference: `<turn_id>#code<N>`.
+    pub fragment_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    pub byte_length: u32,
+}
+
+/// Segment descriptor within a [`TurnDescriptor`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SegmentDescriptor {
+    /// `prose`, `tool_call`, `tool_result`, `edit_block` or `status`.
+    pub kind: String,
+    pub byte_length: u32,
 }
 
 /// Protocol version for v0.
@@ -225,15 +586,113 @@ mod tests {
             session: "abc-123".into(),
             pid: 4567,
             pattern: "generic".into(),
+            boundary: Some("prompt+quiet:500".into()),
+            parser: Some("claude".into()),
+            name: Some("planner".into()),
+            command: vec!["aider".into(), "--model".into(), "gpt-4o".into()],
+            cwd: Some("/home/user/project".into()),
+            started_at: Some(1_700_000_000_000),
+            cols: 120,
+            rows: 40,
         };
         assert_eq!(round_trip(&msg), msg);
     }
 
+    #[test]
+    fn register_compat_without_metadata() {
+        // Older wrappers send only the v0 fields.
+        #[derive(serde::Serialize)]
+        struct V0Register {
+            #[serde(rename = "type")]
+            msg_type: &'static str,
+            id: u32,
+            session: &'static str,
+            pid: u32,
+            pattern: &'static str,
+        }
+        let wire = V0Register {
+            msg_type: "register",
+            id: 1,
+            session: "s1",
+            pid: 100,
+            pattern: "claude",
+        };
+        let encoded = rmp_serde::to_vec_named(&wire).unwrap();
+        let decoded: Message = rmp_serde::from_slice(&encoded).unwrap();
+        let Message::Register {
+            command,
+            cwd,
+            started_at,
+            cols,
+            rows,
+            ..
+        } = decoded
+        else {
+            panic!("expected Register, got {decoded:?}");
+        };
+        assert!(command.is_empty());
+        assert_eq!((cwd, started_at, cols, rows), (None, None, 0, 0));
+    }
+
+    #[test]
+    fn session_names() {
+        for name in ["planner", "a", "review-2", "x_y.z"] {
+            assert_eq!(validate_session_name(name), Ok(()), "{name}");
+        }
+        let long = "a".repeat(MAX_SESSION_NAME + 1);
+        for name in ["", "2nd", "-x", "a:1", "a#b", "has space", "ünï", &long] {
+            assert_eq!(validate_session_name(name), Err("invalid_name"), "{name}");
+        }
+    }
+
+    #[test]
+    fn mirroring_round_trip() {
+        let msgs = [
+            Message::Subscribe {
+                id: 3,
+                session: "planner".into(),
+            },
+            Message::Mirror {
+                id: 0,
+                session: "abc-123".into(),
+                enabled: true,
+            },
+            Message::Output {
+                id: 4,
+                session: "abc-123".into(),
+                data: b"\x1b[1mhi\x1b[0m".to_vec(),
+            },
+            Message::Tail {
+                id: 0,
+                session: "abc-123".into(),
+                data: b"hi".to_vec(),
+            },
+            Message::TailEnded {
+                id: 0,
+                session: "abc-123".into(),
+                reason: "lagged".into(),
+            },
+        ];
+        for msg in &msgs {
+            assert_eq!(&round_trip(msg), msg);
+        }
+    }
+
     #[test]
     fn deregister_round_trip() {
         let msg = Message::Deregister {
             id: 2,
             session: "abc-123".into(),
+            exit_code: None,
+            reason: None,
+        };
+        assert_eq!(round_trip(&msg), msg);
+
+        let msg = Message::Deregister {
+            id: 3,
+            session: "abc-123".into(),
+            exit_code: Some(130),
+            reason: Some(EndReason::Killed),
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -267,12 +726,21 @@ mod tests {
                 content,
                 interrupted,
                 timestamp,
+                cols,
+                rows,
+                exit_status,
+                prompt,
+                screen_rendered,
             } => {
                 assert_eq!(id, 5);
                 assert_eq!(session, "s1");
                 assert_eq!(content, b"hello");
                 assert!(!interrupted);
                 assert_eq!(timestamp, 0, "missing timestamp must default to 0");
+                assert_eq!((cols, rows), (0, 0), "missing size must default to 0");
+                assert_eq!(exit_status, None);
+                assert_eq!(prompt, None);
+                assert!(!screen_rendered);
             }
             _ => panic!("expected TurnCompleted"),
         }
@@ -286,6 +754,24 @@ mod tests {
             content: b"hello world\nline 2\n".to_vec(),
             interrupted: false,
             timestamp: 1000,
+            cols: 80,
+            rows: 24,
+            exit_status: Some(2),
+            prompt: Some(b"fix the build".to_vec()),
+            screen_rendered: false,
+        };
+        assert_eq!(round_trip(&msg), msg);
+    }
+
+    #[test]
+    fn turn_progress_round_trip() {
+        let msg = Message::TurnProgress {
+            id: 5,
+            session: "abc-123".into(),
+            content: b"partial\x1b[0m".to_vec(),
+            timestamp: 1000,
+            cols: 80,
+            rows: 24,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -300,6 +786,11 @@ mod tests {
             content: binary_content.clone(),
             interrupted: true,
             timestamp: 1000,
+            cols: 80,
+            rows: 24,
+            exit_status: None,
+            prompt: None,
+            screen_rendered: false,
         };
         let decoded = round_trip(&msg);
         match decoded {
@@ -315,6 +806,7 @@ mod tests {
         let msg = Message::Capture {
             id: 5,
             session: "abc-123".into(),
+            format: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -324,6 +816,8 @@ mod tests {
         let msg = Message::Paste {
             id: 6,
             session: "abc-123".into(),
+            format: None,
+            options: InjectOptions::default(),
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -333,10 +827,66 @@ mod tests {
         let msg = Message::Inject {
             id: 0,
             content: b"injected data".to_vec(),
+            options: InjectOptions::default(),
+        };
+        assert_eq!(round_trip(&msg), msg);
+    }
+
+    #[test]
+    fn inject_options_round_trip() {
+        let msg = Message::Paste {
+            id: 6,
+            session: "abc-123".into(),
+            format: None,
+            options: InjectOptions {
+                bracketed: Some(true),
+                chunk_size: Some(512),
+                chunk_delay_ms: Some(20),
+                max_bytes: Some(65536),
+                submit: true,
+            },
+        };
+        assert_eq!(round_trip(&msg), msg);
+        let msg = Message::Inject {
+            id: 0,
+            content: b"a\nb\n".to_vec(),
+            options: InjectOptions {
+                bracketed: Some(false),
+                submit: true,
+                ..InjectOptions::default()
+            },
         };
         assert_eq!(round_trip(&msg), msg);
     }
 
+    #[test]
+    fn inject_options_flat_on_wire() {
+        #[derive(serde::Serialize)]
+        struct Paste {
+            #[serde(rename = "type")]
+            msg_type: &'static str,
+            id: u32,
+            session: &'static str,
+            chunk_size: u32,
+            submit: bool,
+        }
+        let wire = Paste {
+            msg_type: "paste",
+            id: 7,
+            session: "s1",
+            chunk_size: 256,
+            submit: true,
+        };
+        let encoded = rmp_serde::to_vec_named(&wire).unwrap();
+        let decoded: Message = rmp_serde::from_slice(&encoded).unwrap();
+        let Message::Paste { options, .. } = decoded else {
+            panic!("expected Paste, got {decoded:?}");
+        };
+        assert_eq!(options.chunk_size, Some(256));
+        assert!(options.submit);
+        assert_eq!(options.bracketed, None);
+    }
+
     #[test]
     fn list_sessions_round_trip() {
         let msg = Message::ListSessions { id: 7 };
@@ -358,6 +908,10 @@ mod tests {
             interrupted: None,
             truncated: None,
             turns: None,
+            exit_status: None,
+            language: None,
+            prompt: None,
+            screen_rendered: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -377,6 +931,10 @@ mod tests {
             interrupted: None,
             truncated: None,
             turns: None,
+            exit_status: None,
+            language: None,
+            prompt: None,
+            screen_rendered: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -393,11 +951,33 @@ mod tests {
                     session: "s1".into(),
                     pid: 100,
                     has_turn: true,
+                    boundary: Some("quiet:2000".into()),
+                    name: None,
+                    state: SessionState::Live,
+                    exit_code: None,
+                    end_reason: None,
+                    ended_at: None,
+                    pattern: Some("claude".into()),
+                    command: vec!["claude".into(), "--resume".into()],
+                    cwd: Some("/home/user/project".into()),
+                    started_at: Some(1_699_999_000_000),
+                    cols: 120,
+                    rows: 40,
+                    turn_count: 3,
+                    latest_turn: Some("s1:3".into()),
+                    last_turn_at: Some(1_700_000_000_000),
                 },
                 SessionDescriptor {
                     session: "s2".into(),
                     pid: 200,
                     has_turn: false,
+                    boundary: None,
+                    name: None,
+                    state: SessionState::Ended,
+                    exit_code: Some(1),
+                    end_reason: Some(EndReason::Exited),
+                    ended_at: Some(1_700_000_000_000),
+                    ..SessionDescriptor::default()
                 },
             ]),
             turn_id: None,
@@ -407,6 +987,10 @@ mod tests {
             interrupted: None,
             truncated: None,
             turns: None,
+            exit_status: None,
+            language: None,
+            prompt: None,
+            screen_rendered: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -426,6 +1010,10 @@ mod tests {
             interrupted: None,
             truncated: None,
             turns: None,
+            exit_status: None,
+            language: None,
+            prompt: None,
+            screen_rendered: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -445,6 +1033,10 @@ mod tests {
             interrupted: None,
             truncated: None,
             turns: None,
+            exit_status: None,
+            language: None,
+            prompt: None,
+            screen_rendered: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -457,6 +1049,10 @@ mod tests {
             byte_length: 256,
             interrupted: false,
             truncated: false,
+            exit_status: None,
+            code_blocks: Vec::new(),
+            screen_rendered: false,
+            segments: Vec::new(),
         };
         let encoded = rmp_serde::to_vec_named(&td).unwrap();
         let decoded: TurnDescriptor = rmp_serde::from_slice(&encoded).unwrap();
@@ -468,6 +1064,7 @@ mod tests {
         let msg = Message::GetTurn {
             id: 10,
             turn_id: "s1:3".into(),
+            format: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -516,10 +1113,68 @@ mod tests {
         let msg = Message::CaptureByID {
             id: 13,
             turn_id: "s1:2".into(),
+            format: None,
+        };
+        assert_eq!(round_trip(&msg), msg);
+    }
+
+    #[test]
+    fn content_format_round_trip() {
+        let msg = Message::Paste {
+            id: 14,
+            session: "s1".into(),
+            format: Some(ContentFormat::Screen),
+            options: InjectOptions::default(),
         };
         assert_eq!(round_trip(&msg), msg);
     }
 
+    #[test]
+    fn plain_format_round_trip() {
+        let msg = Message::GetTurn {
+            id: 16,
+            turn_id: "s1:1".into(),
+            format: Some(ContentFormat::Plain),
+        };
+        assert_eq!(round_trip(&msg), msg);
+        let msg = Message::Deliver {
+            id: 17,
+            sink: "clipboard".into(),
+            session: None,
+            path: None,
+            format: Some(ContentFormat::Plain),
+            options: InjectOptions::default(),
+        };
+        assert_eq!(round_trip(&msg), msg);
+    }
+
+    #[test]
+    fn content_format_wire_names() {
+        #[derive(serde::Serialize)]
+        struct Capture {
+            #[serde(rename = "type")]
+            msg_type: &'static str,
+            id: u32,
+            session: &'static str,
+            format: &'static str,
+        }
+        let wire = Capture {
+            msg_type: "capture",
+            id: 15,
+            session: "s1",
+            format: "prose",
+        };
+        let encoded = rmp_serde::to_vec_named(&wire).unwrap();
+        let decoded: Message = rmp_serde::from_slice(&encoded).unwrap();
+        assert!(matches!(
+            decoded,
+            Message::Capture {
+                format: Some(ContentFormat::Prose),
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn deliver_inject_round_trip() {
         let msg = Message::Deliver {
@@ -527,6 +1182,8 @@ mod tests {
             sink: "inject".into(),
             session: Some("s1".into()),
             path: None,
+            format: None,
+            options: InjectOptions::default(),
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -538,6 +1195,8 @@ mod tests {
             sink: "file".into(),
             session: None,
             path: Some("/tmp/turn.txt".into()),
+            format: None,
+            options: InjectOptions::default(),
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -557,6 +1216,10 @@ mod tests {
             interrupted: Some(false),
             truncated: Some(false),
             turns: None,
+            exit_status: None,
+            language: None,
+            prompt: None,
+            screen_rendered: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -582,6 +1245,10 @@ mod tests {
                     byte_length: 100,
                     interrupted: false,
                     truncated: false,
+                    exit_status: None,
+                    code_blocks: Vec::new(),
+                    screen_rendered: false,
+                    segments: Vec::new(),
                 },
                 TurnDescriptor {
                     turn_id: "s1:1".into(),
@@ -589,8 +1256,16 @@ mod tests {
                     byte_length: 50,
                     interrupted: true,
                     truncated: false,
+                    exit_status: None,
+                    code_blocks: Vec::new(),
+                    screen_rendered: false,
+                    segments: Vec::new(),
                 },
             ]),
+            exit_status: None,
+            language: None,
+            prompt: None,
+            screen_rendered: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -1,6 +1,7 @@
 mod broker;
 mod cli;
 mod client;
+mod detect;
 mod hotkey;
 mod ipc;
 mod pty;
@@ -8,7 +9,7 @@ mod resolver;
 mod turn;
 
 use clap::Parser;
-use cli::{Cli, Command};
+use cli::{Cli, Command, RecordFormatArg};
 use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
@@ -19,18 +20,90 @@ async fn main() {
 
     let cli = Cli::parse();
 
-    match cli.command {
-        Command::Wrap { pattern, command } => match pty::run_session(pattern, command).await {
-            Ok(code) => std::process::exit(code),
+    if let Some(args) = cli.command.detached_wrapper_args() {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        match pty::attach::spawn_detached(&session_id, args).await {
+            Ok(()) => {
+                println!("{session_id}");
+                std::process::exit(0);
+            }
             Err(e) => {
-                tracing::error!(error = %e, "wrap failed");
                 eprintln!("clippyctl wrap: {e}");
                 std::process::exit(1);
             }
-        },
+        }
+    }
+
+    match cli.command {
+        Command::Wrap {
+            pattern,
+            boundary,
+            record,
+            record_format,
+            asciicast,
+            name,
+            export_env,
+            detached: _,
+            detached_session,
+            command,
+        } => {
+            let transcript = record.map(|path| {
+                let format = match record_format {
+                    RecordFormatArg::Json => turn::transcript::TranscriptFormat::Json,
+                    RecordFormatArg::Binary => turn::transcript::TranscriptFormat::Binary,
+                };
+                (path, format)
+            });
+            let recording = pty::Recording {
+                transcript,
+                asciicast,
+            };
+            match pty::run_session(
+                pattern,
+                boundary,
+                recording,
+                name,
+                export_env,
+                detached_session,
+                command,
+            )
+            .await
+            {
+                Ok(code) => std::process::exit(code),
+                Err(e) => {
+                    tracing::error!(error = %e, "wrap failed");
+                    eprintln!("clippyctl wrap: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Attach { session } => {
+            // IDs attach directly; names are resolved by the broker.
+            let session_id = if pty::attach::is_attachable(&session) {
+                session
+            } else {
+                match client::resolve_session(&session).await {
+                    Ok(id) => id,
+                    Err(e) => {
+                        eprintln!("clippyctl attach: {e}");
+                        std::process::exit(1);
+                    }
+                }
+            };
+            match pty::attach::run(&session_id).await {
+                Ok(Some(code)) => std::process::exit(code),
+                Ok(None) => eprintln!("clippyctl attach: detached from {session_id}"),
+                Err(e) => {
+                    tracing::error!(error = %e, "attach failed");
+                    eprintln!("clippyctl attach: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
         Command::Broker {
             ring_depth,
             max_turn_size,
+            ended_grace,
         } => {
             let depth = usize::try_from(ring_depth).unwrap_or_else(|_| {
                 eprintln!("clippyctl broker: --ring-depth value too large for this platform");
@@ -39,6 +112,7 @@ async fn main() {
             let config = broker::state::RingConfig {
                 depth,
                 max_turn_bytes: max_turn_size,
+                ended_grace_ms: ended_grace.saturating_mul(1000),
             };
             // Construct clipboard writer closure from X11ClipboardProvider.
             let clipboard = resolver::x11::clipboard::X11ClipboardProvider::new();
@@ -93,5 +167,11 @@ async fn main() {
                 std::process::exit(1);
             }
         }
+        Command::Detect { action } => {
+            if let Err(e) = detect::run(action) {
+                eprintln!("clippyctl detect: {e}");
+                std::process::exit(1);
+            }
+        }
     }
 }
@@ -0,0 +1,308 @@
+//! Asciicast recording — a replayable record of the session.
+//!
+//! `wrap --asciicast <path>` writes the session in asciinema's v2
+//! format: a JSON header line, then one JSON array per event, `[time,
+//! code, data]`, with `time` in seconds since recording started. The
+//! wrapper records child output (`o`), terminal resizes (`r`) and a
+//! marker (`m`) at each completed turn, so that a player can step
+//! through the recording turn by turn. See CONTRACT_PTY.md §Asciicast
+//! Recording.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::turn::screen::ScreenSize;
+
+/// Asciicast format version written in the header.
+pub const ASCIICAST_VERSION: u32 = 2;
+
+/// Environment variables recorded in the header, as asciinema does.
+const RECORDED_ENV: &[&str] = &["SHELL", "TERM"];
+
+/// The first line of a recording.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Header {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    /// Unix epoch seconds when recording started.
+    pub timestamp: u64,
+    /// The wrapped command line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// The session name, if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub env: BTreeMap<String, String>,
+}
+
+impl Header {
+    /// Header for a recording of `command` starting now at `size`, with
+    /// [`RECORDED_ENV`] from this process's environment.
+    pub fn new(size: ScreenSize, command: &[String], title: Option<String>) -> Self {
+        let env = RECORDED_ENV
+            .iter()
+            .filter_map(|&name| Some((name.to_string(), std::env::var(name).ok()?)))
+            .collect();
+        Self {
+            version: ASCIICAST_VERSION,
+            width: size.cols,
+            height: size.rows,
+            timestamp: crate::turn::epoch_millis() / 1000,
+            command: Some(shell_words(command)),
+            title,
+            env,
+        }
+    }
+}
+
+/// Writes an asciicast as the session runs.
+///
+/// Each event is written to the file as it is recorded, so the file
+/// is complete up to the latest event even if the wrapper dies.
+/// Only the tail of an output chunk that ends mid UTF-8 sequence is
+/// held back, until the next chunk or [`flush`](Self::flush).
+#[derive(Debug)]
+pub struct AsciicastWriter {
+    out: BufWriter<File>,
+    start: Instant,
+    /// Trailing bytes of an output chunk that end mid UTF-8 sequence,
+    /// held for the next chunk.
+    pending: Vec<u8>,
+}
+
+impl AsciicastWriter {
+    /// Create (or truncate) `path` and write the header. Event times
+    /// are measured from now.
+    pub fn create(path: &Path, header: &Header) -> io::Result<Self> {
+        let mut writer = Self {
+            out: BufWriter::new(File::create(path)?),
+            start: Instant::now(),
+            pending: Vec::new(),
+        };
+        serde_json::to_writer(&mut writer.out, header).map_err(io::Error::from)?;
+        writer.out.write_all(b"\n")?;
+        writer.out.flush()?;
+        Ok(writer)
+    }
+
+    /// Record a chunk of child output.
+    ///
+    /// Event data is text, so a UTF-8 sequence split across chunks is
+    /// completed from the next one; invalid bytes become U+FFFD.
+    pub fn output(&mut self, data: &[u8]) -> io::Result<()> {
+        let text = self.decode(data);
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.event("o", &text)
+    }
+
+    /// Record a terminal resize.
+    pub fn resize(&mut self, size: ScreenSize) -> io::Result<()> {
+        self.event("r", &format!("{}x{}", size.cols, size.rows))
+    }
+
+    /// Record a marker, e.g. at a turn boundary.
+    pub fn marker(&mut self, label: &str) -> io::Result<()> {
+        self.event("m", label)
+    }
+
+    /// Write held output to the file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            let text = String::from_utf8_lossy(&std::mem::take(&mut self.pending)).into_owned();
+            self.event("o", &text)?;
+        }
+        self.out.flush()
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> io::Result<()> {
+        // Microsecond resolution, as asciinema records.
+        let time = self.start.elapsed().as_micros() as f64 / 1e6;
+        serde_json::to_writer(&mut self.out, &(time, code, data)).map_err(io::Error::from)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()
+    }
+
+    /// Decode held bytes and `data` as far as they form UTF-8, holding
+    /// back an incomplete sequence at the end.
+    fn decode(&mut self, data: &[u8]) -> String {
+        self.pending.extend_from_slice(data);
+        let mut text = String::new();
+        let mut start = 0;
+        while start < self.pending.len() {
+            match std::str::from_utf8(&self.pending[start..]) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    start = self.pending.len();
+                }
+                Err(e) => {
+                    let end = start + e.valid_up_to();
+                    text.push_str(
+                        std::str::from_utf8(&self.pending[start..end]).expect("valid up to end"),
+                    );
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            start = end + len;
+                        }
+                        // Incomplete: the rest may come with the next chunk.
+                        None => {
+                            start = end;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.pending.drain(..start);
+        text
+    }
+}
+
+/// A command line as one string, quoting arguments that need it.
+fn shell_words(command: &[String]) -> String {
+    command
+        .iter()
+        .map(|arg| {
+            let plain = !arg.is_empty()
+                && arg
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"-_./=:,+@%".contains(&b));
+            if plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: ScreenSize = ScreenSize { cols: 80, rows: 24 };
+
+    fn read_lines(path: &Path) -> Vec<serde_json::Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    /// `[code, data]` of each event line.
+    fn events(lines: &[serde_json::Value]) -> Vec<(String, String)> {
+        lines[1..]
+            .iter()
+            .map(|event| {
+                assert!(event[0].is_f64() || event[0].is_u64());
+                (
+                    event[1].as_str().unwrap().to_string(),
+                    event[2].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn header_then_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.cast");
+        let header = Header {
+            version: ASCIICAST_VERSION,
+            width: 80,
+            height: 24,
+            timestamp: 1_700_000_000,
+            command: Some("claude".into()),
+            title: Some("planner".into()),
+            env: BTreeMap::from([("TERM".to_string(), "xterm-256color".to_string())]),
+        };
+        let mut writer = AsciicastWriter::create(&path, &header).unwrap();
+        writer.output(b"\x1b[1mhello\x1b[0m\r\n").unwrap();
+        writer
+            .resize(ScreenSize {
+                cols: 120,
+                rows: 40,
+            })
+            .unwrap();
+        writer.marker("turn 1").unwrap();
+        writer.flush().unwrap();
+
+        let lines = read_lines(&path);
+        assert_eq!(
+            lines[0],
+            serde_json::json!({
+                "version": 2,
+                "width": 80,
+                "height": 24,
+                "timestamp": 1_700_000_000,
+                "command": "claude",
+                "title": "planner",
+                "env": {"TERM": "xterm-256color"},
+            })
+        );
+        assert_eq!(
+            events(&lines),
+            [
+                ("o".into(), "\x1b[1mhello\x1b[0m\r\n".into()),
+                ("r".into(), "120x40".into()),
+                ("m".into(), "turn 1".into()),
+            ]
+        );
+        let times: Vec<f64> = lines[1..].iter().map(|e| e[0].as_f64().unwrap()).collect();
+        assert!(times.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn split_utf8_carried_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.cast");
+        let mut writer = AsciicastWriter::create(&path, &Header::new(SIZE, &[], None)).unwrap();
+        let check = "✓".as_bytes();
+        writer.output(&[b'a', check[0]]).unwrap();
+        writer.output(&check[1..2]).unwrap();
+        writer.output(&[check[2], b'b', 0xff, b'c']).unwrap();
+        // A sequence cut short by the end of the session.
+        writer.output(&check[..2]).unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(
+            events(&read_lines(&path)),
+            [
+                ("o".into(), "a".into()),
+                ("o".into(), "✓b\u{fffd}c".into()),
+                ("o".into(), "\u{fffd}".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn events_reach_file_as_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.cast");
+        let mut writer = AsciicastWriter::create(&path, &Header::new(SIZE, &[], None)).unwrap();
+        assert_eq!(read_lines(&path).len(), 1);
+        writer.output(b"hello").unwrap();
+        writer.marker("turn 1").unwrap();
+
+        assert_eq!(
+            events(&read_lines(&path)),
+            [("o".into(), "hello".into()), ("m".into(), "turn 1".into())]
+        );
+    }
+
+    #[test]
+    fn command_quoted_where_needed() {
+        let command = ["aider", "--model", "gpt 4", "it's"].map(String::from);
+        assert_eq!(shell_words(&command), r"aider --model 'gpt 4' 'it'\''s'");
+    }
+}
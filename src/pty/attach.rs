@@ -0,0 +1,440 @@
+//! Detached sessions — dtach-style detach and reattach.
+//!
+//! `wrap --detached` runs the wrapper again in the background, in a new
+//! session with no controlling terminal, so closing the terminal does
+//! not hang up the agent. The background wrapper listens on a socket of
+//! its own; `clippyctl attach` connects a terminal to it, and detaches
+//! on the detach key. See CONTRACT_PTY.md §Detached Sessions.
+
+use std::ffi::OsString;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use bytes::{BufMut, BytesMut};
+use futures::stream::SplitStream;
+use futures::{SinkExt, StreamExt};
+use nix::libc;
+use serde::{Deserialize, Serialize};
+use tokio::io::unix::AsyncFd;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{SignalKind, signal as tokio_signal};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+use crate::ipc::codec::{CodecError, FrameCodec};
+use crate::ipc::protocol::MAX_PAYLOAD_SIZE;
+
+use super::terminal::{TerminalGuard, get_terminal_size};
+use super::{PtyError, StdinFd, nix_read, nix_write_all};
+
+/// Ctrl-] — typed in an attached terminal, detaches it. Ctrl-\ is left
+/// to the child, which sees it as an interrupt.
+pub const DETACH_KEY: u8 = 0x1d;
+
+/// Frames queued for an attached terminal before it counts as stalled.
+const ATTACH_BUFFER: usize = 256;
+
+/// How long `wrap --detached` waits for the background wrapper to
+/// start listening.
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A message between a detached wrapper and an attached terminal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Frame {
+    /// Keyboard input from the attached terminal.
+    Input {
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+    /// The attached terminal's size, sent on attach and on resize.
+    Resize { cols: u16, rows: u16 },
+    /// Child output.
+    Output {
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+    /// The child exited with this code; the session is over.
+    Exit { code: i32 },
+}
+
+/// Length-prefixed MessagePack framing of [`Frame`]s, as on the broker
+/// socket (CONTRACT_BROKER.md §Wire Protocol §Framing).
+#[derive(Debug, Default)]
+pub struct AttachCodec {
+    frames: FrameCodec,
+}
+
+impl Decoder for AttachCodec {
+    type Item = Frame;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.frames.decode(src)? {
+            Some(payload) => Ok(Some(rmp_serde::from_slice(&payload)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Encoder<Frame> for AttachCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let payload = rmp_serde::to_vec_named(&item)?;
+        if payload.len() > MAX_PAYLOAD_SIZE {
+            return Err(CodecError::PayloadTooLarge(payload.len()));
+        }
+        dst.reserve(4 + payload.len());
+        dst.put_u32(payload.len() as u32);
+        dst.extend_from_slice(&payload);
+        Ok(())
+    }
+}
+
+/// A connection between a detached wrapper and an attached terminal.
+pub type AttachStream = Framed<UnixStream, AttachCodec>;
+
+/// The socket a detached session listens on:
+/// `$XDG_RUNTIME_DIR/clippy/sessions/<session_id>.sock`.
+pub fn socket_path(session_id: &str) -> Result<PathBuf, PtyError> {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
+        .map_err(|_| PtyError::Attach("$XDG_RUNTIME_DIR not set".into()))?;
+    Ok(PathBuf::from(runtime_dir)
+        .join("clippy")
+        .join("sessions")
+        .join(format!("{session_id}.sock")))
+}
+
+/// Whether `session` is the ID of a detached session on this host.
+/// Anything else has to be resolved (as a name) through the broker.
+pub fn is_attachable(session: &str) -> bool {
+    !session.contains('/') && socket_path(session).is_ok_and(|path| path.exists())
+}
+
+/// The listening socket of a detached wrapper. The socket file is
+/// removed when the listener is dropped.
+pub struct AttachListener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl AttachListener {
+    /// Listen for terminals at `path` (see [`socket_path`]), creating
+    /// the socket directory (mode 0700) if needed.
+    pub fn bind(path: PathBuf) -> Result<Self, PtyError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let parent = path.parent().expect("socket path has parent");
+        std::fs::create_dir_all(parent)?;
+        std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700))?;
+        let listener = UnixListener::bind(&path)?;
+        Ok(Self { listener, path })
+    }
+
+    /// Wait for the next terminal to attach.
+    pub async fn accept(&self) -> Result<AttachClient, PtyError> {
+        let (stream, _) = self.listener.accept().await?;
+        Ok(AttachClient::new(Framed::new(
+            stream,
+            AttachCodec::default(),
+        )))
+    }
+}
+
+/// An attached terminal, as seen by the detached wrapper.
+///
+/// Frames to the terminal go through a bounded queue, written out by a
+/// task of its own, so a terminal that stops reading never holds up the
+/// session: once the queue is full, [`try_send`](Self::try_send) fails
+/// and the wrapper detaches it.
+pub struct AttachClient {
+    frames: SplitStream<AttachStream>,
+    tx: mpsc::Sender<Frame>,
+    writer: JoinHandle<()>,
+}
+
+impl AttachClient {
+    fn new(stream: AttachStream) -> Self {
+        let (mut sink, frames) = stream.split();
+        let (tx, mut rx) = mpsc::channel(ATTACH_BUFFER);
+        let writer = tokio::spawn(async move {
+            while let Some(frame) = rx.recv().await {
+                let last = matches!(frame, Frame::Exit { .. });
+                if let Err(e) = sink.send(frame).await {
+                    tracing::debug!(error = %e, "attached terminal write failed");
+                    break;
+                }
+                if last {
+                    break;
+                }
+            }
+        });
+        Self { frames, tx, writer }
+    }
+
+    /// The next frame from the terminal; `None` once it hangs up.
+    pub async fn next(&mut self) -> Option<Result<Frame, CodecError>> {
+        self.frames.next().await
+    }
+
+    /// Queue `frame` for the terminal without waiting. `false` if the
+    /// terminal has fallen behind or gone away.
+    pub fn try_send(&self, frame: Frame) -> bool {
+        self.tx.try_send(frame).is_ok()
+    }
+
+    /// Tell the terminal the session is over with `Frame::Exit`, and
+    /// wait up to `timeout` for it and any queued output to be written.
+    pub async fn exit(mut self, code: i32, timeout: Duration) {
+        let _ = tokio::time::timeout(timeout, async {
+            if self.tx.send(Frame::Exit { code }).await.is_ok() {
+                let _ = (&mut self.writer).await;
+            }
+        })
+        .await;
+    }
+}
+
+impl Drop for AttachClient {
+    fn drop(&mut self) {
+        self.writer.abort();
+    }
+}
+
+impl Drop for AttachListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Start this `wrap --detached` invocation again in the background as
+/// the detached session `session_id`, and wait until it is listening.
+///
+/// The background wrapper runs `wrap --detached-session <session_id>`
+/// followed by `args`, the options and command of this invocation (see
+/// [`Command::detached_wrapper_args`](crate::cli::Command::detached_wrapper_args)).
+/// It runs in a new session (`setsid`), so hanging up this terminal does
+/// not reach it.
+pub async fn spawn_detached(session_id: &str, args: Vec<OsString>) -> Result<(), PtyError> {
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .arg("wrap")
+        .arg("--detached-session")
+        .arg(session_id)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // SAFETY: setsid is async-signal-safe.
+    unsafe {
+        command.pre_exec(|| {
+            nix::unistd::setsid().map_err(std::io::Error::from)?;
+            Ok(())
+        });
+    }
+    let mut child = command.spawn()?;
+
+    let path = socket_path(session_id)?;
+    let deadline = Instant::now() + START_TIMEOUT;
+    while !path.exists() {
+        if let Some(status) = child.try_wait()? {
+            return Err(PtyError::Attach(format!(
+                "detached wrapper exited before listening ({status})"
+            )));
+        }
+        if Instant::now() >= deadline {
+            return Err(PtyError::Attach(
+                "timed out waiting for the detached wrapper".into(),
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    Ok(())
+}
+
+/// Attach this terminal to the detached session `session_id` until the
+/// detach key is typed or the session ends.
+///
+/// Returns the child's exit code if the session ended, `None` if the
+/// terminal detached.
+pub async fn run(session_id: &str) -> Result<Option<i32>, PtyError> {
+    let path = socket_path(session_id)?;
+    let stream = UnixStream::connect(&path)
+        .await
+        .map_err(|e| PtyError::Attach(format!("{}: {e}", path.display())))?;
+    let mut framed = Framed::new(stream, AttachCodec::default());
+
+    // Install the handler BEFORE entering raw mode, as `wrap` does.
+    let mut sig_winch = tokio_signal(SignalKind::window_change())?;
+    let winsize = get_terminal_size()?;
+
+    // Raw mode for the whole attachment (RAII guard restores on any
+    // exit path).
+    let _terminal_guard = TerminalGuard::enter_raw_mode()?;
+    let stdin_async = AsyncFd::new(StdinFd)?;
+
+    // The wrapper sizes the session to this terminal, which also makes
+    // the agent redraw.
+    send(
+        &mut framed,
+        Frame::Resize {
+            cols: winsize.ws_col,
+            rows: winsize.ws_row,
+        },
+    )
+    .await?;
+
+    let mut stdin_buf = [0u8; 8192];
+    loop {
+        tokio::select! {
+            guard = stdin_async.readable() => {
+                let mut guard = guard?;
+                match guard.try_io(|_| nix_read(libc::STDIN_FILENO, &mut stdin_buf)) {
+                    Ok(Ok(0)) => return Ok(None),
+                    Ok(Ok(n)) => {
+                        let (input, detach) = split_detach(&stdin_buf[..n]);
+                        if !input.is_empty() {
+                            send(&mut framed, Frame::Input { data: input.to_vec() }).await?;
+                        }
+                        if detach {
+                            return Ok(None);
+                        }
+                    }
+                    Ok(Err(e)) => return Err(e.into()),
+                    Err(_would_block) => {} // Spurious wakeup.
+                }
+            }
+
+            frame = framed.next() => match frame {
+                Some(Ok(Frame::Output { data })) => nix_write_all(libc::STDOUT_FILENO, &data)?,
+                Some(Ok(Frame::Exit { code })) => return Ok(Some(code)),
+                Some(Ok(other)) => tracing::warn!(?other, "unexpected attach frame"),
+                Some(Err(e)) => return Err(PtyError::Attach(e.to_string())),
+                None => return Err(PtyError::Attach("session closed the connection".into())),
+            },
+
+            _ = sig_winch.recv() => {
+                if let Ok(ws) = get_terminal_size() {
+                    send(&mut framed, Frame::Resize { cols: ws.ws_col, rows: ws.ws_row }).await?;
+                }
+            }
+        }
+    }
+}
+
+/// Send a frame, mapping codec errors.
+async fn send(framed: &mut AttachStream, frame: Frame) -> Result<(), PtyError> {
+    framed
+        .send(frame)
+        .await
+        .map_err(|e| PtyError::Attach(e.to_string()))
+}
+
+/// Split typed input at the detach key: the bytes before it, and
+/// whether it was typed. Bytes after it are dropped.
+fn split_detach(input: &[u8]) -> (&[u8], bool) {
+    match memchr::memchr(DETACH_KEY, input) {
+        Some(i) => (&input[..i], true),
+        None => (input, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip() {
+        let frames = [
+            Frame::Input {
+                data: b"hi\r".to_vec(),
+            },
+            Frame::Resize {
+                cols: 120,
+                rows: 40,
+            },
+            Frame::Output {
+                data: b"\x1b[1mok\x1b[0m".to_vec(),
+            },
+            Frame::Exit { code: 3 },
+        ];
+        let mut codec = AttachCodec::default();
+        let mut buf = BytesMut::new();
+        for frame in &frames {
+            codec.encode(frame.clone(), &mut buf).unwrap();
+        }
+        for frame in &frames {
+            assert_eq!(codec.decode(&mut buf).unwrap().as_ref(), Some(frame));
+        }
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn detach_key_ends_input() {
+        assert_eq!(split_detach(b"abc"), (&b"abc"[..], false));
+        assert_eq!(split_detach(b"ab\x1dcd"), (&b"ab"[..], true));
+        assert_eq!(split_detach(b"\x1d"), (&b""[..], true));
+        assert_eq!(split_detach(b"\x1c"), (&b"\x1c"[..], false));
+    }
+
+    #[tokio::test]
+    async fn listener_removes_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions").join("s1.sock");
+        let listener = AttachListener::bind(path.clone()).unwrap();
+        assert!(path.exists());
+
+        let client = async {
+            let stream = UnixStream::connect(&path).await.unwrap();
+            let mut framed = Framed::new(stream, AttachCodec::default());
+            framed
+                .send(Frame::Resize { cols: 80, rows: 24 })
+                .await
+                .unwrap();
+        };
+        let (server, ()) = tokio::join!(listener.accept(), client);
+        let frame = server.unwrap().next().await.unwrap().unwrap();
+        assert_eq!(frame, Frame::Resize { cols: 80, rows: 24 });
+
+        drop(listener);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn stalled_terminal_overflows_queue() {
+        let (server, _terminal) = UnixStream::pair().unwrap();
+        let client = AttachClient::new(Framed::new(server, AttachCodec::default()));
+        // The writer task cannot run between these sends, so nothing
+        // drains the queue.
+        let sent = (0..=ATTACH_BUFFER)
+            .take_while(|_| {
+                client.try_send(Frame::Output {
+                    data: vec![b'x'; 64],
+                })
+            })
+            .count();
+        assert_eq!(sent, ATTACH_BUFFER);
+    }
+
+    #[tokio::test]
+    async fn exit_follows_queued_output() {
+        let (server, terminal) = UnixStream::pair().unwrap();
+        let client = AttachClient::new(Framed::new(server, AttachCodec::default()));
+        let output = Frame::Output {
+            data: b"bye".to_vec(),
+        };
+        assert!(client.try_send(output.clone()));
+        client.exit(3, Duration::from_secs(1)).await;
+
+        let mut terminal = Framed::new(terminal, AttachCodec::default());
+        assert_eq!(terminal.next().await.unwrap().unwrap(), output);
+        assert_eq!(
+            terminal.next().await.unwrap().unwrap(),
+            Frame::Exit { code: 3 }
+        );
+    }
+}
@@ -6,6 +6,7 @@
 //! §Wire Protocol.
 
 use std::path::PathBuf;
+use std::task::{Context, Poll, Waker};
 
 use futures::stream::SplitSink;
 use futures::stream::SplitStream;
@@ -14,8 +15,9 @@ use tokio::net::UnixStream;
 use tokio_util::codec::Framed;
 
 use crate::ipc::codec::LengthPrefixedCodec;
-use crate::ipc::protocol::{Message, PROTOCOL_VERSION, Role, Status};
+use crate::ipc::protocol::{EndReason, Message, PROTOCOL_VERSION, Role, Status};
 use crate::turn::Turn;
+use crate::turn::screen::ScreenSize;
 
 use super::PtyError;
 
@@ -31,13 +33,39 @@ pub struct BrokerClient {
     session_id: String,
 }
 
+/// What the wrapper reports about its session at registration
+/// (CONTRACT_BROKER.md §Register).
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub session_id: String,
+    /// Child process PID.
+    pub pid: u32,
+    /// Preset name or custom regex.
+    pub pattern: String,
+    /// Boundary strategy spec.
+    pub boundary: String,
+    /// Output parser name, if the preset has one.
+    pub parser: Option<String>,
+    /// Name given with `wrap --name`.
+    pub name: Option<String>,
+    /// The wrapped command line.
+    pub command: Vec<String>,
+    /// The command's working directory, if it could be read.
+    pub cwd: Option<String>,
+    /// Unix epoch millis when the session started.
+    pub started_at: u64,
+    /// Terminal size to report when registering.
+    pub size: ScreenSize,
+}
+
 impl BrokerClient {
     /// Connect to the broker, perform handshake, and register the session.
     ///
     /// Returns `Err` if the broker is unreachable, handshake fails, or
     /// registration fails. The caller should log the error and continue
-    /// in standalone mode.
-    pub async fn connect(session_id: &str, pid: u32, pattern: &str) -> Result<Self, PtyError> {
+    /// in standalone mode. A registration the broker refuses (a taken
+    /// name or session ID, an invalid name) is [`PtyError::Rejected`].
+    pub async fn connect(info: &SessionInfo) -> Result<Self, PtyError> {
         // Resolve socket path.
         let socket_path = resolve_socket_path()?;
 
@@ -82,9 +110,17 @@ impl BrokerClient {
         framed
             .send(Message::Register {
                 id: 1,
-                session: session_id.to_string(),
-                pid,
-                pattern: pattern.to_string(),
+                session: info.session_id.clone(),
+                pid: info.pid,
+                pattern: info.pattern.clone(),
+                boundary: Some(info.boundary.clone()),
+                parser: info.parser.clone(),
+                name: info.name.clone(),
+                command: info.command.clone(),
+                cwd: info.cwd.clone(),
+                started_at: Some(info.started_at),
+                cols: info.size.cols,
+                rows: info.size.rows,
             })
             .await
             .map_err(|e| PtyError::Broker(format!("send register: {e}")))?;
@@ -94,10 +130,7 @@ impl BrokerClient {
                 status: Status::Ok, ..
             })) => {}
             Some(Ok(Message::Response { error, .. })) => {
-                return Err(PtyError::Broker(format!(
-                    "register failed: {}",
-                    error.unwrap_or_default()
-                )));
+                return Err(PtyError::Rejected(error.unwrap_or_default()));
             }
             other => {
                 return Err(PtyError::Broker(format!(
@@ -112,41 +145,116 @@ impl BrokerClient {
             sink,
             stream,
             next_id: 2, // 0=Hello, 1=Register
-            session_id: session_id.to_string(),
+            session_id: info.session_id.clone(),
         })
     }
 
-    /// Send a completed turn to the broker (fire-and-forget).
+    /// Queue a completed turn for the broker (fire-and-forget); it is
+    /// written by the next [`flush`](Self::flush) or send.
     ///
-    /// Writes the `TurnCompleted` message to the sink and returns
-    /// immediately without waiting for the response ack. The ack
-    /// arrives on the stream and is handled in the select! broker
-    /// arm (as an ignored `Response`).
+    /// Does not wait for the response ack. The ack arrives on the
+    /// stream and is handled in the select! broker arm (as an ignored
+    /// `Response`).
     ///
     /// This avoids blocking the I/O loop (CONTRACT_PTY.md §46, §49)
     /// and prevents inject messages from being dropped during the
-    /// ack wait.
-    pub async fn send_turn(&mut self, turn: &Turn) -> Result<(), PtyError> {
+    /// ack wait. Once this returns the turn is the sink's to deliver,
+    /// so a timeout around the flush never has it sent twice.
+    ///
+    /// `size` is the terminal size when the turn was detected, which
+    /// the broker uses to render the turn's screen representation.
+    pub async fn feed_turn(&mut self, turn: &Turn, size: ScreenSize) -> Result<(), PtyError> {
         let id = self.next_id;
         self.next_id += 1;
 
         self.sink
-            .send(Message::TurnCompleted {
+            .feed(Message::TurnCompleted {
                 id,
                 session: self.session_id.clone(),
                 content: turn.content.clone(),
                 interrupted: turn.interrupted,
                 timestamp: turn.timestamp,
+                cols: size.cols,
+                rows: size.rows,
+                exit_status: turn.exit_status,
+                prompt: turn.prompt.clone(),
+                screen_rendered: turn.screen_rendered,
             })
             .await
             .map_err(|e| PtyError::Broker(format!("send turn: {e}")))
     }
 
-    /// Send deregister and close the connection.
+    /// Write out messages queued with [`feed_turn`](Self::feed_turn).
+    pub async fn flush(&mut self) -> Result<(), PtyError> {
+        self.sink
+            .flush()
+            .await
+            .map_err(|e| PtyError::Broker(format!("flush: {e}")))
+    }
+
+    /// Send the content of the turn in progress (fire-and-forget).
+    ///
+    /// Like [`feed_turn`](Self::feed_turn), the ack is ignored in the
+    /// select! broker arm. The detector throttles progress events, so
+    /// this is called at most every `PROGRESS_INTERVAL`
+    /// (CONTRACT_BROKER.md §Turn Progress).
+    pub async fn send_progress(
+        &mut self,
+        content: &[u8],
+        size: ScreenSize,
+    ) -> Result<(), PtyError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.sink
+            .send(Message::TurnProgress {
+                id,
+                session: self.session_id.clone(),
+                content: content.to_vec(),
+                timestamp: crate::turn::epoch_millis(),
+                cols: size.cols,
+                rows: size.rows,
+            })
+            .await
+            .map_err(|e| PtyError::Broker(format!("send progress: {e}")))
+    }
+
+    /// Send a chunk of PTY output without waiting, while the broker has
+    /// asked for it with `mirror` (CONTRACT_BROKER.md §Live Mirroring).
+    ///
+    /// Returns `false`, dropping the chunk, while the connection is
+    /// still backed up with earlier messages. What the socket does not
+    /// take straight away is written by the next send. The ack is
+    /// ignored in the select! broker arm.
+    pub fn try_send_output(&mut self, data: &[u8]) -> Result<bool, PtyError> {
+        let mut cx = Context::from_waker(Waker::noop());
+        let failed = |e| PtyError::Broker(format!("send output: {e}"));
+        match self.sink.poll_ready_unpin(&mut cx) {
+            Poll::Ready(result) => result.map_err(failed)?,
+            Poll::Pending => return Ok(false),
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.sink
+            .start_send_unpin(Message::Output {
+                id,
+                session: self.session_id.clone(),
+                data: data.to_vec(),
+            })
+            .map_err(failed)?;
+        if let Poll::Ready(Err(e)) = self.sink.poll_flush_unpin(&mut cx) {
+            return Err(failed(e));
+        }
+        Ok(true)
+    }
+
+    /// Send deregister, reporting the child's exit code and why the
+    /// session ended, and close the connection.
     ///
     /// Best-effort — errors are logged but not propagated since we're
     /// shutting down anyway.
-    pub async fn deregister(&mut self) {
+    pub async fn deregister(&mut self, exit_code: i32, reason: EndReason) {
         let id = self.next_id;
         self.next_id += 1;
 
@@ -155,6 +263,8 @@ impl BrokerClient {
             .send(Message::Deregister {
                 id,
                 session: self.session_id.clone(),
+                exit_code: Some(exit_code),
+                reason: Some(reason),
             })
             .await
         {
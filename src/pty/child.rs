@@ -5,12 +5,13 @@
 
 use std::ffi::CString;
 use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
 
 use nix::libc;
 
 use nix::pty::{Winsize, openpty};
 use nix::sys::wait::{WaitPidFlag, WaitStatus, waitpid};
-use nix::unistd::{ForkResult, Pid, execvp, fork, setsid};
+use nix::unistd::{ForkResult, Pid, execvp, execvpe, fork, setsid};
 
 use super::PtyError;
 
@@ -29,11 +30,19 @@ pub struct ChildProcess {
 /// controlling terminal, and execs the command. The master fd is
 /// returned in non-blocking mode for async I/O.
 ///
+/// The child inherits the wrapper's environment, with `env` added
+/// (replacing variables of the same name). An empty `env` leaves it
+/// untouched (CONTRACT_PTY.md §Environment).
+///
 /// # Safety
 ///
 /// Uses `fork()` internally. Only async-signal-safe operations are
 /// performed between fork and exec/exit in the child branch.
-pub fn spawn_child(command: &[String], winsize: &Winsize) -> Result<ChildProcess, PtyError> {
+pub fn spawn_child(
+    command: &[String],
+    winsize: &Winsize,
+    env: &[(&str, &str)],
+) -> Result<ChildProcess, PtyError> {
     if command.is_empty() {
         return Err(PtyError::Exec("empty command".into()));
     }
@@ -48,6 +57,11 @@ pub fn spawn_child(command: &[String], winsize: &Winsize) -> Result<ChildProcess
                 .map_err(|_| PtyError::Exec(format!("argument contains null byte: {s:?}")))
         })
         .collect::<Result<Vec<_>, _>>()?;
+    let c_env = if env.is_empty() {
+        None
+    } else {
+        Some(child_env(env)?)
+    };
 
     // Allocate PTY pair with initial dimensions matching user's terminal.
     let pty = openpty(Some(winsize), None).map_err(PtyError::PtyAlloc)?;
@@ -110,7 +124,10 @@ pub fn spawn_child(command: &[String], winsize: &Winsize) -> Result<ChildProcess
             }
 
             // Exec the command — replaces process image.
-            let _ = execvp(&c_args[0], &c_args);
+            let _ = match &c_env {
+                Some(c_env) => execvpe(&c_args[0], &c_args, c_env),
+                None => execvp(&c_args[0], &c_args),
+            };
 
             // If exec failed, exit with 127 (command not found convention).
             unsafe { libc::_exit(127) };
@@ -118,6 +135,25 @@ pub fn spawn_child(command: &[String], winsize: &Winsize) -> Result<ChildProcess
     }
 }
 
+/// The wrapper's environment with `extra` added, as `NAME=value`
+/// C strings for `execvpe`.
+fn child_env(extra: &[(&str, &str)]) -> Result<Vec<CString>, PtyError> {
+    let nul = |s: &str| PtyError::Exec(format!("environment contains null byte: {s:?}"));
+    let mut env: Vec<CString> = std::env::vars_os()
+        .filter(|(name, _)| !extra.iter().any(|(n, _)| name.as_bytes() == n.as_bytes()))
+        .filter_map(|(name, value)| {
+            let mut var = name.as_bytes().to_vec();
+            var.push(b'=');
+            var.extend_from_slice(value.as_bytes());
+            CString::new(var).ok()
+        })
+        .collect();
+    for (name, value) in extra {
+        env.push(CString::new(format!("{name}={value}")).map_err(|_| nul(value))?);
+    }
+    Ok(env)
+}
+
 /// Wait for the child process to exit and return its exit code.
 ///
 /// For signal-terminated children, returns 128 + signal number
@@ -137,11 +173,30 @@ fn test_winsize() -> Winsize {
     }
 }
 
-pub fn wait_for_exit(pid: Pid) -> Result<i32, PtyError> {
+/// How the child ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChildExit {
+    /// Exit code, 128+N if killed by signal N.
+    pub code: i32,
+    /// Whether a signal killed it.
+    pub signaled: bool,
+}
+
+pub fn wait_for_exit(pid: Pid) -> Result<ChildExit, PtyError> {
     loop {
         match waitpid(pid, Some(WaitPidFlag::WNOHANG)).map_err(PtyError::Signal)? {
-            WaitStatus::Exited(_, code) => return Ok(code),
-            WaitStatus::Signaled(_, sig, _) => return Ok(128 + sig as i32),
+            WaitStatus::Exited(_, code) => {
+                return Ok(ChildExit {
+                    code,
+                    signaled: false,
+                });
+            }
+            WaitStatus::Signaled(_, sig, _) => {
+                return Ok(ChildExit {
+                    code: 128 + sig as i32,
+                    signaled: true,
+                });
+            }
             WaitStatus::StillAlive => {
                 // Child still running — brief sleep then retry.
                 // This path is rare (PTY EOF usually means child exited).
@@ -162,7 +217,7 @@ mod tests {
     #[test]
     fn empty_command_rejected() {
         let ws = test_winsize();
-        let err = spawn_child(&[], &ws).unwrap_err();
+        let err = spawn_child(&[], &ws, &[]).unwrap_err();
         assert!(
             matches!(err, PtyError::Exec(ref msg) if msg.contains("empty command")),
             "expected Exec error, got: {err}"
@@ -173,7 +228,7 @@ mod tests {
     fn nul_byte_in_argument_rejected() {
         let ws = test_winsize();
         let cmd = vec!["echo".into(), "hello\0world".into()];
-        let err = spawn_child(&cmd, &ws).unwrap_err();
+        let err = spawn_child(&cmd, &ws, &[]).unwrap_err();
         assert!(
             matches!(err, PtyError::Exec(ref msg) if msg.contains("null byte")),
             "expected Exec error about null byte, got: {err}"
@@ -184,7 +239,7 @@ mod tests {
     fn nul_byte_in_first_argument_rejected() {
         let ws = test_winsize();
         let cmd = vec!["\0bad".into()];
-        let err = spawn_child(&cmd, &ws).unwrap_err();
+        let err = spawn_child(&cmd, &ws, &[]).unwrap_err();
         assert!(
             matches!(err, PtyError::Exec(ref msg) if msg.contains("null byte")),
             "expected Exec error about null byte, got: {err}"
@@ -194,27 +249,48 @@ mod tests {
     #[test]
     fn spawn_true_exits_zero() {
         let ws = test_winsize();
-        let child = spawn_child(&["true".into()], &ws).unwrap();
-        let code = wait_for_exit(child.pid).unwrap();
-        assert_eq!(code, 0);
+        let child = spawn_child(&["true".into()], &ws, &[]).unwrap();
+        let exit = wait_for_exit(child.pid).unwrap();
+        assert_eq!(
+            exit,
+            ChildExit {
+                code: 0,
+                signaled: false
+            }
+        );
     }
 
     #[test]
     fn spawn_false_exits_nonzero() {
         let ws = test_winsize();
-        let child = spawn_child(&["false".into()], &ws).unwrap();
-        let code = wait_for_exit(child.pid).unwrap();
+        let child = spawn_child(&["false".into()], &ws, &[]).unwrap();
+        let code = wait_for_exit(child.pid).unwrap().code;
         assert_eq!(code, 1);
     }
 
     #[test]
     fn nonexistent_command_exits_127() {
         let ws = test_winsize();
-        let child = spawn_child(&["__clippy_nonexistent_cmd_12345__".into()], &ws).unwrap();
-        let code = wait_for_exit(child.pid).unwrap();
+        let child = spawn_child(&["__clippy_nonexistent_cmd_12345__".into()], &ws, &[]).unwrap();
+        let code = wait_for_exit(child.pid).unwrap().code;
         assert_eq!(code, 127);
     }
 
+    #[test]
+    fn killed_child_reported_as_signaled() {
+        let ws = test_winsize();
+        let command = ["sh".into(), "-c".into(), "kill -9 $$".into()];
+        let child = spawn_child(&command, &ws, &[]).unwrap();
+        let exit = wait_for_exit(child.pid).unwrap();
+        assert_eq!(
+            exit,
+            ChildExit {
+                code: 137,
+                signaled: true
+            }
+        );
+    }
+
     #[test]
     fn spawn_preserves_arguments() {
         // Spawn a command that writes its argument count to the PTY.
@@ -231,10 +307,36 @@ mod tests {
                 "c".into(),
             ],
             &ws,
+            &[],
         )
         .unwrap();
 
-        // Read output from PTY master.
+        let text = read_all(&child);
+        assert!(
+            text.contains('3'),
+            "expected output to contain '3', got: {text:?}"
+        );
+    }
+
+    #[test]
+    fn spawn_adds_environment() {
+        let ws = test_winsize();
+        let child = spawn_child(
+            &[
+                "sh".into(),
+                "-c".into(),
+                "echo \"[$CLIPPY_SESSION_NAME]\"".into(),
+            ],
+            &ws,
+            &[("CLIPPY_SESSION_NAME", "planner")],
+        )
+        .unwrap();
+        let text = read_all(&child);
+        assert!(text.contains("[planner]"), "got: {text:?}");
+    }
+
+    /// Read output from the PTY master until the child exits.
+    fn read_all(child: &ChildProcess) -> String {
         let mut buf = [0u8; 256];
         let mut output = Vec::new();
         loop {
@@ -262,10 +364,6 @@ mod tests {
             }
         }
 
-        let text = String::from_utf8_lossy(&output);
-        assert!(
-            text.contains('3'),
-            "expected output to contain '3', got: {text:?}"
-        );
+        String::from_utf8_lossy(&output).into_owned()
     }
 }
@@ -0,0 +1,173 @@
+//! Paced injection — injected content written a chunk at a time.
+//!
+//! Some agents drop input when a large paste arrives in one burst.
+//! [`InjectQueue`] splits injected content into chunks with a pause
+//! between them; the wrapper's I/O loop writes one chunk each time the
+//! queue is due, so typing and output carry on in between. See
+//! CONTRACT_PTY.md §Paced Injection.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::ipc::protocol::InjectOptions;
+
+/// The Enter key, as sent for [`InjectOptions::submit`].
+const SUBMIT: &[u8] = b"\r";
+
+/// A chunk of injected input, and the pause before whatever follows it.
+#[derive(Debug)]
+struct Chunk {
+    data: Vec<u8>,
+    pause: Duration,
+}
+
+/// Injected input waiting to be written to the child, in order.
+#[derive(Debug, Default)]
+pub struct InjectQueue {
+    chunks: VecDeque<Chunk>,
+    /// When the next chunk may be written; `None` if right away.
+    ready_at: Option<Instant>,
+}
+
+impl InjectQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `content` (already bracketed, if it is to be) after any
+    /// injection still in progress, chunked and paced per `options`.
+    ///
+    /// The broker rejects a chunk size of 0; should one arrive anyway,
+    /// the content is written unchunked.
+    pub fn push(&mut self, content: Vec<u8>, options: &InjectOptions) {
+        let pause = Duration::from_millis(options.chunk_delay_ms.map_or(0, u64::from));
+        let mut chunks: Vec<Vec<u8>> = match options.chunk_size {
+            Some(size) if size > 0 && content.len() > size as usize => {
+                content.chunks(size as usize).map(<[u8]>::to_vec).collect()
+            }
+            _ => vec![content],
+        };
+        if options.submit {
+            // Separately, so that the agent has taken the content in
+            // before Enter arrives.
+            chunks.push(SUBMIT.to_vec());
+        }
+
+        let last = chunks.len() - 1;
+        self.chunks
+            .extend(chunks.into_iter().enumerate().map(|(i, data)| Chunk {
+                data,
+                pause: if i == last { Duration::ZERO } else { pause },
+            }));
+    }
+
+    /// When the next chunk is due, if any is queued.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.chunks.is_empty() {
+            return None;
+        }
+        Some(self.ready_at.unwrap_or_else(Instant::now))
+    }
+
+    /// Take the next chunk to write at `now`, if one is due.
+    pub fn pop(&mut self, now: Instant) -> Option<Vec<u8>> {
+        if self.ready_at.is_some_and(|ready_at| now < ready_at) {
+            return None;
+        }
+        let chunk = self.chunks.pop_front()?;
+        self.ready_at = (!chunk.pause.is_zero()).then(|| now + chunk.pause);
+        Some(chunk.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(
+        chunk_size: Option<u32>,
+        chunk_delay_ms: Option<u32>,
+        submit: bool,
+    ) -> InjectOptions {
+        InjectOptions {
+            chunk_size,
+            chunk_delay_ms,
+            submit,
+            ..InjectOptions::default()
+        }
+    }
+
+    /// Pop everything, each chunk as soon as it is due.
+    fn drain(queue: &mut InjectQueue, start: Instant) -> Vec<(Vec<u8>, Duration)> {
+        let mut out = Vec::new();
+        while queue.deadline().is_some() {
+            let now = queue.ready_at.unwrap_or(start);
+            out.push((queue.pop(now).unwrap(), now - start));
+        }
+        out
+    }
+
+    #[test]
+    fn unchunked_content_written_at_once() {
+        let mut queue = InjectQueue::new();
+        assert_eq!(queue.deadline(), None);
+        queue.push(b"hello\n".to_vec(), &InjectOptions::default());
+        let start = Instant::now();
+        assert_eq!(
+            drain(&mut queue, start),
+            [(b"hello\n".to_vec(), Duration::ZERO)]
+        );
+    }
+
+    #[test]
+    fn zero_chunk_size_not_chunked() {
+        let mut queue = InjectQueue::new();
+        queue.push(b"hello".to_vec(), &options(Some(0), Some(10), true));
+        let chunks: Vec<_> = drain(&mut queue, Instant::now())
+            .into_iter()
+            .map(|(data, _)| data)
+            .collect();
+        assert_eq!(chunks, [&b"hello"[..], b"\r"]);
+    }
+
+    #[test]
+    fn chunks_paced_then_submitted() {
+        let mut queue = InjectQueue::new();
+        queue.push(b"abcdefg".to_vec(), &options(Some(3), Some(10), true));
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        assert_eq!(
+            drain(&mut queue, start),
+            [
+                (b"abc".to_vec(), ms(0)),
+                (b"def".to_vec(), ms(10)),
+                (b"g".to_vec(), ms(20)),
+                (b"\r".to_vec(), ms(30)),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_not_written_early() {
+        let mut queue = InjectQueue::new();
+        queue.push(b"abcd".to_vec(), &options(Some(2), Some(50), false));
+        let start = Instant::now();
+        assert_eq!(queue.pop(start).unwrap(), b"ab");
+        assert_eq!(queue.pop(start), None);
+        assert_eq!(queue.deadline(), Some(start + Duration::from_millis(50)));
+        assert_eq!(queue.pop(start + Duration::from_millis(50)).unwrap(), b"cd");
+        assert_eq!(queue.deadline(), None);
+    }
+
+    #[test]
+    fn injections_queue_in_order() {
+        let mut queue = InjectQueue::new();
+        queue.push(b"abcd".to_vec(), &options(Some(2), None, false));
+        queue.push(b"xy".to_vec(), &options(None, None, true));
+        let chunks: Vec<_> = drain(&mut queue, Instant::now())
+            .into_iter()
+            .map(|(data, _)| data)
+            .collect();
+        assert_eq!(chunks, [&b"ab"[..], b"cd", b"xy", b"\r"]);
+    }
+}
@@ -0,0 +1,255 @@
+//! Input classification — what the bytes written to the child mean
+//! for turn detection.
+//!
+//! In raw mode the wrapper sees keystrokes, not signals: Ctrl+C is the
+//! byte 0x03, and a pasted block arrives wrapped in bracketed-paste
+//! markers with its newlines intact. [`InputClassifier`] interprets
+//! the stream (typed or injected) so the turn detector learns when a
+//! turn really starts and when it was interrupted.
+//! See CONTRACT_PTY.md §Turn Detector Integration.
+
+use crate::turn::TurnDetector;
+
+/// Something the user did, as far as turn detection is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    /// Enter outside a paste, or a paste ending in a newline.
+    Submit,
+    /// Ctrl+C (0x03) or Ctrl+\ (0x1C).
+    Interrupt,
+    /// A lone Escape key press — agents use it to cancel a response.
+    Cancel,
+}
+
+/// Escape-sequence parser state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyState {
+    Ground,
+    /// Saw ESC.
+    Escape,
+    /// Inside `ESC [ ...`, collecting parameters until a final byte.
+    Csi,
+    /// Saw `ESC O` — the next byte is the key.
+    Ss3,
+}
+
+/// Bracketed paste start and end parameters (`ESC [ 200 ~`, `ESC [ 201 ~`).
+const PASTE_START: &[u8] = b"200";
+const PASTE_END: &[u8] = b"201";
+
+/// Longest CSI parameter string retained; longer ones are not markers.
+const MAX_CSI_PARAMS: usize = 16;
+
+/// Classifies input bytes into [`InputEvent`]s.
+///
+/// State carries across chunks, so a paste split over several reads is
+/// still one paste. A lone ESC is recognised as Escape only when it
+/// ends a chunk: terminals send Alt+key and escape sequences in a
+/// single write.
+#[derive(Debug)]
+pub struct InputClassifier {
+    key_state: KeyState,
+    csi_params: Vec<u8>,
+    /// Inside a bracketed paste.
+    in_paste: bool,
+    /// The last pasted byte was CR or LF.
+    paste_newline: bool,
+    /// The previous byte was CR (so a following LF is not a second
+    /// submission).
+    last_cr: bool,
+}
+
+impl InputClassifier {
+    pub fn new() -> Self {
+        Self {
+            key_state: KeyState::Ground,
+            csi_params: Vec::new(),
+            in_paste: false,
+            paste_newline: false,
+            last_cr: false,
+        }
+    }
+
+    /// Classify a chunk of bytes written to the child.
+    pub fn classify(&mut self, data: &[u8]) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        for &byte in data {
+            let was_cr = std::mem::replace(&mut self.last_cr, false);
+
+            match self.key_state {
+                KeyState::Escape => {
+                    self.key_state = match byte {
+                        b'[' => {
+                            self.csi_params.clear();
+                            KeyState::Csi
+                        }
+                        b'O' => KeyState::Ss3,
+                        // ESC ESC: the first was a key press of its own.
+                        0x1B => {
+                            if !self.in_paste {
+                                events.push(InputEvent::Cancel);
+                            }
+                            KeyState::Escape
+                        }
+                        _ => KeyState::Ground,
+                    };
+                    continue;
+                }
+                KeyState::Csi => {
+                    if (0x40..=0x7E).contains(&byte) {
+                        self.key_state = KeyState::Ground;
+                        if byte == b'~' {
+                            self.paste_marker(&mut events);
+                        }
+                    } else if self.csi_params.len() < MAX_CSI_PARAMS {
+                        self.csi_params.push(byte);
+                    }
+                    continue;
+                }
+                KeyState::Ss3 => {
+                    self.key_state = KeyState::Ground;
+                    continue;
+                }
+                KeyState::Ground => {}
+            }
+
+            if byte == 0x1B {
+                self.key_state = KeyState::Escape;
+                continue;
+            }
+            if self.in_paste {
+                // Pasted text is literal: control bytes are content
+                // and newlines do not submit.
+                self.paste_newline = byte == b'\r' || byte == b'\n';
+                continue;
+            }
+            match byte {
+                b'\r' => {
+                    events.push(InputEvent::Submit);
+                    self.last_cr = true;
+                }
+                b'\n' if !was_cr => events.push(InputEvent::Submit),
+                0x03 | 0x1C => events.push(InputEvent::Interrupt),
+                _ => {}
+            }
+        }
+
+        if self.key_state == KeyState::Escape && !self.in_paste {
+            events.push(InputEvent::Cancel);
+            self.key_state = KeyState::Ground;
+        }
+        events
+    }
+
+    /// Classify `data` and pass it on to the turn detector: the bytes
+    /// are recorded for echo stripping, then each submission and
+    /// interrupt is notified in order. A cancel interrupts the turn
+    /// like Ctrl+C. Returns the events.
+    pub fn forward(&mut self, data: &[u8], detector: &mut TurnDetector) -> Vec<InputEvent> {
+        detector.record_input(data);
+        let events = self.classify(data);
+        for event in &events {
+            match event {
+                InputEvent::Submit => detector.notify_user_input(),
+                InputEvent::Interrupt | InputEvent::Cancel => detector.notify_interrupt(),
+            }
+        }
+        events
+    }
+
+    /// Handle a completed `ESC [ <params> ~` sequence.
+    fn paste_marker(&mut self, events: &mut Vec<InputEvent>) {
+        if self.csi_params == PASTE_START {
+            self.in_paste = true;
+            self.paste_newline = false;
+        } else if self.csi_params == PASTE_END && self.in_paste {
+            self.in_paste = false;
+            if self.paste_newline {
+                events.push(InputEvent::Submit);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use InputEvent::{Cancel, Interrupt, Submit};
+
+    fn classify(chunks: &[&[u8]]) -> Vec<InputEvent> {
+        let mut c = InputClassifier::new();
+        chunks.iter().flat_map(|chunk| c.classify(chunk)).collect()
+    }
+
+    #[test]
+    fn enter_submits() {
+        assert_eq!(classify(&[b"hello\r"]), [Submit]);
+        assert_eq!(classify(&[b"a\rb\n"]), [Submit, Submit]);
+        // CRLF is one Enter, even across chunks.
+        assert_eq!(classify(&[b"a\r\n"]), [Submit]);
+        assert_eq!(classify(&[b"a\r", b"\n"]), [Submit]);
+        assert_eq!(classify(&[b"typing"]), []);
+    }
+
+    #[test]
+    fn control_bytes_interrupt() {
+        assert_eq!(classify(&[b"\x03"]), [Interrupt]);
+        assert_eq!(classify(&[b"\x1c"]), [Interrupt]);
+        assert_eq!(classify(&[b"abc\x03"]), [Interrupt]);
+    }
+
+    #[test]
+    fn paste_newlines_do_not_submit() {
+        assert_eq!(classify(&[b"\x1b[200~line one\rline two\x1b[201~"]), []);
+        // Enter after the paste submits it.
+        assert_eq!(classify(&[b"\x1b[200~one\ntwo\x1b[201~", b"\r"]), [Submit]);
+    }
+
+    #[test]
+    fn paste_ending_in_newline_submits_once() {
+        assert_eq!(classify(&[b"\x1b[200~one\ntwo\n\x1b[201~"]), [Submit]);
+        assert_eq!(classify(&[b"\x1b[200~one\r\n\x1b[201~"]), [Submit]);
+    }
+
+    #[test]
+    fn paste_split_across_chunks() {
+        assert_eq!(
+            classify(&[b"\x1b[200~one\r", b"two\x03\r", b"\x1b[20", b"1~"]),
+            [Submit]
+        );
+    }
+
+    #[test]
+    fn lone_escape_cancels() {
+        assert_eq!(classify(&[b"\x1b"]), [Cancel]);
+        assert_eq!(classify(&[b"\x1b\x1b"]), [Cancel, Cancel]);
+    }
+
+    #[test]
+    fn escape_sequences_are_not_cancels() {
+        // Arrow keys, F1 (SS3), Alt+b, Delete.
+        assert_eq!(classify(&[b"\x1b[A\x1bOP\x1bb\x1b[3~"]), []);
+        // An escape inside a paste is pasted text.
+        assert_eq!(classify(&[b"\x1b[200~\x1b", b"x\x1b[201~"]), []);
+    }
+
+    #[test]
+    fn forward_notifies_detector() {
+        let mut d = TurnDetector::new(r"^> $").unwrap();
+        let mut c = InputClassifier::new();
+        d.feed_output(b"> \n");
+
+        // A pasted multi-line prompt is one turn, with its echo stripped.
+        c.forward(b"\x1b[200~one\rtwo\r\x1b[201~", &mut d);
+        d.feed_output(b"> one\r\ntwo\r\nanswer\r\n");
+        c.forward(b"\x03", &mut d);
+        let events = d.feed_output(b"> \n");
+        match events.as_slice() {
+            [crate::turn::TurnEvent::TurnCompleted(turn)] => {
+                assert_eq!(turn.content, b"answer\r\n");
+                assert!(turn.interrupted);
+            }
+            other => panic!("expected TurnCompleted, got {other:?}"),
+        }
+    }
+}
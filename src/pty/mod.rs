@@ -6,12 +6,20 @@
 //!
 //! See CONTRACT_PTY.md.
 
+mod asciicast;
+pub mod attach;
 mod broker_client;
 mod child;
+mod inject;
+pub mod input;
+mod paste;
+mod reconnect;
 mod terminal;
+mod turn_queue;
 
 use std::io;
 use std::os::fd::{AsRawFd, BorrowedFd, RawFd};
+use std::path::PathBuf;
 
 use nix::libc;
 
@@ -22,10 +30,22 @@ use tokio::io::unix::AsyncFd;
 use tokio::signal::unix::{SignalKind, signal as tokio_signal};
 use tokio::time;
 
-use broker_client::BrokerClient;
+use asciicast::AsciicastWriter;
+use attach::{AttachClient, AttachListener, Frame};
+use broker_client::{BrokerClient, SessionInfo};
 use child::{spawn_child, wait_for_exit};
-use terminal::{TerminalGuard, get_terminal_size, propagate_window_size};
-
+use inject::InjectQueue;
+use input::InputClassifier;
+use paste::PasteMode;
+use terminal::{TerminalGuard, get_terminal_size, propagate_window_size, set_window_size};
+use turn_queue::TurnQueue;
+
+use crate::ipc::protocol::EndReason;
+use crate::turn::presets::{PresetError, PresetSet};
+use crate::turn::screen::ScreenSize;
+use crate::turn::transcript::{
+    Header, TRANSCRIPT_VERSION, TranscriptError, TranscriptFormat, TranscriptWriter,
+};
 use crate::turn::{TurnDetector, TurnError, TurnEvent};
 
 /// PTY wrapper errors.
@@ -43,12 +63,39 @@ pub enum PtyError {
     Io(#[from] io::Error),
     #[error("turn detector error: {0}")]
     TurnDetector(#[from] TurnError),
+    #[error("presets: {0}")]
+    Presets(#[from] PresetError),
+    #[error("transcript: {0}")]
+    Transcript(#[from] TranscriptError),
     #[error("broker: {0}")]
     Broker(String),
+    /// The broker refused to register the session; retrying will not
+    /// change its answer.
+    #[error("broker rejected registration: {0}")]
+    Rejected(String),
     #[error("signal error: {0}")]
     Signal(nix::Error),
+    #[error("attach: {0}")]
+    Attach(String),
+}
+
+/// What `wrap` records of the session, besides reporting turns.
+#[derive(Debug)]
+pub struct Recording {
+    /// Transcript file and encoding (`--record`).
+    pub transcript: Option<(PathBuf, TranscriptFormat)>,
+    /// Asciicast file (`--asciicast`).
+    pub asciicast: Option<PathBuf>,
 }
 
+/// PTY size of a detached session until a terminal attaches.
+const DETACHED_SIZE: nix::pty::Winsize = nix::pty::Winsize {
+    ws_row: 24,
+    ws_col: 80,
+    ws_xpixel: 0,
+    ws_ypixel: 0,
+};
+
 /// Run a PTY-wrapped session for the given command with turn detection.
 ///
 /// This is the main entry point called from `main.rs` for the `wrap`
@@ -63,14 +110,46 @@ pub enum PtyError {
 /// - Broker optional — standalone if unreachable (§155–158)
 /// - Signal forwarding per full table (§182–198) incl. SIGTSTP/SIGCONT
 /// - SIGWINCH → TIOCSWINSZ, not forwarded (§200–211)
-/// - Late registration with local turn buffer (§119, §155–158)
+/// - Late registration with a bounded local turn queue (§119, §155–158)
+/// - Background reconnection with backoff, under the same session ID
 /// - Exit with child's code (§169–178)
-pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, PtyError> {
-    // Generate session ID.
-    let session_id = uuid::Uuid::new_v4().to_string();
-
-    // Initialize turn detector (fail early on invalid pattern).
-    let mut turn_detector = TurnDetector::new(&pattern)?;
+///
+/// With a `recording` transcript, the session's detector input is
+/// written to it (CONTRACT_TURN.md §Transcripts); with an asciicast,
+/// its output, resizes and turn boundaries are written in asciinema's
+/// format (CONTRACT_PTY.md §Asciicast Recording). `name` is registered
+/// with the broker as the session's name; with `export_env`, the
+/// child gets `CLIPPY_SESSION_ID` (and `CLIPPY_SESSION_NAME`) in its
+/// environment (CONTRACT_PTY.md §Environment).
+///
+/// With `detached`, the session runs under that ID without a terminal
+/// of its own, for terminals to attach to (CONTRACT_PTY.md §Detached
+/// Sessions).
+pub async fn run_session(
+    pattern: Option<String>,
+    boundary: Option<String>,
+    recording: Recording,
+    name: Option<String>,
+    export_env: bool,
+    detached: Option<String>,
+    command: Vec<String>,
+) -> Result<i32, PtyError> {
+    // Generate session ID, unless `wrap --detached` chose it.
+    let is_detached = detached.is_some();
+    let session_id = detached.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    // Resolve the preset: explicit --pattern, else by command name.
+    let presets = PresetSet::load()?;
+    let preset = presets.resolve(pattern.as_deref(), &command[0]);
+    let boundary = boundary
+        .or(preset.boundary)
+        .unwrap_or_else(|| "prompt".into());
+    tracing::debug!(preset = %preset.name, %boundary, "turn detection configured");
+
+    // Initialize turn detector (fail early on invalid pattern or
+    // boundary strategy).
+    let mut turn_detector = TurnDetector::with_boundary(&preset.pattern, &boundary)?;
+    let boundary = turn_detector.boundary_spec();
 
     // Install signal handlers BEFORE entering raw mode.
     let mut sig_int = tokio_signal(SignalKind::interrupt())?;
@@ -82,12 +161,58 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
     let mut sig_cont = tokio_signal(SignalKind::from_raw(libc::SIGCONT))?;
 
     // Get terminal dimensions for the child PTY.
-    let winsize = get_terminal_size()?;
+    let winsize = if is_detached {
+        DETACHED_SIZE
+    } else {
+        get_terminal_size()?
+    };
+
+    // Current size, reported with each turn for screen rendering.
+    let mut screen_size = ScreenSize {
+        cols: winsize.ws_col,
+        rows: winsize.ws_row,
+    };
+    turn_detector.set_screen_size(screen_size);
+
+    // Open the transcript before the child starts, so that it sees all
+    // output and an unwritable path fails the session up front.
+    let mut recorder = match recording.transcript {
+        Some((path, format)) => {
+            let header = Header {
+                version: TRANSCRIPT_VERSION,
+                command: command.clone(),
+                preset: preset.name.clone(),
+                pattern: preset.pattern.clone(),
+                boundary: boundary.clone(),
+                cols: screen_size.cols,
+                rows: screen_size.rows,
+            };
+            Some(TranscriptWriter::create(&path, format, &header)?)
+        }
+        None => None,
+    };
+    let mut cast = match recording.asciicast {
+        Some(path) => {
+            let header = asciicast::Header::new(screen_size, &command, name.clone());
+            Some(AsciicastWriter::create(&path, &header)?)
+        }
+        None => None,
+    };
+    // Completed turns so far, to label asciicast markers.
+    let mut turns_completed: u64 = 0;
 
     // Spawn child process with PTY.
-    let child_result = spawn_child(&command, &winsize)?;
+    let mut env = Vec::new();
+    if export_env {
+        env.push(("CLIPPY_SESSION_ID", session_id.as_str()));
+        if let Some(name) = &name {
+            env.push(("CLIPPY_SESSION_NAME", name.as_str()));
+        }
+    }
+    let child_result = spawn_child(&command, &winsize, &env)?;
     let child_pid = child_result.pid;
     let master_fd = child_result.master.as_raw_fd();
+    let started_at = crate::turn::epoch_millis();
 
     tracing::info!(
         session = %session_id,
@@ -96,45 +221,131 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
         "session started"
     );
 
+    // Attempt to connect to broker (optional — standalone if
+    // unreachable), before raw mode so that a rejection can be reported
+    // on the terminal.
+    let mut registration_rejected = false;
+    let info = SessionInfo {
+        session_id: session_id.clone(),
+        pid: child_pid.as_raw() as u32,
+        pattern: preset.name,
+        boundary: boundary.clone(),
+        parser: preset.parser,
+        name,
+        cwd: std::env::current_dir()
+            .ok()
+            .map(|dir| dir.to_string_lossy().into_owned()),
+        command,
+        started_at,
+        size: screen_size,
+    };
+    let mut broker_client = match BrokerClient::connect(&info).await {
+        Ok(client) => {
+            tracing::info!("connected to broker");
+            Some(client)
+        }
+        Err(e @ PtyError::Rejected(_)) => {
+            // Not retried, so the user is told even without logging.
+            registration_rejected = true;
+            eprintln!("clippyctl wrap: {e} — running standalone");
+            None
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "broker unavailable — running standalone");
+            None
+        }
+    };
+
     // Enter raw mode (RAII guard ensures restore on any exit path).
-    let terminal_guard = TerminalGuard::enter_raw_mode()?;
-
-    // Attempt to connect to broker (optional — standalone if unreachable).
-    let mut broker_client =
-        match BrokerClient::connect(&session_id, child_pid.as_raw() as u32, &pattern).await {
-            Ok(client) => {
-                tracing::info!("connected to broker");
-                Some(client)
-            }
-            Err(e) => {
-                tracing::warn!(error = %e, "broker unavailable — running standalone");
-                None
-            }
-        };
+    // A detached session has no terminal.
+    let terminal_guard = if is_detached {
+        None
+    } else {
+        Some(TerminalGuard::enter_raw_mode()?)
+    };
 
     // Wrap PTY master in AsyncFd for tokio integration.
     // We need to keep `child_result.master` alive (owns the fd).
     let pty_async = AsyncFd::new(child_result.master)?;
 
-    // Non-owning stdin wrapper (don't close on drop).
-    let stdin_async = AsyncFd::new(StdinFd)?;
+    // Non-owning stdin wrapper (don't close on drop). A detached
+    // session takes input from the attached terminal instead; it
+    // starts listening once registered, so that `wrap --detached`
+    // returns with the session attachable by name.
+    let stdin_async = if is_detached {
+        None
+    } else {
+        Some(AsyncFd::new(StdinFd)?)
+    };
+    let attach_listener = if is_detached {
+        Some(AttachListener::bind(attach::socket_path(&session_id)?)?)
+    } else {
+        None
+    };
+    let mut attach_client: Option<AttachClient> = None;
+
+    // Completed turns not yet sent — held while the broker is
+    // unreachable, for late registration (CONTRACT_PTY.md §Turn
+    // Detector Integration).
+    let mut turn_queue = TurnQueue::new();
+
+    // Interprets typed and injected input for the turn detector.
+    let mut input_classifier = InputClassifier::new();
 
-    // Latest completed turn buffer — retained locally for late
-    // registration when broker is unreachable (CONTRACT_PTY.md §119).
-    let mut latest_turn: Option<crate::turn::Turn> = None;
+    // Whether the child asked for pastes to be bracketed
+    // (CONTRACT_PTY.md §Bracketed Paste).
+    let mut paste_mode = PasteMode::new();
+
+    // Injected input not yet written to the child.
+    let mut inject_queue = InjectQueue::new();
+
+    // Whether the broker has viewers following the session's output
+    // (CONTRACT_BROKER.md §Live Mirroring).
+    let mut mirroring = false;
+
+    // Background reconnection while the broker is unreachable
+    // (CONTRACT_PTY.md §Broker Reconnection).
+    // A rejected registration is final: the session stays standalone.
+    let mut reconnect_task: Option<tokio::task::JoinHandle<Result<BrokerClient, PtyError>>> = None;
+
+    // Whether the loop ended because the child exited, rather than
+    // the wrapper stopping first (CONTRACT_BROKER.md §Ended sessions).
+    let mut child_exited = false;
 
     // -- Main I/O loop --
     let mut stdin_buf = [0u8; 8192];
     let mut pty_buf = [0u8; 8192];
 
     let loop_result: Result<(), PtyError> = loop {
+        if broker_client.is_none() && reconnect_task.is_none() && !registration_rejected {
+            // Registered at the current size, not the starting one.
+            reconnect_task = Some(reconnect::spawn(SessionInfo {
+                size: screen_size,
+                ..info.clone()
+            }));
+        }
+
         // Pending turns to send after select! (avoids borrow conflicts).
         // Vec instead of Option: a single read chunk can emit multiple turns.
         let mut pending_turns: Vec<crate::turn::Turn> = Vec::new();
+        // Latest in-progress turn content, if the detector emitted any.
+        let mut pending_progress: Option<Vec<u8>> = None;
+        // Output to mirror to the broker, if it asked for it.
+        let mut pending_output: Vec<u8> = Vec::new();
+
+        // Boundary strategy timer (CONTRACT_TURN.md §Boundary Strategies).
+        let timer_deadline = turn_detector.deadline();
+        // Next chunk of a paced injection (CONTRACT_PTY.md §Paced Injection).
+        let inject_deadline = inject_queue.deadline();
 
         tokio::select! {
             // -- User stdin → PTY master --
-            guard = stdin_async.readable() => {
+            guard = async {
+                match stdin_async.as_ref() {
+                    Some(stdin) => stdin.readable().await,
+                    None => std::future::pending().await,
+                }
+            } => {
                 let mut guard = guard?;
                 match guard.try_io(|_| {
                     nix_read(libc::STDIN_FILENO, &mut stdin_buf)
@@ -145,18 +356,92 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
                     }
                     Ok(Ok(n)) => {
                         // Forward to PTY master unmodified.
-                        nix_write_all(master_fd, &stdin_buf[..n])?;
-
-                        // Detect Enter key → notify turn detector.
-                        if stdin_buf[..n].iter().any(|&b| b == b'\r' || b == b'\n') {
-                            turn_detector.notify_user_input();
-                        }
+                        forward_input(
+                            master_fd,
+                            &stdin_buf[..n],
+                            &mut recorder,
+                            &mut input_classifier,
+                            &mut turn_detector,
+                        )?;
                     }
                     Ok(Err(e)) => break Err(e.into()),
                     Err(_would_block) => {} // Spurious wakeup.
                 }
             }
 
+            // -- Terminal attaching to a detached session --
+            client = async {
+                match attach_listener.as_ref() {
+                    Some(listener) => listener.accept().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                match client {
+                    Ok(client) => {
+                        // One terminal at a time: a new one takes over.
+                        if attach_client.replace(client).is_some() {
+                            tracing::info!("terminal attached — previous one detached");
+                        } else {
+                            tracing::info!("terminal attached");
+                        }
+                    }
+                    Err(e) => tracing::warn!(error = %e, "attach accept failed"),
+                }
+            }
+
+            // -- Attached terminal → PTY master --
+            frame = async {
+                match attach_client.as_mut() {
+                    Some(client) => client.next().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                match frame {
+                    Some(Ok(Frame::Input { data })) => {
+                        forward_input(
+                            master_fd,
+                            &data,
+                            &mut recorder,
+                            &mut input_classifier,
+                            &mut turn_detector,
+                        )?;
+                    }
+                    Some(Ok(Frame::Resize { cols, rows })) => {
+                        record_event(&mut recorder, |w| w.signal(Signal::SIGWINCH.as_str()));
+                        let ws = nix::pty::Winsize {
+                            ws_row: rows,
+                            ws_col: cols,
+                            ws_xpixel: 0,
+                            ws_ypixel: 0,
+                        };
+                        if let Err(e) = set_window_size(master_fd, &ws) {
+                            tracing::warn!(error = %e, "attach resize failed");
+                        }
+                        let size = ScreenSize { cols, rows };
+                        if size == screen_size {
+                            // No resize for the kernel to signal, but
+                            // the new terminal needs a redraw all the same.
+                            forward_signal(child_pid, Signal::SIGWINCH)?;
+                        } else {
+                            cast_event(&mut cast, |w| w.resize(size));
+                        }
+                        screen_size = size;
+                        turn_detector.set_screen_size(screen_size);
+                    }
+                    Some(Ok(other)) => {
+                        tracing::warn!(?other, "unexpected attach frame");
+                    }
+                    Some(Err(e)) => {
+                        tracing::warn!(error = %e, "attach codec error — detaching");
+                        attach_client = None;
+                    }
+                    None => {
+                        tracing::info!("terminal detached");
+                        attach_client = None;
+                    }
+                }
+            }
+
             // -- PTY master → stdout + turn detector --
             guard = pty_async.readable() => {
                 let mut guard = guard?;
@@ -165,35 +450,67 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
                 }) {
                     Ok(Ok(0)) => {
                         // PTY EOF — child exited.
+                        child_exited = true;
                         break Ok(());
                     }
                     Ok(Ok(n)) => {
-                        // Forward to stdout unmodified.
-                        nix_write_all(libc::STDOUT_FILENO, &pty_buf[..n])?;
+                        // Forward to stdout (or the attached terminal)
+                        // unmodified.
+                        if !is_detached {
+                            nix_write_all(libc::STDOUT_FILENO, &pty_buf[..n])?;
+                        } else if let Some(client) = attach_client.as_ref() {
+                            let frame = Frame::Output { data: pty_buf[..n].to_vec() };
+                            if !client.try_send(frame) {
+                                tracing::warn!("attached terminal fell behind — detaching");
+                                attach_client = None;
+                            }
+                        }
+                        record_event(&mut recorder, |w| w.output(&pty_buf[..n]));
+                        cast_event(&mut cast, |w| w.output(&pty_buf[..n]));
+                        paste_mode.observe(&pty_buf[..n]);
+                        if mirroring {
+                            pending_output.extend_from_slice(&pty_buf[..n]);
+                        }
 
                         // Feed to turn detector.
                         let events = turn_detector.feed_output(&pty_buf[..n]);
-                        for event in events {
-                            match event {
-                                TurnEvent::SessionReady => {
-                                    tracing::info!("session ready — first prompt detected");
-                                }
-                                TurnEvent::TurnCompleted(turn) => {
-                                    tracing::debug!(
-                                        len = turn.content.len(),
-                                        interrupted = turn.interrupted,
-                                        "turn completed"
-                                    );
-                                    pending_turns.push(turn);
-                                }
-                            }
-                        }
+                        collect_turns(events, &mut pending_turns, &mut pending_progress);
                     }
                     Ok(Err(e)) => break Err(e.into()),
                     Err(_would_block) => {} // Spurious wakeup.
                 }
             }
 
+            // -- Queued injection → PTY master --
+            _ = async {
+                match inject_deadline {
+                    Some(deadline) => time::sleep_until(deadline.into()).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                // Injected input starts turns like typed input.
+                if let Some(chunk) = inject_queue.pop(std::time::Instant::now()) {
+                    forward_input(
+                        master_fd,
+                        &chunk,
+                        &mut recorder,
+                        &mut input_classifier,
+                        &mut turn_detector,
+                    )?;
+                }
+            }
+
+            // -- Boundary timer (quiescence) --
+            _ = async {
+                match timer_deadline {
+                    Some(deadline) => time::sleep_until(deadline.into()).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                let events = turn_detector.poll_timer(std::time::Instant::now());
+                collect_turns(events, &mut pending_turns, &mut pending_progress);
+            }
+
             // -- Broker inject messages --
             msg = async {
                 match broker_client.as_mut() {
@@ -202,10 +519,21 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
                 }
             } => {
                 match msg {
-                    Some(Ok(crate::ipc::protocol::Message::Inject { content, .. })) => {
-                        // Write injected bytes to PTY master input.
-                        tracing::debug!(len = content.len(), "inject received");
-                        nix_write_all(master_fd, &content)?;
+                    Some(Ok(crate::ipc::protocol::Message::Inject { content, options, .. })) => {
+                        // Queue injected bytes for the PTY master input,
+                        // as a paste if the child takes them that way.
+                        let bracketed = options.bracketed.unwrap_or(paste_mode.enabled());
+                        tracing::debug!(len = content.len(), bracketed, "inject received");
+                        let content = if bracketed {
+                            paste::bracket(&content)
+                        } else {
+                            content
+                        };
+                        inject_queue.push(content, &options);
+                    }
+                    Some(Ok(crate::ipc::protocol::Message::Mirror { enabled, .. })) => {
+                        tracing::debug!(enabled, "output mirroring toggled");
+                        mirroring = enabled;
                     }
                     Some(Ok(crate::ipc::protocol::Message::Response { .. })) => {
                         // Ack to a previous request — ignore.
@@ -216,57 +544,111 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
                     Some(Err(e)) => {
                         tracing::warn!(error = %e, "broker codec error — disconnecting");
                         broker_client = None;
+                        mirroring = false;
                     }
                     None => {
                         tracing::warn!("broker disconnected");
                         broker_client = None;
+                        mirroring = false;
                     }
                 }
             }
 
+            // -- Broker reconnected in the background --
+            client = async {
+                match reconnect_task.as_mut() {
+                    Some(task) => task.await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                reconnect_task = None;
+                match client {
+                    Ok(Ok(client)) => {
+                        tracing::info!(
+                            queued = turn_queue.len(),
+                            "reconnected to broker"
+                        );
+                        broker_client = Some(client);
+                    }
+                    Ok(Err(e)) => {
+                        tracing::warn!(error = %e, "broker reconnect abandoned — running standalone");
+                        registration_rejected = true;
+                        // No broker will take them now.
+                        if !turn_queue.is_empty() {
+                            tracing::warn!(
+                                lost = turn_queue.len(),
+                                "dropping turns queued for the broker"
+                            );
+                            turn_queue = TurnQueue::new();
+                        }
+                    }
+                    // The task never fails; if it panicked, the next
+                    // iteration starts another.
+                    Err(e) => tracing::warn!(error = %e, "broker reconnect task failed"),
+                }
+            }
+
             // -- Signal handlers --
             _ = sig_int.recv() => {
+                record_event(&mut recorder, |w| w.signal(Signal::SIGINT.as_str()));
                 turn_detector.notify_interrupt();
                 forward_signal(child_pid, Signal::SIGINT)?;
             }
 
             _ = sig_term.recv() => {
+                record_event(&mut recorder, |w| w.signal(Signal::SIGTERM.as_str()));
                 forward_signal(child_pid, Signal::SIGTERM)?;
                 break Ok(());
             }
 
             _ = sig_hup.recv() => {
+                record_event(&mut recorder, |w| w.signal(Signal::SIGHUP.as_str()));
                 forward_signal(child_pid, Signal::SIGHUP)?;
             }
 
             _ = sig_quit.recv() => {
+                record_event(&mut recorder, |w| w.signal(Signal::SIGQUIT.as_str()));
                 forward_signal(child_pid, Signal::SIGQUIT)?;
             }
 
             _ = sig_winch.recv() => {
-                if let Err(e) = propagate_window_size(master_fd) {
-                    tracing::warn!(error = %e, "SIGWINCH handling failed");
+                record_event(&mut recorder, |w| w.signal(Signal::SIGWINCH.as_str()));
+                match propagate_window_size(master_fd) {
+                    Ok(ws) => {
+                        screen_size = ScreenSize {
+                            cols: ws.ws_col,
+                            rows: ws.ws_row,
+                        };
+                        turn_detector.set_screen_size(screen_size);
+                        cast_event(&mut cast, |w| w.resize(screen_size));
+                    }
+                    Err(e) => tracing::warn!(error = %e, "SIGWINCH handling failed"),
                 }
             }
 
             _ = sig_tstp.recv() => {
+                record_event(&mut recorder, |w| w.signal(Signal::SIGTSTP.as_str()));
                 // CONTRACT_PTY.md §190: forward to child, suspend wrapper.
                 forward_signal(child_pid, Signal::SIGTSTP)?;
-                // Restore terminal before suspending so the user's shell
-                // works while we're stopped.
-                if let Err(e) = terminal_guard.restore() {
-                    tracing::warn!(error = %e, "terminal restore before SIGTSTP failed");
-                }
-                // Raise SIGSTOP on self — tokio consumed SIGTSTP, so we
-                // use SIGSTOP to actually suspend the process.
-                signal::kill(Pid::this(), Signal::SIGSTOP).map_err(PtyError::Signal)?;
-                // Execution resumes here after SIGCONT — re-enter raw mode.
-                if let Err(e) = terminal_guard.reenter_raw() {
-                    tracing::warn!(error = %e, "terminal re-raw after resume failed");
+                // A detached wrapper has no shell to return to.
+                if let Some(terminal_guard) = &terminal_guard {
+                    // Restore terminal before suspending so the user's shell
+                    // works while we're stopped.
+                    if let Err(e) = terminal_guard.restore() {
+                        tracing::warn!(error = %e, "terminal restore before SIGTSTP failed");
+                    }
+                    // Raise SIGSTOP on self — tokio consumed SIGTSTP, so we
+                    // use SIGSTOP to actually suspend the process.
+                    signal::kill(Pid::this(), Signal::SIGSTOP).map_err(PtyError::Signal)?;
+                    // Execution resumes here after SIGCONT — re-enter raw mode.
+                    if let Err(e) = terminal_guard.reenter_raw() {
+                        tracing::warn!(error = %e, "terminal re-raw after resume failed");
+                    }
                 }
             }
 
             _ = sig_cont.recv() => {
+                record_event(&mut recorder, |w| w.signal(Signal::SIGCONT.as_str()));
                 // CONTRACT_PTY.md §191: forward to child, resume wrapper.
                 forward_signal(child_pid, Signal::SIGCONT)?;
             }
@@ -275,44 +657,65 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
         // Send pending turns (outside select! to avoid borrow conflicts).
         // All broker I/O is bounded by a timeout so it cannot stall the
         // main I/O loop (CONTRACT_PTY.md §46, §49).
-        if !pending_turns.is_empty() {
-            // Always update the local latest-turn buffer (for late registration).
-            latest_turn = pending_turns.last().cloned();
+        const BROKER_IO_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
+
+        // Every turn goes through the queue, so that turns held while
+        // the broker was unreachable are sent first (CONTRACT_BROKER.md
+        // §Late registration). A rejected session has no broker to
+        // hold them for.
+        for turn in pending_turns {
+            turns_completed += 1;
+            cast_event(&mut cast, |w| w.marker(&format!("turn {turns_completed}")));
+            if registration_rejected {
+                continue;
+            }
+            let dropped = turn_queue.push(turn, screen_size);
+            if dropped > 0 {
+                tracing::warn!(dropped, "turn queue full — oldest turns dropped");
+            }
+        }
 
-            const BROKER_IO_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
+        if let Some(broker) = broker_client.as_mut()
+            && let Err(e) = send_queued_turns(broker, &mut turn_queue, BROKER_IO_TIMEOUT).await
+        {
+            tracing::warn!(error = %e, "failed to send turn to broker — disconnecting");
+            broker_client = None;
+            mirroring = false;
+        }
 
-            if let Some(ref mut broker) = broker_client {
-                for turn in &pending_turns {
-                    match time::timeout(BROKER_IO_TIMEOUT, broker.send_turn(turn)).await {
-                        Ok(Err(e)) => {
-                            tracing::warn!(error = %e, "failed to send turn to broker");
-                        }
-                        Err(_elapsed) => {
-                            tracing::warn!("broker send timed out — skipping");
-                            break;
-                        }
-                        Ok(Ok(())) => {}
-                    }
+        // Progress is only worth sending while connected; a stale
+        // snapshot is superseded by the next one or by the turn itself.
+        if let (Some(content), Some(broker)) = (pending_progress, broker_client.as_mut()) {
+            match time::timeout(
+                BROKER_IO_TIMEOUT,
+                broker.send_progress(&content, screen_size),
+            )
+            .await
+            {
+                Ok(Err(e)) => {
+                    tracing::warn!(error = %e, "failed to send turn progress to broker");
                 }
-            } else if let Some(ref buffered) = latest_turn {
-                // Broker disconnected — attempt late registration.
-                // CONTRACT_PTY.md §119: retain latest turn and send on
-                // successful registration.
-                let reconnect = async {
-                    let mut client =
-                        BrokerClient::connect(&session_id, child_pid.as_raw() as u32, &pattern)
-                            .await?;
-                    client.send_turn(buffered).await?;
-                    Ok::<_, PtyError>(client)
-                };
-                match time::timeout(BROKER_IO_TIMEOUT, reconnect).await {
-                    Ok(Ok(client)) => {
-                        tracing::info!("late registration — connected to broker");
-                        broker_client = Some(client);
-                    }
-                    Ok(Err(_)) | Err(_) => {
-                        // Unreachable or timed out — will retry on next turn.
-                    }
+                Err(_elapsed) => {
+                    tracing::warn!("broker progress send timed out — skipping");
+                }
+                Ok(Ok(())) => {}
+            }
+        }
+
+        // Mirrored output is already on the user's terminal, so it is
+        // sent last, and never waited on: a chunk the backed-up
+        // connection cannot take is dropped, as the broker drops output
+        // for a viewer that falls behind.
+        if !pending_output.is_empty()
+            && let Some(broker) = broker_client.as_mut()
+        {
+            match broker.try_send_output(&pending_output) {
+                Ok(true) => {}
+                Ok(false) => {
+                    tracing::warn!("broker connection backed up — output not mirrored");
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to mirror output to broker");
                 }
             }
         }
@@ -320,28 +723,66 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
 
     // -- Post-loop cleanup --
 
+    if let Some(task) = reconnect_task {
+        task.abort();
+    }
+
     // Flush any unterminated prompt.
     const SHUTDOWN_IO_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
     let events = turn_detector.flush_line();
     for event in events {
         if let TurnEvent::TurnCompleted(turn) = event {
-            latest_turn = Some(turn.clone());
-            if let Some(ref mut broker) = broker_client {
-                let _ = time::timeout(SHUTDOWN_IO_TIMEOUT, broker.send_turn(&turn)).await;
+            turns_completed += 1;
+            cast_event(&mut cast, |w| w.marker(&format!("turn {turns_completed}")));
+            if !registration_rejected {
+                turn_queue.push(turn, screen_size);
             }
         }
     }
+    if let Some(ref mut broker) = broker_client {
+        let _ = send_queued_turns(broker, &mut turn_queue, SHUTDOWN_IO_TIMEOUT).await;
+    }
+    if !turn_queue.is_empty() {
+        tracing::warn!(
+            lost = turn_queue.len(),
+            "session ended with turns the broker never received"
+        );
+    }
+
+    if let Some(mut writer) = recorder
+        && let Err(e) = writer.flush()
+    {
+        tracing::warn!(error = %e, "transcript flush failed");
+    }
+    if let Some(mut writer) = cast
+        && let Err(e) = writer.flush()
+    {
+        tracing::warn!(error = %e, "asciicast flush failed");
+    }
 
-    // Suppress unused warning when broker is never connected.
-    drop(latest_turn);
+    // Wait for child exit.
+    let exit = wait_for_exit(child_pid)?;
+    let exit_code = exit.code;
+    let reason = if !child_exited {
+        EndReason::Terminated
+    } else if exit.signaled {
+        EndReason::Killed
+    } else {
+        EndReason::Exited
+    };
 
-    // Deregister from broker.
+    // Deregister from broker, which keeps the session's turns for its
+    // grace period (CONTRACT_BROKER.md §Ended sessions).
     if let Some(ref mut broker) = broker_client {
-        let _ = time::timeout(SHUTDOWN_IO_TIMEOUT, broker.deregister()).await;
+        let _ = time::timeout(SHUTDOWN_IO_TIMEOUT, broker.deregister(exit_code, reason)).await;
     }
 
-    // Wait for child exit.
-    let exit_code = wait_for_exit(child_pid)?;
+    // Tell the attached terminal the session is over; the listener
+    // removes its socket when dropped.
+    if let Some(client) = attach_client {
+        client.exit(exit_code, SHUTDOWN_IO_TIMEOUT).await;
+    }
+    drop(attach_listener);
 
     // Terminal guard drops here → restores terminal.
     drop(terminal_guard);
@@ -357,6 +798,110 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
 
 // -- Helpers --
 
+/// Log detector events and queue completed turns and the latest turn
+/// progress for the broker.
+fn collect_turns(
+    events: Vec<TurnEvent>,
+    pending_turns: &mut Vec<crate::turn::Turn>,
+    pending_progress: &mut Option<Vec<u8>>,
+) {
+    for event in events {
+        match event {
+            TurnEvent::SessionReady => {
+                tracing::info!("session ready — first prompt detected");
+            }
+            TurnEvent::TurnCompleted(turn) => {
+                tracing::debug!(
+                    len = turn.content.len(),
+                    interrupted = turn.interrupted,
+                    "turn completed"
+                );
+                pending_turns.push(turn);
+            }
+            TurnEvent::TurnProgress(content) => {
+                *pending_progress = Some(content);
+            }
+        }
+    }
+}
+
+/// Send queued turns to the broker, oldest first, each with its
+/// detection timestamp and size.
+///
+/// Stops early if the broker does not take a turn within `timeout`;
+/// that turn and the rest stay queued for the next call. An error
+/// means the connection is broken.
+async fn send_queued_turns(
+    broker: &mut BrokerClient,
+    queue: &mut TurnQueue,
+    timeout: std::time::Duration,
+) -> Result<(), PtyError> {
+    if queue.is_empty() {
+        return Ok(());
+    }
+    while let Some(queued) = queue.front() {
+        match time::timeout(timeout, broker.feed_turn(&queued.turn, queued.size)).await {
+            Ok(Ok(())) => {
+                queue.pop_front();
+            }
+            Ok(Err(e)) => return Err(e),
+            Err(_elapsed) => {
+                tracing::warn!(queued = queue.len(), "broker send timed out — turns held");
+                return Ok(());
+            }
+        }
+    }
+    match time::timeout(timeout, broker.flush()).await {
+        Ok(result) => result,
+        // Already handed to the connection; written by the next send.
+        Err(_elapsed) => Ok(()),
+    }
+}
+
+/// Forward typed, attached or injected input to the PTY master
+/// unmodified, record it, and track it for echo stripping; the
+/// detector hears of submissions and interrupts.
+fn forward_input(
+    master_fd: RawFd,
+    data: &[u8],
+    recorder: &mut Option<TranscriptWriter>,
+    input_classifier: &mut InputClassifier,
+    turn_detector: &mut TurnDetector,
+) -> Result<(), PtyError> {
+    nix_write_all(master_fd, data)?;
+    record_event(recorder, |w| w.input(data));
+    input_classifier.forward(data, turn_detector);
+    Ok(())
+}
+
+/// Write a transcript event. Recording stops at the first error; the
+/// session itself carries on.
+fn record_event(
+    recorder: &mut Option<TranscriptWriter>,
+    write: impl FnOnce(&mut TranscriptWriter) -> Result<(), TranscriptError>,
+) {
+    if let Some(writer) = recorder.as_mut()
+        && let Err(e) = write(writer)
+    {
+        tracing::warn!(error = %e, "transcript write failed — recording stopped");
+        *recorder = None;
+    }
+}
+
+/// Write an asciicast event. Like transcripts, recording stops at the
+/// first error.
+fn cast_event(
+    cast: &mut Option<AsciicastWriter>,
+    write: impl FnOnce(&mut AsciicastWriter) -> io::Result<()>,
+) {
+    if let Some(writer) = cast.as_mut()
+        && let Err(e) = write(writer)
+    {
+        tracing::warn!(error = %e, "asciicast write failed — recording stopped");
+        *cast = None;
+    }
+}
+
 /// Forward a signal to the child's process group.
 fn forward_signal(child_pid: Pid, sig: Signal) -> Result<(), PtyError> {
     // Negative PID → send to process group.
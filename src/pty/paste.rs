@@ -0,0 +1,185 @@
+//! Bracketed paste — how injected content reaches the child.
+//!
+//! Written as is, injected content is indistinguishable from typing: a
+//! multi-line relay arrives as a series of Enter presses, and the
+//! agent submits it line by line. A child that enables bracketed paste
+//! mode (`ESC [ ? 2004 h`) asks for pastes to be marked instead;
+//! [`PasteMode`] watches its output for the switch and [`bracket`]
+//! marks the content. See CONTRACT_PTY.md §Bracketed Paste.
+
+use crate::turn::ansi::csi_numbers;
+
+/// Bracketed paste start and end markers.
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// The DEC private mode that enables bracketed paste.
+const BRACKETED_PASTE_MODE: u16 = 2004;
+
+/// Longest CSI parameter string retained; longer ones are not mode
+/// switches we interpret.
+const MAX_CSI_PARAMS: usize = 16;
+
+/// Escape-sequence parser state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    /// Saw ESC.
+    Escape,
+    /// Inside `ESC [ ...`, collecting parameters until a final byte.
+    Csi,
+}
+
+/// Tracks whether the child has bracketed paste mode enabled.
+///
+/// State carries across chunks, so a switch split over several reads
+/// is still seen. A terminal reset (`ESC c`) disables the mode, as it
+/// does in a real terminal.
+#[derive(Debug)]
+pub struct PasteMode {
+    state: State,
+    csi_params: Vec<u8>,
+    enabled: bool,
+}
+
+impl PasteMode {
+    pub fn new() -> Self {
+        Self {
+            state: State::Ground,
+            csi_params: Vec::new(),
+            enabled: false,
+        }
+    }
+
+    /// Whether injected content should be bracketed by default.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Scan a chunk of child output for mode switches.
+    pub fn observe(&mut self, output: &[u8]) {
+        let mut rest = output;
+        while !rest.is_empty() {
+            if self.state == State::Ground {
+                match memchr::memchr(0x1B, rest) {
+                    Some(i) => {
+                        self.state = State::Escape;
+                        rest = &rest[i + 1..];
+                    }
+                    None => return,
+                }
+                continue;
+            }
+            let byte = rest[0];
+            rest = &rest[1..];
+            self.step(byte);
+        }
+    }
+
+    /// Advance the escape-sequence states by one byte.
+    fn step(&mut self, byte: u8) {
+        match self.state {
+            State::Ground => unreachable!("handled in bulk by observe"),
+            State::Escape => {
+                self.state = match byte {
+                    b'[' => {
+                        self.csi_params.clear();
+                        State::Csi
+                    }
+                    0x1B => State::Escape,
+                    b'c' => {
+                        self.enabled = false;
+                        State::Ground
+                    }
+                    _ => State::Ground,
+                };
+            }
+            State::Csi => {
+                if byte == 0x1B {
+                    // Sequence abandoned; a new one starts.
+                    self.state = State::Escape;
+                } else if (0x40..=0x7E).contains(&byte) {
+                    self.state = State::Ground;
+                    self.complete_csi(byte);
+                } else if self.csi_params.len() < MAX_CSI_PARAMS {
+                    self.csi_params.push(byte);
+                }
+            }
+        }
+    }
+
+    /// Handle a completed CSI sequence: `ESC [ ? ... 2004 ... h` or `l`.
+    fn complete_csi(&mut self, final_byte: u8) {
+        if !matches!(final_byte, b'h' | b'l') || self.csi_params.first() != Some(&b'?') {
+            return;
+        }
+        if csi_numbers(&self.csi_params).contains(&BRACKETED_PASTE_MODE) {
+            self.enabled = final_byte == b'h';
+        }
+    }
+}
+
+/// Wrap `content` in bracketed paste markers. Markers inside the
+/// content lose their ESC, so that it cannot end the paste early (and
+/// have the rest read as typed input).
+pub fn bracket(content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(PASTE_START.len() + content.len() + PASTE_END.len());
+    out.extend_from_slice(PASTE_START);
+    let mut rest = content;
+    while let Some(i) = memchr::memchr(0x1B, rest) {
+        out.extend_from_slice(&rest[..i]);
+        let marker = [PASTE_START, PASTE_END]
+            .iter()
+            .any(|marker| rest[i..].starts_with(marker));
+        if !marker {
+            out.push(0x1B);
+        }
+        rest = &rest[i + 1..];
+    }
+    out.extend_from_slice(rest);
+    out.extend_from_slice(PASTE_END);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observe(chunks: &[&[u8]]) -> bool {
+        let mut mode = PasteMode::new();
+        for chunk in chunks {
+            mode.observe(chunk);
+        }
+        mode.enabled()
+    }
+
+    #[test]
+    fn mode_follows_switches() {
+        assert!(!observe(&[b"plain output\r\n"]));
+        assert!(observe(&[b"\x1b[?2004h> "]));
+        assert!(!observe(&[b"\x1b[?2004h> ", b"\x1b[?2004l$ "]));
+        // Combined with other modes, and split across reads.
+        assert!(observe(&[b"\x1b[?1049;20", b"04h"]));
+        assert!(observe(&[b"\x1b", b"[?2004h"]));
+        // Not a private mode, or a different one.
+        assert!(!observe(&[b"\x1b[2004h\x1b[?2005h"]));
+        // A terminal reset disables it.
+        assert!(!observe(&[b"\x1b[?2004h", b"\x1bc"]));
+    }
+
+    #[test]
+    fn bracket_wraps_content() {
+        assert_eq!(bracket(b"a\nb\n"), b"\x1b[200~a\nb\n\x1b[201~");
+        assert_eq!(bracket(b""), b"\x1b[200~\x1b[201~");
+        // Other escape sequences are kept.
+        assert_eq!(bracket(b"\x1b[1mx"), b"\x1b[200~\x1b[1mx\x1b[201~");
+    }
+
+    #[test]
+    fn bracket_neutralises_embedded_markers() {
+        assert_eq!(
+            bracket(b"a\x1b[201~\rrm -rf\x1b[200~b"),
+            b"\x1b[200~a[201~\rrm -rf[200~b\x1b[201~"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Broker reconnection — re-registering after the broker goes away.
+//!
+//! When the broker is unreachable (not started yet, restarted, or the
+//! connection broke), a background task retries the connection with
+//! exponential backoff and hands the registered [`BrokerClient`] back
+//! to the I/O loop, or gives up if the broker rejects the registration. Retrying does not wait for the agent to finish a
+//! turn, so an idle session reappears as soon as a broker is back. See
+//! CONTRACT_PTY.md §Broker Reconnection.
+
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use super::PtyError;
+use super::broker_client::{BrokerClient, SessionInfo};
+
+/// Delay before the first attempt.
+const INITIAL_DELAY: Duration = Duration::from_millis(250);
+
+/// Longest delay between attempts.
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// How long one attempt may take, so that a broker that accepts but
+/// never answers does not stall the retries.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Delays between connection attempts: doubling from
+/// [`INITIAL_DELAY`] up to [`MAX_DELAY`].
+#[derive(Debug)]
+struct Backoff {
+    next: Duration,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Self {
+            next: INITIAL_DELAY,
+        }
+    }
+
+    /// The delay before the next attempt.
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(MAX_DELAY);
+        delay
+    }
+}
+
+/// Retry connecting to the broker, registering the session under its
+/// own ID, until it succeeds or the broker rejects the registration.
+///
+/// The task runs until it connects or is rejected
+/// ([`PtyError::Rejected`]); abort it when the session ends.
+pub fn spawn(info: SessionInfo) -> JoinHandle<Result<BrokerClient, PtyError>> {
+    tokio::spawn(async move {
+        let mut backoff = Backoff::new();
+        loop {
+            let delay = backoff.next_delay();
+            tokio::time::sleep(delay).await;
+            match tokio::time::timeout(ATTEMPT_TIMEOUT, BrokerClient::connect(&info)).await {
+                Ok(Ok(client)) => return Ok(client),
+                // Our own previous registration, until the broker sees
+                // the old connection close; retried only while backing off.
+                Ok(Err(PtyError::Rejected(reason)))
+                    if reason == "duplicate_session" && delay < MAX_DELAY =>
+                {
+                    tracing::debug!(?delay, "session still registered — retrying");
+                }
+                Ok(Err(e @ PtyError::Rejected(_))) => return Err(e),
+                Ok(Err(e)) => {
+                    tracing::debug!(error = %e, ?delay, "broker reconnect failed — retrying");
+                }
+                Err(_elapsed) => {
+                    tracing::debug!(?delay, "broker reconnect timed out — retrying");
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<u64> = (0..8)
+            .map(|_| backoff.next_delay().as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [250, 500, 1000, 2000, 4000, 5000, 5000, 5000]);
+    }
+}
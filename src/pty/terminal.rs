@@ -138,18 +138,22 @@ pub fn get_terminal_size() -> Result<nix::pty::Winsize, PtyError> {
 /// Set the PTY window size from the user's current terminal.
 ///
 /// Called on SIGWINCH. Reads the new size from the user's terminal
-/// and sets it on the PTY master via `ioctl(TIOCSWINSZ)`.
-/// The kernel automatically delivers SIGWINCH to the child.
-pub fn propagate_window_size(pty_master_fd: RawFd) -> Result<(), PtyError> {
-    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
-
+/// and sets it on the PTY master via [`set_window_size`]. Returns the
+/// size set.
+pub fn propagate_window_size(pty_master_fd: RawFd) -> Result<nix::pty::Winsize, PtyError> {
     // Read from user's terminal.
-    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } < 0 {
-        return Err(PtyError::Terminal(nix::Error::last()));
-    }
+    let ws = get_terminal_size()?;
+    set_window_size(pty_master_fd, &ws)?;
+    Ok(ws)
+}
 
+/// Set the PTY window size via `ioctl(TIOCSWINSZ)`.
+///
+/// The kernel automatically delivers SIGWINCH to the child if the size
+/// changed.
+pub fn set_window_size(pty_master_fd: RawFd, ws: &nix::pty::Winsize) -> Result<(), PtyError> {
     // Set on PTY master — kernel delivers SIGWINCH to child.
-    if unsafe { libc::ioctl(pty_master_fd, libc::TIOCSWINSZ, &ws) } < 0 {
+    if unsafe { libc::ioctl(pty_master_fd, libc::TIOCSWINSZ, ws) } < 0 {
         return Err(PtyError::Terminal(nix::Error::last()));
     }
 
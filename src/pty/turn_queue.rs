@@ -0,0 +1,143 @@
+//! Turn queue — completed turns the broker has not received yet.
+//!
+//! While the broker is unreachable, completed turns wait here; on
+//! (re)registration they are sent in detection order, with their
+//! original timestamps. The queue is bounded: when it is full the
+//! oldest turns are dropped. See CONTRACT_PTY.md §Turn Detector
+//! Integration.
+
+use std::collections::VecDeque;
+
+use crate::turn::Turn;
+use crate::turn::screen::ScreenSize;
+
+/// Most turns held while the broker is unreachable.
+pub const MAX_QUEUED_TURNS: usize = 64;
+
+/// Most content bytes held while the broker is unreachable.
+pub const MAX_QUEUED_BYTES: usize = 4 * 1024 * 1024;
+
+/// A completed turn and the terminal size it was detected at.
+#[derive(Debug, Clone)]
+pub struct QueuedTurn {
+    pub turn: Turn,
+    pub size: ScreenSize,
+}
+
+/// Completed turns not yet sent to the broker, oldest first.
+#[derive(Debug, Default)]
+pub struct TurnQueue {
+    turns: VecDeque<QueuedTurn>,
+    /// Total content bytes of `turns`.
+    bytes: usize,
+}
+
+impl TurnQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.turns.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.turns.len()
+    }
+
+    /// Queue a turn detected at `size`. Returns how many of the oldest
+    /// turns were dropped to make room.
+    ///
+    /// The newest turn is always kept, even if it alone is over the
+    /// byte bound: the latest turn is the one a capture wants.
+    pub fn push(&mut self, turn: Turn, size: ScreenSize) -> usize {
+        self.bytes += turn.content.len();
+        self.turns.push_back(QueuedTurn { turn, size });
+
+        let mut dropped = 0;
+        while self.turns.len() > 1
+            && (self.turns.len() > MAX_QUEUED_TURNS || self.bytes > MAX_QUEUED_BYTES)
+        {
+            self.pop_front();
+            dropped += 1;
+        }
+        dropped
+    }
+
+    /// The oldest queued turn.
+    pub fn front(&self) -> Option<&QueuedTurn> {
+        self.turns.front()
+    }
+
+    /// Remove the oldest queued turn, once it has been sent.
+    pub fn pop_front(&mut self) -> Option<QueuedTurn> {
+        let queued = self.turns.pop_front()?;
+        self.bytes -= queued.turn.content.len();
+        Some(queued)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: ScreenSize = ScreenSize { cols: 80, rows: 24 };
+
+    fn turn(content: &[u8], timestamp: u64) -> Turn {
+        Turn {
+            content: content.to_vec(),
+            interrupted: false,
+            timestamp,
+            exit_status: None,
+            prompt: None,
+            screen_rendered: false,
+        }
+    }
+
+    fn timestamps(queue: &mut TurnQueue) -> Vec<u64> {
+        std::iter::from_fn(|| queue.pop_front())
+            .map(|queued| queued.turn.timestamp)
+            .collect()
+    }
+
+    #[test]
+    fn turns_kept_in_order() {
+        let mut queue = TurnQueue::new();
+        assert!(queue.is_empty());
+        for ts in [1000, 2000, 3000] {
+            assert_eq!(queue.push(turn(b"t", ts), SIZE), 0);
+        }
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.front().unwrap().turn.timestamp, 1000);
+        assert_eq!(timestamps(&mut queue), [1000, 2000, 3000]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn oldest_dropped_beyond_turn_bound() {
+        let mut queue = TurnQueue::new();
+        let mut dropped = 0;
+        for ts in 0..MAX_QUEUED_TURNS as u64 + 2 {
+            dropped += queue.push(turn(b"t", ts), SIZE);
+        }
+        assert_eq!(dropped, 2);
+        assert_eq!(queue.len(), MAX_QUEUED_TURNS);
+        assert_eq!(queue.front().unwrap().turn.timestamp, 2);
+    }
+
+    #[test]
+    fn oldest_dropped_beyond_byte_bound() {
+        let mut queue = TurnQueue::new();
+        let half = vec![b'x'; MAX_QUEUED_BYTES / 2];
+        queue.push(turn(&half, 1), SIZE);
+        queue.push(turn(&half, 2), SIZE);
+        assert_eq!(queue.push(turn(b"more", 3), SIZE), 1);
+        assert_eq!(timestamps(&mut queue), [2, 3]);
+
+        // A single oversized turn is still kept.
+        let huge = vec![b'x'; MAX_QUEUED_BYTES + 1];
+        queue.push(turn(b"old", 4), SIZE);
+        assert_eq!(queue.push(turn(&huge, 5), SIZE), 1);
+        assert_eq!(timestamps(&mut queue), [5]);
+    }
+}
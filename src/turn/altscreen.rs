@@ -0,0 +1,235 @@
+//! Alternate-screen tracking — turn boundaries for full-screen TUIs.
+//!
+//! Agents that switch to the alternate screen buffer redraw in place
+//! with cursor addressing, so their byte stream has no meaningful
+//! lines. While the alternate screen is active the
+//! [`TurnDetector`](super::TurnDetector) replays output into a
+//! [`Screen`] and matches the prompt against the rendered rows at the
+//! cursor instead. See CONTRACT_TURN.md §Alternate Screen.
+
+use regex::Regex;
+
+use super::echo;
+use super::screen::{Screen, ScreenSize};
+
+/// The rendered alternate screen at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Whether the rows ending at the cursor row match the prompt.
+    pub prompt: bool,
+    /// The rows matched against the prompt, joined with `\n`.
+    pub window: String,
+    /// All rows, trailing blank rows removed.
+    pub rows: Vec<String>,
+    /// The rows outside that window, trailing blank rows removed.
+    pub outside: Vec<String>,
+    /// The rows outside a matched prompt block (all rows if there is
+    /// no match), trailing blank rows removed.
+    pub body: Vec<String>,
+}
+
+/// A live alternate screen and the state of the turn drawn on it.
+#[derive(Debug)]
+pub struct AltScreen {
+    screen: Screen,
+    /// The screen when the user last submitted input. Its rows are
+    /// what the turn started from, left out of its content; the rows
+    /// at the cursor held the input being typed.
+    baseline: Option<Snapshot>,
+}
+
+impl AltScreen {
+    /// A blank alternate screen of the given size.
+    pub fn new(size: ScreenSize) -> Self {
+        let mut screen = Screen::new(size);
+        // Switch the model itself over, so that scrolled-off rows do
+        // not collect as scrollback.
+        screen.feed(b"\x1b[?1047h");
+        Self {
+            screen,
+            baseline: None,
+        }
+    }
+
+    /// Feed output bytes drawn on the alternate screen.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.screen.feed_partial(data);
+    }
+
+    /// Start over at a new size. Full-screen programs redraw
+    /// everything after a resize; the baseline is kept.
+    pub fn resize(&mut self, size: ScreenSize) {
+        let baseline = self.baseline.take();
+        *self = Self::new(size);
+        self.baseline = baseline;
+    }
+
+    /// Render the screen and match the `lines`-row window ending at the
+    /// cursor row against `pattern`. Rendered rows have no trailing
+    /// spaces, except that the cursor row extends to the cursor: a
+    /// prompt's trailing space is where the cursor waits.
+    pub fn snapshot(&self, pattern: &Regex, lines: usize) -> Snapshot {
+        let rows = self.screen.rows();
+        let (cursor, col) = self.screen.cursor();
+        let start = (cursor + 1).saturating_sub(lines);
+        let mut window = rows[start..=cursor].join("\n");
+        let cursor_row_len = rows[cursor].chars().count();
+        window.extend(std::iter::repeat_n(' ', col.saturating_sub(cursor_row_len)));
+        let prompt = pattern.is_match(&window);
+
+        let mut outside = rows.clone();
+        outside.drain(start..=cursor);
+        trim_blank_tail(&mut outside);
+        let mut rows = rows;
+        trim_blank_tail(&mut rows);
+        let body = if prompt {
+            outside.clone()
+        } else {
+            rows.clone()
+        };
+        Snapshot {
+            prompt,
+            window,
+            rows,
+            outside,
+            body,
+        }
+    }
+
+    /// The user submitted input: what is on screen now is not part of
+    /// the turn about to start.
+    pub fn mark_input(&mut self, snapshot: Snapshot) {
+        self.baseline = Some(snapshot);
+    }
+
+    /// Whether the screen away from the cursor differs from what it
+    /// showed at the last submission (always, if there was none).
+    pub fn changed(&self, snapshot: &Snapshot) -> bool {
+        self.baseline
+            .as_ref()
+            .is_none_or(|baseline| baseline.outside != snapshot.outside)
+    }
+
+    /// Turn content extracted from a snapshot: the body rows after
+    /// those it shares with the screen at submission, each terminated
+    /// by CRLF.
+    /// Leading rows that echo the `submitted` lines, and decoration
+    /// (blank rows, box-drawing rules) around them, are left out as
+    /// on the main screen; so are trailing blank rows.
+    pub fn content(&self, snapshot: &Snapshot, submitted: &[Vec<u8>]) -> Vec<u8> {
+        let body = &snapshot.body;
+        let shared = self.baseline.as_ref().map_or(0, |baseline| {
+            baseline
+                .rows
+                .iter()
+                .zip(body)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+
+        let mut rows = &body[shared..];
+        let mut pending = submitted.iter().peekable();
+        while let Some((row, rest)) = rows.split_first() {
+            let row = row.as_bytes();
+            if pending
+                .peek()
+                .is_some_and(|line| echo::is_echo_of(row, line))
+            {
+                pending.next();
+            } else if !echo::is_decoration_line(row) {
+                break;
+            }
+            rows = rest;
+        }
+
+        let mut out = Vec::new();
+        for row in rows {
+            out.extend_from_slice(row.as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+        out
+    }
+}
+
+/// Drop trailing blank rows.
+fn trim_blank_tail(rows: &mut Vec<String>) {
+    while rows.last().is_some_and(String::is_empty) {
+        rows.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(output: &[u8]) -> AltScreen {
+        let mut alt = AltScreen::new(ScreenSize { cols: 20, rows: 6 });
+        alt.feed(output);
+        alt
+    }
+
+    #[test]
+    fn prompt_matched_at_cursor_row() {
+        let prompt = Regex::new(r"^> $").unwrap();
+        let alt = screen(b"\x1b[1;1Htitle\x1b[5;1H> \x1b[3;1Hbusy\x1b[5;3H");
+        let snap = alt.snapshot(&prompt, 1);
+        assert!(snap.prompt);
+        assert_eq!(snap.window, "> ");
+        assert_eq!(snap.body, ["title", "", "busy"]);
+
+        // The same row with the cursor elsewhere is not a prompt.
+        let alt = screen(b"> \x1b[3;1Hbusy");
+        assert!(!alt.snapshot(&prompt, 1).prompt);
+    }
+
+    #[test]
+    fn multi_row_prompt_window() {
+        let prompt = Regex::new(r"^─+\n> $").unwrap();
+        let alt = screen("answer\r\n───\r\n> ".as_bytes());
+        let snap = alt.snapshot(&prompt, 2);
+        assert!(snap.prompt);
+        assert_eq!(snap.body, ["answer"]);
+    }
+
+    #[test]
+    fn content_excludes_rows_shared_with_baseline() {
+        let prompt = Regex::new(r"^> $").unwrap();
+        let mut alt = screen(b"history\r\n\r\n> hi");
+        alt.mark_input(alt.snapshot(&prompt, 1));
+
+        // Clearing the input line changes nothing away from the cursor.
+        alt.feed(b"\r\x1b[K> ");
+        let snap = alt.snapshot(&prompt, 1);
+        assert!(snap.prompt);
+        assert!(!alt.changed(&snap));
+
+        alt.feed(b"\x1b[2;1Hanswer 1\r\nanswer 2\r\n\r\n> \x1b[K");
+        let snap = alt.snapshot(&prompt, 1);
+        assert!(snap.prompt);
+        assert!(alt.changed(&snap));
+        assert_eq!(alt.content(&snap, &[]), b"answer 1\r\nanswer 2\r\n");
+    }
+
+    #[test]
+    fn content_excludes_echoed_prompt() {
+        let prompt = Regex::new(r"^> $").unwrap();
+        let alt = screen("│ > hi │\r\n\r\nanswer\r\n> ".as_bytes());
+        let snap = alt.snapshot(&prompt, 1);
+        assert_eq!(alt.content(&snap, &[b"hi".to_vec()]), b"answer\r\n");
+        // Without the submission the echo is content.
+        assert_eq!(
+            alt.content(&snap, &[]),
+            "│ > hi │\r\n\r\nanswer\r\n".as_bytes()
+        );
+    }
+
+    #[test]
+    fn resize_keeps_baseline() {
+        let prompt = Regex::new(r"^> $").unwrap();
+        let mut alt = screen(b"old\r\n> ");
+        alt.mark_input(alt.snapshot(&prompt, 1));
+        alt.resize(ScreenSize { cols: 30, rows: 4 });
+        alt.feed(b"old\r\nnew\r\n> ");
+        assert_eq!(alt.content(&alt.snapshot(&prompt, 1), &[]), b"new\r\n");
+    }
+}
@@ -22,125 +22,452 @@ enum State {
     OscEscape,
 }
 
-/// Strips ANSI escape sequences from a byte slice.
+/// A lexical unit of terminal output.
 ///
-/// Returns a new `Vec<u8>` containing only the visible text content.
-/// This is a stateless convenience wrapper — each call processes a
-/// complete buffer independently.
-#[allow(dead_code)]
-pub fn strip_ansi(input: &[u8]) -> Vec<u8> {
-    let mut stripper = AnsiStripper::new();
-    stripper.strip(input)
+/// Produced by [`Tokens`]. Escape sequences borrow their parameter
+/// bytes from the input; text is decoded as UTF-8 one character at a
+/// time (invalid bytes become U+FFFD).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// A printable character (including TAB, which callers treat as
+    /// a single cell).
+    Text(char),
+    /// A C0 control byte other than ESC and TAB (e.g. `\r`, `\n`, BS).
+    Control(u8),
+    /// A CSI sequence: `ESC [ params final`. `params` holds the raw
+    /// parameter and intermediate bytes, including any private-mode
+    /// prefix such as `?`.
+    Csi { params: &'a [u8], final_byte: u8 },
+    /// An OSC payload (between `ESC ]` and BEL / ST).
+    Osc(&'a [u8]),
+    /// Any other escape sequence: `ESC intermediates final`.
+    Esc {
+        intermediates: &'a [u8],
+        final_byte: u8,
+    },
+}
+
+/// Iterator over the [`Token`]s of a complete buffer.
+///
+/// Unlike [`AnsiStripper`], this is not resumable: an escape sequence
+/// truncated at the end of the input is dropped.
+#[derive(Debug, Clone)]
+pub struct Tokens<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+/// Tokenize a complete buffer of terminal output.
+pub fn tokenize(input: &[u8]) -> Tokens<'_> {
+    Tokens { input, pos: 0 }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let input = self.input;
+        let byte = *input.get(self.pos)?;
+
+        if byte == 0x1B {
+            return self.escape();
+        }
+        if (byte < 0x20 && byte != b'\t') || byte == 0x7F {
+            self.pos += 1;
+            return Some(Token::Control(byte));
+        }
+
+        // UTF-8 decode a single character.
+        let width = match byte {
+            0x00..=0x7F => 1,
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => 0,
+        };
+        let end = self.pos + width;
+        let ch = if width == 0 || end > input.len() {
+            None
+        } else {
+            std::str::from_utf8(&input[self.pos..end])
+                .ok()
+                .and_then(|s| s.chars().next())
+        };
+        match ch {
+            Some(c) => {
+                self.pos = end;
+                Some(Token::Text(c))
+            }
+            None => {
+                self.pos += 1;
+                Some(Token::Text(char::REPLACEMENT_CHARACTER))
+            }
+        }
+    }
+}
+
+impl<'a> Tokens<'a> {
+    /// Parse an escape sequence starting at `self.pos` (which is ESC).
+    fn escape(&mut self) -> Option<Token<'a>> {
+        let input = self.input;
+        let start = self.pos + 1;
+        match input.get(start)? {
+            b'[' => {
+                let params_start = start + 1;
+                let mut i = params_start;
+                while let Some(&b) = input.get(i) {
+                    if (0x40..=0x7E).contains(&b) {
+                        self.pos = i + 1;
+                        return Some(Token::Csi {
+                            params: &input[params_start..i],
+                            final_byte: b,
+                        });
+                    }
+                    i += 1;
+                }
+                self.pos = input.len();
+                None
+            }
+            b']' => {
+                let payload_start = start + 1;
+                let mut i = payload_start;
+                while let Some(&b) = input.get(i) {
+                    if b == 0x07 {
+                        self.pos = i + 1;
+                        return Some(Token::Osc(&input[payload_start..i]));
+                    }
+                    if b == 0x1B && input.get(i + 1) == Some(&b'\\') {
+                        self.pos = i + 2;
+                        return Some(Token::Osc(&input[payload_start..i]));
+                    }
+                    i += 1;
+                }
+                self.pos = input.len();
+                None
+            }
+            _ => {
+                let mut i = start;
+                while let Some(&b) = input.get(i) {
+                    if !(0x20..=0x2F).contains(&b) {
+                        self.pos = i + 1;
+                        return Some(Token::Esc {
+                            intermediates: &input[start..i],
+                            final_byte: b,
+                        });
+                    }
+                    i += 1;
+                }
+                self.pos = input.len();
+                None
+            }
+        }
+    }
+}
+
+/// Parse the numeric parameters of a CSI sequence.
+///
+/// Leading private-mode markers (`?`, `>`, `<`, `=`) and intermediate
+/// bytes are ignored. Empty parameters are reported as `0`.
+pub fn csi_numbers(params: &[u8]) -> Vec<u16> {
+    let body: Vec<u8> = params
+        .iter()
+        .copied()
+        .filter(|b| b.is_ascii_digit() || *b == b';' || *b == b':')
+        .collect();
+    if body.is_empty() {
+        return Vec::new();
+    }
+    body.split(|&b| b == b';' || b == b':')
+        .map(|part| {
+            part.iter().fold(0u16, |acc, &d| {
+                acc.saturating_mul(10).saturating_add(u16::from(d - b'0'))
+            })
+        })
+        .collect()
+}
+
+/// Payload bytes retained per OSC sequence. Longer payloads (window
+/// titles, hyperlinks) are truncated; the markers we interpret are
+/// short.
+const MAX_OSC_PAYLOAD: usize = 128;
+
+/// CSI parameter bytes retained per sequence; only short private-mode
+/// parameters are interpreted.
+const MAX_CSI_PARAMS: usize = 16;
+
+/// Private modes that switch to the alternate screen buffer.
+const ALT_SCREEN_MODES: [u16; 3] = [47, 1047, 1049];
+
+/// A completed OSC sequence seen by [`AnsiStripper`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OscSequence {
+    /// Payload between `ESC ]` and the terminator, truncated to
+    /// [`MAX_OSC_PAYLOAD`] bytes.
+    pub payload: Vec<u8>,
+    /// Length of the whole sequence in the input, including the
+    /// introducer and terminator.
+    pub len: usize,
 }
 
 /// Stateful ANSI escape sequence stripper.
 ///
-/// Maintains parser state across calls to [`strip`] so that escape
-/// sequences split across chunk boundaries are handled correctly.
+/// Maintains parser state across calls to
+/// [`strip_into`](Self::strip_into) so that escape sequences split
+/// across chunk boundaries are handled correctly.
+/// The most recently completed OSC sequence is retained for
+/// [`take_osc`](Self::take_osc), and the most recent alternate-screen
+/// switch for [`take_alt_screen`](Self::take_alt_screen).
 #[derive(Debug)]
 pub struct AnsiStripper {
     state: State,
+    /// Bytes consumed by the escape sequence in progress.
+    seq_len: usize,
+    /// Payload of the OSC sequence in progress.
+    osc_payload: Vec<u8>,
+    /// Last completed OSC sequence, until taken.
+    completed_osc: Option<OscSequence>,
+    /// Parameter bytes of the CSI sequence in progress.
+    csi_params: Vec<u8>,
+    /// Last alternate-screen switch (`true` = entered), until taken.
+    alt_screen: Option<bool>,
 }
 
 impl AnsiStripper {
     pub fn new() -> Self {
         Self {
             state: State::Ground,
+            seq_len: 0,
+            osc_payload: Vec::new(),
+            completed_osc: None,
+            csi_params: Vec::new(),
+            alt_screen: None,
         }
     }
 
-    /// Strip ANSI escape sequences from `input`, returning visible text.
+    /// Take the most recently completed OSC sequence, if any.
     ///
-    /// State is preserved between calls — a sequence that starts in one
-    /// chunk and ends in the next is handled correctly.
-    pub fn strip(&mut self, input: &[u8]) -> Vec<u8> {
-        let mut output = Vec::with_capacity(input.len());
+    /// Only the last sequence completed since the previous call is
+    /// kept; callers that need every sequence use
+    /// [`strip_into`](Self::strip_into), which stops after each one.
+    pub fn take_osc(&mut self) -> Option<OscSequence> {
+        self.completed_osc.take()
+    }
+
+    /// Take the most recent alternate-screen switch (`ESC [ ? 1049 h`
+    /// and friends): `Some(true)` on entry, `Some(false)` on exit.
+    pub fn take_alt_screen(&mut self) -> Option<bool> {
+        self.alt_screen.take()
+    }
 
-        for &byte in input {
+    /// Finish the OSC sequence in progress.
+    fn complete_osc(&mut self) {
+        self.completed_osc = Some(OscSequence {
+            payload: std::mem::take(&mut self.osc_payload),
+            len: self.seq_len,
+        });
+    }
+
+    /// Strip a prefix of `input`, appending visible text to `output`.
+    ///
+    /// Returns the number of input bytes consumed. Stops early just
+    /// after an OSC sequence or an alternate-screen switch completes,
+    /// so that the caller can [`take_osc`](Self::take_osc) or
+    /// [`take_alt_screen`](Self::take_alt_screen) it at its exact
+    /// position; otherwise consumes all of `input`. Runs of plain text and OSC payload are
+    /// copied in bulk — nothing is allocated per byte.
+    pub fn strip_into(&mut self, input: &[u8], output: &mut Vec<u8>) -> usize {
+        let mut pos = 0;
+        while pos < input.len() {
+            let rest = &input[pos..];
             match self.state {
                 State::Ground => {
-                    if byte == 0x1B {
+                    let run = memchr::memchr(0x1B, rest).unwrap_or(rest.len());
+                    output.extend_from_slice(&rest[..run]);
+                    pos += run;
+                    if run < rest.len() {
                         self.state = State::Escape;
-                    } else {
-                        output.push(byte);
-                    }
-                }
-                State::Escape => match byte {
-                    b'[' => self.state = State::Csi,
-                    b']' => self.state = State::Osc,
-                    // Two-character sequences: ESC followed by a single
-                    // byte in the 0x40..0x5F range (C1 control shorthand)
-                    // or common sequences like ESC ( B, ESC ) 0, etc.
-                    // For simplicity, consume one byte after ESC for
-                    // sequences that aren't CSI or OSC.
-                    0x20..=0x2F => {
-                        // Intermediate byte — start of an nF escape
-                        // sequence (e.g. ESC ( B for charset select).
-                        // Consume intermediate bytes then a final byte.
-                        self.state = State::EscapeIntermediate;
-                    }
-                    _ => {
-                        // Single-character escape sequence (e.g., ESC M,
-                        // ESC 7, ESC 8, ESC =, ESC >, etc.)
-                        self.state = State::Ground;
-                    }
-                },
-                State::Csi => {
-                    // CSI sequences: ESC [ (parameter bytes 0x30-0x3F)*
-                    //                      (intermediate bytes 0x20-0x2F)*
-                    //                      (final byte 0x40-0x7E)
-                    if (0x40..=0x7E).contains(&byte) {
-                        self.state = State::Ground;
-                    }
-                    // Otherwise consume parameter/intermediate bytes.
-                }
-                State::EscapeIntermediate => {
-                    // nF sequences: ESC (intermediate 0x20-0x2F)+
-                    //               (final 0x30-0x7E)
-                    // Consume additional intermediate bytes; transition
-                    // to Ground on the final byte.
-                    if (0x20..=0x2F).contains(&byte) {
-                        // More intermediate bytes — stay.
-                    } else {
-                        // Final byte (or unexpected) — sequence done.
-                        self.state = State::Ground;
+                        self.seq_len = 1;
+                        pos += 1;
                     }
                 }
                 State::Osc => {
                     // OSC sequences end with BEL (0x07) or ST (ESC \).
-                    if byte == 0x07 {
-                        self.state = State::Ground;
-                    } else if byte == 0x1B {
+                    let run = memchr::memchr2(0x07, 0x1B, rest).unwrap_or(rest.len());
+                    let room = MAX_OSC_PAYLOAD.saturating_sub(self.osc_payload.len());
+                    self.osc_payload.extend_from_slice(&rest[..run.min(room)]);
+                    self.seq_len += run;
+                    pos += run;
+                    if run < rest.len() {
+                        self.seq_len += 1;
+                        pos += 1;
+                        if rest[run] == 0x07 {
+                            self.state = State::Ground;
+                            self.complete_osc();
+                            return pos;
+                        }
                         self.state = State::OscEscape;
                     }
-                    // Otherwise consume OSC content.
                 }
-                State::OscEscape => {
-                    // Expecting '\' to complete ST (String Terminator).
-                    if byte == b'\\' {
-                        self.state = State::Ground;
-                    } else {
-                        // Malformed — treat as new escape sequence.
-                        self.state = State::Escape;
-                        // Re-process this byte as if we just saw ESC.
-                        match byte {
-                            b'[' => self.state = State::Csi,
-                            b']' => self.state = State::Osc,
-                            _ => self.state = State::Ground,
-                        }
+                _ => {
+                    self.seq_len += 1;
+                    pos += 1;
+                    if self.step(rest[0]) {
+                        return pos;
                     }
                 }
             }
         }
+        pos
+    }
 
-        output
+    /// Advance the escape-sequence states by one byte. Returns `true`
+    /// if the byte completed an OSC sequence or an alternate-screen
+    /// switch.
+    fn step(&mut self, byte: u8) -> bool {
+        match self.state {
+            State::Ground | State::Osc => unreachable!("handled in bulk by strip_into"),
+            State::Escape => match byte {
+                b'[' => {
+                    self.state = State::Csi;
+                    self.csi_params.clear();
+                }
+                b']' => {
+                    self.state = State::Osc;
+                    self.osc_payload.clear();
+                }
+                // Two-character sequences: ESC followed by a single
+                // byte in the 0x40..0x5F range (C1 control shorthand)
+                // or common sequences like ESC ( B, ESC ) 0, etc.
+                // For simplicity, consume one byte after ESC for
+                // sequences that aren't CSI or OSC.
+                0x20..=0x2F => {
+                    // Intermediate byte — start of an nF escape
+                    // sequence (e.g. ESC ( B for charset select).
+                    // Consume intermediate bytes then a final byte.
+                    self.state = State::EscapeIntermediate;
+                }
+                _ => {
+                    // Single-character escape sequence (e.g., ESC M,
+                    // ESC 7, ESC 8, ESC =, ESC >, etc.)
+                    self.state = State::Ground;
+                }
+            },
+            State::Csi => {
+                // CSI sequences: ESC [ (parameter bytes 0x30-0x3F)*
+                //                      (intermediate bytes 0x20-0x2F)*
+                //                      (final byte 0x40-0x7E)
+                if (0x40..=0x7E).contains(&byte) {
+                    self.state = State::Ground;
+                    return self.complete_csi(byte);
+                }
+                // Otherwise consume parameter/intermediate bytes.
+                if self.csi_params.len() < MAX_CSI_PARAMS {
+                    self.csi_params.push(byte);
+                }
+            }
+            State::EscapeIntermediate => {
+                // nF sequences: ESC (intermediate 0x20-0x2F)+
+                //               (final 0x30-0x7E)
+                // Consume additional intermediate bytes; transition
+                // to Ground on the final byte.
+                if !(0x20..=0x2F).contains(&byte) {
+                    // Final byte (or unexpected) — sequence done.
+                    self.state = State::Ground;
+                }
+            }
+            State::OscEscape => {
+                // Expecting '\' to complete ST (String Terminator).
+                if byte == b'\\' {
+                    self.state = State::Ground;
+                    self.complete_osc();
+                    return true;
+                }
+                // Malformed — treat as new escape sequence, re-processing
+                // this byte as if we just saw ESC.
+                self.seq_len = 2;
+                match byte {
+                    b'[' => {
+                        self.state = State::Csi;
+                        self.csi_params.clear();
+                    }
+                    b']' => {
+                        self.state = State::Osc;
+                        self.osc_payload.clear();
+                    }
+                    _ => self.state = State::Ground,
+                }
+            }
+        }
+        false
+    }
+
+    /// Handle a completed CSI sequence. Returns `true` if it switched
+    /// the alternate screen on or off.
+    fn complete_csi(&mut self, final_byte: u8) -> bool {
+        if !matches!(final_byte, b'h' | b'l') || self.csi_params.first() != Some(&b'?') {
+            return false;
+        }
+        let switches = csi_numbers(&self.csi_params)
+            .iter()
+            .any(|mode| ALT_SCREEN_MODES.contains(mode));
+        if switches {
+            self.alt_screen = Some(final_byte == b'h');
+        }
+        switches
     }
 }
 
+/// Length of the longest prefix of `input` that holds only complete
+/// tokens: an escape sequence or UTF-8 character cut off at the end
+/// is excluded, so a streaming caller can hold it back for the next
+/// chunk.
+pub fn complete_len(input: &[u8]) -> usize {
+    let mut tokens = tokenize(input);
+    let mut end = 0;
+    while tokens.next().is_some() {
+        end = tokens.pos;
+    }
+    if end < input.len() {
+        // `Tokens` drops a truncated escape sequence.
+        return input.iter().rposition(|&b| b == 0x1B).unwrap_or(end);
+    }
+    // A multi-byte character missing its last bytes.
+    for (i, &b) in input.iter().enumerate().rev().take(3) {
+        let width = match b {
+            0x80..=0xBF => continue,
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => break,
+        };
+        if i + width > input.len() {
+            return i;
+        }
+        break;
+    }
+    input.len()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Strip all of `input`, returning the visible text. State carries
+    /// over between calls, as with [`AnsiStripper::strip_into`].
+    fn strip(stripper: &mut AnsiStripper, input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(input.len());
+        let mut pos = 0;
+        while pos < input.len() {
+            pos += stripper.strip_into(&input[pos..], &mut output);
+        }
+        output
+    }
+
+    /// Strip a complete buffer with a fresh stripper.
+    fn strip_ansi(input: &[u8]) -> Vec<u8> {
+        strip(&mut AnsiStripper::new(), input)
+    }
+
     #[test]
     fn plain_text_unchanged() {
         assert_eq!(strip_ansi(b"hello world"), b"hello world");
@@ -197,8 +524,8 @@ mod tests {
         let mut stripper = AnsiStripper::new();
 
         // ESC[31m split across two chunks
-        let out1 = stripper.strip(b"before\x1b[3");
-        let out2 = stripper.strip(b"1mafter");
+        let out1 = strip(&mut stripper, b"before\x1b[3");
+        let out2 = strip(&mut stripper, b"1mafter");
 
         let mut combined = out1;
         combined.extend(out2);
@@ -214,10 +541,10 @@ mod tests {
     #[test]
     fn bare_esc_at_end() {
         let mut stripper = AnsiStripper::new();
-        let out = stripper.strip(b"hello\x1b");
+        let out = strip(&mut stripper, b"hello\x1b");
         assert_eq!(out, b"hello");
         // Next chunk completes the sequence
-        let out2 = stripper.strip(b"[0mworld");
+        let out2 = strip(&mut stripper, b"[0mworld");
         assert_eq!(out2, b"world");
     }
 
@@ -255,14 +582,266 @@ mod tests {
         assert_eq!(strip_ansi(input), b"visible");
     }
 
+    // -- Tokenizer --
+
+    #[test]
+    fn tokenize_text_and_controls() {
+        let tokens: Vec<_> = tokenize(b"a\r\n\tb").collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Text('a'),
+                Token::Control(b'\r'),
+                Token::Control(b'\n'),
+                Token::Text('\t'),
+                Token::Text('b'),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_csi_with_private_params() {
+        let tokens: Vec<_> = tokenize(b"\x1b[?1049h\x1b[2K").collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Csi {
+                    params: b"?1049",
+                    final_byte: b'h'
+                },
+                Token::Csi {
+                    params: b"2",
+                    final_byte: b'K'
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_osc_bel_and_st() {
+        let tokens: Vec<_> = tokenize(b"\x1b]0;title\x07\x1b]133;A\x1b\\").collect();
+        assert_eq!(tokens, vec![Token::Osc(b"0;title"), Token::Osc(b"133;A")]);
+    }
+
+    #[test]
+    fn tokenize_utf8_and_invalid_bytes() {
+        let tokens: Vec<_> = tokenize("é─".as_bytes()).collect();
+        assert_eq!(tokens, vec![Token::Text('é'), Token::Text('─')]);
+
+        let tokens: Vec<_> = tokenize(b"\xffx").collect();
+        assert_eq!(
+            tokens,
+            vec![Token::Text(char::REPLACEMENT_CHARACTER), Token::Text('x')]
+        );
+    }
+
+    #[test]
+    fn tokenize_truncated_sequence_dropped() {
+        let tokens: Vec<_> = tokenize(b"ok\x1b[3").collect();
+        assert_eq!(tokens, vec![Token::Text('o'), Token::Text('k')]);
+    }
+
+    #[test]
+    fn csi_numbers_parsing() {
+        assert_eq!(csi_numbers(b""), Vec::<u16>::new());
+        assert_eq!(csi_numbers(b"2"), vec![2]);
+        assert_eq!(csi_numbers(b"?1049"), vec![1049]);
+        assert_eq!(csi_numbers(b"10;;3"), vec![10, 0, 3]);
+    }
+
     #[test]
     fn nf_split_across_chunks() {
         let mut stripper = AnsiStripper::new();
-        let out1 = stripper.strip(b"before\x1b(");
-        let out2 = stripper.strip(b"Bafter");
+        let out1 = strip(&mut stripper, b"before\x1b(");
+        let out2 = strip(&mut stripper, b"Bafter");
 
         let mut combined = out1;
         combined.extend(out2);
         assert_eq!(combined, b"beforeafter");
     }
+
+    #[test]
+    fn completed_osc_reported_with_length() {
+        let mut stripper = AnsiStripper::new();
+        assert_eq!(strip(&mut stripper, b"a\x1b]133;D;1\x07b"), b"ab");
+        assert_eq!(
+            stripper.take_osc(),
+            Some(OscSequence {
+                payload: b"133;D;1".to_vec(),
+                len: 10,
+            })
+        );
+        assert_eq!(stripper.take_osc(), None);
+    }
+
+    #[test]
+    fn osc_split_across_chunks_with_st() {
+        let mut stripper = AnsiStripper::new();
+        strip(&mut stripper, b"\x1b]133");
+        assert_eq!(stripper.take_osc(), None);
+        strip(&mut stripper, b";A\x1b\\");
+        let osc = stripper.take_osc().unwrap();
+        assert_eq!(osc.payload, b"133;A");
+        assert_eq!(osc.len, 9);
+    }
+
+    #[test]
+    fn long_osc_payload_truncated() {
+        let mut input = b"\x1b]0;".to_vec();
+        input.extend(vec![b'x'; 500]);
+        input.push(0x07);
+        let mut stripper = AnsiStripper::new();
+        assert!(strip(&mut stripper, &input).is_empty());
+        let osc = stripper.take_osc().unwrap();
+        assert_eq!(osc.payload.len(), MAX_OSC_PAYLOAD);
+        assert_eq!(osc.len, input.len());
+    }
+
+    // -- Bulk stripping --
+
+    /// Inputs and expected output of the per-byte stripper's tests
+    /// above, plus the awkward cases for bulk scanning — sequences at
+    /// chunk edges, back-to-back OSCs, a malformed ST — with the output
+    /// the per-byte stripper gave for them.
+    const CORPUS: &[(&[u8], &[u8])] = &[
+        (b"hello world", b"hello world"),
+        (b"", b""),
+        (b"\x1b[31mhello\x1b[0m", b"hello"),
+        (
+            b"\x1b[1;32mbold green\x1b[0m normal \x1b[4munderline\x1b[0m",
+            b"bold green normal underline",
+        ),
+        (b"\x1b[2J\x1b[Hprompt> ", b"prompt> "),
+        (b"\x1b]0;my terminal\x07prompt> ", b"prompt> "),
+        (b"\x1b]0;my terminal\x1b\\prompt> ", b"prompt> "),
+        (b"\x1bMhello", b"hello"),
+        (b"before\x1b[31mafter", b"beforeafter"),
+        (
+            b"\x1b[32mline1\nline2\x1b[0m\nline3",
+            b"line1\nline2\nline3",
+        ),
+        (b"hello\x1b[0mworld", b"helloworld"),
+        (b"a\x1b[1mb\x1b[2mc\x1b[3md", b"abcd"),
+        (b"\x1b(Bhello", b"hello"),
+        (b"\x1b)0world", b"world"),
+        (b"before\x1b(Bafter", b"beforeafter"),
+        (b"\x1b #8visible", b"visible"),
+        (b"a\x1b]133;D;1\x07b\x1b]133;A\x07\x1b]133;B\x1b\\c", b"abc"),
+        (b"\x1b]0;bad\x1b[1mtext\x1b]2;t\x1bxrest", b"textrest"),
+        (b"\x1b", b""),
+    ];
+
+    /// Strip `input` in `chunk`-sized pieces with [`AnsiStripper::strip_into`],
+    /// collecting output and every completed OSC.
+    fn strip_chunked(input: &[u8], chunk: usize) -> (Vec<u8>, Vec<OscSequence>) {
+        let mut stripper = AnsiStripper::new();
+        let mut output = Vec::new();
+        let mut oscs = Vec::new();
+        for piece in input.chunks(chunk) {
+            let mut pos = 0;
+            while pos < piece.len() {
+                pos += stripper.strip_into(&piece[pos..], &mut output);
+                oscs.extend(stripper.take_osc());
+            }
+        }
+        (output, oscs)
+    }
+
+    #[test]
+    fn bulk_strip_matches_corpus() {
+        for &(input, expected) in CORPUS {
+            for chunk in [1, 2, 3, 7, input.len().max(1)] {
+                assert_eq!(
+                    strip_chunked(input, chunk).0,
+                    expected,
+                    "input {input:?}, chunk size {chunk}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bulk_strip_reports_oscs() {
+        let osc = |payload: &[u8], len| OscSequence {
+            payload: payload.to_vec(),
+            len,
+        };
+        let mut long_osc = b"\x1b]0;".to_vec();
+        long_osc.extend(vec![b'x'; 500]);
+        long_osc.extend_from_slice(b"\x1b\\tail");
+        let mut truncated = b"0;".to_vec();
+        truncated.resize(MAX_OSC_PAYLOAD, b'x');
+
+        let cases: [(&[u8], &[u8], Vec<OscSequence>); 3] = [
+            (
+                b"a\x1b]133;D;1\x07b\x1b]133;A\x07\x1b]133;B\x1b\\c",
+                b"abc",
+                vec![osc(b"133;D;1", 10), osc(b"133;A", 8), osc(b"133;B", 9)],
+            ),
+            (b"\x1b]133;A\x1b\\", b"", vec![osc(b"133;A", 9)]),
+            (&long_osc, b"tail", vec![osc(&truncated, 506)]),
+        ];
+        for (input, output, oscs) in cases {
+            for chunk in [1, 2, 3, 7, input.len()] {
+                assert_eq!(
+                    strip_chunked(input, chunk),
+                    (output.to_vec(), oscs.clone()),
+                    "input {input:?}, chunk size {chunk}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn strip_into_stops_after_osc() {
+        let mut stripper = AnsiStripper::new();
+        let mut out = Vec::new();
+        let input = b"a\x1b]133;A\x07b\x1b]133;B\x07c";
+        assert_eq!(stripper.strip_into(input, &mut out), 9);
+        assert_eq!(stripper.take_osc().unwrap().payload, b"133;A");
+        assert_eq!(stripper.strip_into(&input[9..], &mut out), 9);
+        assert_eq!(stripper.take_osc().unwrap().payload, b"133;B");
+        assert_eq!(stripper.strip_into(&input[18..], &mut out), 1);
+        assert_eq!(out, b"abc");
+    }
+
+    #[test]
+    fn alt_screen_switch_reported() {
+        let mut s = AnsiStripper::new();
+        let mut out = Vec::new();
+        let input = b"a\x1b[?1049hb\x1b[?25l\x1b[?1049lc";
+        let n = s.strip_into(input, &mut out);
+        assert_eq!(n, 9);
+        assert_eq!(s.take_alt_screen(), Some(true));
+        let n2 = s.strip_into(&input[n..], &mut out);
+        assert_eq!(s.take_alt_screen(), Some(false));
+        assert_eq!(n + n2, input.len() - 1);
+        s.strip_into(&input[n + n2..], &mut out);
+        assert_eq!(out, b"abc");
+        assert_eq!(s.take_alt_screen(), None);
+    }
+
+    #[test]
+    fn alt_screen_switch_split_across_chunks() {
+        let mut s = AnsiStripper::new();
+        let mut out = Vec::new();
+        s.strip_into(b"\x1b[?10", &mut out);
+        assert_eq!(s.take_alt_screen(), None);
+        s.strip_into(b"47l", &mut out);
+        assert_eq!(s.take_alt_screen(), Some(false));
+        // Other private modes are not switches.
+        s.strip_into(b"\x1b[?2004h\x1b[1049h", &mut out);
+        assert_eq!(s.take_alt_screen(), None);
+    }
+
+    #[test]
+    fn complete_len_holds_back_truncated_tail() {
+        assert_eq!(complete_len(b"abc"), 3);
+        assert_eq!(complete_len(b"ab\x1b[3"), 2);
+        assert_eq!(complete_len(b"ab\x1b"), 2);
+        assert_eq!(complete_len(b"ab\x1b]0;ti"), 2);
+        assert_eq!(complete_len("a\u{2500}".as_bytes()), 4);
+        assert_eq!(complete_len(&"a\u{2500}".as_bytes()[..3]), 1);
+        assert_eq!(complete_len(b"a\xff"), 2);
+    }
 }
@@ -0,0 +1,325 @@
+//! Turn boundary strategies — deciding when a turn ends.
+//!
+//! The [`TurnDetector`](super::TurnDetector) owns line assembly and
+//! content accumulation; a [`BoundaryStrategy`] decides which lines
+//! and which silences mark a boundary. See CONTRACT_TURN.md
+//! §Boundary Strategies.
+//!
+//! Strategies are selected by a spec string:
+//!
+//! | Spec                | Strategy                                   |
+//! |---------------------|--------------------------------------------|
+//! | `prompt`            | [`PromptBoundary`] (default)               |
+//! | `quiet:<ms>`        | [`QuietBoundary`]                          |
+//! | `prompt+quiet:<ms>` | [`PromptQuietBoundary`]                    |
+
+use std::time::{Duration, Instant};
+
+use super::TurnError;
+
+/// Upper bound on quiescence timeouts (10 minutes).
+const MAX_QUIET_MS: u64 = 10 * 60 * 1000;
+
+/// How the detector should treat a completed line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineAction {
+    /// Ordinary output — append to turn content.
+    Content,
+    /// A turn boundary. The line (prompt block) is excluded.
+    Boundary,
+    /// A possible boundary: the line is held as content but excluded
+    /// if a timer boundary follows before any further line.
+    Tentative,
+}
+
+/// The unterminated line at the time a timer fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartialLine {
+    /// No visible text on the current line.
+    Empty,
+    /// Visible text; `prompt` is whether it matches the prompt pattern.
+    Text { prompt: bool },
+}
+
+/// Decides where turns end.
+///
+/// Time is passed in explicitly so that strategies are deterministic
+/// under test. The detector calls [`on_timer`](Self::on_timer) once
+/// [`deadline`](Self::deadline) has passed.
+pub trait BoundaryStrategy: std::fmt::Debug + Send {
+    /// The spec string this strategy was built from (reported to the
+    /// broker at registration).
+    fn spec(&self) -> String;
+
+    /// Classify a completed line. `is_prompt` is the prompt-pattern
+    /// result for the line (or multi-line window).
+    fn on_line(&mut self, is_prompt: bool) -> LineAction;
+
+    /// Agent output arrived.
+    fn on_output(&mut self, now: Instant);
+
+    /// The user submitted input (a new turn begins).
+    fn on_input(&mut self);
+
+    /// When [`on_timer`](Self::on_timer) should next be called.
+    fn deadline(&self) -> Option<Instant>;
+
+    /// The deadline has passed. Returns `true` if this is a boundary.
+    fn on_timer(&mut self, partial: PartialLine) -> bool;
+}
+
+/// Parse a boundary spec string.
+pub fn parse(spec: &str) -> Result<Box<dyn BoundaryStrategy>, TurnError> {
+    let invalid = || TurnError::InvalidBoundary(spec.to_string());
+    let quiet_ms = |ms: &str| -> Result<Duration, TurnError> {
+        let ms: u64 = ms.parse().map_err(|_| invalid())?;
+        if ms == 0 || ms > MAX_QUIET_MS {
+            return Err(invalid());
+        }
+        Ok(Duration::from_millis(ms))
+    };
+
+    if spec == "prompt" {
+        Ok(Box::new(PromptBoundary))
+    } else if let Some(ms) = spec.strip_prefix("prompt+quiet:") {
+        Ok(Box::new(PromptQuietBoundary::new(quiet_ms(ms)?)))
+    } else if let Some(ms) = spec.strip_prefix("quiet:") {
+        Ok(Box::new(QuietBoundary::new(quiet_ms(ms)?)))
+    } else {
+        Err(invalid())
+    }
+}
+
+/// A prompt-pattern match ends the turn immediately.
+#[derive(Debug, Default)]
+pub struct PromptBoundary;
+
+impl BoundaryStrategy for PromptBoundary {
+    fn spec(&self) -> String {
+        "prompt".into()
+    }
+
+    fn on_line(&mut self, is_prompt: bool) -> LineAction {
+        if is_prompt {
+            LineAction::Boundary
+        } else {
+            LineAction::Content
+        }
+    }
+
+    fn on_output(&mut self, _now: Instant) {}
+
+    fn on_input(&mut self) {}
+
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    fn on_timer(&mut self, _partial: PartialLine) -> bool {
+        false
+    }
+}
+
+/// No output for `quiet` ends the turn. The prompt pattern is not
+/// consulted for completed lines.
+#[derive(Debug)]
+pub struct QuietBoundary {
+    quiet: Duration,
+    deadline: Option<Instant>,
+}
+
+impl QuietBoundary {
+    pub fn new(quiet: Duration) -> Self {
+        Self {
+            quiet,
+            deadline: None,
+        }
+    }
+}
+
+impl BoundaryStrategy for QuietBoundary {
+    fn spec(&self) -> String {
+        format!("quiet:{}", self.quiet.as_millis())
+    }
+
+    fn on_line(&mut self, _is_prompt: bool) -> LineAction {
+        LineAction::Content
+    }
+
+    fn on_output(&mut self, now: Instant) {
+        self.deadline = Some(now + self.quiet);
+    }
+
+    fn on_input(&mut self) {
+        // Quiet time is measured from agent output, not from the
+        // submission itself: an agent that has not started responding
+        // has not finished.
+        self.deadline = None;
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    fn on_timer(&mut self, _partial: PartialLine) -> bool {
+        self.deadline = None;
+        true
+    }
+}
+
+/// A prompt match followed by `quiet` without further output ends the
+/// turn. Output after the prompt disarms it — the match was a
+/// prompt-like line inside the response.
+///
+/// An unterminated line matching the prompt also counts once quiet,
+/// which covers prompts drawn without a trailing newline.
+#[derive(Debug)]
+pub struct PromptQuietBoundary {
+    quiet: Duration,
+    armed: bool,
+    deadline: Option<Instant>,
+}
+
+impl PromptQuietBoundary {
+    pub fn new(quiet: Duration) -> Self {
+        Self {
+            quiet,
+            armed: false,
+            deadline: None,
+        }
+    }
+}
+
+impl BoundaryStrategy for PromptQuietBoundary {
+    fn spec(&self) -> String {
+        format!("prompt+quiet:{}", self.quiet.as_millis())
+    }
+
+    fn on_line(&mut self, is_prompt: bool) -> LineAction {
+        self.armed = is_prompt;
+        if is_prompt {
+            LineAction::Tentative
+        } else {
+            LineAction::Content
+        }
+    }
+
+    fn on_output(&mut self, now: Instant) {
+        self.deadline = Some(now + self.quiet);
+    }
+
+    fn on_input(&mut self) {
+        self.armed = false;
+        self.deadline = None;
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    fn on_timer(&mut self, partial: PartialLine) -> bool {
+        self.deadline = None;
+        let boundary = match partial {
+            PartialLine::Empty => self.armed,
+            PartialLine::Text { prompt } => prompt,
+        };
+        self.armed = false;
+        boundary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_specs() {
+        assert_eq!(parse("prompt").unwrap().spec(), "prompt");
+        assert_eq!(parse("quiet:1500").unwrap().spec(), "quiet:1500");
+        assert_eq!(
+            parse("prompt+quiet:300").unwrap().spec(),
+            "prompt+quiet:300"
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_specs() {
+        for spec in [
+            "",
+            "regex",
+            "quiet",
+            "quiet:",
+            "quiet:abc",
+            "quiet:0",
+            "prompt+quiet:-1",
+        ] {
+            assert!(
+                matches!(parse(spec), Err(TurnError::InvalidBoundary(_))),
+                "{spec:?} should be rejected"
+            );
+        }
+        let too_long = format!("quiet:{}", MAX_QUIET_MS + 1);
+        assert!(parse(&too_long).is_err());
+    }
+
+    #[test]
+    fn prompt_boundary_has_no_timer() {
+        let now = Instant::now();
+        let mut b = PromptBoundary;
+        b.on_output(now);
+        assert_eq!(b.deadline(), None);
+        assert_eq!(b.on_line(true), LineAction::Boundary);
+        assert_eq!(b.on_line(false), LineAction::Content);
+    }
+
+    #[test]
+    fn quiet_deadline_tracks_last_output() {
+        let t0 = Instant::now();
+        let mut b = QuietBoundary::new(Duration::from_millis(100));
+        assert_eq!(b.deadline(), None);
+        b.on_output(t0);
+        b.on_output(t0 + Duration::from_millis(50));
+        assert_eq!(b.deadline(), Some(t0 + Duration::from_millis(150)));
+        assert!(b.on_timer(PartialLine::Empty));
+        assert_eq!(b.deadline(), None);
+    }
+
+    #[test]
+    fn quiet_ignores_prompt_lines() {
+        let mut b = QuietBoundary::new(Duration::from_millis(100));
+        assert_eq!(b.on_line(true), LineAction::Content);
+    }
+
+    #[test]
+    fn prompt_quiet_requires_prompt_then_silence() {
+        let t0 = Instant::now();
+        let mut b = PromptQuietBoundary::new(Duration::from_millis(100));
+
+        // Output without a prompt: timer fires but no boundary.
+        b.on_output(t0);
+        assert_eq!(b.on_line(false), LineAction::Content);
+        assert!(!b.on_timer(PartialLine::Empty));
+
+        // Prompt line, then silence: boundary.
+        b.on_output(t0);
+        assert_eq!(b.on_line(true), LineAction::Tentative);
+        assert!(b.on_timer(PartialLine::Empty));
+    }
+
+    #[test]
+    fn prompt_quiet_disarmed_by_further_output() {
+        let mut b = PromptQuietBoundary::new(Duration::from_millis(100));
+        b.on_line(true);
+        b.on_line(false);
+        assert!(!b.on_timer(PartialLine::Empty));
+
+        b.on_line(true);
+        assert!(!b.on_timer(PartialLine::Text { prompt: false }));
+    }
+
+    #[test]
+    fn prompt_quiet_accepts_unterminated_prompt() {
+        let mut b = PromptQuietBoundary::new(Duration::from_millis(100));
+        assert!(b.on_timer(PartialLine::Text { prompt: true }));
+    }
+}
@@ -0,0 +1,288 @@
+//! Echo tracking — reconstructs the lines the user submitted.
+//!
+//! The PTY echoes user input back through the output stream, so the
+//! first line(s) of every turn would otherwise contain the user's own
+//! text. [`EchoTracker`] consumes the bytes written from stdin to the
+//! PTY master and applies the same line editing the terminal does
+//! (backspace, kill-line, kill-word), so the detector knows exactly
+//! which text to expect back as echo. See CONTRACT_TURN.md §Exclusions.
+
+use super::render::render_line;
+
+/// Maximum bytes tracked for a single input line. Longer lines are
+/// truncated and will simply fail to match their echo (fail-safe:
+/// the echo stays in the content rather than eating real output).
+const MAX_LINE_BYTES: usize = 16 * 1024;
+
+/// Maximum number of submitted lines retained between turns.
+const MAX_SUBMITTED_LINES: usize = 256;
+
+/// Input escape-sequence parser state (arrow keys, bracketed paste
+/// markers, Alt+key). Only used to skip these sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyState {
+    Ground,
+    /// Saw ESC.
+    Escape,
+    /// Inside `ESC [ ...` — consuming until a final byte.
+    Csi,
+    /// Saw `ESC O` — the next byte is the key.
+    Ss3,
+}
+
+/// Tracks user input and produces the logical lines it submitted.
+#[derive(Debug)]
+pub struct EchoTracker {
+    /// The line currently being edited.
+    current: Vec<u8>,
+    /// Lines submitted with CR/LF since the last [`take_submitted`](Self::take_submitted).
+    submitted: Vec<Vec<u8>>,
+    /// Whether the previous byte was CR (so a following LF is not a
+    /// second submission).
+    last_cr: bool,
+    key_state: KeyState,
+}
+
+impl EchoTracker {
+    pub fn new() -> Self {
+        Self {
+            current: Vec::new(),
+            submitted: Vec::new(),
+            last_cr: false,
+            key_state: KeyState::Ground,
+        }
+    }
+
+    /// Feed bytes written from the user's terminal to the PTY master.
+    pub fn feed(&mut self, data: &[u8]) {
+        for &byte in data {
+            let was_cr = std::mem::replace(&mut self.last_cr, false);
+
+            match self.key_state {
+                KeyState::Escape => {
+                    self.key_state = match byte {
+                        b'[' => KeyState::Csi,
+                        b'O' => KeyState::Ss3,
+                        _ => KeyState::Ground,
+                    };
+                    continue;
+                }
+                KeyState::Csi => {
+                    if (0x40..=0x7E).contains(&byte) {
+                        self.key_state = KeyState::Ground;
+                    }
+                    continue;
+                }
+                KeyState::Ss3 => {
+                    self.key_state = KeyState::Ground;
+                    continue;
+                }
+                KeyState::Ground => {}
+            }
+
+            match byte {
+                0x1B => self.key_state = KeyState::Escape,
+                b'\r' => {
+                    self.commit();
+                    self.last_cr = true;
+                }
+                b'\n' => {
+                    if !was_cr {
+                        self.commit();
+                    }
+                }
+                // Backspace / DEL — erase one character.
+                0x08 | 0x7F => self.erase_char(),
+                // Ctrl+U — kill line. Ctrl+C — line discarded.
+                0x15 | 0x03 => self.current.clear(),
+                // Ctrl+W — erase word.
+                0x17 => self.erase_word(),
+                b'\t' => self.push(byte),
+                0x00..=0x1F => {}
+                _ => self.push(byte),
+            }
+        }
+    }
+
+    /// Take the lines submitted since the last call.
+    pub fn take_submitted(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.submitted)
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.current.len() < MAX_LINE_BYTES {
+            self.current.push(byte);
+        }
+    }
+
+    fn commit(&mut self) {
+        let line = std::mem::take(&mut self.current);
+        if self.submitted.len() < MAX_SUBMITTED_LINES {
+            self.submitted.push(line);
+        }
+    }
+
+    /// Remove the last UTF-8 character from the current line.
+    fn erase_char(&mut self) {
+        while let Some(byte) = self.current.pop() {
+            // Stop after removing a non-continuation byte.
+            if byte & 0xC0 != 0x80 {
+                break;
+            }
+        }
+    }
+
+    /// Remove trailing whitespace, then the preceding word.
+    fn erase_word(&mut self) {
+        while self.current.last().is_some_and(|b| b.is_ascii_whitespace()) {
+            self.current.pop();
+        }
+        while self
+            .current
+            .last()
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.current.pop();
+        }
+    }
+}
+
+/// Whether a character is layout decoration: whitespace or a
+/// box-drawing glyph used by agent TUIs to frame the input line.
+fn is_decoration(c: char) -> bool {
+    c.is_whitespace() || ('\u{2500}'..='\u{257F}').contains(&c) || c == '|'
+}
+
+/// Visible text of a raw output line with decoration trimmed from
+/// the end.
+fn visible(raw_line: &[u8]) -> String {
+    let rendered = render_line(raw_line);
+    rendered.trim_end_matches(is_decoration).to_string()
+}
+
+/// Whether a raw output line consists solely of decoration (blank
+/// lines and box-drawing rules).
+pub fn is_decoration_line(raw_line: &[u8]) -> bool {
+    render_line(raw_line).chars().all(is_decoration)
+}
+
+/// Whether a raw output line is the echo of a submitted input line.
+///
+/// The rendered line must end with the submitted text (the prompt and
+/// any box framing may precede or follow it). An empty submission
+/// matches only a line with no visible text.
+pub fn is_echo_of(raw_line: &[u8], submitted: &[u8]) -> bool {
+    let expected = String::from_utf8_lossy(submitted);
+    let expected = expected.trim_end();
+    let shown = visible(raw_line);
+    if expected.is_empty() {
+        return shown.trim().is_empty();
+    }
+    shown.ends_with(expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submitted(input: &[u8]) -> Vec<Vec<u8>> {
+        let mut t = EchoTracker::new();
+        t.feed(input);
+        t.take_submitted()
+    }
+
+    // -- Tracker --
+
+    #[test]
+    fn simple_line() {
+        assert_eq!(submitted(b"hello\r"), vec![b"hello".to_vec()]);
+    }
+
+    #[test]
+    fn crlf_is_one_submission() {
+        assert_eq!(submitted(b"a\r\nb\n"), vec![b"a".to_vec(), b"b".to_vec()]);
+    }
+
+    #[test]
+    fn partial_line_not_submitted() {
+        let mut t = EchoTracker::new();
+        t.feed(b"hel");
+        assert!(t.take_submitted().is_empty());
+        t.feed(b"lo\r");
+        assert_eq!(t.take_submitted(), vec![b"hello".to_vec()]);
+    }
+
+    #[test]
+    fn backspace_and_delete() {
+        assert_eq!(submitted(b"helk\x7flo\r"), vec![b"hello".to_vec()]);
+        assert_eq!(submitted(b"x\x08y\r"), vec![b"y".to_vec()]);
+    }
+
+    #[test]
+    fn backspace_removes_whole_utf8_char() {
+        assert_eq!(
+            submitted("caf\u{e9}\x7fe\r".as_bytes()),
+            vec![b"cafe".to_vec()]
+        );
+    }
+
+    #[test]
+    fn kill_line_and_word() {
+        assert_eq!(submitted(b"garbage\x15ok\r"), vec![b"ok".to_vec()]);
+        assert_eq!(
+            submitted(b"fix the bug \x17\x17it\r"),
+            vec![b"fix it".to_vec()]
+        );
+    }
+
+    #[test]
+    fn arrow_keys_and_paste_markers_skipped() {
+        assert_eq!(submitted(b"ab\x1b[D\x1bOAc\r"), vec![b"abc".to_vec()]);
+        assert_eq!(
+            submitted(b"\x1b[200~pasted\x1b[201~\r"),
+            vec![b"pasted".to_vec()]
+        );
+    }
+
+    #[test]
+    fn ctrl_c_discards_line() {
+        assert_eq!(submitted(b"oops\x03real\r"), vec![b"real".to_vec()]);
+    }
+
+    // -- Matching --
+
+    #[test]
+    fn echo_with_prompt_prefix() {
+        assert!(is_echo_of(b"> hello\r\n", b"hello"));
+        assert!(!is_echo_of(b"Hello! How can I help?\r\n", b"hello"));
+    }
+
+    #[test]
+    fn echo_with_canonical_backspace() {
+        assert!(is_echo_of(b"> helk\x08 \x08lo\r\n", b"hello"));
+    }
+
+    #[test]
+    fn echo_rerendered_by_agent() {
+        let raw = b"\r\x1b[2K\x1b[1m> \x1b[0mh\r\x1b[2K> he\r\x1b[2K> hello\r\n";
+        assert!(is_echo_of(raw, b"hello"));
+    }
+
+    #[test]
+    fn echo_inside_box() {
+        assert!(is_echo_of("│ > hello      │\r\n".as_bytes(), b"hello"));
+    }
+
+    #[test]
+    fn empty_submission_matches_blank_line() {
+        assert!(is_echo_of(b"\r\n", b""));
+        assert!(!is_echo_of(b"output\r\n", b""));
+    }
+
+    #[test]
+    fn decoration_lines() {
+        assert!(is_decoration_line("╭────────╮\r\n".as_bytes()));
+        assert!(is_decoration_line(b"   \r\n"));
+        assert!(!is_decoration_line(b"text\r\n"));
+    }
+}
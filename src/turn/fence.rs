@@ -0,0 +1,168 @@
+//! Markdown fenced code block extraction.
+//!
+//! Scans the plain-text rendition of a turn for CommonMark fenced code
+//! blocks (```` ``` ```` or `~~~`) so that each block can be addressed
+//! on its own. See CONTRACT_TURN.md §Code blocks.
+
+/// A fenced code block found in a turn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// First word of the info string (e.g. `rust`), if any.
+    pub language: Option<String>,
+    /// Block body, one `\n`-terminated line per source line, with the
+    /// fence's indentation removed.
+    pub content: Vec<u8>,
+}
+
+/// An opening fence: its character, run length and indentation.
+struct Fence {
+    ch: u8,
+    len: usize,
+    indent: usize,
+}
+
+/// Extract all fenced code blocks from `text`, in order.
+///
+/// A block left open at the end of the text runs to the end, as in
+/// CommonMark. Indented code blocks are not recognised — agents almost
+/// always fence their code.
+pub fn code_blocks(text: &[u8]) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(Fence, CodeBlock)> = None;
+
+    for line in text.split(|&b| b == b'\n') {
+        match open.take() {
+            None => {
+                if let Some((fence, info)) = opening_fence(line) {
+                    let language = info
+                        .split(|b: &u8| b.is_ascii_whitespace())
+                        .find(|word| !word.is_empty())
+                        .map(|word| String::from_utf8_lossy(word).into_owned());
+                    let block = CodeBlock {
+                        language,
+                        content: Vec::new(),
+                    };
+                    open = Some((fence, block));
+                }
+            }
+            Some((fence, mut block)) => {
+                if closes(line, &fence) {
+                    blocks.push(block);
+                } else {
+                    let strip = line
+                        .iter()
+                        .take(fence.indent)
+                        .take_while(|&&b| b == b' ')
+                        .count();
+                    block.content.extend_from_slice(&line[strip..]);
+                    block.content.push(b'\n');
+                    open = Some((fence, block));
+                }
+            }
+        }
+    }
+
+    if let Some((_, mut block)) = open {
+        // The text's final `\n` yields an empty last segment, which is
+        // not a line of the block.
+        if text.ends_with(b"\n") && block.content.ends_with(b"\n") {
+            block.content.pop();
+        }
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// Match an opening fence: up to three spaces of indentation, then at
+/// least three backticks or tildes. Returns the fence and the info
+/// string that follows it.
+fn opening_fence(line: &[u8]) -> Option<(Fence, &[u8])> {
+    let indent = line.iter().take_while(|&&b| b == b' ').count();
+    if indent > 3 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let ch = *rest.first().filter(|&&b| b == b'`' || b == b'~')?;
+    let len = rest.iter().take_while(|&&b| b == ch).count();
+    if len < 3 {
+        return None;
+    }
+    let info = &rest[len..];
+    // A backtick fence's info string may not contain backticks.
+    if ch == b'`' && info.contains(&b'`') {
+        return None;
+    }
+    Some((Fence { ch, len, indent }, info))
+}
+
+/// Whether `line` closes `fence`: same character, at least as long,
+/// nothing but whitespace after it.
+fn closes(line: &[u8], fence: &Fence) -> bool {
+    let indent = line.iter().take_while(|&&b| b == b' ').count();
+    if indent > 3 {
+        return false;
+    }
+    let rest = &line[indent..];
+    let len = rest.iter().take_while(|&&b| b == fence.ch).count();
+    len >= fence.len && rest[len..].iter().all(u8::is_ascii_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_block_with_language() {
+        let text = b"Here:\n```rust\nfn main() {}\n```\nDone.\n";
+        assert_eq!(
+            code_blocks(text),
+            vec![CodeBlock {
+                language: Some("rust".into()),
+                content: b"fn main() {}\n".to_vec(),
+            }]
+        );
+    }
+
+    #[test]
+    fn multiple_blocks_in_order() {
+        let text = b"```\nplain\n```\ntext\n~~~ python extra\nprint(1)\n\nprint(2)\n~~~\n";
+        let blocks = code_blocks(text);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].language, None);
+        assert_eq!(blocks[0].content, b"plain\n");
+        assert_eq!(blocks[1].language.as_deref(), Some("python"));
+        assert_eq!(blocks[1].content, b"print(1)\n\nprint(2)\n");
+    }
+
+    #[test]
+    fn closing_fence_must_match() {
+        // A tilde line and a shorter backtick run do not close a
+        // four-backtick fence.
+        let text = b"````md\n~~~\n```\n````\n";
+        let blocks = code_blocks(text);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].content, b"~~~\n```\n");
+    }
+
+    #[test]
+    fn indented_fence_strips_indentation() {
+        let text = b"  ```sh\n  ls -la\n    cd /tmp\n  ```\n";
+        let blocks = code_blocks(text);
+        assert_eq!(blocks[0].content, b"ls -la\n  cd /tmp\n");
+    }
+
+    #[test]
+    fn unclosed_block_runs_to_end() {
+        let blocks = code_blocks(b"```js\nlet a = 1;\n");
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].content, b"let a = 1;\n");
+    }
+
+    #[test]
+    fn not_a_fence() {
+        assert!(code_blocks(b"``not a fence``\n").is_empty());
+        assert!(code_blocks(b"    ```\n    indented\n    ```\n").is_empty());
+        assert!(code_blocks(b"``` a`b\n").is_empty());
+        assert!(code_blocks(b"").is_empty());
+    }
+}
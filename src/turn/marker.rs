@@ -0,0 +1,100 @@
+//! Semantic prompt markers (FinalTerm / OSC 133, VS Code OSC 633).
+//!
+//! Shells with shell integration bracket each prompt, command line
+//! and command output with OSC markers. Where present they give exact
+//! turn boundaries. See CONTRACT_TURN.md §Semantic Prompt Markers.
+
+/// A semantic prompt marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    /// `A` — the prompt is about to be drawn.
+    PromptStart,
+    /// `B` — the prompt ended; user input follows.
+    CommandStart,
+    /// `C` — the command was submitted; its output follows.
+    OutputStart,
+    /// `D[;<exit>]` — the command finished.
+    CommandFinished { exit_status: Option<i32> },
+}
+
+/// Parse an OSC payload (the bytes between `ESC ]` and the terminator)
+/// as a semantic prompt marker. Returns `None` for any other OSC,
+/// including OSC 633 extensions (`E`, `P`) that carry no boundary.
+pub fn parse(payload: &[u8]) -> Option<Marker> {
+    let rest = payload
+        .strip_prefix(b"133;")
+        .or_else(|| payload.strip_prefix(b"633;"))?;
+    let mut fields = rest.split(|&b| b == b';');
+    let kind = fields.next()?;
+    match kind {
+        b"A" => Some(Marker::PromptStart),
+        b"B" => Some(Marker::CommandStart),
+        b"C" => Some(Marker::OutputStart),
+        b"D" => {
+            let exit_status = fields
+                .next()
+                .and_then(|code| std::str::from_utf8(code).ok())
+                .and_then(|code| code.parse().ok());
+            Some(Marker::CommandFinished { exit_status })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_osc_133_markers() {
+        assert_eq!(parse(b"133;A"), Some(Marker::PromptStart));
+        assert_eq!(parse(b"133;A;cl=m;aid=42"), Some(Marker::PromptStart));
+        assert_eq!(parse(b"133;B"), Some(Marker::CommandStart));
+        assert_eq!(parse(b"133;C"), Some(Marker::OutputStart));
+    }
+
+    #[test]
+    fn parses_exit_status() {
+        assert_eq!(
+            parse(b"133;D;0"),
+            Some(Marker::CommandFinished {
+                exit_status: Some(0)
+            })
+        );
+        assert_eq!(
+            parse(b"133;D;130;aid=42"),
+            Some(Marker::CommandFinished {
+                exit_status: Some(130)
+            })
+        );
+        assert_eq!(
+            parse(b"133;D"),
+            Some(Marker::CommandFinished { exit_status: None })
+        );
+        assert_eq!(
+            parse(b"133;D;err"),
+            Some(Marker::CommandFinished { exit_status: None })
+        );
+    }
+
+    #[test]
+    fn parses_osc_633_markers() {
+        assert_eq!(parse(b"633;A"), Some(Marker::PromptStart));
+        assert_eq!(
+            parse(b"633;D;1"),
+            Some(Marker::CommandFinished {
+                exit_status: Some(1)
+            })
+        );
+        assert_eq!(parse(b"633;E;ls -la"), None);
+        assert_eq!(parse(b"633;P;Cwd=/tmp"), None);
+    }
+
+    #[test]
+    fn ignores_other_osc() {
+        assert_eq!(parse(b"0;window title"), None);
+        assert_eq!(parse(b"8;;https://example.com"), None);
+        assert_eq!(parse(b"133;"), None);
+        assert_eq!(parse(b"1337;A"), None);
+    }
+}
@@ -3,26 +3,44 @@
 //! See CONTRACT_TURN.md.
 //!
 //! The [`TurnDetector`] is a state machine that consumes agent output
-//! byte-by-byte, detects prompt patterns (after ANSI stripping), and
-//! emits [`TurnEvent`]s when turn boundaries are found.
+//! a line at a time, detects prompt patterns (after ANSI stripping),
+//! and emits [`TurnEvent`]s when turn boundaries are found. While the
+//! agent is on the alternate screen, the rendered screen takes the
+//! place of lines (see [`altscreen`]).
 
+pub mod altscreen;
 pub mod ansi;
+pub mod boundary;
+pub mod echo;
+pub mod fence;
+pub mod marker;
+pub mod parsers;
 pub mod presets;
+pub mod render;
+pub mod screen;
+pub mod transcript;
 
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use altscreen::{AltScreen, Snapshot};
 use ansi::AnsiStripper;
+use boundary::{BoundaryStrategy, LineAction, PartialLine};
+use echo::EchoTracker;
+use marker::Marker;
 use regex::Regex;
+use regex_syntax::hir::{Hir, HirKind};
+use screen::ScreenSize;
 
 /// Errors that can occur when constructing a [`TurnDetector`].
 #[derive(Debug, thiserror::Error)]
 pub enum TurnError {
-    #[error(
-        "prompt pattern contains literal newline — multi-line patterns are not supported in v0"
-    )]
-    MultiLinePattern,
+    #[error("prompt pattern spans {lines} lines — at most {max} are supported")]
+    PatternTooManyLines { lines: usize, max: usize },
     #[error("invalid regex pattern: {0}")]
     InvalidPattern(#[from] regex::Error),
+    #[error("invalid boundary strategy {0:?} (expected prompt, quiet:<ms> or prompt+quiet:<ms>)")]
+    InvalidBoundary(String),
 }
 
 /// A completed turn — the agent output between user input and the
@@ -35,6 +53,16 @@ pub struct Turn {
     pub interrupted: bool,
     /// Unix epoch milliseconds when the turn was detected.
     pub timestamp: u64,
+    /// Exit status from an OSC 133 `D` marker, if one ended the turn.
+    pub exit_status: Option<i32>,
+    /// The input line(s) the user submitted to start the turn, joined
+    /// with `\n`, as typed rather than as echoed. `None` when nothing
+    /// was typed (e.g. a bare Enter, or a turn started by a marker).
+    pub prompt: Option<Vec<u8>>,
+    /// Whether the turn ended on the alternate screen and `content` is
+    /// text extracted from the rendered screen rather than raw output
+    /// (CONTRACT_TURN.md §Alternate Screen).
+    pub screen_rendered: bool,
 }
 
 /// Current time as Unix epoch milliseconds.
@@ -53,6 +81,57 @@ pub enum TurnEvent {
     SessionReady,
     /// A complete turn was detected.
     TurnCompleted(Turn),
+    /// The turn in progress has grown. Carries the content accumulated
+    /// so far (CONTRACT_TURN.md §Turn Progress). Emitted by
+    /// [`TurnDetector::poll_timer`], at most once per
+    /// [`PROGRESS_INTERVAL`]. Empty when a turn that reported progress
+    /// ends without content.
+    TurnProgress(Vec<u8>),
+}
+
+/// How the detector handled a completed output line, or a prompt seen
+/// on the alternate screen. Recorded only when tracing is enabled (see
+/// [`TurnDetector::enable_trace`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineTrace {
+    /// ANSI-stripped text, without the line terminator. On the
+    /// alternate screen, the rendered rows matched against the prompt.
+    pub text: String,
+    /// Whether the line (with the window before it) matched the prompt
+    /// pattern.
+    pub matched: bool,
+    pub decision: LineDecision,
+}
+
+/// What the detector did with a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineDecision {
+    /// Echo of submitted input, or decoration around it — dropped.
+    Echo,
+    /// Ended the turn, or showed a prompt outside one.
+    Boundary,
+    /// Held as a possible prompt until a timer decides.
+    Tentative,
+    /// Appended to the turn.
+    Content,
+    /// Outside any turn and not a boundary — ignored.
+    Idle,
+}
+
+/// Minimum time between [`TurnEvent::TurnProgress`] events for a turn.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Maximum number of lines a prompt pattern may span.
+pub const MAX_PATTERN_LINES: usize = 8;
+
+/// A recently completed line, retained for multi-line prompt matching.
+#[derive(Debug)]
+struct RecentLine {
+    /// ANSI-stripped text, without the line terminator.
+    text: String,
+    /// Offset in `content_buf` where this line's raw bytes start, if
+    /// the line was appended to turn content.
+    content_start: Option<usize>,
 }
 
 /// Internal state of the turn detector state machine.
@@ -69,20 +148,46 @@ enum DetectorState {
 
 /// Prompt-pattern turn detector.
 ///
-/// Feed agent output via [`feed_output`] and user input notifications
-/// via [`notify_user_input`]. The detector emits [`TurnEvent`]s when
-/// turn boundaries are found.
+/// Feed agent output via [`feed_output`], user input bytes via
+/// [`record_input`], and user input notifications via
+/// [`notify_user_input`]. The detector emits [`TurnEvent`]s when turn
+/// boundaries are found. Timer-based boundary strategies additionally
+/// need [`poll_timer`](Self::poll_timer) called once
+/// [`deadline`](Self::deadline) has passed.
 ///
 /// # Contract compliance
 ///
-/// - Prompt matching is per-line, after ANSI stripping.
+/// - Prompt matching is per-line (or over a window of the last N lines
+///   for an N-line pattern), after ANSI stripping.
 /// - First prompt → `SessionReady` (no turn).
 /// - Consecutive prompts without output → no empty turns.
 /// - Interrupted turns are marked.
 /// - ANSI sequences are preserved in turn content.
+/// - Echoed user input is excluded from turn content.
+/// - OSC 133 / 633 semantic prompt markers, once seen, replace prompt
+///   matching and the boundary strategy.
 #[derive(Debug)]
 pub struct TurnDetector {
     pattern: Regex,
+    /// Decides which lines and silences end a turn.
+    boundary: Box<dyn BoundaryStrategy>,
+    /// Offset in `content_buf` where a tentative prompt block starts
+    /// (see [`LineAction::Tentative`]).
+    tentative_start: Option<usize>,
+    /// Whether semantic prompt markers have been seen. Once set, they
+    /// alone delimit turns (CONTRACT_TURN.md §Semantic Prompt Markers).
+    markers: bool,
+    /// Exit status reported for the current turn by a `D` marker.
+    exit_status: Option<i32>,
+    /// When the next progress event is due, if the turn has grown
+    /// since the last one.
+    progress_due: Option<Instant>,
+    /// Provisional content length reported by the last progress event.
+    progress_len: usize,
+    /// Number of lines the prompt pattern spans (window size).
+    pattern_lines: usize,
+    /// The previous `pattern_lines - 1` lines, oldest first.
+    recent_lines: VecDeque<RecentLine>,
     state: DetectorState,
     stripper: AnsiStripper,
 
@@ -99,6 +204,37 @@ pub struct TurnDetector {
 
     /// Whether the current turn was interrupted.
     interrupted: bool,
+
+    /// Reconstructs the lines the user submitted, for echo removal.
+    echo_tracker: EchoTracker,
+
+    /// Submitted lines whose echo has not yet been seen in the output.
+    pending_echo: VecDeque<Vec<u8>>,
+
+    /// Whether the current turn is still in its leading echo region.
+    /// Ends at the first output line that is neither echo nor
+    /// decoration.
+    echo_active: bool,
+
+    /// Length of `content_buf` at the end of the last matched echo
+    /// line. Content after this offset is tentative while
+    /// `echo_active` (decoration that may precede further echo).
+    echo_mark: usize,
+
+    /// Lines submitted to start the current turn.
+    prompt: Vec<Vec<u8>>,
+
+    /// Terminal size, for rendering the alternate screen.
+    screen_size: ScreenSize,
+
+    /// The alternate screen, while the agent has switched to it.
+    alt: Option<AltScreen>,
+
+    /// The turn being ended was extracted from the alternate screen.
+    screen_rendered: bool,
+
+    /// Per-line decisions, when tracing is enabled.
+    trace: Option<Vec<LineTrace>>,
 }
 
 impl TurnDetector {
@@ -107,68 +243,197 @@ impl TurnDetector {
     /// If `pattern` matches a known preset name, the preset regex is
     /// used. Otherwise, `pattern` is compiled as a custom regex.
     ///
-    /// Returns an error if the pattern contains literal newlines or
-    /// is not a valid regex.
+    /// A pattern that must match newlines spans that many lines plus
+    /// one, and is matched against the last N lines joined with `\n`.
+    /// Optional newlines (e.g. in `(?:^|\n)`) do not count.
+    ///
+    /// Returns an error if the pattern spans more than
+    /// [`MAX_PATTERN_LINES`] lines or is not a valid regex.
+    ///
+    /// Uses the `prompt` boundary strategy; see
+    /// [`with_boundary`](Self::with_boundary).
+    #[cfg(test)]
     pub fn new(pattern: &str) -> Result<Self, TurnError> {
+        Self::with_boundary(pattern, "prompt")
+    }
+
+    /// Create a turn detector with an explicit boundary strategy spec
+    /// (see [`boundary::parse`] and CONTRACT_TURN.md §Boundary
+    /// strategies).
+    pub fn with_boundary(pattern: &str, boundary: &str) -> Result<Self, TurnError> {
+        let boundary = boundary::parse(boundary)?;
+
         // Resolve preset or use as custom regex.
         let pattern_str = presets::preset_pattern(pattern).unwrap_or(pattern);
 
-        // Reject multi-line patterns (CONTRACT_TURN.md §Matching rules).
-        if pattern_str.contains('\n') {
-            return Err(TurnError::MultiLinePattern);
-        }
-
         let regex = Regex::new(pattern_str)?;
 
+        // Bound the match window (CONTRACT_TURN.md §Matching rules).
+        let lines = pattern_line_count(pattern_str);
+        if lines > MAX_PATTERN_LINES {
+            return Err(TurnError::PatternTooManyLines {
+                lines,
+                max: MAX_PATTERN_LINES,
+            });
+        }
+
         Ok(Self {
             pattern: regex,
+            boundary,
+            tentative_start: None,
+            markers: false,
+            exit_status: None,
+            progress_due: None,
+            progress_len: 0,
+            pattern_lines: lines,
+            recent_lines: VecDeque::with_capacity(lines - 1),
             state: DetectorState::AwaitingFirstPrompt,
             stripper: AnsiStripper::new(),
             line_buf: Vec::new(),
             content_buf: Vec::new(),
             raw_line_buf: Vec::new(),
             interrupted: false,
+            echo_tracker: EchoTracker::new(),
+            pending_echo: VecDeque::new(),
+            echo_active: false,
+            echo_mark: 0,
+            prompt: Vec::new(),
+            screen_size: ScreenSize::default(),
+            alt: None,
+            screen_rendered: false,
+            trace: None,
         })
     }
 
+    /// Set the terminal size the agent draws on (default 80x24). Used
+    /// to render the alternate screen; call again on resize.
+    pub fn set_screen_size(&mut self, size: ScreenSize) {
+        self.screen_size = size;
+        if let Some(alt) = &mut self.alt {
+            alt.resize(size);
+        }
+    }
+
     /// Feed agent output bytes to the detector.
     ///
     /// Returns any events produced by processing this chunk. Output
-    /// is processed byte-by-byte: lines are assembled and checked
+    /// is processed a line at a time: lines are assembled and checked
     /// against the prompt pattern after ANSI stripping.
     ///
-    /// **Echo-stripping**: The caller (PTY wrapper) is responsible for
-    /// excluding echoed user input before feeding data here.
-    /// CONTRACT_TURN.md §126–136 requires turn content to exclude
-    /// echoed input, but the mechanism is an upstream concern.
+    /// **Echo-stripping**: Leading output lines that render to the
+    /// lines the user submitted (see [`record_input`](Self::record_input))
+    /// are dropped from turn content, along with decoration lines
+    /// around them (CONTRACT_TURN.md §Exclusions).
     pub fn feed_output(&mut self, data: &[u8]) -> Vec<TurnEvent> {
+        self.feed_output_at(data, Instant::now())
+    }
+
+    /// [`feed_output`](Self::feed_output) with an explicit arrival time,
+    /// for timer-based boundary strategies.
+    ///
+    /// The chunk is split at newlines and each piece is stripped in
+    /// bulk into the reusable line buffers. Events are identical to
+    /// feeding the same bytes one at a time.
+    pub fn feed_output_at(&mut self, data: &[u8], now: Instant) -> Vec<TurnEvent> {
         let mut events = Vec::new();
+        if !data.is_empty() {
+            self.boundary.on_output(now);
+        }
 
-        for &byte in data {
-            self.raw_line_buf.push(byte);
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (piece, line_complete) = match memchr::memchr(b'\n', rest) {
+                Some(i) => (&rest[..=i], true),
+                None => (rest, false),
+            };
+            rest = &rest[piece.len()..];
+
+            // The stripper stops after each OSC sequence and screen
+            // switch so that each is handled at its exact position.
+            let mut pos = 0;
+            while pos < piece.len() {
+                let consumed = self.stripper.strip_into(&piece[pos..], &mut self.line_buf);
+                let bytes = &piece[pos..pos + consumed];
+                self.raw_line_buf.extend_from_slice(bytes);
+                if let Some(alt) = &mut self.alt {
+                    alt.feed(bytes);
+                }
+                pos += consumed;
 
-            // Strip ANSI for prompt detection.
-            let stripped = self.stripper.strip(&[byte]);
-            self.line_buf.extend_from_slice(&stripped);
+                if let Some(osc) = self.stripper.take_osc()
+                    && let Some(marker) = marker::parse(&osc.payload)
+                {
+                    self.handle_marker(marker, osc.len, &mut events);
+                }
+                if let Some(entered) = self.stripper.take_alt_screen() {
+                    self.switch_screen(entered);
+                }
+            }
 
-            if byte == b'\n' {
-                // Line complete — check for prompt match.
-                self.process_line(&mut events);
+            if line_complete {
+                if self.alt.is_some() {
+                    self.alt_line();
+                } else {
+                    // Line complete — check for prompt match.
+                    self.process_line(&mut events);
+                }
             }
         }
 
+        if self.alt.is_some() {
+            self.check_screen(&mut events);
+        }
+        self.schedule_progress(now);
         events
     }
 
+    /// Record bytes written from the user's terminal to the PTY.
+    ///
+    /// Must be called with every stdin chunk, before
+    /// [`notify_user_input`](Self::notify_user_input), so the detector
+    /// knows which text to expect back as echo.
+    pub fn record_input(&mut self, data: &[u8]) {
+        self.echo_tracker.feed(data);
+    }
+
     /// Notify the detector that the user has submitted input.
     ///
     /// Transitions from `AwaitingUserInput` to `AccumulatingOutput`.
+    /// Lines submitted while the turn is still in its echo region
+    /// (e.g., a paste split across reads) extend the expected echo.
     /// No-op in other states.
     pub fn notify_user_input(&mut self) {
-        if self.state == DetectorState::AwaitingUserInput {
-            self.state = DetectorState::AccumulatingOutput;
-            self.content_buf.clear();
-            self.interrupted = false;
+        let submitted = self.echo_tracker.take_submitted();
+        match self.state {
+            DetectorState::AwaitingUserInput => {
+                self.boundary.on_input();
+                self.state = DetectorState::AccumulatingOutput;
+                self.content_buf.clear();
+                self.tentative_start = None;
+                self.interrupted = false;
+                self.echo_active = !submitted.is_empty();
+                self.prompt = submitted.clone();
+                self.pending_echo = submitted.into();
+                self.echo_mark = 0;
+                self.progress_due = None;
+                self.progress_len = 0;
+            }
+            DetectorState::AccumulatingOutput if self.echo_active => {
+                self.prompt.extend(submitted.iter().cloned());
+                self.pending_echo.extend(submitted);
+            }
+            _ => {}
+        }
+
+        // On the alternate screen the echo is drawn, not streamed;
+        // the screen as it is now stands in for it.
+        if self.state == DetectorState::AccumulatingOutput
+            && let Some(snapshot) = self.screen_snapshot()
+        {
+            self.end_echo_region();
+            if let Some(alt) = &mut self.alt {
+                alt.mark_input(snapshot);
+            }
         }
     }
 
@@ -199,55 +464,488 @@ impl TurnDetector {
             }
             _ => line.as_slice(),
         };
-        let line_str = String::from_utf8_lossy(trimmed);
-        let is_prompt = self.pattern.is_match(&line_str);
+        let line_str = String::from_utf8_lossy(trimmed).into_owned();
+        let is_prompt = !self.markers && self.matches_prompt(&line_str);
+        let content_len = self.content_buf.len();
+        let accumulating = self.state == DetectorState::AccumulatingOutput;
+
+        let decision = if self.echo_active && self.strip_echo_line() {
+            // Echo (or decoration around it) — not turn content.
+            LineDecision::Echo
+        } else {
+            let action = if self.markers {
+                LineAction::Content
+            } else {
+                self.boundary.on_line(is_prompt)
+            };
+            match action {
+                LineAction::Boundary => {
+                    // Content excludes the prompt block (the matched line
+                    // and any earlier lines of the window).
+                    let block_start = self.prompt_block_start(content_len);
+                    self.end_turn(events, block_start);
+                    LineDecision::Boundary
+                }
+                LineAction::Tentative if accumulating => {
+                    // Keep the line until a timer confirms the boundary;
+                    // remember where the prompt block starts.
+                    self.tentative_start = Some(self.prompt_block_start(content_len));
+                    self.content_buf.extend_from_slice(&self.raw_line_buf);
+                    LineDecision::Tentative
+                }
+                LineAction::Content if accumulating => {
+                    // Append to turn content (raw bytes, ANSI preserved).
+                    self.tentative_start = None;
+                    self.content_buf.extend_from_slice(&self.raw_line_buf);
+                    LineDecision::Content
+                }
+                LineAction::Tentative => LineDecision::Idle,
+                LineAction::Content => {
+                    self.tentative_start = None;
+                    LineDecision::Idle
+                }
+            }
+        };
 
+        if let Some(trace) = &mut self.trace {
+            trace.push(LineTrace {
+                text: line_str.clone(),
+                matched: is_prompt,
+                decision,
+            });
+        }
+
+        // Slide the window. A matched prompt block is never reused as
+        // the start of another match.
         if is_prompt {
-            match self.state {
-                DetectorState::AwaitingFirstPrompt => {
-                    events.push(TurnEvent::SessionReady);
-                    self.state = DetectorState::AwaitingUserInput;
+            self.recent_lines.clear();
+        } else if self.pattern_lines > 1 {
+            if self.recent_lines.len() == self.pattern_lines - 1 {
+                self.recent_lines.pop_front();
+            }
+            let content_start = (self.content_buf.len() > content_len).then_some(content_len);
+            self.recent_lines.push_back(RecentLine {
+                text: line_str,
+                content_start,
+            });
+        }
+
+        self.line_buf.clear();
+        self.raw_line_buf.clear();
+    }
+
+    /// The agent switched to (`entered`) or back from the alternate
+    /// screen. Line matching stops or resumes; the line window and a
+    /// tentative block do not carry across.
+    fn switch_screen(&mut self, entered: bool) {
+        if entered == self.alt.is_some() {
+            return;
+        }
+        if entered {
+            self.alt = Some(AltScreen::new(self.screen_size));
+            self.end_echo_region();
+        } else {
+            self.alt = None;
+            // Text drawn on the alternate screen is not a line.
+            self.line_buf.clear();
+            self.raw_line_buf.clear();
+        }
+        self.recent_lines.clear();
+        self.tentative_start = None;
+    }
+
+    /// A line completed on the alternate screen. It is not matched —
+    /// its raw bytes only join the turn content, which stays
+    /// authoritative unless the turn ends on the alternate screen.
+    fn alt_line(&mut self) {
+        if self.state == DetectorState::AccumulatingOutput {
+            self.content_buf.extend_from_slice(&self.raw_line_buf);
+        }
+        self.line_buf.clear();
+        self.raw_line_buf.clear();
+    }
+
+    /// Render the alternate screen, if active and not superseded by
+    /// markers. While a turn accumulates, a prompt on an unchanged
+    /// screen does not count: the agent has not drawn anything yet.
+    fn screen_snapshot(&self) -> Option<Snapshot> {
+        if self.markers {
+            return None;
+        }
+        let alt = self.alt.as_ref()?;
+        let mut snapshot = alt.snapshot(&self.pattern, self.pattern_lines);
+        if self.state == DetectorState::AccumulatingOutput && !alt.changed(&snapshot) {
+            snapshot.prompt = false;
+        }
+        Some(snapshot)
+    }
+
+    /// Decide a boundary from the rendered alternate screen, after a
+    /// chunk of output.
+    fn check_screen(&mut self, events: &mut Vec<TurnEvent>) {
+        let Some(snapshot) = self.screen_snapshot() else {
+            return;
+        };
+        let accumulating = self.state == DetectorState::AccumulatingOutput;
+        let decision = match self.boundary.on_line(snapshot.prompt) {
+            LineAction::Boundary => {
+                self.end_screen_turn(events, &snapshot);
+                LineDecision::Boundary
+            }
+            LineAction::Tentative if accumulating => LineDecision::Tentative,
+            LineAction::Content if accumulating => LineDecision::Content,
+            _ => LineDecision::Idle,
+        };
+        if snapshot.prompt
+            && let Some(trace) = &mut self.trace
+        {
+            trace.push(LineTrace {
+                text: snapshot.window,
+                matched: true,
+                decision,
+            });
+        }
+    }
+
+    /// End the turn with content extracted from the alternate screen.
+    fn end_screen_turn(&mut self, events: &mut Vec<TurnEvent>, snapshot: &Snapshot) {
+        if self.state == DetectorState::AccumulatingOutput
+            && let Some(alt) = &self.alt
+        {
+            self.content_buf = alt.content(snapshot, &self.prompt);
+            self.screen_rendered = true;
+        }
+        let content_end = self.content_buf.len();
+        self.end_turn(events, content_end);
+    }
+
+    /// Whether `line` (with the retained window before it) matches the
+    /// prompt pattern.
+    fn matches_prompt(&self, line: &str) -> bool {
+        if self.recent_lines.is_empty() {
+            return self.pattern.is_match(line);
+        }
+        let mut window = String::new();
+        for recent in &self.recent_lines {
+            window.push_str(&recent.text);
+            window.push('\n');
+        }
+        window.push_str(line);
+        self.pattern.is_match(&window)
+    }
+
+    /// Offset in `content_buf` where the prompt block ending at the
+    /// current line starts. `content_len` is the length before the
+    /// current line.
+    fn prompt_block_start(&self, content_len: usize) -> usize {
+        self.recent_lines
+            .iter()
+            .find_map(|recent| recent.content_start)
+            .unwrap_or(content_len)
+    }
+
+    /// Handle a turn boundary. In `AccumulatingOutput`, emits the
+    /// content up to `content_end` as a turn.
+    fn end_turn(&mut self, events: &mut Vec<TurnEvent>, content_end: usize) {
+        match self.state {
+            DetectorState::AwaitingFirstPrompt => {
+                events.push(TurnEvent::SessionReady);
+                self.state = DetectorState::AwaitingUserInput;
+            }
+            DetectorState::AwaitingUserInput => {
+                // Consecutive prompt without intervening output.
+                // No empty turn produced (CONTRACT_TURN.md §Matching rules).
+            }
+            DetectorState::AccumulatingOutput => {
+                self.content_buf.truncate(content_end);
+                let content = std::mem::take(&mut self.content_buf);
+                let prompt = std::mem::take(&mut self.prompt);
+
+                if content.is_empty() {
+                    // Withdraw the progress reported for it.
+                    if self.progress_len != 0 {
+                        events.push(TurnEvent::TurnProgress(Vec::new()));
+                    }
+                } else {
+                    events.push(TurnEvent::TurnCompleted(Turn {
+                        content,
+                        interrupted: self.interrupted,
+                        timestamp: epoch_millis(),
+                        exit_status: self.exit_status,
+                        prompt: join_prompt(prompt),
+                        screen_rendered: self.screen_rendered,
+                    }));
+                }
+                // Even if content was empty (e.g., only whitespace
+                // was accumulated), transition to awaiting input.
+                self.interrupted = false;
+                self.end_echo_region();
+                self.state = DetectorState::AwaitingUserInput;
+            }
+        }
+        self.tentative_start = None;
+        self.exit_status = None;
+        self.screen_rendered = false;
+        self.progress_due = None;
+        self.progress_len = 0;
+    }
+
+    /// Handle a semantic prompt marker whose sequence (`len` bytes)
+    /// ends `raw_line_buf`.
+    ///
+    /// `A` and `D` end the turn at the start of the sequence; `C`
+    /// starts one after it, discarding everything before (the echoed
+    /// command line). The marker bytes themselves are never content.
+    fn handle_marker(&mut self, marker: Marker, len: usize, events: &mut Vec<TurnEvent>) {
+        self.markers = true;
+        let seq_start = self.raw_line_buf.len().saturating_sub(len);
+
+        match marker {
+            Marker::PromptStart | Marker::CommandFinished { .. } => {
+                if let Marker::CommandFinished { exit_status } = marker {
+                    self.exit_status = exit_status;
                 }
-                DetectorState::AwaitingUserInput => {
-                    // Consecutive prompt without intervening output.
-                    // No empty turn produced (CONTRACT_TURN.md §Matching rules).
+                if self.state == DetectorState::AccumulatingOutput {
+                    self.content_buf
+                        .extend_from_slice(&self.raw_line_buf[..seq_start]);
                 }
-                DetectorState::AccumulatingOutput => {
-                    // Turn boundary — emit completed turn.
-                    // Content is everything accumulated so far, excluding
-                    // the prompt line itself.
-                    let content = std::mem::take(&mut self.content_buf);
-
-                    if !content.is_empty() {
-                        events.push(TurnEvent::TurnCompleted(Turn {
-                            content,
-                            interrupted: self.interrupted,
-                            timestamp: epoch_millis(),
-                        }));
-                    }
-                    // Even if content was empty (e.g., only whitespace
-                    // was accumulated), transition to awaiting input.
+                let content_end = self.content_buf.len();
+                self.end_turn(events, content_end);
+            }
+            Marker::CommandStart => {}
+            Marker::OutputStart => {
+                if self.state != DetectorState::AccumulatingOutput {
+                    // Output without a detected Enter (e.g. a scripted
+                    // command) still starts a turn.
+                    self.state = DetectorState::AccumulatingOutput;
                     self.interrupted = false;
-                    self.state = DetectorState::AwaitingUserInput;
+                    self.prompt.clear();
                 }
+                self.content_buf.clear();
+                self.exit_status = None;
+                self.end_echo_region();
             }
-        } else if self.state == DetectorState::AccumulatingOutput {
-            // Non-prompt line during output accumulation — append to
-            // turn content (raw bytes, ANSI preserved).
-            self.content_buf.extend_from_slice(&self.raw_line_buf);
         }
 
-        self.line_buf.clear();
+        // Bytes before the marker have been consumed (or discarded).
         self.raw_line_buf.clear();
+        self.line_buf.clear();
+    }
+
+    /// When [`poll_timer`](Self::poll_timer) should next be called, if
+    /// the boundary strategy is waiting on a timer or a progress event
+    /// is due.
+    pub fn deadline(&self) -> Option<Instant> {
+        match (self.boundary_deadline(), self.progress_due) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// The boundary strategy's timer, unless markers have replaced it.
+    fn boundary_deadline(&self) -> Option<Instant> {
+        if self.markers {
+            return None;
+        }
+        self.boundary.deadline()
+    }
+
+    /// The boundary strategy spec in use (e.g. `prompt+quiet:500`).
+    pub fn boundary_spec(&self) -> String {
+        self.boundary.spec()
+    }
+
+    /// Fire whichever timers have passed: the boundary strategy's, then
+    /// the progress throttle's.
+    ///
+    /// On a timer boundary the current unterminated line is consumed:
+    /// dropped if it matches the prompt pattern, otherwise appended to
+    /// the turn. A tentative prompt block is excluded from the turn
+    /// (CONTRACT_TURN.md §Boundary Strategies). A turn that ends here
+    /// gets no further progress event.
+    pub fn poll_timer(&mut self, now: Instant) -> Vec<TurnEvent> {
+        let mut events = Vec::new();
+        if self.boundary_deadline().is_some_and(|d| d <= now) {
+            self.fire_boundary_timer(&mut events);
+        }
+        if self.progress_due.is_some_and(|d| d <= now) {
+            self.progress_due = None;
+            let content = self.provisional_content();
+            // On the alternate screen, progress is scheduled on any
+            // output; only a change is reported.
+            let len = content.len();
+            if len != 0 && len != self.progress_len {
+                events.push(TurnEvent::TurnProgress(content));
+            }
+            self.progress_len = len;
+        }
+        events
+    }
+
+    /// The boundary strategy's timer has passed.
+    fn fire_boundary_timer(&mut self, events: &mut Vec<TurnEvent>) {
+        if let Some(snapshot) = self.screen_snapshot() {
+            let partial = if snapshot.body.is_empty() && !snapshot.prompt {
+                PartialLine::Empty
+            } else {
+                PartialLine::Text {
+                    prompt: snapshot.prompt,
+                }
+            };
+            if !self.boundary.on_timer(partial) {
+                return;
+            }
+            // As on the main screen, silence before the agent has drawn
+            // anything is not the end of a turn.
+            if self.state == DetectorState::AccumulatingOutput
+                && self.alt.as_ref().is_some_and(|alt| !alt.changed(&snapshot))
+            {
+                return;
+            }
+            self.end_screen_turn(events, &snapshot);
+            return;
+        }
+
+        let partial = if self.line_buf.iter().all(u8::is_ascii_whitespace) {
+            PartialLine::Empty
+        } else {
+            let line = String::from_utf8_lossy(&self.line_buf).into_owned();
+            PartialLine::Text {
+                prompt: self.matches_prompt(line.trim_end_matches(['\r', '\n'])),
+            }
+        };
+        if !self.boundary.on_timer(partial) {
+            return;
+        }
+
+        // Silence with nothing accumulated is not the end of a turn —
+        // the agent has not started responding.
+        if self.state == DetectorState::AccumulatingOutput
+            && self.content_buf.is_empty()
+            && partial == PartialLine::Empty
+        {
+            return;
+        }
+
+        // The user may be typing at a shown prompt; leave that line alone.
+        if partial != PartialLine::Empty && self.state != DetectorState::AwaitingUserInput {
+            if partial == (PartialLine::Text { prompt: false })
+                && self.state == DetectorState::AccumulatingOutput
+            {
+                self.content_buf.extend_from_slice(&self.raw_line_buf);
+            }
+            self.line_buf.clear();
+            self.raw_line_buf.clear();
+            self.recent_lines.clear();
+        }
+
+        let content_end = self.tentative_start.unwrap_or(self.content_buf.len());
+        self.end_turn(events, content_end);
+    }
+
+    /// Start the progress throttle if the turn in progress has grown
+    /// since the last progress event and none is pending.
+    ///
+    /// On the alternate screen the content can only be known by
+    /// extracting it from the screen, which is left to the timer: any
+    /// output starts it.
+    fn schedule_progress(&mut self, now: Instant) {
+        if self.progress_due.is_none()
+            && self.state == DetectorState::AccumulatingOutput
+            && self
+                .provisional_len()
+                .is_none_or(|len| len != self.progress_len)
+        {
+            self.progress_due = Some(now + PROGRESS_INTERVAL);
+        }
+    }
+
+    /// Length of [`provisional_content`](Self::provisional_content),
+    /// without building it; `None` on the alternate screen, where that
+    /// takes extracting the content.
+    fn provisional_len(&self) -> Option<usize> {
+        if self.alt.is_some() && !self.markers {
+            return None;
+        }
+        if self.echo_active {
+            return Some(0);
+        }
+        Some(self.tentative_start.unwrap_or(self.content_buf.len()))
+    }
+
+    /// The turn content so far: its complete lines. The unterminated
+    /// line (often the next prompt, still being drawn), lines still in
+    /// the echo region and a tentative prompt block are left out. On
+    /// the alternate screen, the content extracted from the screen.
+    fn provisional_content(&self) -> Vec<u8> {
+        if let Some(snapshot) = self.screen_snapshot()
+            && let Some(alt) = &self.alt
+        {
+            return alt.content(&snapshot, &self.prompt);
+        }
+        if self.echo_active {
+            return Vec::new();
+        }
+        let end = self.tentative_start.unwrap_or(self.content_buf.len());
+        self.content_buf[..end].to_vec()
+    }
+
+    /// Handle the current raw line while the turn is in its leading
+    /// echo region. Returns `true` if the line was consumed as echo or
+    /// tentative decoration, `false` if it should be processed as
+    /// ordinary output (the echo region has ended).
+    ///
+    /// Echo lines are matched in submission order. Decoration lines
+    /// (blank lines, box-drawing rules) seen before a match are held
+    /// tentatively in `content_buf` and discarded when the next echo
+    /// line matches; once every submitted line has been seen, trailing
+    /// decoration is dropped outright.
+    fn strip_echo_line(&mut self) -> bool {
+        let raw = &self.raw_line_buf;
+        match self.pending_echo.front() {
+            Some(expected) if echo::is_echo_of(raw, expected) => {
+                self.pending_echo.pop_front();
+                self.content_buf.truncate(self.echo_mark);
+                true
+            }
+            Some(_) if echo::is_decoration_line(raw) => {
+                self.content_buf.extend_from_slice(raw);
+                true
+            }
+            None if echo::is_decoration_line(raw) => true,
+            _ => {
+                self.end_echo_region();
+                false
+            }
+        }
+    }
+
+    /// Leave the echo region: unmatched lines will not be stripped.
+    fn end_echo_region(&mut self) {
+        self.echo_active = false;
+        self.pending_echo.clear();
+    }
+
+    /// Record a [`LineTrace`] for every completed line from now on,
+    /// for [`take_trace`](Self::take_trace).
+    pub fn enable_trace(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    /// Line decisions recorded since the last call. Empty unless
+    /// tracing is enabled.
+    pub fn take_trace(&mut self) -> Vec<LineTrace> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
     }
 
     /// Check for a prompt match on a partial (unterminated) line.
     ///
     /// Some agents emit a prompt without a trailing newline. This
     /// method allows the PTY wrapper to flush the line buffer when
-    /// idle (e.g., after a read timeout with no new data).
+    /// idle (e.g., after a read timeout with no new data). A no-op on
+    /// the alternate screen, which is checked after every chunk.
     pub fn flush_line(&mut self) -> Vec<TurnEvent> {
-        if self.line_buf.is_empty() {
+        if self.line_buf.is_empty() || self.alt.is_some() {
             return Vec::new();
         }
 
@@ -257,6 +955,37 @@ impl TurnDetector {
     }
 }
 
+/// Join submitted lines into a turn's prompt; `None` if all are empty.
+fn join_prompt(lines: Vec<Vec<u8>>) -> Option<Vec<u8>> {
+    if lines.iter().all(Vec::is_empty) {
+        return None;
+    }
+    Some(lines.join(&b'\n'))
+}
+
+/// Number of lines a prompt pattern spans: one plus the fewest
+/// newlines any match of it contains. Newlines the pattern may skip,
+/// such as the `\n` of `(?:^|\n)` or one in `\s`, do not widen the
+/// window. 1 for a pattern that does not parse.
+fn pattern_line_count(pattern: &str) -> usize {
+    regex_syntax::parse(pattern).map_or(1, |hir| 1 + min_newlines(&hir))
+}
+
+/// Fewest newlines a match of `hir` contains.
+fn min_newlines(hir: &Hir) -> usize {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => 0,
+        HirKind::Literal(literal) => literal.0.iter().filter(|&&b| b == b'\n').count(),
+        HirKind::Class(class) => usize::from(class.literal().as_deref() == Some(b"\n")),
+        HirKind::Repetition(repetition) => {
+            (repetition.min as usize).saturating_mul(min_newlines(&repetition.sub))
+        }
+        HirKind::Capture(capture) => min_newlines(&capture.sub),
+        HirKind::Concat(subs) => subs.iter().map(min_newlines).sum(),
+        HirKind::Alternation(subs) => subs.iter().map(min_newlines).min().unwrap_or(0),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -280,9 +1009,67 @@ mod tests {
     }
 
     #[test]
-    fn multiline_pattern_rejected() {
-        let err = TurnDetector::new("foo\nbar").unwrap_err();
-        assert!(matches!(err, TurnError::MultiLinePattern));
+    fn multiline_pattern_accepted() {
+        let d = detector("foo\nbar");
+        assert_eq!(d.pattern_lines, 2);
+        let d = detector(r"^─+\n> $");
+        assert_eq!(d.pattern_lines, 2);
+    }
+
+    #[test]
+    fn pattern_line_counting() {
+        assert_eq!(pattern_line_count(r"^> $"), 1);
+        assert_eq!(pattern_line_count(r"a\nb\nc"), 3);
+        assert_eq!(pattern_line_count("a\nb"), 2);
+        // Escaped backslash followed by `n`, and `\n` inside a class.
+        assert_eq!(pattern_line_count(r"a\\n"), 1);
+        assert_eq!(pattern_line_count(r"^> [^\n]*$"), 1);
+        // Newlines a match may skip do not count.
+        assert_eq!(pattern_line_count(r"(?:^|\n)\s*>\s*$"), 1);
+        assert_eq!(pattern_line_count(r"a\n?b(\n|c)\s+"), 1);
+        assert_eq!(pattern_line_count(r"(?:\n|x\n)y[\n]"), 3);
+        assert_eq!(pattern_line_count(r"(?:a\n){2,}"), 3);
+    }
+
+    #[test]
+    fn builtin_presets_keep_last_line() {
+        for (preset, prompt) in [
+            ("claude", &b"> \n"[..]),
+            ("aider", b"aider> \n"),
+            ("generic", b"$ \n"),
+        ] {
+            let mut d = detector(preset);
+            assert_eq!(d.pattern_lines, 1, "{preset}");
+            d.feed_output(prompt);
+            d.record_input(b"hi\r");
+            d.notify_user_input();
+            let output = [&b"hi\r\nfirst line\r\nlast line\r\n"[..], prompt].concat();
+            assert_eq!(
+                single_turn(d.feed_output(&output)),
+                b"first line\r\nlast line\r\n",
+                "{preset}"
+            );
+        }
+
+        let mut d = detector("osc133");
+        d.feed_output(&marked_prompt());
+        d.notify_user_input();
+        let output = [OUTPUT_C, b"first line\r\nlast line\r\n", &marked_prompt()].concat();
+        assert_eq!(
+            single_turn(d.feed_output(&output)),
+            b"first line\r\nlast line\r\n"
+        );
+    }
+
+    #[test]
+    fn pattern_with_too_many_lines_rejected() {
+        let pattern = ["x"; MAX_PATTERN_LINES + 1].join("\n");
+        let err = TurnDetector::new(&pattern).unwrap_err();
+        assert!(matches!(
+            err,
+            TurnError::PatternTooManyLines { lines, max: MAX_PATTERN_LINES }
+                if lines == MAX_PATTERN_LINES + 1
+        ));
     }
 
     #[test]
@@ -519,6 +1306,268 @@ mod tests {
         assert_eq!(d.state, DetectorState::AccumulatingOutput);
     }
 
+    // -- Multi-line prompts --
+
+    /// Three-line prompt box: rule, prompt, hint.
+    const BOX_PROMPT: &str = r"^─+\n> \n  \? for shortcuts$";
+
+    #[test]
+    fn multiline_prompt_first_match_session_ready() {
+        let mut d = detector(BOX_PROMPT);
+        let events = d.feed_output("banner\n──────\n> \n  ? for shortcuts\n".as_bytes());
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], TurnEvent::SessionReady));
+    }
+
+    #[test]
+    fn multiline_prompt_block_excluded_from_content() {
+        let mut d = detector(BOX_PROMPT);
+        d.feed_output("──────\n> \n  ? for shortcuts\n".as_bytes());
+        d.notify_user_input();
+
+        let events = d.feed_output(
+            "answer line 1\nanswer line 2\n──────\n> \n  ? for shortcuts\n".as_bytes(),
+        );
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            TurnEvent::TurnCompleted(turn) => {
+                assert_eq!(turn.content, b"answer line 1\nanswer line 2\n");
+            }
+            _ => panic!("expected TurnCompleted"),
+        }
+    }
+
+    #[test]
+    fn multiline_prompt_partial_block_is_content() {
+        let mut d = detector(BOX_PROMPT);
+        d.feed_output("──────\n> \n  ? for shortcuts\n".as_bytes());
+        d.notify_user_input();
+
+        // A rule and `> ` without the hint line are ordinary output.
+        let events = d.feed_output("──────\n> \nmore\n".as_bytes());
+        assert!(events.is_empty());
+
+        let events = d.feed_output("──────\n> \n  ? for shortcuts\n".as_bytes());
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => {
+                assert_eq!(turn.content, "──────\n> \nmore\n".as_bytes());
+            }
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn multiline_prompt_matched_across_chunks() {
+        let mut d = detector(r"^─+\n> $");
+        let mut events = Vec::new();
+        events.extend(d.feed_output("───".as_bytes()));
+        events.extend(d.feed_output("───\n".as_bytes()));
+        events.extend(d.feed_output(b"> \n"));
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], TurnEvent::SessionReady));
+    }
+
+    #[test]
+    fn multiline_prompt_window_not_reused_after_match() {
+        let mut d = detector(r"^─+\n> $");
+        d.feed_output("───\n> \n".as_bytes());
+        d.notify_user_input();
+
+        // `> ` alone right after a matched block must not re-match with
+        // the consumed rule line.
+        let events = d.feed_output("out\n───\n> \n> \n".as_bytes());
+        let turns = events
+            .iter()
+            .filter(|e| matches!(e, TurnEvent::TurnCompleted(_)))
+            .count();
+        assert_eq!(turns, 1);
+        assert_eq!(d.state, DetectorState::AwaitingUserInput);
+    }
+
+    // -- Echo stripping --
+
+    /// Run one turn: prompt, user input, then agent output. Returns
+    /// the content of the single completed turn.
+    fn turn_content(input: &[u8], output: &[u8]) -> Vec<u8> {
+        let mut d = detector(r"^> $");
+        d.feed_output(b"> \n");
+        d.record_input(input);
+        d.notify_user_input();
+        let events = d.feed_output(output);
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => turn.content.clone(),
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn echo_stripped_canonical() {
+        let content = turn_content(b"hello\r", b"hello\r\nworld\r\n> \n");
+        assert_eq!(content, b"world\r\n");
+    }
+
+    #[test]
+    fn echo_on_unterminated_prompt_line_stripped() {
+        let mut d = detector(r"^> $");
+        d.feed_output(b"> \n");
+        // Prompt without newline; user types one key at a time and
+        // each key is echoed onto the prompt line.
+        d.feed_output(b"> ");
+        for key in [b"h", b"i"] {
+            d.record_input(key);
+            d.feed_output(key);
+        }
+        d.record_input(b"\r");
+        d.notify_user_input();
+
+        let events = d.feed_output(b"\r\nhi there\r\n> \n");
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => assert_eq!(turn.content, b"hi there\r\n"),
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn echo_with_line_editing_stripped() {
+        let content = turn_content(
+            b"helk\x7flo wrld\x17world\r",
+            b"helk\x08 \x08lo wrld\x08\x08\x08\x08    \x08\x08\x08\x08world\r\nok\r\n> \n",
+        );
+        assert_eq!(content, b"ok\r\n");
+    }
+
+    #[test]
+    fn echo_rerendered_by_agent_stripped() {
+        let content = turn_content(
+            b"hello\r",
+            b"\r\x1b[2K\x1b[1m> \x1b[0mhello\r\n\x1b[2mThinking\x1b[0m\r\n> \n",
+        );
+        assert_eq!(content, b"\x1b[2mThinking\x1b[0m\r\n");
+    }
+
+    #[test]
+    fn echo_in_box_strips_decoration() {
+        let output = "╭──────────╮\r\n│ > hello  │\r\n╰──────────╯\r\n\r\nanswer\r\n> \n";
+        let content = turn_content(b"hello\r", output.as_bytes());
+        assert_eq!(content, b"answer\r\n");
+    }
+
+    #[test]
+    fn multi_line_paste_echo_stripped() {
+        let content = turn_content(
+            b"line one\rline two\r",
+            b"line one\r\nline two\r\nreply\r\n> \n",
+        );
+        assert_eq!(content, b"reply\r\n");
+    }
+
+    #[test]
+    fn paste_split_across_reads_stripped() {
+        let mut d = detector(r"^> $");
+        d.feed_output(b"> \n");
+        d.record_input(b"first\r");
+        d.notify_user_input();
+        d.record_input(b"second\r");
+        d.notify_user_input();
+
+        let events = d.feed_output(b"first\r\nsecond\r\nreply\r\n> \n");
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => assert_eq!(turn.content, b"reply\r\n"),
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn output_resembling_input_not_stripped() {
+        // The agent never echoed; its first line must survive.
+        let content = turn_content(b"hello\r", b"Hello! What next?\r\nhello\r\n> \n");
+        assert_eq!(content, b"Hello! What next?\r\nhello\r\n");
+    }
+
+    #[test]
+    fn decoration_before_real_output_kept() {
+        // Decoration held tentatively is kept when no echo follows.
+        let content = turn_content(b"hello\r", b"\r\n----\r\n\r\nresult\r\n> \n");
+        assert_eq!(content, b"\r\n----\r\n\r\nresult\r\n");
+    }
+
+    #[test]
+    fn empty_input_echo_stripped() {
+        let content = turn_content(b"\r", b"\r\nstill here\r\n> \n");
+        assert_eq!(content, b"still here\r\n");
+    }
+
+    #[test]
+    fn echo_region_resets_between_turns() {
+        let mut d = detector(r"^> $");
+        d.feed_output(b"> \n");
+
+        d.record_input(b"one\r");
+        d.notify_user_input();
+        d.feed_output(b"one\r\nfirst\r\n> \n");
+
+        d.record_input(b"two\r");
+        d.notify_user_input();
+        let events = d.feed_output(b"two\r\nsecond\r\n> \n");
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => assert_eq!(turn.content, b"second\r\n"),
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    // -- Prompt capture --
+
+    /// Run one turn like [`turn_content`], returning its prompt.
+    fn turn_prompt(input: &[u8], output: &[u8]) -> Option<Vec<u8>> {
+        let mut d = detector(r"^> $");
+        d.feed_output(b"> \n");
+        d.record_input(input);
+        d.notify_user_input();
+        let events = d.feed_output(output);
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => turn.prompt.clone(),
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn prompt_taken_from_input_not_echo() {
+        // Edited input yields the final line; the agent re-renders it
+        // differently, which must not matter.
+        let prompt = turn_prompt(b"helk\x7flo\r", b"\x1b[1m> \x1b[0mhello\r\nhi\r\n> \n");
+        assert_eq!(prompt.as_deref(), Some(&b"hello"[..]));
+    }
+
+    #[test]
+    fn multi_line_prompt_joined() {
+        let prompt = turn_prompt(b"one\rtwo\r", b"one\r\ntwo\r\nreply\r\n> \n");
+        assert_eq!(prompt.as_deref(), Some(&b"one\ntwo"[..]));
+    }
+
+    #[test]
+    fn bare_enter_has_no_prompt() {
+        assert_eq!(turn_prompt(b"\r", b"\r\nstill here\r\n> \n"), None);
+    }
+
+    #[test]
+    fn prompt_resets_between_turns() {
+        let mut d = detector(r"^> $");
+        d.feed_output(b"> \n");
+        d.record_input(b"one\r");
+        d.notify_user_input();
+        d.feed_output(b"one\r\nfirst\r\n> \n");
+
+        d.record_input(b"two\r");
+        d.notify_user_input();
+        let events = d.feed_output(b"two\r\nsecond\r\n> \n");
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => {
+                assert_eq!(turn.prompt.as_deref(), Some(&b"two"[..]))
+            }
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
     #[test]
     fn notify_interrupt_noop_outside_accumulating() {
         let mut d = detector(r"^> $");
@@ -531,4 +1580,684 @@ mod tests {
         // Awaiting input — noop, no panic
         d.notify_interrupt();
     }
+
+    // -- Boundary strategies --
+
+    fn ms(n: u64) -> std::time::Duration {
+        std::time::Duration::from_millis(n)
+    }
+
+    fn single_turn(events: Vec<TurnEvent>) -> Vec<u8> {
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => turn.content.clone(),
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn invalid_boundary_rejected() {
+        let err = TurnDetector::with_boundary(r"^> $", "quiet:soon").unwrap_err();
+        assert!(matches!(err, TurnError::InvalidBoundary(_)));
+    }
+
+    #[test]
+    fn quiet_boundary_ends_turn_after_silence() {
+        let t0 = Instant::now();
+        let mut d = TurnDetector::with_boundary(r"^> $", "quiet:100").unwrap();
+
+        // Silence after the banner stands in for the first prompt.
+        d.feed_output_at(b"welcome\n", t0);
+        assert!(d.poll_timer(t0 + ms(50)).is_empty());
+        assert!(matches!(
+            d.poll_timer(t0 + ms(100)).as_slice(),
+            [TurnEvent::SessionReady]
+        ));
+
+        d.notify_user_input();
+        assert_eq!(d.deadline(), None);
+        d.feed_output_at(b"thinking\n", t0 + ms(200));
+        d.feed_output_at(b"done\n> ", t0 + ms(250));
+        assert_eq!(d.deadline(), Some(t0 + ms(350)));
+
+        // An unterminated line matching the prompt is dropped.
+        let content = single_turn(d.poll_timer(t0 + ms(350)));
+        assert_eq!(content, b"thinking\ndone\n");
+    }
+
+    #[test]
+    fn quiet_boundary_keeps_unmatched_partial_line() {
+        let t0 = Instant::now();
+        let mut d = TurnDetector::with_boundary(r"^> $", "quiet:100").unwrap();
+        d.feed_output_at(b"> \n", t0);
+        d.poll_timer(t0 + ms(100));
+
+        d.notify_user_input();
+        d.feed_output_at(b"answer\nNo newline", t0 + ms(200));
+        let content = single_turn(d.poll_timer(t0 + ms(300)));
+        assert_eq!(content, b"answer\nNo newline");
+    }
+
+    #[test]
+    fn quiet_boundary_waits_for_first_output() {
+        let t0 = Instant::now();
+        let mut d = TurnDetector::with_boundary(r"^> $", "quiet:100").unwrap();
+        d.feed_output_at(b"> \n", t0);
+        d.poll_timer(t0 + ms(100));
+
+        // Echo only, then silence: the agent has not answered yet.
+        d.record_input(b"hi\r");
+        d.notify_user_input();
+        d.feed_output_at(b"hi\r\n", t0 + ms(200));
+        assert!(d.poll_timer(t0 + ms(300)).is_empty());
+
+        d.feed_output_at(b"hello\r\n", t0 + ms(400));
+        assert_eq!(single_turn(d.poll_timer(t0 + ms(500))), b"hello\r\n");
+    }
+
+    #[test]
+    fn prompt_quiet_excludes_confirmed_prompt() {
+        let t0 = Instant::now();
+        let mut d = TurnDetector::with_boundary(r"^> $", "prompt+quiet:100").unwrap();
+        d.feed_output_at(b"> \n", t0);
+        assert!(matches!(
+            d.poll_timer(t0 + ms(100)).as_slice(),
+            [TurnEvent::SessionReady]
+        ));
+
+        d.notify_user_input();
+        // A prompt-like line mid-response is followed by more output,
+        // so it stays in the turn.
+        assert!(
+            d.feed_output_at(b"> \nstill going\n", t0 + ms(200))
+                .is_empty()
+        );
+        assert!(d.poll_timer(t0 + ms(300)).is_empty());
+
+        d.feed_output_at(b"> \n", t0 + ms(400));
+        let content = single_turn(d.poll_timer(t0 + ms(500)));
+        assert_eq!(content, b"> \nstill going\n");
+    }
+
+    #[test]
+    fn prompt_quiet_accepts_unterminated_prompt() {
+        let t0 = Instant::now();
+        let mut d = TurnDetector::with_boundary(r"^> $", "prompt+quiet:100").unwrap();
+        d.feed_output_at(b"> ", t0);
+        assert!(matches!(
+            d.poll_timer(t0 + ms(100)).as_slice(),
+            [TurnEvent::SessionReady]
+        ));
+
+        d.notify_user_input();
+        d.feed_output_at(b"\r\nanswer\r\n> ", t0 + ms(200));
+        let content = single_turn(d.poll_timer(t0 + ms(300)));
+        assert_eq!(content, b"\r\nanswer\r\n");
+    }
+
+    #[test]
+    fn prompt_quiet_excludes_multi_line_prompt_block() {
+        let t0 = Instant::now();
+        let mut d = TurnDetector::with_boundary(BOX_PROMPT, "prompt+quiet:100").unwrap();
+        d.feed_output_at(b"\xe2\x94\x80\xe2\x94\x80\n> \n  ? for shortcuts\n", t0);
+        d.poll_timer(t0 + ms(100));
+
+        d.notify_user_input();
+        d.feed_output_at(
+            b"answer\n\xe2\x94\x80\xe2\x94\x80\n> \n  ? for shortcuts\n",
+            t0 + ms(200),
+        );
+        assert_eq!(single_turn(d.poll_timer(t0 + ms(300))), b"answer\n");
+    }
+
+    // -- Semantic prompt markers --
+
+    const PROMPT_A: &[u8] = b"\x1b]133;A\x07";
+    const PROMPT_B: &[u8] = b"\x1b]133;B\x07";
+    const OUTPUT_C: &[u8] = b"\x1b]133;C\x07";
+
+    /// A shell-integration prompt: `A`, the prompt text, `B`.
+    fn marked_prompt() -> Vec<u8> {
+        [PROMPT_A, b"$ ", PROMPT_B].concat()
+    }
+
+    #[test]
+    fn marker_turn_spans_c_to_d() {
+        let mut d = detector("osc133");
+        let events = d.feed_output(&marked_prompt());
+        assert!(matches!(events.as_slice(), [TurnEvent::SessionReady]));
+
+        d.record_input(b"false\r");
+        d.notify_user_input();
+        let output = [
+            b"false\r\n".as_slice(),
+            OUTPUT_C,
+            b"oops\r\n",
+            b"\x1b]133;D;1\x07",
+            &marked_prompt(),
+        ]
+        .concat();
+        let events = d.feed_output(&output);
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => {
+                assert_eq!(turn.content, b"oops\r\n");
+                assert_eq!(turn.exit_status, Some(1));
+            }
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn prompt_marker_ends_turn_without_exit_status() {
+        let mut d = detector("osc133");
+        d.feed_output(&marked_prompt());
+        d.notify_user_input();
+
+        let output = [OUTPUT_C, b"partial line", &marked_prompt()].concat();
+        match d.feed_output(&output).as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => {
+                assert_eq!(turn.content, b"partial line");
+                assert_eq!(turn.exit_status, None);
+            }
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn output_marker_starts_turn_without_enter() {
+        let mut d = detector("osc133");
+        d.feed_output(&marked_prompt());
+
+        let output = [OUTPUT_C, b"scripted\n", b"\x1b]633;D;0\x1b\\"].concat();
+        match d.feed_output(&output).as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => {
+                assert_eq!(turn.content, b"scripted\n");
+                assert_eq!(turn.exit_status, Some(0));
+            }
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn markers_override_prompt_pattern() {
+        let mut d = detector(r"^> $");
+        d.feed_output(&marked_prompt());
+        d.notify_user_input();
+
+        // A line matching the regex no longer ends the turn.
+        let output = [OUTPUT_C, b"> \nmore\n", PROMPT_A].concat();
+        assert_eq!(single_turn(d.feed_output(&output)), b"> \nmore\n");
+    }
+
+    #[test]
+    fn marker_split_across_chunks() {
+        let mut d = detector("osc133");
+        d.feed_output(&marked_prompt());
+        d.notify_user_input();
+
+        assert!(d.feed_output(b"\x1b]13").is_empty());
+        assert!(d.feed_output(b"3;C\x07out\n\x1b]133;").is_empty());
+        assert_eq!(single_turn(d.feed_output(b"D;0\x07")), b"out\n");
+    }
+
+    #[test]
+    fn markers_disable_boundary_timer() {
+        let t0 = Instant::now();
+        let mut d = TurnDetector::with_boundary("osc133", "quiet:100").unwrap();
+        d.feed_output_at(&marked_prompt(), t0);
+        assert_eq!(d.deadline(), None);
+        assert!(d.poll_timer(t0 + ms(500)).is_empty());
+    }
+
+    // -- Turn progress --
+
+    fn progress(events: Vec<TurnEvent>) -> Vec<u8> {
+        match events.as_slice() {
+            [TurnEvent::TurnProgress(content)] => content.clone(),
+            other => panic!("expected one TurnProgress, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn progress_reported_after_interval() {
+        let t0 = Instant::now();
+        let mut d = detector(r"^> $");
+        d.feed_output_at(b"> \n", t0);
+        d.notify_user_input();
+        assert_eq!(d.deadline(), None);
+
+        d.feed_output_at(b"first\nsecond", t0 + ms(100));
+        assert_eq!(d.deadline(), Some(t0 + ms(100) + PROGRESS_INTERVAL));
+        assert!(d.poll_timer(t0 + ms(200)).is_empty());
+
+        // The unterminated line may be the next prompt: left out.
+        let content = progress(d.poll_timer(t0 + ms(600)));
+        assert_eq!(content, b"first\n");
+        assert_eq!(d.deadline(), None);
+    }
+
+    #[test]
+    fn progress_throttled_and_not_repeated() {
+        let t0 = Instant::now();
+        let mut d = detector(r"^> $");
+        d.feed_output_at(b"> \n", t0);
+        d.notify_user_input();
+
+        d.feed_output_at(b"a\n", t0);
+        // Further output does not push the deadline back.
+        d.feed_output_at(b"b\n", t0 + ms(400));
+        assert_eq!(progress(d.poll_timer(t0 + ms(500))), b"a\nb\n");
+
+        // Nothing new: no further progress is scheduled.
+        d.feed_output_at(b"", t0 + ms(600));
+        assert_eq!(d.deadline(), None);
+
+        d.feed_output_at(b"c\n", t0 + ms(700));
+        assert_eq!(progress(d.poll_timer(t0 + ms(1200))), b"a\nb\nc\n");
+    }
+
+    #[test]
+    fn progress_excludes_echo_region() {
+        let t0 = Instant::now();
+        let mut d = detector(r"^> $");
+        d.feed_output_at(b"> \n", t0);
+        d.record_input(b"hi\r");
+        d.notify_user_input();
+
+        d.feed_output_at(b"hi\r\nanswer\r\n", t0);
+        assert_eq!(progress(d.poll_timer(t0 + ms(500))), b"answer\r\n");
+    }
+
+    #[test]
+    fn no_progress_after_turn_completes() {
+        let t0 = Instant::now();
+        let mut d = detector(r"^> $");
+        d.feed_output_at(b"> \n", t0);
+        d.notify_user_input();
+
+        d.feed_output_at(b"partial", t0);
+        assert_eq!(
+            single_turn(d.feed_output_at(b" done\n> \n", t0 + ms(100))),
+            b"partial done\n"
+        );
+        assert_eq!(d.deadline(), None);
+        assert!(d.poll_timer(t0 + ms(500)).is_empty());
+    }
+
+    #[test]
+    fn progress_withdrawn_when_turn_ends_empty() {
+        let t0 = Instant::now();
+        let mut d = detector("osc133");
+        d.feed_output_at(&marked_prompt(), t0);
+        d.notify_user_input();
+
+        d.feed_output_at(b"cmd\n", t0);
+        assert_eq!(progress(d.poll_timer(t0 + ms(500))), b"cmd\n");
+
+        // `C` discards the echoed command; the command prints nothing.
+        let output = [OUTPUT_C, b"\x1b]133;D;0\x07"].concat();
+        assert_eq!(progress(d.feed_output_at(&output, t0 + ms(600))), b"");
+    }
+
+    // -- Alternate screen --
+
+    /// Full-screen TUI: a title, the prompt at row 3, cursor after it.
+    const TUI_START: &[u8] = b"\x1b[?1049h\x1b[2J\x1b[HAgent v1\r\n\r\n> ";
+
+    #[test]
+    fn alt_screen_turn_extracted_from_screen() {
+        let mut d = detector(r"^> $");
+        assert!(matches!(
+            d.feed_output(TUI_START).as_slice(),
+            [TurnEvent::SessionReady]
+        ));
+
+        d.feed_output(b"hi");
+        d.record_input(b"hi\r");
+        d.notify_user_input();
+        // Input box cleared: the same prompt, nothing new drawn yet.
+        assert!(d.feed_output(b"\r\x1b[K> ").is_empty());
+
+        // The conversation is redrawn above a fresh prompt.
+        let events = d.feed_output(
+            b"\x1b[3;1H\x1b[K> hi\r\n\r\nHello there!\r\n\x1b[1mSecond\x1b[0m line\r\n\r\n> ",
+        );
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => {
+                assert_eq!(turn.content, b"Hello there!\r\nSecond line\r\n");
+                assert!(turn.screen_rendered);
+                assert_eq!(turn.prompt.as_deref(), Some(&b"hi"[..]));
+            }
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn alt_screen_lines_not_matched() {
+        let mut d = detector(r"^> $");
+        d.feed_output(TUI_START);
+        d.record_input(b"go\r");
+        d.notify_user_input();
+        // A `> ` line in the byte stream, but the cursor is elsewhere.
+        assert!(d.feed_output(b"\x1b[5;1H> \r\n\x1b[6;1Hworking").is_empty());
+        assert!(d.flush_line().is_empty());
+    }
+
+    #[test]
+    fn line_matching_resumes_after_alt_screen() {
+        let mut d = detector(r"^> $");
+        d.feed_output(b"> \n");
+        d.record_input(b"edit\r");
+        d.notify_user_input();
+        d.feed_output(b"edit\r\n\x1b[?1049h\x1b[H> full screen\x1b[?1049l");
+        let events = d.feed_output(b"saved\r\n> \n");
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => {
+                assert!(!turn.screen_rendered);
+                assert!(turn.content.ends_with(b"saved\r\n"));
+            }
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn alt_screen_quiet_boundary() {
+        let t0 = Instant::now();
+        let mut d = TurnDetector::with_boundary(r"^> $", "quiet:200").unwrap();
+        d.set_screen_size(ScreenSize { cols: 40, rows: 10 });
+        d.feed_output_at(TUI_START, t0);
+        assert!(matches!(
+            d.poll_timer(t0 + ms(200)).as_slice(),
+            [TurnEvent::SessionReady]
+        ));
+        d.record_input(b"q\r");
+        d.notify_user_input();
+
+        // Silence with the screen unchanged is not the end of a turn.
+        d.feed_output_at(b"\r\x1b[K> ", t0 + ms(210));
+        assert!(d.poll_timer(t0 + ms(500)).is_empty());
+
+        d.feed_output_at(b"\x1b[5;1Hanswer\x1b[10;1Hstatus: idle", t0 + ms(600));
+        let events = d.poll_timer(t0 + ms(800));
+        match events.as_slice() {
+            [TurnEvent::TurnCompleted(turn)] => {
+                assert_eq!(turn.content, b"answer\r\n\r\n\r\n\r\n\r\nstatus: idle\r\n");
+                assert!(turn.screen_rendered);
+            }
+            other => panic!("expected one TurnCompleted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn alt_screen_progress_from_screen() {
+        let t0 = Instant::now();
+        let mut d = detector(r"^> $");
+        d.feed_output_at(TUI_START, t0);
+        d.record_input(b"q\r");
+        d.notify_user_input();
+        d.feed_output_at(b"\x1b[5;1Hthinking", t0 + ms(10));
+        assert_eq!(
+            progress(d.poll_timer(t0 + ms(10) + PROGRESS_INTERVAL)),
+            b"thinking\r\n"
+        );
+
+        // Output schedules progress, but a redraw that leaves the
+        // content as it was reports nothing.
+        let t1 = t0 + ms(600);
+        d.feed_output_at(b"\x1b[5;1Hthinking", t1);
+        assert_eq!(d.deadline(), Some(t1 + PROGRESS_INTERVAL));
+        assert!(d.poll_timer(t1 + PROGRESS_INTERVAL).is_empty());
+        assert_eq!(d.deadline(), None);
+    }
+
+    // -- Chunked hot path --
+
+    /// One step of a detector script.
+    enum Step {
+        Output(&'static [u8]),
+        /// User input: recorded, then submitted.
+        Input(&'static [u8]),
+        /// Submission without recorded input.
+        Submit,
+        Interrupt,
+    }
+
+    /// An event as the scripts expect it: turn content (`<ready>` for
+    /// session ready), interrupted flag and exit status.
+    type Summary = (Vec<u8>, bool, Option<i32>);
+
+    fn ready() -> Summary {
+        (b"<ready>".to_vec(), false, None)
+    }
+
+    /// A completed turn with no interrupt or exit status.
+    fn turn(content: &[u8]) -> Summary {
+        (content.to_vec(), false, None)
+    }
+
+    /// Scripts of the tests above that predate chunked processing, with
+    /// the events those tests expect.
+    fn corpus() -> Vec<(&'static str, &'static str, Vec<Step>, Vec<Summary>)> {
+        use Step::{Input, Interrupt, Output, Submit};
+        vec![
+            (
+                "basic_turn_detection",
+                r"^> $",
+                vec![Output(b"> \n"), Submit, Output(b"hello world\n> \n")],
+                vec![ready(), turn(b"hello world\n")],
+            ),
+            (
+                "multi_line_output_turn",
+                r"^> $",
+                vec![
+                    Output(b"> \n"),
+                    Submit,
+                    Output(b"line 1\nline 2\nline 3\n> \n"),
+                ],
+                vec![ready(), turn(b"line 1\nline 2\nline 3\n")],
+            ),
+            (
+                "interrupted_turn_flagged",
+                r"^> $",
+                vec![
+                    Output(b"> \n"),
+                    Submit,
+                    Output(b"partial out"),
+                    Interrupt,
+                    Output(b"put\n> \n"),
+                ],
+                vec![ready(), (b"partial output\n".to_vec(), true, None)],
+            ),
+            (
+                "ansi_preserved_in_content",
+                r"^> $",
+                vec![
+                    Output(b"\x1b[32m> \x1b[0m\n"),
+                    Submit,
+                    Output(b"\x1b[31mred text\x1b[0m\n> \n"),
+                ],
+                vec![ready(), turn(b"\x1b[31mred text\x1b[0m\n")],
+            ),
+            (
+                "multiple_turns_in_sequence",
+                r"^> $",
+                vec![
+                    Output(b"> \n"),
+                    Submit,
+                    Output(b"output 1\n> \n"),
+                    Submit,
+                    Output(b"output 2\n> \n> \n> \n"),
+                ],
+                vec![ready(), turn(b"output 1\n"), turn(b"output 2\n")],
+            ),
+            (
+                "multiline_prompt_partial_block_is_content",
+                BOX_PROMPT,
+                vec![
+                    Output("banner\n──────\n> \n  ? for shortcuts\n".as_bytes()),
+                    Submit,
+                    Output("──────\n> \nmore\n".as_bytes()),
+                    Output("──────\n> \n  ? for shortcuts\n".as_bytes()),
+                    Submit,
+                    Output("answer line 1\nanswer line 2\n──────\n> \n  ? for shortcuts\n".as_bytes()),
+                ],
+                vec![
+                    ready(),
+                    turn("──────\n> \nmore\n".as_bytes()),
+                    turn(b"answer line 1\nanswer line 2\n"),
+                ],
+            ),
+            (
+                "multiline_prompt_window_not_reused_after_match",
+                r"^─+\n> $",
+                vec![
+                    Output("───\n> \n".as_bytes()),
+                    Submit,
+                    Output("out\n───\n> \n> \n".as_bytes()),
+                ],
+                vec![ready(), turn(b"out\n")],
+            ),
+            (
+                "echo_stripped",
+                r"^> $",
+                vec![
+                    Output(b"> \n"),
+                    Input(b"hello\r"),
+                    Output(b"hello\r\nworld\r\n> \n"),
+                    Input(b"helk\x7flo wrld\x17world\r"),
+                    Output(
+                        b"helk\x08 \x08lo wrld\x08\x08\x08\x08    \x08\x08\x08\x08world\r\nok\r\n> \n",
+                    ),
+                    Input(b"hello\r"),
+                    Output(b"\r\x1b[2K\x1b[1m> \x1b[0mhello\r\n\x1b[2mThinking\x1b[0m\r\n> \n"),
+                    Input(b"hello\r"),
+                    Output(
+                        "╭──────────╮\r\n│ > hello  │\r\n╰──────────╯\r\n\r\nanswer\r\n> \n"
+                            .as_bytes(),
+                    ),
+                    Input(b"line one\rline two\r"),
+                    Output(b"line one\r\nline two\r\nreply\r\n> \n"),
+                    Input(b"first\r"),
+                    Input(b"second\r"),
+                    Output(b"first\r\nsecond\r\nreply\r\n> \n"),
+                    Input(b"\r"),
+                    Output(b"\r\nstill here\r\n> \n"),
+                ],
+                vec![
+                    ready(),
+                    turn(b"world\r\n"),
+                    turn(b"ok\r\n"),
+                    turn(b"\x1b[2mThinking\x1b[0m\r\n"),
+                    turn(b"answer\r\n"),
+                    turn(b"reply\r\n"),
+                    turn(b"reply\r\n"),
+                    turn(b"still here\r\n"),
+                ],
+            ),
+            (
+                "echo_not_found",
+                r"^> $",
+                vec![
+                    Output(b"> \n"),
+                    Input(b"hello\r"),
+                    Output(b"Hello! What next?\r\nhello\r\n> \n"),
+                    Input(b"hello\r"),
+                    Output(b"\r\n----\r\n\r\nresult\r\n> \n"),
+                ],
+                vec![
+                    ready(),
+                    turn(b"Hello! What next?\r\nhello\r\n"),
+                    turn(b"\r\n----\r\n\r\nresult\r\n"),
+                ],
+            ),
+            (
+                "semantic_markers",
+                "osc133",
+                vec![
+                    Output(b"\x1b]133;A\x07$ \x1b]133;B\x07"),
+                    Input(b"false\r"),
+                    Output(b"false\r\n\x1b]133;C\x07oops\r\n\x1b]133;D;1\x07\x1b]133;A\x07$ \x1b]133;B\x07"),
+                    Submit,
+                    Output(b"\x1b]133;C\x07partial line\x1b]133;A\x07$ \x1b]133;B\x07"),
+                    Output(b"\x1b]133;C\x07scripted\n\x1b]633;D;0\x1b\\"),
+                ],
+                vec![
+                    ready(),
+                    (b"oops\r\n".to_vec(), false, Some(1)),
+                    turn(b"partial line"),
+                    (b"scripted\n".to_vec(), false, Some(0)),
+                ],
+            ),
+            (
+                "markers_override_prompt_pattern",
+                r"^> $",
+                vec![
+                    Output(b"\x1b]133;A\x07$ \x1b]133;B\x07"),
+                    Submit,
+                    Output(b"\x1b]133;C\x07> \nmore\n\x1b]133;A\x07"),
+                ],
+                vec![ready(), turn(b"> \nmore\n")],
+            ),
+        ]
+    }
+
+    /// Summary of an event, without the wall-clock timestamp.
+    fn summarize(events: &[TurnEvent]) -> Vec<Summary> {
+        events
+            .iter()
+            .map(|e| match e {
+                TurnEvent::SessionReady => ready(),
+                TurnEvent::TurnCompleted(t) => (t.content.clone(), t.interrupted, t.exit_status),
+                TurnEvent::TurnProgress(c) => ([b"<progress>", &c[..]].concat(), false, None),
+            })
+            .collect()
+    }
+
+    /// Run a script, feeding each output step in `chunk`-sized pieces.
+    fn run_script(pattern: &str, steps: &[Step], chunk: usize) -> Vec<Summary> {
+        let mut d = detector(pattern);
+        let mut events = Vec::new();
+        for step in steps {
+            match step {
+                Step::Output(data) => {
+                    for piece in data.chunks(chunk) {
+                        events.extend(d.feed_output(piece));
+                    }
+                }
+                Step::Input(data) => {
+                    d.record_input(data);
+                    d.notify_user_input();
+                }
+                Step::Submit => d.notify_user_input(),
+                Step::Interrupt => d.notify_interrupt(),
+            }
+        }
+        events.extend(d.flush_line());
+        summarize(&events)
+    }
+
+    #[test]
+    fn chunked_feed_matches_corpus() {
+        for (name, pattern, steps, expected) in corpus() {
+            for chunk in [1, 2, 5, 64, usize::MAX] {
+                assert_eq!(
+                    run_script(pattern, &steps, chunk),
+                    expected,
+                    "{name}, chunk size {chunk}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn line_buffers_reused_across_lines() {
+        let mut d = detector(r"^> $");
+        d.feed_output(b"> \n");
+        d.notify_user_input();
+        d.feed_output(&b"x".repeat(4096));
+        d.feed_output(b"\n");
+        let (raw_cap, line_cap) = (d.raw_line_buf.capacity(), d.line_buf.capacity());
+        d.feed_output(b"short\nlines\nonly\n");
+        assert_eq!(d.raw_line_buf.capacity(), raw_cap);
+        assert_eq!(d.line_buf.capacity(), line_cap);
+    }
 }
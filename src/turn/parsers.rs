@@ -0,0 +1,453 @@
+//! Structured output parsers for known agents.
+//!
+//! A parser splits the plain text of a turn (CONTRACT_TURN.md §Plain
+//! content) into typed [`Segment`]s — assistant prose, tool calls and
+//! their results, edit blocks and status lines — so that a view of
+//! one kind can be captured on its own. Parsers are heuristics over
+//! the agents' current terminal output; anything they do not
+//! recognise is prose. The parser is chosen by the session's preset.
+//!
+//! See CONTRACT_TURN.md §Segments.
+
+/// What a segment of turn content is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    /// Assistant text addressed to the user.
+    Prose,
+    /// A tool invocation, e.g. `⏺ Bash(ls)`.
+    ToolCall,
+    /// Output reported back from a tool.
+    ToolResult,
+    /// A proposed file edit, e.g. an aider SEARCH/REPLACE block.
+    EditBlock,
+    /// Progress, cost and other status lines.
+    Status,
+}
+
+impl SegmentKind {
+    /// Wire name, as used in descriptors and diagnostics.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Prose => "prose",
+            Self::ToolCall => "tool_call",
+            Self::ToolResult => "tool_result",
+            Self::EditBlock => "edit_block",
+            Self::Status => "status",
+        }
+    }
+}
+
+/// A run of consecutive lines of one kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub kind: SegmentKind,
+    /// The segment's lines, each `\n`-terminated.
+    pub content: Vec<u8>,
+}
+
+/// A per-agent output parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parser {
+    /// Claude Code: `⏺` bullets for prose and tool calls, `⎿` for
+    /// tool results, spinner glyphs for status.
+    Claude,
+    /// Aider: SEARCH/REPLACE edit blocks, edit and commit reports,
+    /// token usage lines.
+    Aider,
+}
+
+/// Parser names accepted in presets.
+pub const NAMES: &[&str] = &["claude", "aider"];
+
+impl Parser {
+    /// The parser with the given name, if there is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "claude" => Some(Self::Claude),
+            "aider" => Some(Self::Aider),
+            _ => None,
+        }
+    }
+
+    /// Split plain turn text into segments.
+    pub fn segments(self, plain: &[u8]) -> Vec<Segment> {
+        match self {
+            Self::Claude => claude(plain),
+            Self::Aider => aider(plain),
+        }
+    }
+}
+
+/// Segments of `plain` for a session's parser. Without one, the whole
+/// text is a single prose segment.
+pub fn segments(parser: Option<Parser>, plain: &[u8]) -> Vec<Segment> {
+    match parser {
+        Some(parser) => parser.segments(plain),
+        None => {
+            let mut builder = Builder::default();
+            for line in lines(plain) {
+                builder.line(SegmentKind::Prose, false, line);
+            }
+            builder.finish()
+        }
+    }
+}
+
+/// The content of all segments of `kind`, separated by blank lines.
+pub fn view(segments: &[Segment], kind: SegmentKind) -> Vec<u8> {
+    let mut out = Vec::new();
+    for segment in segments.iter().filter(|s| s.kind == kind) {
+        if !out.is_empty() {
+            out.push(b'\n');
+        }
+        out.extend_from_slice(&segment.content);
+    }
+    out
+}
+
+/// Lines of `text`, without their `\n` terminators.
+fn lines(text: &[u8]) -> impl Iterator<Item = &[u8]> {
+    text.strip_suffix(b"\n")
+        .unwrap_or(text)
+        .split(|&b| b == b'\n')
+        .filter(move |_| !text.is_empty())
+}
+
+/// Accumulates lines into segments. Blank lines are held back: they
+/// join the current segment only if it continues after them, so that
+/// segments neither start nor end blank.
+#[derive(Default)]
+struct Builder {
+    segments: Vec<Segment>,
+    current: Option<Segment>,
+    blanks: usize,
+}
+
+impl Builder {
+    /// Add a line of `kind`. With `start`, the line begins a new
+    /// segment even if the current one is of the same kind.
+    fn line(&mut self, kind: SegmentKind, start: bool, line: &[u8]) {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            if self.current.is_some() {
+                self.blanks += 1;
+            }
+            return;
+        }
+        match &mut self.current {
+            Some(segment) if segment.kind == kind && !start => {
+                segment
+                    .content
+                    .extend(std::iter::repeat_n(b'\n', self.blanks));
+            }
+            _ => self.start(kind),
+        }
+        self.blanks = 0;
+        let segment = self.current.as_mut().expect("segment started");
+        segment.content.extend_from_slice(line);
+        segment.content.push(b'\n');
+    }
+
+    /// Continue the current segment, whatever its kind (prose if
+    /// there is none).
+    fn continuation(&mut self, line: &[u8]) {
+        let kind = self.current.as_ref().map_or(SegmentKind::Prose, |s| s.kind);
+        self.line(kind, false, line);
+    }
+
+    fn start(&mut self, kind: SegmentKind) {
+        self.segments.extend(self.current.take());
+        self.current = Some(Segment {
+            kind,
+            content: Vec::new(),
+        });
+    }
+
+    fn finish(mut self) -> Vec<Segment> {
+        self.segments.extend(self.current.take());
+        self.segments
+    }
+}
+
+// -- Claude Code --
+
+/// Bullets Claude Code puts before prose and tool calls.
+const CLAUDE_BULLETS: &[&str] = &["⏺", "●"];
+
+/// Marker before the first line of a tool result.
+const CLAUDE_RESULT: &str = "⎿";
+
+/// Spinner glyphs that start a status line.
+const CLAUDE_SPINNERS: &[char] = &['✻', '✢', '✳', '✶', '✽', '✺'];
+
+fn claude(plain: &[u8]) -> Vec<Segment> {
+    let mut builder = Builder::default();
+    for line in lines(plain) {
+        let text = String::from_utf8_lossy(line);
+        let trimmed = text.trim_start();
+
+        if let Some(rest) = CLAUDE_BULLETS.iter().find_map(|b| trimmed.strip_prefix(b)) {
+            let rest = rest.trim_start();
+            if is_tool_call(rest) {
+                builder.line(SegmentKind::ToolCall, true, line);
+            } else {
+                // The bullet itself is not part of the prose.
+                builder.line(SegmentKind::Prose, true, rest.as_bytes());
+            }
+        } else if trimmed.starts_with(CLAUDE_RESULT) {
+            builder.line(SegmentKind::ToolResult, true, line);
+        } else if trimmed.starts_with(CLAUDE_SPINNERS) || trimmed.contains("esc to interrupt") {
+            builder.line(SegmentKind::Status, true, line);
+        } else if text.starts_with(char::is_whitespace) {
+            // Indented lines continue a tool call's arguments, a
+            // result's output or a wrapped paragraph.
+            builder.continuation(line);
+        } else {
+            builder.line(SegmentKind::Prose, false, line);
+        }
+    }
+    builder.finish()
+}
+
+/// Whether the text after a bullet is a tool call: a tool name
+/// (`Bash`, `Read`, `mcp__server__tool`…), optionally tagged
+/// ` (MCP)`, followed by its parenthesised arguments.
+fn is_tool_call(text: &str) -> bool {
+    let name_len = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == ':'))
+        .unwrap_or(text.len());
+    let (name, rest) = text.split_at(name_len);
+    let rest = rest.strip_prefix(" (MCP)").unwrap_or(rest);
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && (name.starts_with(|c: char| c.is_ascii_uppercase()) || name.contains("__"))
+        && rest.starts_with('(')
+}
+
+// -- Aider --
+
+const AIDER_SEARCH: &str = "<<<<<<< SEARCH";
+const AIDER_DIVIDER: &str = "=======";
+const AIDER_REPLACE: &str = ">>>>>>> REPLACE";
+
+/// Line prefixes of aider's usage and housekeeping lines.
+const AIDER_STATUS: &[&str] = &["Tokens: ", "Cost: ", "Repo-map: "];
+
+fn aider(plain: &[u8]) -> Vec<Segment> {
+    let lines: Vec<&[u8]> = lines(plain).collect();
+    let mut builder = Builder::default();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let text = String::from_utf8_lossy(line);
+        let trimmed = text.trim();
+
+        if let Some(len) = edit_block_len(&lines[i..]) {
+            for (n, &line) in lines[i..i + len].iter().enumerate() {
+                builder.line(SegmentKind::EditBlock, n == 0, line);
+            }
+            i += len;
+            continue;
+        }
+
+        if is_aider_report(trimmed) {
+            builder.line(SegmentKind::ToolResult, false, line);
+        } else if AIDER_STATUS.iter().any(|p| trimmed.starts_with(p)) {
+            builder.line(SegmentKind::Status, false, line);
+        } else {
+            builder.line(SegmentKind::Prose, false, line);
+        }
+        i += 1;
+    }
+    builder.finish()
+}
+
+/// Whether a line is aider reporting what it did: an applied edit, a
+/// commit, or a file added to or removed from the chat.
+fn is_aider_report(line: &str) -> bool {
+    if line.starts_with("Applied edit to ") {
+        return true;
+    }
+    if let Some(rest) = line.strip_prefix("Commit ") {
+        let hash = rest.split(' ').next().unwrap_or_default();
+        return hash.len() >= 7 && hash.bytes().all(|b| b.is_ascii_hexdigit());
+    }
+    let line = line.trim_end_matches('.');
+    (line.starts_with("Added ") && line.ends_with(" to the chat"))
+        || (line.starts_with("Removed ") && line.ends_with(" from the chat"))
+}
+
+/// If `lines` starts with an edit block, its length in lines.
+///
+/// An edit block is a file name line, an opening fence, `<<<<<<<
+/// SEARCH`, the search text, `=======`, the replacement, `>>>>>>>
+/// REPLACE` and a closing fence. The file name and fences are
+/// optional — aider omits them in some edit formats — and an
+/// unterminated block runs to the end of the turn.
+fn edit_block_len(lines: &[&[u8]]) -> Option<usize> {
+    let is = |i: usize, pred: &dyn Fn(&str) -> bool| {
+        lines
+            .get(i)
+            .is_some_and(|line| pred(String::from_utf8_lossy(line).trim()))
+    };
+    let is_fence = |s: &str| s.starts_with("```");
+    // A single word not ending like a sentence.
+    let is_file = |s: &str| {
+        !s.is_empty()
+            && !s.contains(char::is_whitespace)
+            && !s.ends_with(['.', ':', ',', '!', '?'])
+            && !is_fence(s)
+    };
+
+    let search = [0, 1, 2]
+        .into_iter()
+        .find(|&i| is(i, &|s| s == AIDER_SEARCH))?;
+    let valid_header = match search {
+        0 => true,
+        1 => is(0, &is_fence) || is(0, &is_file),
+        _ => is(0, &is_file) && is(1, &is_fence),
+    };
+    if !valid_header {
+        return None;
+    }
+
+    let mut divided = false;
+    for i in search + 1..lines.len() {
+        if is(i, &|s| s == AIDER_DIVIDER) {
+            divided = true;
+        } else if divided && is(i, &|s| s == AIDER_REPLACE) {
+            let closing = search > 0 && is(search - 1, &is_fence) && is(i + 1, &is_fence);
+            return Some(i + 1 + usize::from(closing));
+        }
+    }
+    Some(lines.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(segments: &[Segment]) -> Vec<SegmentKind> {
+        segments.iter().map(|s| s.kind).collect()
+    }
+
+    fn text(segment: &Segment) -> &str {
+        std::str::from_utf8(&segment.content).unwrap()
+    }
+
+    #[test]
+    fn claude_turn_split() {
+        let plain = "⏺ I'll list the files.\n\
+                     \n\
+                     ⏺ Bash(ls -la)\n\
+                     \u{20} ⎿  total 8\n\
+                     \u{20}    a.txt\n\
+                     \n\
+                     ⏺ There is one file,\n\
+                     \u{20} a.txt.\n\
+                     \n\
+                     ✻ Thinking… (3s · esc to interrupt)\n";
+        let segments = Parser::Claude.segments(plain.as_bytes());
+        use SegmentKind::*;
+        assert_eq!(
+            kinds(&segments),
+            [Prose, ToolCall, ToolResult, Prose, Status]
+        );
+        assert_eq!(text(&segments[0]), "I'll list the files.\n");
+        assert_eq!(text(&segments[1]), "⏺ Bash(ls -la)\n");
+        assert_eq!(text(&segments[2]), "  ⎿  total 8\n     a.txt\n");
+        assert_eq!(text(&segments[3]), "There is one file,\n  a.txt.\n");
+    }
+
+    #[test]
+    fn claude_tool_call_names() {
+        assert!(is_tool_call("Read(src/main.rs)"));
+        assert!(is_tool_call("Update(Cargo.toml)"));
+        assert!(is_tool_call("mcp__github__search (MCP)(query: \"x\")"));
+        assert!(!is_tool_call("Sure, here it is."));
+        assert!(!is_tool_call("done (finally)"));
+        assert!(!is_tool_call("Bash"));
+    }
+
+    #[test]
+    fn claude_multi_line_prose_keeps_inner_blanks() {
+        let plain = "⏺ First paragraph.\n\nSecond paragraph.\n\n\n";
+        let segments = Parser::Claude.segments(plain.as_bytes());
+        assert_eq!(segments.len(), 1);
+        assert_eq!(
+            text(&segments[0]),
+            "First paragraph.\n\nSecond paragraph.\n"
+        );
+    }
+
+    #[test]
+    fn aider_edit_block() {
+        let plain = "I'll fix the typo.\n\
+                     \n\
+                     src/app.py\n\
+                     ```python\n\
+                     <<<<<<< SEARCH\n\
+                     print('helo')\n\
+                     =======\n\
+                     print('hello')\n\
+                     >>>>>>> REPLACE\n\
+                     ```\n\
+                     \n\
+                     Tokens: 2.1k sent, 120 received.\n\
+                     Applied edit to src/app.py\n\
+                     Commit 1a2b3c4 fix: typo\n";
+        let segments = Parser::Aider.segments(plain.as_bytes());
+        use SegmentKind::*;
+        assert_eq!(kinds(&segments), [Prose, EditBlock, Status, ToolResult]);
+        assert_eq!(text(&segments[0]), "I'll fix the typo.\n");
+        assert!(text(&segments[1]).starts_with("src/app.py\n```python\n<<<<<<< SEARCH\n"));
+        assert!(text(&segments[1]).ends_with(">>>>>>> REPLACE\n```\n"));
+        assert_eq!(
+            text(&segments[3]),
+            "Applied edit to src/app.py\nCommit 1a2b3c4 fix: typo\n"
+        );
+    }
+
+    #[test]
+    fn aider_consecutive_edit_blocks_stay_separate() {
+        let block = "a.py\n<<<<<<< SEARCH\nx\n=======\ny\n>>>>>>> REPLACE\n";
+        let plain = format!("{block}{block}");
+        let segments = Parser::Aider.segments(plain.as_bytes());
+        assert_eq!(segments.len(), 2);
+        assert_eq!(
+            view(&segments, SegmentKind::EditBlock),
+            format!("{block}\n{block}").as_bytes()
+        );
+    }
+
+    #[test]
+    fn aider_unterminated_edit_block_runs_to_end() {
+        let plain = b"Fixing.\n<<<<<<< SEARCH\nold\n=======\nnew\n";
+        let segments = Parser::Aider.segments(plain);
+        use SegmentKind::*;
+        assert_eq!(kinds(&segments), [Prose, EditBlock]);
+        assert_eq!(text(&segments[1]), "<<<<<<< SEARCH\nold\n=======\nnew\n");
+    }
+
+    #[test]
+    fn no_parser_is_all_prose() {
+        let all = segments(None, b"\nline 1\n\nline 2\n");
+        assert_eq!(kinds(&all), [SegmentKind::Prose]);
+        assert_eq!(text(&all[0]), "line 1\n\nline 2\n");
+        assert!(segments(None, b"").is_empty());
+    }
+
+    #[test]
+    fn view_joins_segments_of_one_kind() {
+        let plain = "⏺ One.\n⏺ Read(a)\n⏺ Two.\n";
+        let segments = Parser::Claude.segments(plain.as_bytes());
+        assert_eq!(view(&segments, SegmentKind::Prose), b"One.\n\nTwo.\n");
+        assert!(view(&segments, SegmentKind::EditBlock).is_empty());
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for name in NAMES {
+            assert!(Parser::from_name(name).is_some(), "{name}");
+        }
+        assert!(Parser::from_name("generic").is_none());
+    }
+}
@@ -1,22 +1,232 @@
 //! Prompt-pattern presets for common agents.
 //!
-//! Exact patterns are placeholders until validated against real agent
-//! output. Custom `--pattern` works as an escape hatch.
+//! Built-in patterns are placeholders until validated against real
+//! agent output. Users can add presets or override the built-ins in
+//! `$XDG_CONFIG_HOME/clippy/presets.toml`; custom `--pattern` remains
+//! the escape hatch.
 //!
 //! See CONTRACT_TURN.md §Presets.
 
-/// Returns the regex pattern string for a named preset, or `None` if
-/// the name is not a recognized preset (in which case it should be
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use super::{boundary, parsers};
+
+/// Errors loading the presets file.
+#[derive(Debug, thiserror::Error)]
+pub enum PresetError {
+    #[error("cannot read {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid presets file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("preset {name:?} in {path}: {reason}")]
+    Invalid {
+        path: PathBuf,
+        name: String,
+        reason: String,
+    },
+}
+
+/// A named prompt preset.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub name: String,
+    /// Prompt regex (CONTRACT_TURN.md §Pattern format).
+    pub pattern: String,
+    /// Boundary strategy spec, if the preset needs something other
+    /// than `prompt` (CONTRACT_TURN.md §Boundary Strategies).
+    #[serde(default)]
+    pub boundary: Option<String>,
+    /// Output parser splitting turns into segments
+    /// (CONTRACT_TURN.md §Segments), if the agent has one.
+    #[serde(default)]
+    pub parser: Option<String>,
+    /// Command basenames (argv[0]) this preset is selected for when
+    /// `wrap` is given no `--pattern`.
+    #[serde(default)]
+    pub commands: Vec<String>,
+}
+
+/// On-disk layout of `presets.toml`: a list of `[[preset]]` tables.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PresetFile {
+    #[serde(default)]
+    preset: Vec<Preset>,
+}
+
+/// The presets available to a session: user presets first, then the
+/// built-ins they do not override.
+#[derive(Debug, Clone)]
+pub struct PresetSet {
+    presets: Vec<Preset>,
+}
+
+impl PresetSet {
+    /// Built-in presets only.
+    pub fn builtin() -> Self {
+        let presets = BUILTIN
+            .iter()
+            .map(|&(name, pattern, parser, commands)| Preset {
+                name: name.into(),
+                pattern: pattern.into(),
+                boundary: None,
+                parser: parser.map(Into::into),
+                commands: commands.iter().map(|&c| c.into()).collect(),
+            })
+            .collect();
+        Self { presets }
+    }
+
+    /// Built-ins merged with the user's presets file, if it exists.
+    pub fn load() -> Result<Self, PresetError> {
+        match config_path() {
+            Some(path) => Self::load_file(&path),
+            None => Ok(Self::builtin()),
+        }
+    }
+
+    /// Built-ins merged with the presets file at `path`. A missing
+    /// file is not an error.
+    pub fn load_file(path: &Path) -> Result<Self, PresetError> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::builtin()),
+            Err(source) => {
+                return Err(PresetError::Read {
+                    path: path.to_path_buf(),
+                    source,
+                });
+            }
+        };
+        Self::parse(&text, path)
+    }
+
+    /// Parse presets file contents. `path` is used for diagnostics.
+    fn parse(text: &str, path: &Path) -> Result<Self, PresetError> {
+        let file: PresetFile = toml::from_str(text).map_err(|source| PresetError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let invalid = |name: &str, reason: String| PresetError::Invalid {
+            path: path.to_path_buf(),
+            name: name.to_string(),
+            reason,
+        };
+        for (i, preset) in file.preset.iter().enumerate() {
+            if preset.name.is_empty() {
+                return Err(invalid("", "name must not be empty".into()));
+            }
+            if file.preset[..i].iter().any(|p| p.name == preset.name) {
+                return Err(invalid(&preset.name, "defined twice".into()));
+            }
+            Regex::new(&preset.pattern).map_err(|e| invalid(&preset.name, e.to_string()))?;
+            if let Some(spec) = &preset.boundary {
+                boundary::parse(spec).map_err(|e| invalid(&preset.name, e.to_string()))?;
+            }
+            if let Some(parser) = &preset.parser
+                && parsers::Parser::from_name(parser).is_none()
+            {
+                let known = parsers::NAMES.join(", ");
+                return Err(invalid(
+                    &preset.name,
+                    format!("unknown parser {parser:?} (known: {known})"),
+                ));
+            }
+        }
+
+        let mut presets = file.preset;
+        let builtin: Vec<Preset> = Self::builtin()
+            .presets
+            .into_iter()
+            .filter(|b| !presets.iter().any(|p| p.name == b.name))
+            .collect();
+        presets.extend(builtin);
+        Ok(Self { presets })
+    }
+
+    /// Look up a preset by name.
+    pub fn get(&self, name: &str) -> Option<&Preset> {
+        self.presets.iter().find(|p| p.name == name)
+    }
+
+    /// The preset for a command, chosen by the basename of `argv0`.
+    pub fn for_command(&self, argv0: &str) -> Option<&Preset> {
+        let basename = Path::new(argv0).file_name()?.to_str()?;
+        self.presets
+            .iter()
+            .find(|p| p.commands.iter().any(|c| c == basename))
+    }
+
+    /// Resolve a session's preset (CONTRACT_TURN.md §Configuration).
+    ///
+    /// An explicit `pattern` names a preset or is a custom regex
+    /// (returned as an anonymous preset named by the regex itself).
+    /// Without one, the preset for `argv0`'s basename is used, falling
+    /// back to `generic`.
+    pub fn resolve(&self, pattern: Option<&str>, argv0: &str) -> Preset {
+        let preset = match pattern {
+            Some(pattern) => self.get(pattern),
+            None => self.for_command(argv0).or_else(|| self.get("generic")),
+        };
+        preset.cloned().unwrap_or_else(|| {
+            let pattern = pattern.unwrap_or(GENERIC);
+            Preset {
+                name: pattern.into(),
+                pattern: pattern.into(),
+                boundary: None,
+                parser: None,
+                commands: Vec::new(),
+            }
+        })
+    }
+}
+
+/// `$XDG_CONFIG_HOME/clippy/presets.toml`, falling back to
+/// `$HOME/.config` when `XDG_CONFIG_HOME` is unset.
+pub fn config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("clippy").join("presets.toml"))
+}
+
+/// Returns the regex pattern string for a named built-in preset, or
+/// `None` if the name is not a built-in (in which case it should be
 /// treated as a custom regex).
 pub fn preset_pattern(name: &str) -> Option<&'static str> {
-    match name {
-        "claude" => Some(CLAUDE),
-        "aider" => Some(AIDER),
-        "generic" => Some(GENERIC),
-        _ => None,
-    }
+    BUILTIN
+        .iter()
+        .find(|&&(preset, ..)| preset == name)
+        .map(|&(_, pattern, ..)| pattern)
 }
 
+/// Built-in presets: name, pattern, parser, commands.
+type Builtin = (
+    &'static str,
+    &'static str,
+    Option<&'static str>,
+    &'static [&'static str],
+);
+
+const BUILTIN: &[Builtin] = &[
+    ("claude", CLAUDE, Some("claude"), &["claude"]),
+    ("aider", AIDER, Some("aider"), &["aider"]),
+    ("generic", GENERIC, None, &[]),
+    ("osc133", OSC133, None, &[]),
+];
+
 /// Claude Code CLI prompt pattern.
 ///
 /// Placeholder — needs validation against real Claude Code output.
@@ -37,16 +247,29 @@ const AIDER: &str = r"(?:^|\n)[\w/.-]*>\s*$";
 /// Deliberately broad — intended as a fallback.
 const GENERIC: &str = r"[>$#%]\s*$";
 
+/// Semantic prompt markers only.
+///
+/// Matches no line: turns are delimited by OSC 133 / 633 markers
+/// (CONTRACT_TURN.md §Semantic Prompt Markers), which every pattern
+/// honours once seen. Use for shells and agents with shell
+/// integration, where a textual prompt match could only misfire.
+const OSC133: &str = r"\b\B";
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use regex::Regex;
 
+    fn parse(text: &str) -> Result<PresetSet, PresetError> {
+        PresetSet::parse(text, Path::new("presets.toml"))
+    }
+
     #[test]
     fn known_presets_resolve() {
         assert!(preset_pattern("claude").is_some());
         assert!(preset_pattern("aider").is_some());
         assert!(preset_pattern("generic").is_some());
+        assert!(preset_pattern("osc133").is_some());
     }
 
     #[test]
@@ -57,7 +280,7 @@ mod tests {
 
     #[test]
     fn preset_patterns_are_valid_regex() {
-        for name in &["claude", "aider", "generic"] {
+        for name in &["claude", "aider", "generic", "osc133"] {
             let pattern = preset_pattern(name).unwrap();
             Regex::new(pattern).unwrap_or_else(|e| {
                 panic!("preset '{name}' has invalid regex: {e}");
@@ -74,10 +297,130 @@ mod tests {
         assert!(re.is_match("user@host:~$ "));
     }
 
+    #[test]
+    fn osc133_matches_nothing() {
+        let re = Regex::new(preset_pattern("osc133").unwrap()).unwrap();
+        for line in ["", "$ ", "> ", "hello world"] {
+            assert!(!re.is_match(line), "{line:?}");
+        }
+    }
+
     #[test]
     fn generic_does_not_match_plain_text() {
         let re = Regex::new(preset_pattern("generic").unwrap()).unwrap();
         assert!(!re.is_match("hello world"));
         assert!(!re.is_match("no prompt here"));
     }
+
+    // -- Presets file --
+
+    #[test]
+    fn builtin_selected_by_command_basename() {
+        let set = PresetSet::builtin();
+        assert_eq!(set.for_command("claude").unwrap().name, "claude");
+        assert_eq!(
+            set.for_command("/usr/local/bin/aider").unwrap().name,
+            "aider"
+        );
+        assert!(set.for_command("bash").is_none());
+    }
+
+    #[test]
+    fn builtin_parsers() {
+        let set = PresetSet::builtin();
+        assert_eq!(set.get("claude").unwrap().parser.as_deref(), Some("claude"));
+        assert_eq!(set.get("aider").unwrap().parser.as_deref(), Some("aider"));
+        assert_eq!(set.get("generic").unwrap().parser, None);
+    }
+
+    #[test]
+    fn resolve_prefers_explicit_pattern() {
+        let set = PresetSet::builtin();
+        assert_eq!(set.resolve(Some("aider"), "claude").name, "aider");
+
+        let custom = set.resolve(Some(r"^my> $"), "claude");
+        assert_eq!(custom.pattern, r"^my> $");
+        assert_eq!(custom.boundary, None);
+    }
+
+    #[test]
+    fn resolve_falls_back_to_command_then_generic() {
+        let set = PresetSet::builtin();
+        assert_eq!(set.resolve(None, "/opt/bin/claude").name, "claude");
+        assert_eq!(set.resolve(None, "my-agent").name, "generic");
+    }
+
+    #[test]
+    fn file_adds_presets() {
+        let set = parse(
+            r#"
+            [[preset]]
+            name = "codex"
+            pattern = '^› $'
+            boundary = "prompt+quiet:200"
+            commands = ["codex", "codex-cli"]
+            "#,
+        )
+        .unwrap();
+        let codex = set.for_command("codex-cli").unwrap();
+        assert_eq!(codex.name, "codex");
+        assert_eq!(codex.boundary.as_deref(), Some("prompt+quiet:200"));
+        assert_eq!(codex.parser, None);
+        assert!(set.get("claude").is_some(), "built-ins remain");
+    }
+
+    #[test]
+    fn file_overrides_builtin() {
+        let set = parse(
+            r#"
+            [[preset]]
+            name = "claude"
+            pattern = '^> $'
+            commands = ["claude", "claude-dev"]
+            "#,
+        )
+        .unwrap();
+        let claude = set.get("claude").unwrap();
+        assert_eq!(claude.pattern, "^> $");
+        assert_eq!(set.for_command("claude-dev").unwrap().name, "claude");
+        assert_eq!(set.presets.iter().filter(|p| p.name == "claude").count(), 1);
+    }
+
+    #[test]
+    fn empty_file_yields_builtins() {
+        let set = parse("").unwrap();
+        assert_eq!(set.presets.len(), BUILTIN.len());
+    }
+
+    #[test]
+    fn invalid_presets_rejected() {
+        let bad_regex = "[[preset]]\nname = 'x'\npattern = '(unclosed'\n";
+        assert!(matches!(parse(bad_regex), Err(PresetError::Invalid { .. })));
+
+        let bad_boundary = "[[preset]]\nname = 'x'\npattern = 'x'\nboundary = 'soon'\n";
+        assert!(matches!(
+            parse(bad_boundary),
+            Err(PresetError::Invalid { .. })
+        ));
+
+        let duplicate = "[[preset]]\nname = 'x'\npattern = 'x'\n\
+                         [[preset]]\nname = 'x'\npattern = 'y'\n";
+        assert!(matches!(parse(duplicate), Err(PresetError::Invalid { .. })));
+
+        let bad_parser = "[[preset]]\nname = 'x'\npattern = 'x'\nparser = 'codex'\n";
+        assert!(matches!(
+            parse(bad_parser),
+            Err(PresetError::Invalid { .. })
+        ));
+
+        let unknown_key = "[[preset]]\nname = 'x'\npattern = 'x'\nregex = 'y'\n";
+        assert!(matches!(parse(unknown_key), Err(PresetError::Parse { .. })));
+    }
+
+    #[test]
+    fn missing_file_yields_builtins() {
+        let dir = tempfile::tempdir().unwrap();
+        let set = PresetSet::load_file(&dir.path().join("presets.toml")).unwrap();
+        assert_eq!(set.presets.len(), BUILTIN.len());
+    }
 }
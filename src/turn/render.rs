@@ -0,0 +1,217 @@
+//! Single-line visible-text rendering.
+//!
+//! Resolves what a terminal would display for one line of output:
+//! carriage-return overwrites, backspaces, erase-in-line and
+//! horizontal cursor movement are applied; all other escape sequences
+//! are dropped. Used to compare echoed input against what the user
+//! typed, independent of how the agent drew it, and to derive the
+//! plain-text rendition of a turn.
+
+use super::ansi::{Token, csi_numbers, tokenize};
+
+/// Render one line of raw output to the text a terminal would show.
+///
+/// Newlines are ignored — callers split lines beforehand. TAB is kept
+/// as a literal single-cell character so that content is preserved.
+pub fn render_line(raw: &[u8]) -> String {
+    let mut cells: Vec<char> = Vec::new();
+    let mut cursor = 0usize;
+
+    for token in tokenize(raw) {
+        match token {
+            Token::Text(c) => {
+                if cursor < cells.len() {
+                    cells[cursor] = c;
+                } else {
+                    cells.resize(cursor, ' ');
+                    cells.push(c);
+                }
+                cursor += 1;
+            }
+            Token::Control(b'\r') => cursor = 0,
+            Token::Control(0x08) => cursor = cursor.saturating_sub(1),
+            Token::Control(_) => {}
+            Token::Csi { params, final_byte } => {
+                let nums = csi_numbers(params);
+                let first = nums.first().copied().unwrap_or(0) as usize;
+                let count = first.max(1);
+                match final_byte {
+                    // EL — erase in line.
+                    b'K' => match first {
+                        0 => cells.truncate(cursor),
+                        1 => {
+                            let end = (cursor + 1).min(cells.len());
+                            cells[..end].fill(' ');
+                        }
+                        _ => cells.clear(),
+                    },
+                    // CUF / CUB — cursor forward / back.
+                    b'C' => cursor += count,
+                    b'D' => cursor = cursor.saturating_sub(count),
+                    // CHA — cursor horizontal absolute (1-based).
+                    b'G' => cursor = count - 1,
+                    // DCH — delete characters.
+                    b'P' => {
+                        if cursor < cells.len() {
+                            let end = (cursor + count).min(cells.len());
+                            cells.drain(cursor..end);
+                        }
+                    }
+                    // ICH — insert blank characters.
+                    b'@' => {
+                        if cursor < cells.len() {
+                            for _ in 0..count {
+                                cells.insert(cursor, ' ');
+                            }
+                        }
+                    }
+                    // ECH — erase characters.
+                    b'X' => {
+                        let end = (cursor + count).min(cells.len());
+                        if cursor < end {
+                            cells[cursor..end].fill(' ');
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Token::Osc(_) | Token::Esc { .. } => {}
+        }
+    }
+
+    cells.into_iter().collect()
+}
+
+/// Render a whole turn to plain text (CONTRACT_TURN.md §Plain content).
+///
+/// Each `\n`-separated line is resolved with [`render_line`] and its
+/// trailing whitespace trimmed; trailing blank lines are dropped. Every
+/// remaining line ends in `\n`. Unlike the screen rendering, no
+/// terminal size is involved and cursor movement never crosses lines.
+pub fn plain_text(raw: &[u8]) -> Vec<u8> {
+    let mut lines: Vec<String> = raw
+        .split(|&b| b == b'\n')
+        .map(|line| render_line(line).trim_end().to_string())
+        .collect();
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+
+    let mut out = Vec::with_capacity(raw.len());
+    for line in lines {
+        out.extend_from_slice(line.as_bytes());
+        out.push(b'\n');
+    }
+    out
+}
+
+/// A prompt and its answer as one text (CONTRACT_TURN.md §Exchange).
+///
+/// Each prompt line is quoted with `> ` (a bare `>` for an empty
+/// line), followed by a blank line and the answer. Without a prompt
+/// this is just the answer.
+pub fn exchange(prompt: Option<&[u8]>, answer: &[u8]) -> Vec<u8> {
+    let Some(prompt) = prompt else {
+        return answer.to_vec();
+    };
+    let mut out = Vec::with_capacity(prompt.len() + answer.len() + 16);
+    for line in prompt.split(|&b| b == b'\n') {
+        if line.is_empty() {
+            out.extend_from_slice(b">\n");
+        } else {
+            out.extend_from_slice(b"> ");
+            out.extend_from_slice(line);
+            out.push(b'\n');
+        }
+    }
+    out.push(b'\n');
+    out.extend_from_slice(answer);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unstyled_text() {
+        assert_eq!(render_line(b"hello"), "hello");
+    }
+
+    #[test]
+    fn sgr_dropped() {
+        assert_eq!(render_line(b"\x1b[1;32mok\x1b[0m"), "ok");
+    }
+
+    #[test]
+    fn carriage_return_overwrites() {
+        assert_eq!(render_line(b"loading...\rdone"), "doneing...");
+        assert_eq!(render_line(b"loading...\r\x1b[Kdone"), "done");
+    }
+
+    #[test]
+    fn canonical_backspace_echo() {
+        // Terminal echo for "helk<BS>lo": BS, space, BS.
+        assert_eq!(render_line(b"helk\x08 \x08lo"), "hello");
+    }
+
+    #[test]
+    fn erase_whole_line_then_redraw() {
+        assert_eq!(render_line(b"> h\r\x1b[2K> he\r\x1b[2K> hey"), "> hey");
+    }
+
+    #[test]
+    fn cursor_movement() {
+        assert_eq!(render_line(b"abc\x1b[2Dx"), "axc");
+        assert_eq!(render_line(b"abc\x1b[1Gz"), "zbc");
+        assert_eq!(render_line(b"a\x1b[3Cb"), "a   b");
+    }
+
+    #[test]
+    fn delete_insert_erase_chars() {
+        assert_eq!(render_line(b"abcdef\x1b[4D\x1b[2P"), "abef");
+        assert_eq!(render_line(b"abc\x1b[2D\x1b[@"), "a bc");
+        assert_eq!(render_line(b"abcdef\x1b[4D\x1b[2X"), "ab  ef");
+    }
+
+    #[test]
+    fn erase_to_start() {
+        assert_eq!(render_line(b"abcdef\x1b[3D\x1b[1K"), "    ef");
+    }
+
+    #[test]
+    fn tab_kept_literal() {
+        assert_eq!(render_line(b"a\tb"), "a\tb");
+    }
+
+    #[test]
+    fn plain_text_resolves_each_line() {
+        let raw = b"\x1b[1mTitle\x1b[0m  \r\n50%\r\x1b[2K100%\r\nabk\x08c\r\n";
+        assert_eq!(plain_text(raw), b"Title\n100%\nabc\n");
+    }
+
+    #[test]
+    fn plain_text_keeps_inner_blank_lines() {
+        assert_eq!(plain_text(b"a\r\n\r\n  \r\nb\r\n\r\n\r\n"), b"a\n\n\nb\n");
+    }
+
+    #[test]
+    fn plain_text_unterminated_last_line() {
+        assert_eq!(plain_text(b"one\ntwo"), b"one\ntwo\n");
+    }
+
+    #[test]
+    fn plain_text_empty() {
+        assert_eq!(plain_text(b""), b"");
+        assert_eq!(plain_text(b"\x1b[0m\r\n \r\n"), b"");
+    }
+
+    #[test]
+    fn exchange_quotes_prompt() {
+        assert_eq!(
+            exchange(Some(b"fix it\n\nplease"), b"Done.\n"),
+            b"> fix it\n>\n> please\n\nDone.\n"
+        );
+        assert_eq!(exchange(None, b"Done.\n"), b"Done.\n");
+    }
+}
@@ -0,0 +1,671 @@
+//! Virtual-terminal screen model — renders turn bytes to visible text.
+//!
+//! Replays raw turn content through a VT100/xterm-style screen with
+//! scrollback, so that cursor movement, erase sequences, carriage-return
+//! overwrites and spinner frames collapse into the text a human
+//! actually saw. See CONTRACT_TURN.md §Rendered content.
+//!
+//! Scope is deliberately narrow: attributes (SGR), character sets,
+//! mouse/keyboard modes and wide-character widths are ignored. Every
+//! character occupies one cell.
+
+use std::collections::VecDeque;
+
+use super::ansi::{Token, complete_len, csi_numbers, tokenize};
+
+/// Maximum number of lines retained in scrollback. Older lines are
+/// discarded (the raw bytes remain authoritative).
+const MAX_SCROLLBACK: usize = 100_000;
+
+/// Tab stop interval.
+const TAB_WIDTH: usize = 8;
+
+/// Longest incomplete tail held back by [`Screen::feed_partial`]; a
+/// longer one (e.g. a runaway OSC string) is fed, and dropped, as is.
+const MAX_PENDING: usize = 4096;
+
+/// Terminal dimensions used to replay a turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenSize {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+impl ScreenSize {
+    /// Build a size, substituting the default for zero dimensions
+    /// (unknown size, e.g. from a wrapper that did not report one).
+    pub fn or_default(cols: u16, rows: u16) -> Self {
+        let default = Self::default();
+        Self {
+            cols: if cols == 0 { default.cols } else { cols },
+            rows: if rows == 0 { default.rows } else { rows },
+        }
+    }
+}
+
+impl Default for ScreenSize {
+    fn default() -> Self {
+        Self { cols: 80, rows: 24 }
+    }
+}
+
+/// One screen row.
+#[derive(Debug, Clone)]
+struct Row {
+    cells: Vec<char>,
+    /// Whether the row continues onto the next one (auto-wrap), so the
+    /// two are one logical line.
+    wrapped: bool,
+}
+
+impl Row {
+    fn blank(cols: usize) -> Self {
+        Self {
+            cells: vec![' '; cols],
+            wrapped: false,
+        }
+    }
+
+    fn text(&self) -> String {
+        let text: String = self.cells.iter().collect();
+        text.trim_end_matches(' ').to_string()
+    }
+
+    fn is_blank(&self) -> bool {
+        self.cells.iter().all(|&c| c == ' ')
+    }
+}
+
+/// Saved cursor state (DECSC / DECRC).
+#[derive(Debug, Clone, Copy, Default)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+}
+
+/// A VT screen: visible grid, scrollback and cursor.
+#[derive(Debug)]
+pub struct Screen {
+    cols: usize,
+    rows: usize,
+    grid: Vec<Row>,
+    scrollback: VecDeque<Row>,
+    /// Main-screen grid stashed while the alternate screen is active.
+    main_grid: Option<Vec<Row>>,
+    row: usize,
+    col: usize,
+    /// Cursor is past the last column; the next printable wraps first.
+    wrap_pending: bool,
+    autowrap: bool,
+    /// Scroll region, inclusive, 0-based.
+    scroll_top: usize,
+    scroll_bottom: usize,
+    saved: SavedCursor,
+    /// Incomplete escape sequence or character held back by
+    /// [`feed_partial`](Self::feed_partial).
+    pending: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new(size: ScreenSize) -> Self {
+        let cols = usize::from(size.cols.max(1));
+        let rows = usize::from(size.rows.max(1));
+        Self {
+            cols,
+            rows,
+            grid: vec![Row::blank(cols); rows],
+            scrollback: VecDeque::new(),
+            main_grid: None,
+            row: 0,
+            col: 0,
+            wrap_pending: false,
+            autowrap: true,
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            saved: SavedCursor::default(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Feed one chunk of a live output stream.
+    ///
+    /// Unlike [`feed`](Self::feed), an escape sequence or UTF-8
+    /// character cut off at the end of `data` is held back and
+    /// completed by the next chunk.
+    pub fn feed_partial(&mut self, data: &[u8]) {
+        if self.pending.is_empty() {
+            let end = complete_len(data);
+            self.feed(&data[..end]);
+            self.pending.extend_from_slice(&data[end..]);
+        } else {
+            let mut buf = std::mem::take(&mut self.pending);
+            buf.extend_from_slice(data);
+            let end = complete_len(&buf);
+            self.feed(&buf[..end]);
+            buf.drain(..end);
+            self.pending = buf;
+        }
+        if self.pending.len() > MAX_PENDING {
+            let pending = std::mem::take(&mut self.pending);
+            self.feed(&pending);
+        }
+    }
+
+    /// The visible rows, top to bottom, with trailing spaces trimmed.
+    /// Scrollback is not included.
+    pub fn rows(&self) -> Vec<String> {
+        self.grid.iter().map(Row::text).collect()
+    }
+
+    /// The cursor position on the visible screen: row and column,
+    /// 0-based.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// Replay a complete buffer of terminal output.
+    ///
+    /// An escape sequence truncated at the end of `data` is dropped.
+    pub fn feed(&mut self, data: &[u8]) {
+        for token in tokenize(data) {
+            match token {
+                Token::Text('\t') => self.tab(),
+                Token::Text(c) => self.print(c),
+                Token::Control(byte) => self.control(byte),
+                Token::Csi { params, final_byte } => self.csi(params, final_byte),
+                Token::Esc {
+                    intermediates,
+                    final_byte,
+                } => self.esc(intermediates, final_byte),
+                Token::Osc(_) => {}
+            }
+        }
+    }
+
+    /// The visible text: scrollback followed by the screen, one logical
+    /// line per `\n`. Soft-wrapped rows are joined, trailing spaces are
+    /// trimmed and trailing blank lines dropped.
+    pub fn text(&self) -> String {
+        let rows = self.scrollback.iter().chain(self.grid.iter());
+        let mut lines: Vec<String> = Vec::new();
+        let mut continuing = false;
+        for row in rows {
+            // A wrapped row keeps its full width so that spaces at the
+            // wrap point survive the join.
+            let text = if row.wrapped {
+                row.cells.iter().collect()
+            } else {
+                row.text()
+            };
+            match lines.last_mut() {
+                Some(last) if continuing => last.push_str(&text),
+                _ => lines.push(text),
+            }
+            continuing = row.wrapped;
+        }
+        for line in &mut lines {
+            line.truncate(line.trim_end_matches(' ').len());
+        }
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        let mut out = lines.join("\n");
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+
+    // -- Printing and controls --
+
+    fn print(&mut self, c: char) {
+        if self.wrap_pending {
+            self.wrap_pending = false;
+            if self.autowrap {
+                self.grid[self.row].wrapped = true;
+                self.col = 0;
+                self.line_feed();
+            }
+        }
+        self.grid[self.row].cells[self.col] = c;
+        if self.col + 1 < self.cols {
+            self.col += 1;
+        } else {
+            self.wrap_pending = true;
+        }
+    }
+
+    fn tab(&mut self) {
+        let next = (self.col / TAB_WIDTH + 1) * TAB_WIDTH;
+        self.col = next.min(self.cols - 1);
+        self.wrap_pending = false;
+    }
+
+    fn control(&mut self, byte: u8) {
+        match byte {
+            b'\r' => self.carriage_return(),
+            // LF, VT, FF all perform a line feed.
+            b'\n' | 0x0B | 0x0C => {
+                self.wrap_pending = false;
+                self.line_feed();
+            }
+            0x08 => {
+                self.wrap_pending = false;
+                self.col = self.col.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+
+    fn carriage_return(&mut self) {
+        self.col = 0;
+        self.wrap_pending = false;
+    }
+
+    /// Move down one row, scrolling the region if at its bottom.
+    fn line_feed(&mut self) {
+        if self.row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.row + 1 < self.rows {
+            self.row += 1;
+        }
+    }
+
+    /// Move up one row, scrolling the region down if at its top.
+    fn reverse_index(&mut self) {
+        if self.row == self.scroll_top {
+            self.scroll_down(1);
+        } else {
+            self.row = self.row.saturating_sub(1);
+        }
+    }
+
+    /// Scroll the region up by `n` rows. Rows leaving the top of a
+    /// full-screen scroll region on the main screen enter scrollback.
+    fn scroll_up(&mut self, n: usize) {
+        let to_scrollback =
+            self.scroll_top == 0 && self.scroll_bottom == self.rows - 1 && self.main_grid.is_none();
+        for row in self.remove_rows(self.scroll_top, n) {
+            if to_scrollback {
+                self.push_scrollback(row);
+            }
+        }
+    }
+
+    /// Scroll the region down by `n` rows (blank rows enter at top).
+    fn scroll_down(&mut self, n: usize) {
+        self.insert_rows(self.scroll_top, n);
+    }
+
+    /// Remove up to `n` rows at `at`, shifting the rest of the scroll
+    /// region up and filling its bottom with blank rows.
+    fn remove_rows(&mut self, at: usize, n: usize) -> Vec<Row> {
+        let n = n.min(self.scroll_bottom + 1 - at);
+        let removed: Vec<Row> = self.grid.drain(at..at + n).collect();
+        let bottom = self.scroll_bottom + 1 - n;
+        self.grid
+            .splice(bottom..bottom, vec![Row::blank(self.cols); n]);
+        removed
+    }
+
+    /// Insert up to `n` blank rows at `at`, pushing rows off the bottom
+    /// of the scroll region.
+    fn insert_rows(&mut self, at: usize, n: usize) {
+        let n = n.min(self.scroll_bottom + 1 - at);
+        self.grid
+            .drain(self.scroll_bottom + 1 - n..=self.scroll_bottom);
+        self.grid.splice(at..at, vec![Row::blank(self.cols); n]);
+    }
+
+    fn push_scrollback(&mut self, row: Row) {
+        self.scrollback.push_back(row);
+        if self.scrollback.len() > MAX_SCROLLBACK {
+            self.scrollback.pop_front();
+        }
+    }
+
+    fn in_scroll_region(&self) -> bool {
+        (self.scroll_top..=self.scroll_bottom).contains(&self.row)
+    }
+
+    // -- Escape sequences --
+
+    fn esc(&mut self, intermediates: &[u8], final_byte: u8) {
+        if !intermediates.is_empty() {
+            // Charset designation and similar — no visible effect.
+            return;
+        }
+        match final_byte {
+            b'7' => self.save_cursor(),
+            b'8' => self.restore_cursor(),
+            // IND — index.
+            b'D' => self.line_feed(),
+            // NEL — next line.
+            b'E' => {
+                self.carriage_return();
+                self.line_feed();
+            }
+            // RI — reverse index.
+            b'M' => self.reverse_index(),
+            // RIS — full reset. Scrollback is what the user already saw.
+            b'c' => {
+                let scrollback = std::mem::take(&mut self.scrollback);
+                let pending = std::mem::take(&mut self.pending);
+                *self = Self::new(ScreenSize {
+                    cols: self.cols as u16,
+                    rows: self.rows as u16,
+                });
+                self.scrollback = scrollback;
+                self.pending = pending;
+            }
+            _ => {}
+        }
+    }
+
+    fn csi(&mut self, params: &[u8], final_byte: u8) {
+        let private = params.first() == Some(&b'?');
+        let nums = csi_numbers(params);
+        let arg = |i: usize| nums.get(i).copied().unwrap_or(0) as usize;
+        // Count-style parameter: absent or zero means one.
+        let count = arg(0).max(1);
+
+        if private {
+            match final_byte {
+                b'h' => nums.iter().for_each(|&m| self.set_private_mode(m, true)),
+                b'l' => nums.iter().for_each(|&m| self.set_private_mode(m, false)),
+                _ => {}
+            }
+            return;
+        }
+
+        match final_byte {
+            // CUU / CUD / CUF / CUB.
+            b'A' => self.move_to(self.row.saturating_sub(count), self.col),
+            b'B' => self.move_to(self.row + count, self.col),
+            b'C' => self.move_to(self.row, self.col + count),
+            b'D' => self.move_to(self.row, self.col.saturating_sub(count)),
+            // CNL / CPL.
+            b'E' => self.move_to(self.row + count, 0),
+            b'F' => self.move_to(self.row.saturating_sub(count), 0),
+            // CHA / HPA.
+            b'G' | b'`' => self.move_to(self.row, count - 1),
+            // VPA.
+            b'd' => self.move_to(count - 1, self.col),
+            // CUP / HVP.
+            b'H' | b'f' => self.move_to(arg(0).max(1) - 1, arg(1).max(1) - 1),
+            b'J' => self.erase_display(arg(0)),
+            b'K' => self.erase_line(arg(0)),
+            // IL / DL — only inside the scroll region. Deleted lines
+            // never enter scrollback.
+            b'L' if self.in_scroll_region() => self.insert_rows(self.row, count),
+            b'M' if self.in_scroll_region() => {
+                self.remove_rows(self.row, count);
+            }
+            // DCH / ICH / ECH.
+            b'P' => {
+                let cells = &mut self.grid[self.row].cells;
+                let end = (self.col + count).min(self.cols);
+                cells.drain(self.col..end);
+                cells.resize(self.cols, ' ');
+            }
+            b'@' => {
+                let cells = &mut self.grid[self.row].cells;
+                for _ in 0..count.min(self.cols - self.col) {
+                    cells.insert(self.col, ' ');
+                }
+                cells.truncate(self.cols);
+            }
+            b'X' => {
+                let end = (self.col + count).min(self.cols);
+                self.grid[self.row].cells[self.col..end].fill(' ');
+            }
+            // SU / SD.
+            b'S' => self.scroll_up(count),
+            b'T' => self.scroll_down(count),
+            // DECSTBM — set scroll region; cursor homes.
+            b'r' => {
+                let top = arg(0).max(1) - 1;
+                let bottom = if arg(1) == 0 { self.rows } else { arg(1) }.min(self.rows) - 1;
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            // SCOSC / SCORC.
+            b's' => self.save_cursor(),
+            b'u' => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    fn set_private_mode(&mut self, mode: u16, enable: bool) {
+        match mode {
+            // DECAWM — auto-wrap.
+            7 => self.autowrap = enable,
+            // Alternate screen buffer (1049 also saves/restores cursor).
+            47 | 1047 | 1049 => {
+                if enable && self.main_grid.is_none() {
+                    if mode == 1049 {
+                        self.save_cursor();
+                    }
+                    let alt = vec![Row::blank(self.cols); self.rows];
+                    self.main_grid = Some(std::mem::replace(&mut self.grid, alt));
+                } else if !enable && let Some(main) = self.main_grid.take() {
+                    self.grid = main;
+                    if mode == 1049 {
+                        self.restore_cursor();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.row = row.min(self.rows - 1);
+        self.col = col.min(self.cols - 1);
+        self.wrap_pending = false;
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved = SavedCursor {
+            row: self.row,
+            col: self.col,
+        };
+    }
+
+    fn restore_cursor(&mut self) {
+        let SavedCursor { row, col } = self.saved;
+        self.move_to(row, col);
+    }
+
+    fn erase_line(&mut self, mode: usize) {
+        let row = &mut self.grid[self.row];
+        match mode {
+            0 => {
+                row.cells[self.col..].fill(' ');
+                row.wrapped = false;
+            }
+            1 => row.cells[..=self.col].fill(' '),
+            _ => {
+                row.cells.fill(' ');
+                row.wrapped = false;
+            }
+        }
+    }
+
+    fn erase_display(&mut self, mode: usize) {
+        match mode {
+            0 => {
+                self.erase_line(0);
+                for row in &mut self.grid[self.row + 1..] {
+                    *row = Row::blank(self.cols);
+                }
+            }
+            1 => {
+                self.erase_line(1);
+                for row in &mut self.grid[..self.row] {
+                    *row = Row::blank(self.cols);
+                }
+            }
+            // 2 — whole screen. What was visible stays in the record
+            // the user saw: push non-blank rows to scrollback first
+            // (as xterm does on the main screen).
+            2 => {
+                let grid =
+                    std::mem::replace(&mut self.grid, vec![Row::blank(self.cols); self.rows]);
+                if self.main_grid.is_none()
+                    && let Some(last) = grid.iter().rposition(|r| !r.is_blank())
+                {
+                    for row in grid.into_iter().take(last + 1) {
+                        self.push_scrollback(row);
+                    }
+                }
+            }
+            // 3 — clear scrollback.
+            3 => self.scrollback.clear(),
+            _ => {}
+        }
+    }
+}
+
+/// Render raw turn content to the visible text, as UTF-8 bytes.
+pub fn render(content: &[u8], size: ScreenSize) -> Vec<u8> {
+    let mut screen = Screen::new(size);
+    screen.feed(content);
+    screen.text().into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_str(content: &[u8], cols: u16, rows: u16) -> String {
+        String::from_utf8(render(content, ScreenSize { cols, rows })).unwrap()
+    }
+
+    #[test]
+    fn plain_lines() {
+        assert_eq!(render_str(b"one\r\ntwo\r\n", 80, 24), "one\ntwo\n");
+    }
+
+    #[test]
+    fn empty_content() {
+        assert_eq!(render_str(b"", 80, 24), "");
+        assert_eq!(render_str(b"\x1b[1m\x1b[0m", 80, 24), "");
+    }
+
+    #[test]
+    fn bare_line_feed_keeps_column() {
+        assert_eq!(render_str(b"ab\ncd\r\n", 80, 24), "ab\n  cd\n");
+    }
+
+    #[test]
+    fn spinner_frames_collapse() {
+        let raw = b"\xe2\xa0\x8b Thinking\r\xe2\xa0\x99 Thinking\r\x1b[2K\rDone.\r\n";
+        assert_eq!(render_str(raw, 80, 24), "Done.\n");
+    }
+
+    #[test]
+    fn redrawn_line_with_cursor_up() {
+        // Progress line rewritten in place via cursor-up + erase-line.
+        let raw = b"progress 10%\r\n\x1b[1A\x1b[2Kprogress 100%\r\nresult\r\n";
+        assert_eq!(render_str(raw, 80, 24), "progress 100%\nresult\n");
+    }
+
+    #[test]
+    fn sgr_and_osc_invisible() {
+        let raw = b"\x1b]0;title\x07\x1b[1;31mred\x1b[0m\r\n";
+        assert_eq!(render_str(raw, 80, 24), "red\n");
+    }
+
+    #[test]
+    fn scrollback_keeps_scrolled_lines() {
+        let raw = b"1\r\n2\r\n3\r\n4\r\n5\r\n";
+        assert_eq!(render_str(raw, 10, 3), "1\n2\n3\n4\n5\n");
+    }
+
+    #[test]
+    fn autowrap_joins_logical_line() {
+        assert_eq!(render_str(b"abcdefgh\r\n", 5, 4), "abcdefgh\n");
+        // Exactly full row: wrap is pending, CRLF does not add a row.
+        assert_eq!(render_str(b"abcde\r\nx\r\n", 5, 4), "abcde\nx\n");
+    }
+
+    #[test]
+    fn autowrap_disabled_overwrites_last_column() {
+        assert_eq!(render_str(b"\x1b[?7labcdefgh\r\n", 5, 4), "abcdh\n");
+    }
+
+    #[test]
+    fn absolute_positioning() {
+        let raw = b"\x1b[2;3Hx\x1b[1;1Hy";
+        assert_eq!(render_str(raw, 10, 4), "y\n  x\n");
+    }
+
+    #[test]
+    fn erase_display_below() {
+        let raw = b"keep\r\nstale 1\r\nstale 2\x1b[2;1H\x1b[Jnew\r\n";
+        assert_eq!(render_str(raw, 20, 5), "keep\nnew\n");
+    }
+
+    #[test]
+    fn clear_screen_preserves_history() {
+        let raw = b"before\r\n\x1b[2J\x1b[Hafter\r\n";
+        assert_eq!(render_str(raw, 20, 5), "before\nafter\n");
+    }
+
+    #[test]
+    fn alternate_screen_discarded_on_exit() {
+        let raw = b"main\r\n\x1b[?1049hfull-screen UI\x1b[?1049lback\r\n";
+        assert_eq!(render_str(raw, 20, 5), "main\nback\n");
+    }
+
+    #[test]
+    fn scroll_region_does_not_feed_scrollback() {
+        // Region rows 1-2; status line on row 3 stays put and lines
+        // scrolled out of a partial region are gone.
+        let raw = b"\x1b[3;1Hstatus\x1b[1;2r\x1b[1;1Ha\r\nb\r\nc\r\n";
+        assert_eq!(render_str(raw, 10, 3), "c\n\nstatus\n");
+    }
+
+    #[test]
+    fn insert_and_delete_lines() {
+        let raw = b"a\r\nb\r\nc\x1b[2;1H\x1b[Lx\x1b[3;1H\x1b[M";
+        assert_eq!(render_str(raw, 10, 4), "a\nx\nc\n");
+    }
+
+    #[test]
+    fn save_restore_cursor() {
+        let raw = b"ab\x1b7\r\nline\x1b8c\r\n";
+        assert_eq!(render_str(raw, 10, 4), "abc\nline\n");
+    }
+
+    #[test]
+    fn tab_advances_to_stop() {
+        assert_eq!(render_str(b"a\tb\r\n", 20, 4), "a       b\n");
+    }
+
+    #[test]
+    fn zero_size_falls_back_to_default() {
+        assert_eq!(ScreenSize::or_default(0, 0), ScreenSize::default());
+        assert_eq!(
+            ScreenSize::or_default(120, 0),
+            ScreenSize {
+                cols: 120,
+                rows: 24
+            }
+        );
+    }
+
+    #[test]
+    fn feed_partial_completes_split_sequences() {
+        let mut screen = Screen::new(ScreenSize { cols: 10, rows: 3 });
+        for chunk in [&b"ab\x1b[2"[..], b";3Hx\xe2\x94", b"\x80"] {
+            screen.feed_partial(chunk);
+        }
+        assert_eq!(screen.rows(), ["ab", "  x\u{2500}", ""]);
+        assert_eq!(screen.cursor(), (1, 4));
+    }
+}
@@ -0,0 +1,468 @@
+//! PTY session transcripts for offline turn detection.
+//!
+//! A transcript records, with timestamps, everything the turn detector
+//! is fed during a wrapped session: PTY output chunks, stdin chunks and
+//! signals. Replaying it through a [`TurnDetector`](super::TurnDetector)
+//! reproduces the session's turns deterministically.
+//!
+//! Two encodings are supported, chosen when recording:
+//!
+//! - **JSON**: one JSON record per line. Chunks that are valid UTF-8
+//!   are written as strings, others as arrays of byte values.
+//! - **Binary**: [`BINARY_MAGIC`], then length-prefixed MessagePack
+//!   records framed as on the broker socket.
+//!
+//! Readers detect the encoding from the first bytes. See
+//! CONTRACT_TURN.md §Transcripts.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Transcript format version written in the header.
+pub const TRANSCRIPT_VERSION: u32 = 1;
+
+/// Leading bytes of a binary transcript.
+pub const BINARY_MAGIC: &[u8; 8] = b"CLPYTRN\x01";
+
+/// Largest binary record accepted, matching the broker's frame limit.
+const MAX_RECORD_SIZE: usize = 16 * 1024 * 1024;
+
+/// Transcript errors.
+#[derive(Debug, thiserror::Error)]
+pub enum TranscriptError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("JSON error on line {line}: {source}")]
+    Json {
+        line: usize,
+        source: serde_json::Error,
+    },
+    #[error("MessagePack encode error: {0}")]
+    Encode(#[from] rmp_serde::encode::Error),
+    #[error("MessagePack decode error in record {record}: {source}")]
+    Decode {
+        record: usize,
+        source: rmp_serde::decode::Error,
+    },
+    #[error("record too large: {0} bytes")]
+    RecordTooLarge(usize),
+    #[error("missing transcript header")]
+    MissingHeader,
+    #[error("unsupported transcript version {0} (expected {TRANSCRIPT_VERSION})")]
+    UnsupportedVersion(u32),
+}
+
+/// Transcript encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    Json,
+    Binary,
+}
+
+/// What was recorded and how the detector was configured.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    /// The wrapped command line.
+    pub command: Vec<String>,
+    /// Name of the preset used while recording (the regex itself for a
+    /// custom pattern).
+    pub preset: String,
+    /// Prompt regex used while recording.
+    pub pattern: String,
+    /// Boundary strategy spec used while recording.
+    pub boundary: String,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+/// One recorded event. `t_ms` is the time since recording started.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// Bytes read from the PTY master (agent output).
+    Output {
+        t_ms: u64,
+        #[serde(with = "chunk")]
+        data: Vec<u8>,
+    },
+    /// Bytes written to the child: typed on stdin or injected by the
+    /// broker.
+    Input {
+        t_ms: u64,
+        #[serde(with = "chunk")]
+        data: Vec<u8>,
+    },
+    /// A signal received by the wrapper, by name (e.g. `SIGINT`).
+    Signal { t_ms: u64, signal: String },
+}
+
+impl Event {
+    /// Time since recording started, in milliseconds.
+    pub fn t_ms(&self) -> u64 {
+        match self {
+            Event::Output { t_ms, .. } | Event::Input { t_ms, .. } | Event::Signal { t_ms, .. } => {
+                *t_ms
+            }
+        }
+    }
+}
+
+/// A transcript line or frame: the header first, then events.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Record {
+    Event(Event),
+    Header(Header),
+}
+
+/// Writes a transcript as the session runs.
+///
+/// Output is buffered; it is flushed on [`flush`](Self::flush) and
+/// when the writer is dropped.
+#[derive(Debug)]
+pub struct TranscriptWriter {
+    out: BufWriter<File>,
+    format: TranscriptFormat,
+    start: Instant,
+}
+
+impl TranscriptWriter {
+    /// Create (or truncate) `path` and write the header. Event times
+    /// are measured from now.
+    pub fn create(
+        path: &Path,
+        format: TranscriptFormat,
+        header: &Header,
+    ) -> Result<Self, TranscriptError> {
+        let mut writer = Self {
+            out: BufWriter::new(File::create(path)?),
+            format,
+            start: Instant::now(),
+        };
+        if format == TranscriptFormat::Binary {
+            writer.out.write_all(BINARY_MAGIC)?;
+        }
+        writer.write_record(&Record::Header(header.clone()))?;
+        Ok(writer)
+    }
+
+    /// Record a chunk of PTY output.
+    pub fn output(&mut self, data: &[u8]) -> Result<(), TranscriptError> {
+        let t_ms = self.elapsed_ms();
+        self.write_record(&Record::Event(Event::Output {
+            t_ms,
+            data: data.to_vec(),
+        }))
+    }
+
+    /// Record a chunk of input written to the child.
+    pub fn input(&mut self, data: &[u8]) -> Result<(), TranscriptError> {
+        let t_ms = self.elapsed_ms();
+        self.write_record(&Record::Event(Event::Input {
+            t_ms,
+            data: data.to_vec(),
+        }))
+    }
+
+    /// Record a received signal.
+    pub fn signal(&mut self, signal: &str) -> Result<(), TranscriptError> {
+        let t_ms = self.elapsed_ms();
+        self.write_record(&Record::Event(Event::Signal {
+            t_ms,
+            signal: signal.to_string(),
+        }))
+    }
+
+    /// Flush buffered records to the file.
+    pub fn flush(&mut self) -> Result<(), TranscriptError> {
+        Ok(self.out.flush()?)
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    fn write_record(&mut self, record: &Record) -> Result<(), TranscriptError> {
+        match self.format {
+            TranscriptFormat::Json => {
+                serde_json::to_writer(&mut self.out, record).map_err(io::Error::from)?;
+                self.out.write_all(b"\n")?;
+            }
+            TranscriptFormat::Binary => {
+                let payload = rmp_serde::to_vec_named(record)?;
+                if payload.len() > MAX_RECORD_SIZE {
+                    return Err(TranscriptError::RecordTooLarge(payload.len()));
+                }
+                self.out.write_all(&(payload.len() as u32).to_be_bytes())?;
+                self.out.write_all(&payload)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A transcript read back from a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcript {
+    pub header: Header,
+    pub events: Vec<Event>,
+}
+
+impl Transcript {
+    /// Read a transcript in either encoding.
+    pub fn read(path: &Path) -> Result<Self, TranscriptError> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a transcript in either encoding from `reader`.
+    pub fn from_reader<R: BufRead>(mut reader: R) -> Result<Self, TranscriptError> {
+        let records = if reader.fill_buf()?.starts_with(BINARY_MAGIC) {
+            reader.consume(BINARY_MAGIC.len());
+            read_binary(reader)?
+        } else {
+            read_json(reader)?
+        };
+
+        let mut records = records.into_iter();
+        let header = match records.next() {
+            Some(Record::Header(header)) => header,
+            _ => return Err(TranscriptError::MissingHeader),
+        };
+        if header.version != TRANSCRIPT_VERSION {
+            return Err(TranscriptError::UnsupportedVersion(header.version));
+        }
+        // A second header (e.g. concatenated transcripts) is not an event.
+        let events = records
+            .filter_map(|record| match record {
+                Record::Event(event) => Some(event),
+                Record::Header(_) => None,
+            })
+            .collect();
+        Ok(Self { header, events })
+    }
+}
+
+fn read_json<R: BufRead>(reader: R) -> Result<Vec<Record>, TranscriptError> {
+    let mut records = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|source| TranscriptError::Json {
+            line: i + 1,
+            source,
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn read_binary<R: Read>(mut reader: R) -> Result<Vec<Record>, TranscriptError> {
+    let mut records = Vec::new();
+    let mut len_buf = [0u8; 4];
+    loop {
+        // A clean end of file falls between records.
+        match reader.read_exact(&mut len_buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let len = u32::from_be_bytes(len_buf) as usize;
+        if len > MAX_RECORD_SIZE {
+            return Err(TranscriptError::RecordTooLarge(len));
+        }
+        let mut payload = vec![0u8; len];
+        reader.read_exact(&mut payload)?;
+        let record = rmp_serde::from_slice(&payload).map_err(|source| TranscriptError::Decode {
+            record: records.len() + 1,
+            source,
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Serde for a data chunk: a string when the bytes are valid UTF-8 in
+/// human-readable encodings (JSON), raw bytes otherwise.
+mod chunk {
+    use super::*;
+    use serde::de::{self, SeqAccess, Visitor};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        match std::str::from_utf8(data) {
+            Ok(text) if serializer.is_human_readable() => serializer.serialize_str(text),
+            _ => serializer.serialize_bytes(data),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_any(ChunkVisitor)
+    }
+
+    struct ChunkVisitor;
+
+    impl<'de> Visitor<'de> for ChunkVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a string or a byte array")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+            Ok(v.as_bytes().to_vec())
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let mut data = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element()? {
+                data.push(byte);
+            }
+            Ok(data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Header {
+        Header {
+            version: TRANSCRIPT_VERSION,
+            command: vec!["claude".into()],
+            preset: "claude".into(),
+            pattern: r"^> $".into(),
+            boundary: "prompt".into(),
+            cols: 80,
+            rows: 24,
+        }
+    }
+
+    fn write_sample(format: TranscriptFormat) -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.transcript");
+        let mut writer = TranscriptWriter::create(&path, format, &header()).unwrap();
+        writer.output(b"> \r\n").unwrap();
+        writer.input(b"hi\r").unwrap();
+        writer.signal("SIGINT").unwrap();
+        // Invalid UTF-8 and a split multi-byte character.
+        writer.output(&[0xff, b'a', 0xe2, 0x94]).unwrap();
+        drop(writer);
+        (dir, path)
+    }
+
+    fn assert_sample(transcript: &Transcript) {
+        assert_eq!(transcript.header, header());
+        let kinds: Vec<_> = transcript
+            .events
+            .iter()
+            .map(|event| match event {
+                Event::Output { data, .. } => format!("out {data:?}"),
+                Event::Input { data, .. } => format!("in {data:?}"),
+                Event::Signal { signal, .. } => format!("sig {signal}"),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                format!("out {:?}", b"> \r\n"),
+                format!("in {:?}", b"hi\r"),
+                "sig SIGINT".to_string(),
+                format!("out {:?}", [0xff, b'a', 0xe2, 0x94]),
+            ]
+        );
+        let times: Vec<_> = transcript.events.iter().map(Event::t_ms).collect();
+        assert!(times.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let (_dir, path) = write_sample(TranscriptFormat::Json);
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].contains(r#""type":"output""#));
+        // UTF-8 chunks stay readable.
+        assert!(lines[1].contains(r#""data":"> \r\n""#));
+        assert!(lines[4].contains(r#""data":[255,97,226,148]"#));
+        assert_sample(&Transcript::read(&path).unwrap());
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let (_dir, path) = write_sample(TranscriptFormat::Binary);
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.starts_with(BINARY_MAGIC));
+        assert_sample(&Transcript::read(&path).unwrap());
+    }
+
+    #[test]
+    fn hand_written_json_accepted() {
+        let text = concat!(
+            r#"{"version":1,"command":["sh"],"preset":"sh","pattern":"^\\$ $","boundary":"prompt","cols":80,"rows":24}"#,
+            "\n\n",
+            r#"{"type":"output","t_ms":5,"data":"$ \n"}"#,
+            "\n",
+        );
+        let transcript = Transcript::from_reader(text.as_bytes()).unwrap();
+        assert_eq!(transcript.header.pattern, "^\\$ $");
+        assert_eq!(
+            transcript.events,
+            [Event::Output {
+                t_ms: 5,
+                data: b"$ \n".to_vec()
+            }]
+        );
+    }
+
+    #[test]
+    fn missing_header_rejected() {
+        let text = r#"{"type":"signal","t_ms":0,"signal":"SIGINT"}"#;
+        assert!(matches!(
+            Transcript::from_reader(text.as_bytes()),
+            Err(TranscriptError::MissingHeader)
+        ));
+        assert!(matches!(
+            Transcript::from_reader(&b""[..]),
+            Err(TranscriptError::MissingHeader)
+        ));
+    }
+
+    #[test]
+    fn malformed_json_reports_line() {
+        let text = format!(
+            "{}\n{{\"type\":\"output\"\n",
+            serde_json::to_string(&header()).unwrap()
+        );
+        assert!(matches!(
+            Transcript::from_reader(text.as_bytes()),
+            Err(TranscriptError::Json { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn unsupported_version_rejected() {
+        let mut header = header();
+        header.version = 99;
+        let text = serde_json::to_string(&header).unwrap();
+        assert!(matches!(
+            Transcript::from_reader(text.as_bytes()),
+            Err(TranscriptError::UnsupportedVersion(99))
+        ));
+    }
+}